@@ -3,7 +3,7 @@ use std::collections::{BTreeSet, HashMap};
 use hex;
 
 use account::{Account, AccountError, AccountTransactionInteraction, AccountType, PrunedAccount};
-use beserial::Deserialize;
+use beserial::{Deserialize, SerializingError};
 use block::{Block, BlockBody};
 use database::{Environment, ReadTransaction, WriteTransaction};
 use database as db;
@@ -33,25 +33,42 @@ impl<'env> Accounts<'env> {
     pub fn init(&self, txn: &mut WriteTransaction, network_id: NetworkId) {
         let network_info = get_network_info(network_id).unwrap();
         let account_bytes = hex::decode(&network_info.genesis_accounts).unwrap();
-        let reader = &mut &account_bytes[..];
-        let count = u16::deserialize(reader).unwrap();
-
-        for _ in 0..count {
-            let address = Address::deserialize(reader).unwrap();
-            let account = Account::deserialize(reader).unwrap();
-            self.tree.put_batch(txn, &address, account);
-        }
-        self.tree.finalize_batch(txn);
+        let genesis_accounts = Self::parse_genesis_accounts(&account_bytes).unwrap();
 
         let genesis_header = &network_info.genesis_block.header;
         let genesis_body = network_info.genesis_block.body.as_ref().unwrap();
-        self.commit_block_body(txn, genesis_body, genesis_header.height)
+        self.init_with(txn, genesis_accounts, genesis_body, genesis_header.height)
             .expect("Failed to commit genesis block body");
 
         assert_eq!(self.tree.root_hash(txn), genesis_header.accounts_hash,
                    "Genesis AccountHash mismatch");
     }
 
+    /// Puts the genesis accounts into the tree and applies the genesis block body to them.
+    pub fn init_with(&self, txn: &mut WriteTransaction, genesis_accounts: Vec<(Address, Account)>, genesis_body: &BlockBody, genesis_height: u32) -> Result<(), AccountError> {
+        for (address, account) in genesis_accounts {
+            self.tree.put_batch(txn, &address, account);
+        }
+        self.tree.finalize_batch(txn);
+
+        self.commit_block_body(txn, genesis_body, genesis_height)
+    }
+
+    /// Parses genesis accounts in the format of `NetworkInfo::genesis_accounts`: the number of
+    /// accounts as `u16`, followed by pairs of address and account.
+    pub fn parse_genesis_accounts(bytes: &[u8]) -> Result<Vec<(Address, Account)>, SerializingError> {
+        let reader = &mut &bytes[..];
+        let count = u16::deserialize(reader)?;
+
+        let mut genesis_accounts = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let address = Address::deserialize(reader)?;
+            let account = Account::deserialize(reader)?;
+            genesis_accounts.push((address, account));
+        }
+        Ok(genesis_accounts)
+    }
+
     pub fn get(&self, address: &Address, txn_option: Option<&db::Transaction>) -> Account {
         match txn_option {
             Some(txn) => self.tree.get(txn, address),
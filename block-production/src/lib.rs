@@ -4,6 +4,10 @@ extern crate nimiq_hash as hash;
 extern crate nimiq_keys as keys;
 extern crate nimiq_mempool as mempool;
 extern crate nimiq_network_primitives as network_primitives;
+extern crate nimiq_primitives as primitives;
+extern crate nimiq_transaction as transaction;
+
+pub mod policy;
 
 use std::sync::Arc;
 
@@ -15,14 +19,21 @@ use keys::Address;
 use mempool::Mempool;
 use network_primitives::networks::get_network_info;
 
+use crate::policy::{DefaultSelectionPolicy, TransactionSelectionPolicy};
+
 pub struct BlockProducer<'env> {
     blockchain: Arc<Blockchain<'env>>,
     mempool: Arc<Mempool<'env>>,
+    policy: Arc<dyn TransactionSelectionPolicy>,
 }
 
 impl<'env> BlockProducer<'env> {
     pub fn new(blockchain: Arc<Blockchain<'env>>, mempool: Arc<Mempool<'env>>) -> Self {
-        BlockProducer { blockchain, mempool }
+        Self::with_policy(blockchain, mempool, Arc::new(DefaultSelectionPolicy::default()))
+    }
+
+    pub fn with_policy(blockchain: Arc<Blockchain<'env>>, mempool: Arc<Mempool<'env>>, policy: Arc<dyn TransactionSelectionPolicy>) -> Self {
+        BlockProducer { blockchain, mempool, policy }
     }
 
     pub fn next_block(&self, timestamp: u32, miner: Address, extra_data: Vec<u8>) -> Block {
@@ -46,7 +57,10 @@ impl<'env> BlockProducer<'env> {
             - BlockHeader::SIZE
             - interlink_size
             - BlockBody::get_metadata_size(extra_data.len());
-        let mut transactions = self.mempool.get_transactions_for_block(max_size);
+        // The mempool returns the transactions in ascending order, the policy expects the highest fees first.
+        let mut candidates = self.mempool.get_transactions(usize::max_value(), 0f64);
+        candidates.reverse();
+        let mut transactions = self.policy.select_transactions(&candidates, max_size);
         let mut pruned_accounts = self.blockchain.state().accounts()
             .collect_pruned_accounts(&transactions, self.blockchain.height() + 1)
             .expect("Failed to collect pruned accounts during block production");
@@ -0,0 +1,121 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use beserial::Serialize;
+use keys::Address;
+use mempool::MempoolConfig;
+use transaction::Transaction;
+
+/// Decides which pending transactions go into a block template.
+pub trait TransactionSelectionPolicy: Debug + Send + Sync {
+    /// Selects the transactions for the next block from `candidates`, which are sorted by fee per
+    /// byte in descending order, i.e. in reverse mempool order. The selected transactions must not exceed
+    /// `max_size` bytes in total. Transactions that are dropped from the end of the returned list
+    /// to make room for pruned accounts should be the least important ones.
+    fn select_transactions(&self, candidates: &[Arc<Transaction>], max_size: usize) -> Vec<Transaction>;
+
+    /// Whether a newly pending transaction could change the selection. This is used to decide
+    /// when long-polling block template requests are answered.
+    fn is_relevant(&self, transaction: &Transaction) -> bool;
+}
+
+/// The default selection policy. Without any options set, it greedily takes the transactions
+/// with the highest fee per byte until the block is full.
+#[derive(Debug, Clone)]
+pub struct DefaultSelectionPolicy {
+    /// Minimum fee per byte for a transaction to be included. Free transactions can still
+    /// be included in the space reserved for them.
+    pub fee_per_byte_min: f64,
+    /// Space in bytes that is reserved for free transactions.
+    pub free_space: usize,
+    /// Transactions paying less than this fee per byte are free. This should be the threshold of
+    /// the mempool, see `MempoolConfig::transaction_relay_fee_min`.
+    pub free_fee_per_byte: f64,
+    /// Transactions from or to these addresses are included regardless of their fee.
+    pub always_include: HashSet<Address>,
+    /// Transactions from or to these addresses are never included.
+    pub never_include: HashSet<Address>,
+    /// Maximum number of transactions per sender in a block.
+    pub transactions_per_sender_max: Option<usize>,
+}
+
+impl DefaultSelectionPolicy {
+    fn involves(addresses: &HashSet<Address>, tx: &Transaction) -> bool {
+        addresses.contains(&tx.sender) || addresses.contains(&tx.recipient)
+    }
+
+    fn is_free(&self, tx: &Transaction) -> bool {
+        tx.fee_per_byte() < self.free_fee_per_byte
+    }
+}
+
+impl Default for DefaultSelectionPolicy {
+    fn default() -> Self {
+        DefaultSelectionPolicy {
+            fee_per_byte_min: 0f64,
+            free_space: 0,
+            free_fee_per_byte: MempoolConfig::DEFAULT_TRANSACTION_RELAY_FEE_MIN,
+            always_include: HashSet::new(),
+            never_include: HashSet::new(),
+            transactions_per_sender_max: None,
+        }
+    }
+}
+
+impl TransactionSelectionPolicy for DefaultSelectionPolicy {
+    fn select_transactions(&self, candidates: &[Arc<Transaction>], max_size: usize) -> Vec<Transaction> {
+        let candidates: Vec<&Arc<Transaction>> = candidates.iter()
+            .filter(|tx| !Self::involves(&self.never_include, tx))
+            .collect();
+
+        let mut selected = vec![false; candidates.len()];
+        let mut txs = Vec::new();
+        let mut size = 0;
+        let mut txs_per_sender: HashMap<&Address, usize> = HashMap::new();
+
+        // Transactions are selected in three passes: Those that must always be included, those that
+        // pay at least the minimum fee and finally free transactions in the remaining space.
+        let passes: [(usize, &dyn Fn(&Transaction) -> bool); 3] = [
+            (max_size, &|tx| Self::involves(&self.always_include, tx)),
+            (max_size.saturating_sub(self.free_space), &|tx| tx.fee_per_byte() >= self.fee_per_byte_min),
+            (max_size, &|tx| self.is_free(tx)),
+        ];
+
+        for (i, (pass_max_size, accepts)) in passes.iter().enumerate() {
+            for (j, tx) in candidates.iter().enumerate() {
+                if selected[j] || !accepts(tx) {
+                    continue;
+                }
+
+                // Always included transactions are not subject to the per sender limit.
+                let sender_count = txs_per_sender.entry(&tx.sender).or_insert(0);
+                if i > 0 && self.transactions_per_sender_max.map_or(false, |max| *sender_count >= max) {
+                    continue;
+                }
+
+                let tx_size = tx.serialized_size();
+                if size + tx_size <= *pass_max_size {
+                    selected[j] = true;
+                    txs.push(Transaction::clone(tx));
+                    size += tx_size;
+                    *sender_count += 1;
+                } else if pass_max_size.saturating_sub(size) < Transaction::MIN_SIZE {
+                    // Break if we can't fit the smallest possible transaction anymore.
+                    break;
+                }
+            }
+        }
+
+        txs
+    }
+
+    fn is_relevant(&self, tx: &Transaction) -> bool {
+        if Self::involves(&self.never_include, tx) {
+            return false;
+        }
+        Self::involves(&self.always_include, tx)
+            || tx.fee_per_byte() >= self.fee_per_byte_min
+            || (self.free_space > 0 && self.is_free(tx))
+    }
+}
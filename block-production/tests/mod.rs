@@ -1,8 +1,9 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use beserial::Serialize;
 use nimiq_account::AccountType;
 use nimiq_block_production::BlockProducer;
+use nimiq_block_production::policy::{DefaultSelectionPolicy, TransactionSelectionPolicy};
 use nimiq_blockchain::{Blockchain, PushResult};
 use nimiq_database::volatile::VolatileEnvironment;
 use nimiq_keys::{Address, KeyPair, PrivateKey};
@@ -106,3 +107,57 @@ fn it_can_produce_nonempty_blocks() {
     assert_eq!(contract.account_type(), AccountType::Basic);
     assert_eq!(contract.balance(), Coin::ZERO);
 }
+
+/// Remembers the fees of the candidates it was given.
+#[derive(Debug, Default)]
+struct RecordingPolicy {
+    candidate_fees: Mutex<Vec<f64>>,
+}
+
+impl TransactionSelectionPolicy for RecordingPolicy {
+    fn select_transactions(&self, candidates: &[Arc<Transaction>], max_size: usize) -> Vec<Transaction> {
+        *self.candidate_fees.lock().unwrap() = candidates.iter().map(|tx| tx.fee_per_byte()).collect();
+        DefaultSelectionPolicy::default().select_transactions(candidates, max_size)
+    }
+
+    fn is_relevant(&self, transaction: &Transaction) -> bool {
+        DefaultSelectionPolicy::default().is_relevant(transaction)
+    }
+}
+
+#[test]
+fn it_passes_candidates_with_the_highest_fees_first() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain.clone(), MempoolConfig::default());
+
+    let keypair: KeyPair = PrivateKey::from([1u8; PrivateKey::SIZE]).into();
+    let miner = Address::from(&keypair.public);
+
+    let policy = Arc::new(RecordingPolicy::default());
+    let producer = BlockProducer::with_policy(Arc::clone(&blockchain), Arc::clone(&mempool), policy.clone());
+    let mut block = producer.next_block(1523727060, miner.clone(), Vec::new());
+    block.header.nonce = 34932;
+    assert_eq!(blockchain.push(block), PushResult::Extended);
+
+    for fee_per_byte in &[1u64, 3, 0, 2] {
+        let mut tx = Transaction::new_basic(
+            miner.clone(),
+            Address::from([*fee_per_byte as u8 + 1; Address::SIZE]),
+            Coin::from_u64(100).unwrap(),
+            Coin::ZERO,
+            1,
+            NetworkId::Main,
+        );
+        tx.fee = Coin::from_u64(fee_per_byte * tx.serialized_size() as u64).unwrap();
+        let signature = keypair.sign(tx.serialize_content().as_slice());
+        tx.proof = SignatureProof::from(keypair.public, signature).serialize_to_vec();
+        assert_eq!(mempool.push_transaction(tx), ReturnCode::Accepted);
+    }
+
+    let block = producer.next_block(1523727120, miner, Vec::new());
+    let candidate_fees = policy.candidate_fees.lock().unwrap();
+    assert_eq!(candidate_fees.len(), 4);
+    assert!(candidate_fees.windows(2).all(|fees| fees[0] > fees[1]), "Candidates not sorted by fee: {:?}", candidate_fees);
+    assert_eq!(block.body.unwrap().transactions.len(), 4);
+}
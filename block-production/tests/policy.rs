@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use beserial::Serialize;
+use nimiq_block_production::policy::{DefaultSelectionPolicy, TransactionSelectionPolicy};
+use nimiq_keys::Address;
+use nimiq_network_primitives::networks::NetworkId;
+use nimiq_primitives::coin::Coin;
+use nimiq_transaction::Transaction;
+
+/// Creates an unsigned transaction with the given fee per byte.
+fn transaction(sender: u8, recipient: u8, fee_per_byte: u64) -> Arc<Transaction> {
+    let mut tx = Transaction::new_basic(
+        Address::from([sender; Address::SIZE]),
+        Address::from([recipient; Address::SIZE]),
+        Coin::from_u64(100).unwrap(),
+        Coin::ZERO,
+        1,
+        NetworkId::Main,
+    );
+    tx.fee = Coin::from_u64(fee_per_byte * tx.serialized_size() as u64).unwrap();
+    Arc::new(tx)
+}
+
+fn fees(txs: &[Transaction]) -> Vec<f64> {
+    txs.iter().map(Transaction::fee_per_byte).collect()
+}
+
+#[test]
+fn it_selects_highest_fees_by_default() {
+    let candidates = vec![transaction(1, 10, 3), transaction(2, 10, 2), transaction(3, 10, 0)];
+    let tx_size = candidates[0].serialized_size();
+    let policy = DefaultSelectionPolicy::default();
+
+    assert_eq!(fees(&policy.select_transactions(&candidates, 3 * tx_size)), vec![3f64, 2f64, 0f64]);
+    assert_eq!(fees(&policy.select_transactions(&candidates, 2 * tx_size)), vec![3f64, 2f64]);
+}
+
+#[test]
+fn it_reserves_space_for_free_transactions() {
+    let candidates = vec![
+        transaction(1, 10, 3),
+        transaction(2, 10, 2),
+        transaction(3, 10, 1),
+        transaction(4, 10, 0),
+    ];
+    let tx_size = candidates[0].serialized_size();
+    let policy = DefaultSelectionPolicy {
+        fee_per_byte_min: 2f64,
+        free_space: tx_size,
+        ..Default::default()
+    };
+
+    // The transaction paying below the minimum fee is never included.
+    assert_eq!(fees(&policy.select_transactions(&candidates, 4 * tx_size)), vec![3f64, 2f64, 0f64]);
+    // The reserved space is used for the free transaction.
+    assert_eq!(fees(&policy.select_transactions(&candidates, 2 * tx_size)), vec![3f64, 0f64]);
+    assert!(policy.is_relevant(&candidates[3]));
+    assert!(!policy.is_relevant(&candidates[2]));
+
+    // Transactions below the free threshold of the mempool are free.
+    let policy = DefaultSelectionPolicy {
+        free_fee_per_byte: 2f64,
+        ..policy
+    };
+    assert_eq!(fees(&policy.select_transactions(&candidates, 3 * tx_size)), vec![3f64, 2f64, 1f64]);
+    assert!(policy.is_relevant(&candidates[2]));
+
+    // Without reserved space, transactions below the minimum fee are irrelevant.
+    let policy = DefaultSelectionPolicy {
+        free_space: 0,
+        ..policy
+    };
+    assert!(!policy.is_relevant(&candidates[2]));
+    assert!(!policy.is_relevant(&candidates[3]));
+}
+
+#[test]
+fn it_applies_address_lists() {
+    let candidates = vec![transaction(1, 10, 3), transaction(2, 20, 2), transaction(3, 30, 0)];
+    let tx_size = candidates[0].serialized_size();
+    let mut policy = DefaultSelectionPolicy {
+        fee_per_byte_min: 1f64,
+        ..Default::default()
+    };
+    policy.always_include.insert(Address::from([30u8; Address::SIZE]));
+    policy.never_include.insert(Address::from([2u8; Address::SIZE]));
+
+    // Always included transactions are selected first.
+    assert_eq!(fees(&policy.select_transactions(&candidates, 3 * tx_size)), vec![0f64, 3f64]);
+    assert_eq!(fees(&policy.select_transactions(&candidates, tx_size)), vec![0f64]);
+
+    assert!(policy.is_relevant(&candidates[0]));
+    assert!(!policy.is_relevant(&candidates[1]));
+    assert!(policy.is_relevant(&candidates[2]));
+}
+
+#[test]
+fn it_limits_transactions_per_sender() {
+    let candidates = vec![
+        transaction(1, 10, 4),
+        transaction(1, 20, 3),
+        transaction(1, 30, 2),
+        transaction(2, 10, 1),
+    ];
+    let tx_size = candidates[0].serialized_size();
+    let policy = DefaultSelectionPolicy {
+        transactions_per_sender_max: Some(2),
+        ..Default::default()
+    };
+
+    assert_eq!(fees(&policy.select_transactions(&candidates, 4 * tx_size)), vec![4f64, 3f64, 1f64]);
+}
@@ -501,6 +501,12 @@ impl<'env> Blockchain<'env> {
     }
 
     pub fn get_next_target(&self, head_hash: Option<&Blake2bHash>) -> Target {
+        // Regtest blocks are mined on demand, so the difficulty stays at the genesis block's minimum.
+        if self.network_id == NetworkId::Regtest {
+            let network_info = get_network_info(self.network_id).unwrap();
+            return Target::from(network_info.genesis_block.header.n_bits);
+        }
+
         let state = self.state.read();
 
         let chain_info;
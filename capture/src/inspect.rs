@@ -0,0 +1,50 @@
+use hash::{Blake2bHash, Hash};
+use network::capture::{CaptureRecord, Direction};
+use network_messages::{InvVector, Message};
+
+/// Formats a record as a single line.
+pub fn format_record(record: &CaptureRecord) -> String {
+    let direction = match record.direction {
+        Direction::Inbound => "<",
+        Direction::Outbound => ">",
+    };
+    let peer_id = record.peer_id.as_ref().map_or_else(|| "-".to_string(), |peer_id| peer_id.to_string());
+    format!("{}.{:03} #{} {} {} {} {}", record.timestamp / 1000, record.timestamp % 1000,
+            record.connection_id, direction, record.net_address, peer_id, describe(&record.message))
+}
+
+/// Describes a message by its type and the fields most useful for debugging.
+pub fn describe(message: &Message) -> String {
+    let details = match message {
+        Message::Version(version) => format!("peer={} genesis={} head={} user_agent={}",
+            version.peer_address, version.genesis_hash, version.head_hash,
+            version.user_agent.as_ref().map_or("-", String::as_str)),
+        Message::Inv(vectors)
+        | Message::GetData(vectors)
+        | Message::GetHeader(vectors)
+        | Message::NotFound(vectors) => describe_vectors(vectors),
+        Message::Block(block) => format!("height={} hash={}", block.header.height, block.header.hash::<Blake2bHash>()),
+        Message::Header(header)
+        | Message::Head(header) => format!("height={} hash={}", header.height, header.hash::<Blake2bHash>()),
+        Message::Tx(tx) => format!("hash={} proof={}", tx.transaction.hash::<Blake2bHash>(), tx.accounts_proof.is_some()),
+        Message::GetBlocks(get_blocks) => format!("locators={} max_inv_size={} direction={:?}",
+            get_blocks.locators.len(), get_blocks.max_inv_size, get_blocks.direction),
+        Message::Addr(addr) => format!("addresses={}", addr.addresses.len()),
+        Message::Ping(nonce)
+        | Message::Pong(nonce) => format!("nonce={}", nonce),
+        Message::Reject(reject) => format!("{:?}", reject),
+        _ => String::new(),
+    };
+    if details.is_empty() {
+        message.ty().to_string()
+    } else {
+        format!("{} {}", message.ty(), details)
+    }
+}
+
+fn describe_vectors(vectors: &[InvVector]) -> String {
+    let hashes: Vec<String> = vectors.iter()
+        .map(|vector| format!("{:?}:{}", vector.ty, vector.hash))
+        .collect();
+    format!("count={} [{}]", vectors.len(), hashes.join(", "))
+}
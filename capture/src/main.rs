@@ -0,0 +1,134 @@
+//! Inspects traffic captures written by a node (see the `startCapture` RPC method) and replays
+//! them into a node for debugging.
+
+extern crate nimiq_hash as hash;
+extern crate nimiq_messages as network_messages;
+extern crate nimiq_network as network;
+
+mod inspect;
+mod replay;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::process;
+use std::time::Duration;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use failure::{Error, format_err};
+use url::Url;
+
+use network::capture::{CaptureReader, CaptureRecord};
+
+use crate::replay::ReplayOptions;
+
+fn read_capture(path: &str) -> Result<CaptureReader<BufReader<File>>, Error> {
+    Ok(CaptureReader::new(BufReader::new(File::open(path)?))?)
+}
+
+fn inspect(matches: &ArgMatches) -> Result<(), Error> {
+    let ty = matches.value_of("type");
+    let verbose = matches.is_present("verbose");
+
+    for record in read_capture(matches.value_of("capture").unwrap())? {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                eprintln!("Skipping record: {}", e);
+                continue;
+            },
+        };
+        if ty.map_or(false, |ty| ty != record.message.ty().to_string()) {
+            continue;
+        }
+        println!("{}", inspect::format_record(&record));
+        if verbose {
+            println!("{:#?}", record.message);
+        }
+    }
+    Ok(())
+}
+
+fn replay(matches: &ArgMatches) -> Result<(), Error> {
+    let url = Url::parse(matches.value_of("node").unwrap())?;
+    let options = ReplayOptions {
+        connection_id: matches.value_of("connection").map(str::parse::<u64>).transpose()
+            .map_err(|_| format_err!("Invalid connection ID"))?,
+        delay: Duration::from_millis(matches.value_of("delay").unwrap().parse()
+            .map_err(|_| format_err!("Invalid delay"))?),
+        wait: Duration::from_secs(matches.value_of("wait").unwrap().parse()
+            .map_err(|_| format_err!("Invalid wait time"))?),
+    };
+
+    let records: Vec<CaptureRecord> = read_capture(matches.value_of("capture").unwrap())?
+        .filter_map(Result::ok)
+        .collect();
+    replay::replay(records, url, &options)
+}
+
+fn run() -> Result<(), Error> {
+    let capture_arg = Arg::with_name("capture")
+        .value_name("CAPTURE")
+        .help("Capture file")
+        .required(true);
+
+    let matches = App::new("nimiq-capture")
+        .version("0.1.0")
+        .about("Inspects and replays Nimiq network traffic captures")
+        .author("The Nimiq Core Development Team <info@nimiq.com>")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("inspect")
+            .about("Prints the messages of a capture")
+            .arg(capture_arg.clone())
+            .arg(Arg::with_name("type")
+                .short("t")
+                .long("type")
+                .value_name("TYPE")
+                .help("Only print messages of TYPE, e.g. 'inv' or 'block'")
+                .takes_value(true))
+            .arg(Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .help("Print the full messages")))
+        .subcommand(SubCommand::with_name("replay")
+            .about("Sends the messages received in a capture to a node")
+            .arg(capture_arg)
+            .arg(Arg::with_name("node")
+                .value_name("NODE")
+                .help("WebSocket URL of the node, e.g. ws://127.0.0.1:8443")
+                .required(true))
+            .arg(Arg::with_name("connection")
+                .short("c")
+                .long("connection")
+                .value_name("ID")
+                .help("Only replay the messages of connection ID")
+                .takes_value(true))
+            .arg(Arg::with_name("delay")
+                .short("d")
+                .long("delay")
+                .value_name("MS")
+                .help("Pause between messages in milliseconds")
+                .default_value("0"))
+            .arg(Arg::with_name("wait")
+                .short("w")
+                .long("wait")
+                .value_name("SECS")
+                .help("Keep printing responses for SECS seconds after the last message")
+                .default_value("5")))
+        .get_matches();
+
+    match matches.subcommand() {
+        ("inspect", Some(matches)) => inspect(matches),
+        ("replay", Some(matches)) => replay(matches),
+        _ => unreachable!(),
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        for cause in e.iter_causes() {
+            eprintln!("Caused by: {}", cause);
+        }
+        process::exit(1);
+    }
+}
@@ -0,0 +1,134 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use failure::{Error, format_err};
+use futures::prelude::*;
+use tokio::prelude::FutureExt;
+use tokio::runtime::current_thread::Runtime;
+use url::Url;
+
+use network::NetworkConfig;
+use network::capture::{CaptureRecord, Direction};
+use network::websocket::{Message as WebSocketMessage, NimiqMessageStream, nimiq_connect_async};
+use network_messages::{ChallengeNonce, Message, VerAckMessage, VersionMessage};
+
+use crate::inspect::describe;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct ReplayOptions {
+    /// Only replay the messages of this connection.
+    pub connection_id: Option<u64>,
+    /// Pause between two replayed messages.
+    pub delay: Duration,
+    /// How long to keep printing responses after the last message was sent.
+    pub wait: Duration,
+}
+
+/// Connects to the node at `url` and sends it the messages that the capturing node received.
+///
+/// The handshake can't be replayed since it is signed by the original peer. Instead, a handshake
+/// with a new identity is performed, announcing the genesis and head hash of the first captured
+/// version message. Responses of the node are printed.
+pub fn replay(records: Vec<CaptureRecord>, url: Url, options: &ReplayOptions) -> Result<(), Error> {
+    let messages: Vec<Message> = records.into_iter()
+        .filter(|record| record.direction == Direction::Inbound)
+        .filter(|record| options.connection_id.map_or(true, |id| id == record.connection_id))
+        .map(|record| record.message)
+        .collect();
+    let version = messages.iter()
+        .filter_map(|message| match message {
+            Message::Version(version) => Some(version.clone()),
+            _ => None,
+        })
+        .next()
+        .ok_or_else(|| format_err!("No version message in capture, can't tell which network to use"))?;
+
+    let mut network_config = NetworkConfig::new_dumb_network_config();
+    network_config.init_volatile();
+    network_config.set_user_agent(format!("nimiq-capture/{}", env!("CARGO_PKG_VERSION")));
+
+    let mut runtime = Runtime::new()?;
+    let mut stream = runtime.block_on(nimiq_connect_async(url, None))?;
+
+    stream = send(&mut runtime, stream, VersionMessage::new(network_config.peer_address(),
+        version.head_hash.clone(), version.genesis_hash.clone(), ChallengeNonce::generate(),
+        network_config.user_agent().clone()))?;
+    let (mut version_received, mut ver_ack_received) = (false, false);
+    while !version_received || !ver_ack_received {
+        let (message, next) = receive(&mut runtime, stream, HANDSHAKE_TIMEOUT)?
+            .ok_or_else(|| format_err!("Handshake timed out"))?;
+        stream = next;
+        match message {
+            Some(Message::Version(node_version)) => {
+                println!("< {}", describe(&Message::Version(node_version.clone())));
+                stream = send(&mut runtime, stream, VerAckMessage::new(&node_version.peer_address.peer_id,
+                    &node_version.challenge_nonce, network_config.key_pair()))?;
+                version_received = true;
+            },
+            Some(Message::VerAck(_)) => ver_ack_received = true,
+            Some(message) => stream = respond(&mut runtime, stream, message)?,
+            None => return Err(format_err!("Node closed the connection during the handshake")),
+        }
+    }
+
+    for message in messages {
+        match message {
+            Message::Version(_) | Message::VerAck(_) => continue,
+            _ => {},
+        }
+        println!("> {}", describe(&message));
+        stream = send(&mut runtime, stream, message)?;
+        thread::sleep(options.delay);
+    }
+
+    let deadline = Instant::now() + options.wait;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        match receive(&mut runtime, stream, deadline - now)? {
+            Some((Some(message), next)) => stream = respond(&mut runtime, next, message)?,
+            Some((None, _)) => {
+                println!("Node closed the connection");
+                break;
+            },
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+fn send(runtime: &mut Runtime, stream: NimiqMessageStream, message: Message) -> Result<NimiqMessageStream, Error> {
+    Ok(runtime.block_on(stream.send(WebSocketMessage::Message(message)))?)
+}
+
+/// Prints a message of the node and answers pings, so that the node keeps the connection open.
+fn respond(runtime: &mut Runtime, stream: NimiqMessageStream, message: Message) -> Result<NimiqMessageStream, Error> {
+    println!("< {}", describe(&message));
+    match message {
+        Message::Ping(nonce) => send(runtime, stream, Message::Pong(nonce)),
+        _ => Ok(stream),
+    }
+}
+
+/// Receives the next message of the node. Returns `None` on timeout and a `None` message if the
+/// node closed the connection.
+fn receive(runtime: &mut Runtime, stream: NimiqMessageStream, timeout: Duration) -> Result<Option<(Option<Message>, NimiqMessageStream)>, Error> {
+    let mut stream = stream;
+    loop {
+        let result = runtime.block_on(stream.into_future().timeout(timeout));
+        match result {
+            Ok((Some(WebSocketMessage::Message(message)), next)) => return Ok(Some((Some(message), next))),
+            Ok((Some(WebSocketMessage::Resume(..)), next)) => stream = next,
+            Ok((Some(WebSocketMessage::Close(_)), next))
+            | Ok((None, next)) => return Ok(Some((None, next))),
+            Err(ref e) if e.is_elapsed() => return Ok(None),
+            Err(e) => return Err(match e.into_inner() {
+                Some((e, _)) => e.into(),
+                None => format_err!("Timer failed"),
+            }),
+        }
+    }
+}
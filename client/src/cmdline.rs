@@ -71,7 +71,7 @@ impl Options {
                      .use_delimiter(true))
             .arg(Arg::with_name("passive")
                 .long("passive")
-                .help("Do not actively connect to the network.")
+                .help("Do not connect to peers, only accept inbound connections.")
                 .takes_value(false))
             .arg(Arg::with_name("consensus_type")
                 .long("type")
@@ -82,8 +82,8 @@ impl Options {
             .arg(Arg::with_name("network")
                 .long("network")
                 .value_name("NAME")
-                .help("Configure the network to connect to, one of main (default), test or dev.")
-                .possible_values(&["main", "test", "dev"]))
+                .help("Configure the network to connect to, one of main (default), test, dev or regtest.")
+                .possible_values(&["main", "test", "dev", "regtest"]))
     }
 
     /// Parses a command line option from a string into `T` and returns `error`, when parsing fails.
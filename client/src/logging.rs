@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 use chrono::Local;
@@ -5,7 +7,7 @@ use colored::Colorize;
 use failure::Fail;
 use fern::colors::{Color, ColoredLevelConfig};
 use fern::Dispatch;
-use log::{Level, LevelFilter};
+use log::{Level, LevelFilter, Metadata};
 
 static MAX_MODULE_WIDTH: AtomicUsize = AtomicUsize::new(0);
 
@@ -35,6 +37,65 @@ const NIMIQ_MODULES: [&str; 22] = [
 ];
 pub const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
 
+lazy_static! {
+    static ref LOG_LEVELS: RwLock<LogLevels> = RwLock::new(LogLevels::default());
+}
+
+/// Log levels that can be changed while the client is running.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLevels {
+    /// Level for the nimiq modules.
+    pub nimiq: LevelFilter,
+    /// Levels for individual modules, overriding `nimiq`.
+    pub tags: HashMap<String, LevelFilter>,
+}
+
+impl Default for LogLevels {
+    fn default() -> Self {
+        LogLevels {
+            nimiq: DEFAULT_LEVEL,
+            tags: HashMap::new(),
+        }
+    }
+}
+
+impl LogLevels {
+    /// The level for a target. The most specific tag wins, like with `Dispatch::level_for`.
+    fn level(&self, target: &str) -> LevelFilter {
+        let tag = self.tags.iter()
+            .filter(|(module, _)| target == module.as_str()
+                || (target.starts_with(module.as_str()) && target[module.len()..].starts_with("::")))
+            .max_by_key(|(module, _)| module.len());
+        if let Some((_, &level)) = tag {
+            return level;
+        }
+
+        let module = target.split("::").next().unwrap_or(target);
+        if NIMIQ_MODULES.contains(&module) {
+            self.nimiq
+        } else {
+            DEFAULT_LEVEL
+        }
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.tags.values().cloned()
+            .chain(vec![self.nimiq, DEFAULT_LEVEL])
+            .max()
+            .unwrap_or(DEFAULT_LEVEL)
+    }
+
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level(metadata.target())
+    }
+}
+
+/// Replaces the log levels used by a dispatch with `reloadable_levels`.
+pub fn set_log_levels(levels: LogLevels) {
+    log::set_max_level(levels.max_level());
+    *LOG_LEVELS.write().unwrap() = levels;
+}
+
 /// Retrieve and set max module width.
 fn max_module_width(target: &str) -> usize {
     let mut max_width = MAX_MODULE_WIDTH.load(Ordering::Acquire);
@@ -53,6 +114,10 @@ pub trait NimiqDispatch {
     /// Setup nimiq modules log level.
     fn level_for_nimiq(self, level: LevelFilter) -> Self;
 
+    /// Uses the log levels set with `set_log_levels`, which can be changed after the dispatch was
+    /// applied. Call `set_log_levels` after `apply`, since that sets the max level to trace.
+    fn reloadable_levels(self) -> Self;
+
     /// Filters out every target not starting with "nimiq".
     /// Note that this excludes beserial and libargon2_sys!
     fn only_nimiq(self) -> Self;
@@ -109,6 +174,11 @@ impl NimiqDispatch for Dispatch {
         builder
     }
 
+    fn reloadable_levels(self) -> Self {
+        self.level(LevelFilter::Trace)
+            .filter(|metadata| LOG_LEVELS.read().unwrap().enabled(metadata))
+    }
+
     fn only_nimiq(self) -> Self {
         self.filter(|metadata| metadata.target().starts_with("nimiq"))
     }
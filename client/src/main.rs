@@ -2,7 +2,6 @@
 extern crate lazy_static;
 #[macro_use]
 extern crate log;
-#[cfg(feature = "deadlock-detection")]
 extern crate parking_lot;
 #[macro_use]
 extern crate serde_derive;
@@ -20,7 +19,10 @@ extern crate nimiq_network_primitives as network_primitives;
 extern crate nimiq_primitives as primitives;
 #[cfg(feature = "rpc-server")]
 extern crate nimiq_rpc_server as rpc_server;
+#[cfg(feature = "rpc-server")]
+extern crate nimiq_block_production as block_production;
 extern crate nimiq_keys as keys;
+extern crate nimiq_genesis as genesis;
 
 mod deadlock;
 mod logging;
@@ -29,34 +31,45 @@ mod cmdline;
 mod static_env;
 mod serialization;
 mod files;
+mod reload;
 
+use std::convert::TryFrom;
 use std::io;
+use std::process;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::collections::HashSet;
-use std::iter::FromIterator;
 use std::env;
+use std::net::{IpAddr, SocketAddr};
 
 use failure::{Error, Fail};
 use fern::log_file;
-use futures::{Future, future};
-use log::Level;
+use futures::{Future, Stream, future};
+use log::{Level, LevelFilter};
+#[cfg(feature = "rpc-server")]
+use parking_lot::RwLock;
+use tokio::runtime::Runtime;
 
 use database::lmdb::{LmdbEnvironment, open};
+use genesis::NetworkFile;
 use lib::client::{Client, ClientBuilder};
 use mempool::MempoolConfig;
 #[cfg(feature = "metrics-server")]
 use metrics_server::metrics_server;
+use network_primitives::networks::register_custom_network;
 use network_primitives::protocol::Protocol;
-use network_primitives::address::NetAddress;
-use network::network_config::{Seed, PeerKeyStore};
+use network_primitives::address::{NetAddress, NetAddressRange};
+use network::Network;
+use network::message_limits::MessageLimits;
+use network::network_config::{AllowedPeer, ClientAddressSource, ConnectionMode, PeerKeyStore, ProxyConfig};
+use network::error::ProxyConfigError;
 use primitives::networks::NetworkId;
 #[cfg(feature = "rpc-server")]
-use rpc_server::{rpc_server, Credentials, JsonRpcConfig};
+use rpc_server::rpc_server;
 
 use crate::cmdline::Options;
-use crate::logging::{DEFAULT_LEVEL, NimiqDispatch};
+use crate::logging::{NimiqDispatch, set_log_levels};
 use crate::logging::force_log_error_cause_chain;
+use crate::reload::ConfigReloader;
 use crate::settings as s;
 use crate::settings::Settings;
 use crate::static_env::ENV;
@@ -76,10 +89,22 @@ pub enum ConfigError {
     InvalidIpAddress,
     #[fail(display = "Username or password missing for RPC server.")]
     MissingRpcCredentials,
-    #[fail(display = "The public key for a seed node is missing. Seed nodes without public_key are currently not implemented.")]
+    #[fail(display = "The public key for a seed node is missing. Seed nodes without public_key are not allowed with `require_seed_keys`.")]
     MissingPublicKey,
     #[fail(display = "Config file not found")]
-    MissingConfigFile
+    MissingConfigFile,
+    #[fail(display = "`disable_inbound` can only be combined with `mode = \"outbound-only\"`, outbound-only nodes can't be started with `--passive`.")]
+    ConflictingConnectionMode,
+    #[fail(display = "Unknown account type in the mempool filter: {}", _0)]
+    InvalidAccountType(String),
+    #[fail(display = "Unknown message type in the message limits: {}", _0)]
+    InvalidMessageType(String),
+    #[fail(display = "Please configure the addresses of the reverse proxies with `trusted_proxies` in the `[reverse-proxy]` section.")]
+    NoTrustedProxy,
+    #[fail(display = "A network file can't be combined with `network` in the `[consensus]` section or `--network`.")]
+    ConflictingNetwork,
+    #[fail(display = "Invalid settings in the `[network.proxy]` section: {}", _0)]
+    InvalidProxy(#[cause] ProxyConfigError),
 }
 
 fn main() {
@@ -92,6 +117,7 @@ fn main() {
 
     if let Err(e) = run() {
         force_log_error_cause_chain(e.as_fail(), Level::Error);
+        process::exit(1);
     }
 }
 
@@ -126,18 +152,15 @@ fn run() -> Result<(), Error> {
         Err(ConfigError::MissingConfigFile)?;
     }
     let settings = Settings::from_file(&config_file)?;
+    // Keep the settings, so that we can tell what changed when reloading the config file.
+    let initial_settings = settings.clone();
 
-    // Setup logging.
+    // Setup logging. The log levels can be changed by reloading the config file.
+    let log_level: Option<LevelFilter> = cmdline.log_level.as_ref()
+        .map(|level| level.parse()).transpose()?;
     let mut dispatch = fern::Dispatch::new()
         .pretty_logging(settings.log.timestamps)
-        .level(DEFAULT_LEVEL)
-        .level_for_nimiq(cmdline.log_level.as_ref()
-            .map(|level| level.parse()).transpose()?
-            .or(settings.log.level)
-            .unwrap_or(DEFAULT_LEVEL));
-    for (module, level) in settings.log.tags.iter().chain(cmdline.log_tags.iter()) {
-        dispatch = dispatch.level_for(module.clone(), level.clone());
-    }
+        .reloadable_levels();
     if let Some(ref filename) = settings.log.file {
         dispatch = dispatch.chain(log_file(filename)?);
     }
@@ -145,6 +168,7 @@ fn run() -> Result<(), Error> {
         dispatch = dispatch.chain(io::stderr());
     }
     dispatch.apply()?;
+    set_log_levels(settings.log.log_levels(log_level, &cmdline.log_tags));
     #[cfg(not(feature = "human-panic"))]
     log_panics::init();
 
@@ -158,8 +182,19 @@ fn run() -> Result<(), Error> {
         unimplemented!();
     }
 
-    // get network ID
-    let network_id = NetworkId::from(cmdline.network.unwrap_or(settings.consensus.network));
+    // get network ID, registering the custom network if a network file is given
+    let network = cmdline.network.or(settings.consensus.network);
+    let network_id = match settings.consensus.network_file {
+        Some(_) if network.is_some() => Err(ConfigError::ConflictingNetwork)?,
+        Some(ref network_file) => {
+            let network_info = NetworkFile::from_file(network_file)?.to_network_info()?;
+            let network_id = network_info.network_id;
+            info!("Loaded custom network '{}' from: {}", network_info.name, network_file);
+            register_custom_network(network_info)?;
+            network_id
+        },
+        None => NetworkId::from(network.unwrap_or_default()),
+    };
 
     // Start database and obtain a 'static reference to it.
     let default_database_settings = s::DatabaseSettings::default();
@@ -181,29 +216,42 @@ fn run() -> Result<(), Error> {
     client_builder.with_network_id(network_id);
 
     // add hostname and port to builder
-    if let Some(hostname) = cmdline.hostname.or(settings.network.host) {
+    if let Some(hostname) = cmdline.hostname.or(settings.network.host.clone()) {
         client_builder.with_hostname(&hostname);
     }
-    else if settings.network.protocol == s::Protocol::Ws || settings.network.protocol == s::Protocol::Wss {
+    else if settings.network.protocol == s::Protocol::Ws || settings.network.protocol == s::Protocol::Wss || settings.network.protocol == s::Protocol::Tcp {
         Err(ConfigError::NoHostname)?
     }
     if let Some(port) = cmdline.port.or(settings.network.port) {
         client_builder.with_port(port);
     }
+    if !settings.network.listen_addresses.is_empty() {
+        client_builder.with_listen_addresses(settings.network.listen_addresses.clone());
+    }
+    if !settings.network.additional_hosts.is_empty() {
+        client_builder.with_additional_hosts(settings.network.additional_hosts.clone());
+    }
 
     // add reverse proxy settings to builder
     if let Some(r) = settings.reverse_proxy {
+        let trusted_proxies: Vec<NetAddressRange> = r.address.into_iter()
+            .map(NetAddressRange::from)
+            .chain(r.trusted_proxies)
+            .collect();
+        if trusted_proxies.is_empty() {
+            Err(ConfigError::NoTrustedProxy)?
+        }
         client_builder.with_reverse_proxy(
             r.port.unwrap_or(s::DEFAULT_REVERSE_PROXY_PORT),
-            r.address,
-            r.header,
+            trusted_proxies,
+            if r.proxy_protocol { ClientAddressSource::ProxyProtocol } else { ClientAddressSource::Header(r.header) },
             r.with_tls_termination
         );
     }
 
     // Add mempool settings to filter
-    if let Some(mempool_settings) = settings.mempool {
-        client_builder.with_mempool_config(MempoolConfig::from(mempool_settings));
+    if let Some(ref mempool_settings) = settings.mempool {
+        client_builder.with_mempool_config(MempoolConfig::try_from(mempool_settings.clone())?);
     }
 
     // Add TLS configuration, if present
@@ -217,17 +265,59 @@ fn run() -> Result<(), Error> {
         }
     }
 
+    // Route outbound connections through a SOCKS5 proxy
+    if let Some(ref proxy_settings) = settings.network.proxy {
+        client_builder.with_proxy(ProxyConfig::try_from(proxy_settings.clone())?);
+    }
+
+    // Passive nodes never dial peers, outbound-only nodes don't listen. `disable_inbound` is the
+    // older way to configure outbound-only nodes. `--passive` overrides the mode of the config
+    // file, unless the node is configured to be outbound-only.
+    let connection_mode = match (settings.network.mode, settings.network.disable_inbound) {
+        (Some(mode), false) => mode,
+        (None, false) => s::ConnectionMode::default(),
+        (Some(s::ConnectionMode::OutboundOnly), true) | (None, true) => s::ConnectionMode::OutboundOnly,
+        (Some(_), true) => Err(ConfigError::ConflictingConnectionMode)?,
+    };
+    client_builder.with_connection_mode(match (cmdline.passive, connection_mode) {
+        (true, s::ConnectionMode::OutboundOnly) => Err(ConfigError::ConflictingConnectionMode)?,
+        (true, _) => ConnectionMode::Passive,
+        (false, connection_mode) => connection_mode.into(),
+    });
+
+    // Limit the messages peers may send us
+    if let Some(ref limits_settings) = settings.network.limits {
+        client_builder.with_message_limits(MessageLimits::try_from(limits_settings.clone())?);
+    }
+
+    // Parse the peer scoring options
+    if let Some(ref scoring_settings) = settings.network.scoring {
+        client_builder.with_scoring(s::ScoringSettings::try_from(scoring_settings.clone())?);
+    }
+
+    // Name server to resolve DNS seeds with. The port defaults to 53.
+    if let Some(ref dns_resolver) = settings.network.dns_resolver {
+        let dns_resolver = SocketAddr::from_str(dns_resolver)
+            .or_else(|_| IpAddr::from_str(dns_resolver).map(|ip| SocketAddr::new(ip, 53)))
+            .map_err(|_| ConfigError::InvalidIpAddress)?;
+        client_builder.with_dns_resolver(dns_resolver);
+    }
+
+    // Directory for traffic captures
+    if let Some(ref capture_dir) = settings.network.capture_dir {
+        client_builder.with_capture_dir(PathBuf::from(capture_dir));
+    }
+
     // Parse additional seed nodes and add them
-    let seeds = settings.network.seed_nodes.iter()
-        .map(|s| s::Seed::try_from(s.clone()))
-        .collect::<Result<Vec<Seed>, SeedError>>()?;
-    if seeds.iter().any(|s| match s {
-        Seed::Peer(uri) => uri.public_key().is_none(),
-        _ => false
-    }) {
-        Err(ConfigError::MissingPublicKey)?;
+    client_builder.with_seeds(settings.network.seeds()?);
+
+    // Parse the allowed peers. Configuring them switches to private network mode
+    if let Some(ref allowed_peers) = settings.network.allowed_peers {
+        let allowed_peers = allowed_peers.iter()
+            .map(|p| s::AllowedPeer::try_from(p.clone()))
+            .collect::<Result<Vec<AllowedPeer>, SeedError>>()?;
+        client_builder.with_allowed_peers(allowed_peers);
     }
-    client_builder.with_seeds(seeds);
 
     // Setup client future to initialize and connect
     let client = client_builder.build_client()?;
@@ -238,6 +328,23 @@ fn run() -> Result<(), Error> {
     // Additional futures we want to run.
     let mut other_futures: Vec<Box<dyn Future<Item=(), Error=()> + Send + Sync + 'static>> = Vec::new();
 
+    // Apply changes to the config file at runtime.
+    #[allow(unused_mut)]
+    let mut config_reloader = ConfigReloader::new(config_file.clone(), initial_settings, log_level, cmdline.log_tags.clone(),
+        Arc::clone(&consensus.mempool), Arc::clone(&consensus.network));
+    #[cfg(feature = "rpc-server")]
+    let mempool_settings = settings.mempool.as_ref();
+    #[cfg(feature = "rpc-server")]
+    let rpc_config = settings.rpc_server.as_ref()
+        .map(|rpc_server| rpc_server.json_rpc_config(mempool_settings)).transpose()?
+        .map(|config| Arc::new(RwLock::new(config)));
+    #[cfg(feature = "rpc-server")] {
+        if let Some(ref rpc_config) = rpc_config {
+            config_reloader.set_rpc_config(Arc::clone(rpc_config));
+        }
+    }
+    let config_reloader = Arc::new(config_reloader);
+
     // start RPC server if enabled
     #[cfg(feature = "rpc-server")] {
         if let Some(rpc_settings) = settings.rpc_server {
@@ -247,12 +354,8 @@ fn run() -> Result<(), Error> {
                 .unwrap_or_else(|| NetAddress::from_str("127.0.0.1").unwrap())
                 .into_ip_address().unwrap();
             let port = rpc_settings.port.unwrap_or(s::DEFAULT_RPC_PORT);
-            let credentials = match (rpc_settings.username, rpc_settings.password) {
-                (Some(username), Some(password)) => Some(Credentials::new(&username, &password)),
-                (None, None) => None,
-                _ => Err(ConfigError::MissingRpcCredentials)?
-            };
-            if credentials.is_none() {
+            let rpc_config = rpc_config.expect("The RPC config is parsed if the RPC server is enabled");
+            if rpc_config.read().credentials.is_none() {
                 warn!("Running RPC server without authentication! Consider setting a username and password.")
             }
             if !rpc_settings.corsdomain.is_empty() {
@@ -262,12 +365,8 @@ fn run() -> Result<(), Error> {
                 warn!("'allowip' for RPC server is currently not implemented!");
             }
             info!("Starting RPC server listening on port {}", port);
-            other_futures.push(rpc_server(Arc::clone(&consensus), bind, port, JsonRpcConfig {
-                credentials,
-                methods: HashSet::from_iter(rpc_settings.methods),
-                allowip: (), // TODO
-                corsdomain: rpc_settings.corsdomain
-            })?);
+            let config_reload = Arc::clone(&config_reloader);
+            other_futures.push(rpc_server(Arc::clone(&consensus), bind, port, rpc_config, Some(config_reload))?);
         }
     }
     // If the RPC server is enabled, but the client is not compiled with it, inform the user
@@ -299,14 +398,46 @@ fn run() -> Result<(), Error> {
         }
     }
 
-    // Run client and other futures
-    tokio::run(
-        client.and_then(|c| c.connect()) // Run Nimiq client
-            .map(|_| info!("Client initialized")) // Map Result to None
-            .map_err(|e| error!("Client initialization failed: {}", e))
-            .and_then( move |_| future::join_all(other_futures)) // Run other futures (e.g. RPC server)
-            .map(|_| info!("Other futures finished"))
-    );
+    // Shut down cleanly on Ctrl-C or SIGTERM.
+    let shutdown = shutdown_on_signal(Arc::clone(&consensus.network));
+
+    // Reload the config file on SIGHUP.
+    #[cfg(unix)]
+    let sighup = reload::reload_on_sighup(Arc::clone(&config_reloader));
+
+    // Run client and other futures until the client is shut down or fails to initialize.
+    let mut runtime = Runtime::new()?;
+    let client = client.and_then(|c| c.connect()) // Run Nimiq client
+        .map_err(|e| Error::from(e.context("Client initialization failed")))
+        .and_then(move |_| {
+            info!("Client initialized");
+            #[cfg(unix)]
+            tokio::spawn(sighup);
+            tokio::spawn(future::join_all(other_futures) // Run other futures (e.g. RPC server)
+                .map(|_| info!("Other futures finished")));
+            future::empty()
+        });
+    let result = runtime.block_on(client.select(shutdown).map(|_| ()).map_err(|(e, _)| e));
+
+    // Stop all tasks, so that everything they hold on to is dropped before the database is closed.
+    runtime.shutdown_now().wait().ok();
+
+    result
+}
 
-    Ok(())
+/// Disconnects from the network, which writes the peer address book to the database, once
+/// Ctrl-C or, on Unix, SIGTERM is received. Fails if the signals can't be listened for.
+fn shutdown_on_signal(network: Arc<Network>) -> impl Future<Item=(), Error=Error> + Send {
+    let signals = tokio_signal::ctrl_c().flatten_stream();
+    #[cfg(unix)]
+    let signals = signals.select(tokio_signal::unix::Signal::new(tokio_signal::unix::SIGTERM)
+        .flatten_stream()
+        .map(|_| ()));
+
+    signals.into_future()
+        .map(move |_| {
+            info!("Shutting down");
+            network.disconnect();
+        })
+        .map_err(|(e, _)| Error::from(e.context("Failed to listen for shutdown signals")))
 }
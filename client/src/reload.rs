@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use failure::Error;
+use futures::{Future, Stream};
+use log::LevelFilter;
+#[cfg(feature = "rpc-server")]
+use parking_lot::RwLock;
+
+use mempool::Mempool;
+use mempool::filter::Rules;
+use network::Network;
+use network::address::peer_address_book::PeerAddressBook;
+#[cfg(feature = "rpc-server")]
+use rpc_server::{ConfigReload, ConfigReloadReport, JsonRpcConfig};
+
+use crate::logging;
+use crate::settings as s;
+use crate::settings::Settings;
+
+/// The settings that changed with a reload of the config file.
+#[derive(Debug, Default)]
+pub(crate) struct ReloadReport {
+    /// Settings that were applied while the client is running.
+    pub applied: Vec<String>,
+    /// Settings that changed, but are overridden on the command line.
+    pub overridden: Vec<String>,
+    /// Settings that only take effect after a restart.
+    pub restart_required: Vec<String>,
+}
+
+/// What the client is currently running with.
+struct ReloadState {
+    /// The settings the client was started with.
+    started: Settings,
+    /// The settings of the last successful reload.
+    current: Settings,
+    /// All seeds that were added to the address book.
+    seeds: Vec<s::Seed>,
+}
+
+/// Re-reads the config file and applies the settings that can be changed while the client is
+/// running: the log levels, the mempool filter rules, the RPC server configuration and
+/// additional seeds.
+pub(crate) struct ConfigReloader {
+    config_file: PathBuf,
+    state: Mutex<ReloadState>,
+    /// Log levels from the command line, which take precedence over the config file.
+    log_level: Option<LevelFilter>,
+    log_tags: HashMap<String, LevelFilter>,
+    mempool: Arc<Mempool<'static>>,
+    network: Arc<Network>,
+    #[cfg(feature = "rpc-server")]
+    rpc_config: Option<Arc<RwLock<JsonRpcConfig>>>,
+}
+
+impl ConfigReloader {
+    pub fn new(config_file: PathBuf, settings: Settings, log_level: Option<LevelFilter>, log_tags: HashMap<String, LevelFilter>,
+               mempool: Arc<Mempool<'static>>, network: Arc<Network>) -> Self {
+        ConfigReloader {
+            config_file,
+            state: Mutex::new(ReloadState {
+                seeds: settings.network.seed_nodes.clone(),
+                started: settings.clone(),
+                current: settings,
+            }),
+            log_level,
+            log_tags,
+            mempool,
+            network,
+            #[cfg(feature = "rpc-server")]
+            rpc_config: None,
+        }
+    }
+
+    /// Sets the configuration of the running RPC server, which is replaced on reload.
+    #[cfg(feature = "rpc-server")]
+    pub fn set_rpc_config(&mut self, rpc_config: Arc<RwLock<JsonRpcConfig>>) {
+        self.rpc_config = Some(rpc_config);
+    }
+
+    /// Re-reads the config file and applies the changed settings that can be changed at runtime.
+    /// Nothing is applied if the config file is invalid.
+    pub fn reload(&self) -> Result<ReloadReport, Error> {
+        let settings = Settings::from_file(&self.config_file)?;
+
+        // Parse everything before applying anything.
+        let log_levels = settings.log.log_levels(self.log_level, &self.log_tags);
+        let seeds = settings.network.seeds()?;
+        let filter = settings.mempool.as_ref().and_then(|mempool| mempool.filter.clone());
+        let filter_rules = filter.clone().map(Rules::try_from).transpose()?;
+        #[cfg(feature = "rpc-server")]
+        let rpc_config = settings.rpc_server.as_ref()
+            .map(|rpc_server| rpc_server.json_rpc_config(settings.mempool.as_ref()))
+            .transpose()?;
+
+        let mut state = self.state.lock().unwrap();
+        let mut report = ReloadReport::default();
+
+        // Log levels
+        let log_level_changed = settings.log.level != state.current.log.level;
+        let log_tags_changed = settings.log.tags != state.current.log.tags;
+        if log_level_changed || log_tags_changed {
+            logging::set_log_levels(log_levels);
+        }
+        if log_level_changed {
+            if self.log_level.is_some() {
+                report.overridden.push("log.level".to_string());
+            } else {
+                report.applied.push("log.level".to_string());
+            }
+        }
+        if log_tags_changed {
+            let mut modules: Vec<&String> = settings.log.tags.iter()
+                .filter(|(module, level)| state.current.log.tags.get(*module) != Some(level))
+                .map(|(module, _)| module)
+                .chain(state.current.log.tags.keys().filter(|module| !settings.log.tags.contains_key(*module)))
+                .collect();
+            modules.sort();
+            for module in modules {
+                if self.log_tags.contains_key(module) {
+                    report.overridden.push(format!("log.tags.{}", module));
+                } else {
+                    report.applied.push(format!("log.tags.{}", module));
+                }
+            }
+        }
+
+        // Mempool filter rules
+        if filter != state.current.mempool.as_ref().and_then(|mempool| mempool.filter.clone()) {
+            self.mempool.set_filter_rules(filter_rules.unwrap_or_default());
+            report.applied.push("mempool.filter".to_string());
+        }
+
+        // Seeds can be added, but removed seeds stay in the address book.
+        let mut new_seeds = Vec::new();
+        for (seed_settings, seed) in settings.network.seed_nodes.iter().zip(seeds) {
+            if !state.seeds.contains(seed_settings) {
+                state.seeds.push(seed_settings.clone());
+                new_seeds.push(seed);
+            }
+        }
+        if !new_seeds.is_empty() {
+            PeerAddressBook::add_seeds(&self.network.addresses, &new_seeds);
+            report.applied.push("network.seed_nodes".to_string());
+        }
+        if state.seeds.iter().any(|seed| !settings.network.seed_nodes.contains(seed)) {
+            report.restart_required.push("network.seed_nodes".to_string());
+        }
+
+        // RPC server
+        #[cfg(feature = "rpc-server")] {
+            if let (Some(rpc_config), Some(new_config)) = (&self.rpc_config, rpc_config) {
+                if settings.rpc_server.as_ref().map(Self::rpc_config_settings)
+                    != state.current.rpc_server.as_ref().map(Self::rpc_config_settings) {
+                    *rpc_config.write() = new_config;
+                    report.applied.push("rpc-server".to_string());
+                }
+            }
+        }
+
+        // Everything else needs a restart. Compare against the settings the client was started
+        // with, so that pending changes are reported until the client is restarted.
+        let started = &state.started;
+        let mut network = settings.network.clone();
+        network.seed_nodes = started.network.seed_nodes.clone();
+        if network != started.network {
+            report.restart_required.push("network".to_string());
+        }
+        if settings.consensus != started.consensus {
+            report.restart_required.push("consensus".to_string());
+        }
+        if settings.database != started.database {
+            report.restart_required.push("database".to_string());
+        }
+        if settings.peer_key_file != started.peer_key_file {
+            report.restart_required.push("peer-key-file".to_string());
+        }
+        if settings.reverse_proxy != started.reverse_proxy {
+            report.restart_required.push("reverse-proxy".to_string());
+        }
+        if settings.metrics_server != started.metrics_server {
+            report.restart_required.push("metrics-server".to_string());
+        }
+        match (&settings.rpc_server, &started.rpc_server) {
+            (Some(new), Some(old)) => {
+                if new.bind != old.bind {
+                    report.restart_required.push("rpc-server.bind".to_string());
+                }
+                if new.port != old.port {
+                    report.restart_required.push("rpc-server.port".to_string());
+                }
+            },
+            (None, None) => (),
+            _ => report.restart_required.push("rpc-server".to_string()),
+        }
+        if settings.log.timestamps != started.log.timestamps || settings.log.file != started.log.file
+            || settings.log.statistics != started.log.statistics {
+            report.restart_required.push("log".to_string());
+        }
+        // A `[mempool]` section with only a filter is the same as none at all.
+        let without_filter = |mempool: &Option<s::MempoolSettings>| s::MempoolSettings {
+            filter: None,
+            ..mempool.clone().unwrap_or_default()
+        };
+        if without_filter(&settings.mempool) != without_filter(&started.mempool) {
+            report.restart_required.push("mempool".to_string());
+        }
+
+        state.current = settings;
+        Ok(report)
+    }
+
+    /// Reloads the config file and logs the outcome.
+    pub fn reload_and_log(&self) {
+        match self.reload() {
+            Ok(ref report) if report.applied.is_empty() && report.overridden.is_empty() && report.restart_required.is_empty() => {
+                info!("Config file reloaded, no changes");
+            },
+            Ok(report) => {
+                if !report.applied.is_empty() {
+                    info!("Config file reloaded, applied: {}", report.applied.join(", "));
+                }
+                if !report.overridden.is_empty() {
+                    info!("Config file reloaded, overridden on the command line: {}", report.overridden.join(", "));
+                }
+                if !report.restart_required.is_empty() {
+                    warn!("Changes to these settings require a restart: {}", report.restart_required.join(", "));
+                }
+            },
+            Err(e) => warn!("Failed to reload config file, keeping current settings: {}", e),
+        }
+    }
+
+    /// The RPC server settings that can be changed while the server is running.
+    #[cfg(feature = "rpc-server")]
+    fn rpc_config_settings(settings: &s::RpcServerSettings) -> s::RpcServerSettings {
+        s::RpcServerSettings {
+            bind: None,
+            port: None,
+            ..settings.clone()
+        }
+    }
+}
+
+#[cfg(feature = "rpc-server")]
+impl ConfigReload for ConfigReloader {
+    fn reload_config(&self) -> Result<ConfigReloadReport, Error> {
+        info!("Reloading config file on RPC request: {}", self.config_file.display());
+        let report = self.reload()?;
+        Ok(ConfigReloadReport {
+            applied: report.applied,
+            overridden: report.overridden,
+            restart_required: report.restart_required,
+        })
+    }
+}
+
+/// Reloads the config file when SIGHUP is received.
+#[cfg(unix)]
+pub(crate) fn reload_on_sighup(reloader: Arc<ConfigReloader>) -> impl Future<Item=(), Error=()> + Send {
+    tokio_signal::unix::Signal::new(tokio_signal::unix::SIGHUP)
+        .flatten_stream()
+        .map_err(|e| error!("Failed to listen for SIGHUP: {}", e))
+        .for_each(move |_| {
+            info!("Received SIGHUP, reloading config file: {}", reloader.config_file.display());
+            reloader.reload_and_log();
+            Ok(())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempdir::TempDir;
+
+    use database::volatile::VolatileEnvironment;
+    use lib::client::ClientBuilder;
+    use network::network_config::PeerKeyStore;
+    use network_primitives::protocol::Protocol;
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [network]
+        host = "seed.nimiq.local"
+        port = 8443
+
+        [log]
+        level = "info"
+    "#;
+
+    struct TestReloader {
+        reloader: ConfigReloader,
+        config_file: PathBuf,
+        _dir: TempDir,
+    }
+
+    impl TestReloader {
+        fn new(config: &str, log_level: Option<LevelFilter>) -> Self {
+            let dir = TempDir::new("nimiq-reload").unwrap();
+            let config_file = dir.path().join("client.toml");
+            fs::write(&config_file, config).unwrap();
+            let settings = Settings::from_file(&config_file).unwrap();
+
+            let env = Box::leak(Box::new(VolatileEnvironment::new(10).unwrap()));
+            let peer_key_store = PeerKeyStore::new(dir.path().join("peer_key.dat").to_str().unwrap().to_string());
+            let consensus = ClientBuilder::new(Protocol::Dumb, env, peer_key_store).build_consensus().unwrap();
+
+            let reloader = ConfigReloader::new(config_file.clone(), settings, log_level, HashMap::new(),
+                Arc::clone(&consensus.mempool), Arc::clone(&consensus.network));
+            TestReloader { reloader, config_file, _dir: dir }
+        }
+
+        fn reload(&self, config: &str) -> Result<ReloadReport, Error> {
+            fs::write(&self.config_file, config).unwrap();
+            self.reloader.reload()
+        }
+    }
+
+    #[test]
+    fn it_reports_no_changes() {
+        let reloader = TestReloader::new(CONFIG, None);
+        let report = reloader.reload(CONFIG).unwrap();
+        assert!(report.applied.is_empty());
+        assert!(report.overridden.is_empty());
+        assert!(report.restart_required.is_empty());
+    }
+
+    #[test]
+    fn it_applies_runtime_settings_and_reports_restarts() {
+        let reloader = TestReloader::new(CONFIG, None);
+        let report = reloader.reload(r#"
+            [network]
+            host = "seed.nimiq.local"
+            port = 8444
+
+            [log]
+            level = "debug"
+
+            [mempool.filter]
+            tx_fee = 1
+        "#).unwrap();
+        assert_eq!(report.applied, vec!["log.level", "mempool.filter"]);
+        assert!(report.overridden.is_empty());
+        assert_eq!(report.restart_required, vec!["network"]);
+
+        // Pending restarts are reported until the client is restarted.
+        let report = reloader.reload(r#"
+            [network]
+            host = "seed.nimiq.local"
+            port = 8444
+
+            [log]
+            level = "debug"
+
+            [mempool.filter]
+            tx_fee = 1
+        "#).unwrap();
+        assert!(report.applied.is_empty());
+        assert_eq!(report.restart_required, vec!["network"]);
+    }
+
+    #[test]
+    fn it_reports_log_level_overridden_on_the_command_line() {
+        let reloader = TestReloader::new(CONFIG, Some(LevelFilter::Trace));
+        let report = reloader.reload(r#"
+            [network]
+            host = "seed.nimiq.local"
+            port = 8443
+
+            [log]
+            level = "debug"
+        "#).unwrap();
+        assert!(report.applied.is_empty());
+        assert_eq!(report.overridden, vec!["log.level"]);
+        assert!(report.restart_required.is_empty());
+    }
+
+    #[test]
+    fn it_reports_log_tags_overridden_on_the_command_line() {
+        let mut reloader = TestReloader::new(CONFIG, None);
+        reloader.reloader.log_tags.insert("nimiq_network".to_string(), LevelFilter::Trace);
+        let report = reloader.reload(r#"
+            [network]
+            host = "seed.nimiq.local"
+            port = 8443
+
+            [log]
+            level = "info"
+            tags = { nimiq_network = "debug", nimiq_mempool = "debug" }
+        "#).unwrap();
+        assert_eq!(report.applied, vec!["log.tags.nimiq_mempool"]);
+        assert_eq!(report.overridden, vec!["log.tags.nimiq_network"]);
+        assert!(report.restart_required.is_empty());
+    }
+
+    #[test]
+    fn it_keeps_the_settings_of_an_invalid_config_file() {
+        let reloader = TestReloader::new(CONFIG, None);
+        assert!(reloader.reload("[log]\nlevel = ").is_err());
+
+        // Nothing was applied, so the original config file is unchanged.
+        let report = reloader.reload(CONFIG).unwrap();
+        assert!(report.applied.is_empty());
+        assert!(report.restart_required.is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::fmt::Display;
 use std::str::FromStr;
 
@@ -7,18 +8,30 @@ use url::Url;
 use hex::FromHex;
 use failure::Fail;
 
-use mempool::filter::{MempoolFilter, Rules};
+#[cfg(feature = "rpc-server")]
+use block_production::policy::DefaultSelectionPolicy;
+use log::LevelFilter;
+#[cfg(feature = "rpc-server")]
+use rpc_server::{Credentials, JsonRpcConfig};
+
+use mempool::filter::{AccountTypeRules, MempoolFilter, Rules};
 use mempool::MempoolConfig;
 use network_primitives::protocol::Protocol;
-use network_primitives::address::SeedList;
-use network_primitives::address::PeerUri;
-use network::network_config::Seed;
+use network_primitives::address::{DnsSeed, SeedList};
+use network_primitives::address::{PeerId, PeerUri};
+use network::message_limits::{MessageLimit, MessageLimits, MessageType};
+use network::network_config::{AllowedPeer, ConnectionMode, ProxyConfig, ScoringConfig, Seed};
+use primitives::account::AccountType;
 use primitives::coin::Coin;
 use primitives::networks::NetworkId;
-use keys::PublicKey;
+use keys::{Address, PublicKey};
 
+use crate::ConfigError;
+use crate::logging::{DEFAULT_LEVEL, LogLevels};
 use crate::settings as s;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(feature = "rpc-server")]
+use std::sync::Arc;
 
 /// Converts protocol from settings into 'normal' protocol
 impl From<s::Protocol> for Protocol {
@@ -27,7 +40,7 @@ impl From<s::Protocol> for Protocol {
             s::Protocol::Dumb => Protocol::Dumb,
             s::Protocol::Ws => Protocol::Ws,
             s::Protocol::Wss => Protocol::Wss,
-            s::Protocol::Rtc => Protocol::Rtc,
+            s::Protocol::Tcp => Protocol::Tcp,
         }
     }
 }
@@ -39,32 +52,179 @@ impl From<s::Network> for NetworkId {
             s::Network::Main => NetworkId::Main,
             s::Network::Test => NetworkId::Test,
             s::Network::Dev => NetworkId::Dev,
+            s::Network::Regtest => NetworkId::Regtest,
+        }
+    }
+}
+
+/// Converts the account type from settings into 'normal' account type
+impl From<s::AccountType> for AccountType {
+    fn from(account_type: s::AccountType) -> AccountType {
+        match account_type {
+            s::AccountType::Basic => AccountType::Basic,
+            s::AccountType::Vesting => AccountType::Vesting,
+            s::AccountType::Htlc => AccountType::HTLC,
+        }
+    }
+}
+
+/// Convert mempool filter settings
+impl TryFrom<s::MempoolFilterSettings> for Rules {
+    type Error = ConfigError;
+
+    fn try_from(f: s::MempoolFilterSettings) -> Result<Rules, ConfigError> {
+        let mut account_types = BTreeMap::new();
+        for (account_type, rules) in f.account_types {
+            let account_type = s::AccountType::from_str(&account_type)
+                .map_err(|_| ConfigError::InvalidAccountType(account_type))?;
+            account_types.insert(AccountType::from(account_type), AccountTypeRules {
+                reject: rules.reject,
+                tx_fee_per_byte: rules.tx_fee_per_byte,
+                tx_value: rules.tx_value,
+                data_size_max: rules.data_size_max,
+            });
         }
+        Ok(Rules {
+            tx_fee: f.tx_fee,
+            tx_fee_per_byte: f.tx_fee_per_byte,
+            tx_value: f.tx_value,
+            tx_value_total: f.tx_value_total,
+            contract_fee: f.contract_fee,
+            contract_fee_per_byte: f.contract_fee_per_byte,
+            contract_value: f.contract_value,
+            creation_fee: f.creation_fee,
+            creation_fee_per_byte: f.creation_fee_per_byte,
+            creation_value: f.creation_value,
+            sender_balance: f.sender_balance,
+            recipient_balance: f.recipient_balance,
+            sender_blacklist: f.sender_blacklist.into_iter().collect(),
+            sender_whitelist: f.sender_whitelist.into_iter().collect(),
+            recipient_blacklist: f.recipient_blacklist.into_iter().collect(),
+            recipient_whitelist: f.recipient_whitelist.into_iter().collect(),
+            data_size_max: f.data_size_max,
+            account_types,
+        })
     }
 }
 
 /// Convert mempool settings
-impl From<s::MempoolSettings> for MempoolConfig {
-    fn from(mempool_settings: s::MempoolSettings) -> MempoolConfig {
-        let rules = if let Some(f) = mempool_settings.filter {
-            Rules {
-                tx_fee: f.tx_fee,
-                tx_fee_per_byte: f.tx_fee_per_byte,
-                tx_value: f.tx_value,
-                tx_value_total: f.tx_value_total,
-                contract_fee: f.contract_fee,
-                contract_fee_per_byte: f.contract_fee_per_byte,
-                contract_value: f.contract_value,
-                creation_fee: f.creation_fee,
-                creation_fee_per_byte: f.creation_fee_per_byte,
-                creation_value: f.creation_value,
-                sender_balance: f.sender_balance,
-                recipient_balance: f.recipient_balance,
-            }
-        } else { Rules::default() };
-        MempoolConfig {
+impl TryFrom<s::MempoolSettings> for MempoolConfig {
+    type Error = ConfigError;
+
+    fn try_from(mempool_settings: s::MempoolSettings) -> Result<MempoolConfig, ConfigError> {
+        let rules = mempool_settings.filter.map(Rules::try_from).transpose()?.unwrap_or_default();
+        Ok(MempoolConfig {
             filter_rules: rules,
-            filter_limit: mempool_settings.blacklist_limit.unwrap_or(MempoolFilter::DEFAULT_BLACKLIST_SIZE)
+            filter_limit: mempool_settings.blacklist_limit.unwrap_or(MempoolFilter::DEFAULT_BLACKLIST_SIZE),
+            replace_by_fee: mempool_settings.replace_by_fee,
+            replacement_fee_bump: mempool_settings.replacement_fee_bump.unwrap_or(MempoolConfig::DEFAULT_REPLACEMENT_FEE_BUMP),
+            size_max: mempool_settings.size_max.unwrap_or(MempoolConfig::DEFAULT_SIZE_MAX),
+            total_size_max: mempool_settings.total_size_max.unwrap_or(MempoolConfig::DEFAULT_TOTAL_SIZE_MAX),
+            transactions_per_sender_max: mempool_settings.transactions_per_sender_max.unwrap_or(MempoolConfig::DEFAULT_TRANSACTIONS_PER_SENDER_MAX),
+            free_transactions_per_sender_max: mempool_settings.free_transactions_per_sender_max.unwrap_or(MempoolConfig::DEFAULT_FREE_TRANSACTIONS_PER_SENDER_MAX),
+            transaction_relay_fee_min: mempool_settings.transaction_relay_fee_min.unwrap_or(MempoolConfig::DEFAULT_TRANSACTION_RELAY_FEE_MIN),
+        })
+    }
+}
+
+/// Converts connection mode from settings into 'normal' connection mode
+impl From<s::ConnectionMode> for ConnectionMode {
+    fn from(mode: s::ConnectionMode) -> ConnectionMode {
+        match mode {
+            s::ConnectionMode::Normal => ConnectionMode::Normal,
+            s::ConnectionMode::Passive => ConnectionMode::Passive,
+            s::ConnectionMode::OutboundOnly => ConnectionMode::OutboundOnly,
+        }
+    }
+}
+
+/// Convert proxy settings, rejecting hosts that can't be used in a proxy URL
+impl TryFrom<s::ProxySettings> for ProxyConfig {
+    type Error = ConfigError;
+
+    fn try_from(settings: s::ProxySettings) -> Result<ProxyConfig, ConfigError> {
+        let config = ProxyConfig {
+            host: settings.host,
+            port: settings.port.unwrap_or(ProxyConfig::DEFAULT_PORT),
+            username: settings.username,
+            password: settings.password,
+            remote_dns: settings.remote_dns,
+        };
+        config.url().map_err(ConfigError::InvalidProxy)?;
+        Ok(config)
+    }
+}
+
+/// Converts the message type from settings into 'normal' message type
+impl From<s::MessageType> for MessageType {
+    fn from(ty: s::MessageType) -> MessageType {
+        match ty {
+            s::MessageType::Version => MessageType::Version,
+            s::MessageType::Inv => MessageType::Inv,
+            s::MessageType::GetData => MessageType::GetData,
+            s::MessageType::GetHeader => MessageType::GetHeader,
+            s::MessageType::NotFound => MessageType::NotFound,
+            s::MessageType::GetBlocks => MessageType::GetBlocks,
+            s::MessageType::Block => MessageType::Block,
+            s::MessageType::Header => MessageType::Header,
+            s::MessageType::Tx => MessageType::Tx,
+            s::MessageType::Mempool => MessageType::Mempool,
+            s::MessageType::Reject => MessageType::Reject,
+            s::MessageType::Subscribe => MessageType::Subscribe,
+            s::MessageType::Addr => MessageType::Addr,
+            s::MessageType::GetAddr => MessageType::GetAddr,
+            s::MessageType::Ping => MessageType::Ping,
+            s::MessageType::Pong => MessageType::Pong,
+            s::MessageType::Signal => MessageType::Signal,
+            s::MessageType::GetChainProof => MessageType::GetChainProof,
+            s::MessageType::ChainProof => MessageType::ChainProof,
+            s::MessageType::GetAccountsProof => MessageType::GetAccountsProof,
+            s::MessageType::AccountsProof => MessageType::AccountsProof,
+            s::MessageType::GetAccountsTreeChunk => MessageType::GetAccountsTreeChunk,
+            s::MessageType::AccountsTreeChunk => MessageType::AccountsTreeChunk,
+            s::MessageType::GetTransactionsProof => MessageType::GetTransactionsProof,
+            s::MessageType::TransactionsProof => MessageType::TransactionsProof,
+            s::MessageType::GetTransactionReceipts => MessageType::GetTransactionReceipts,
+            s::MessageType::TransactionReceipts => MessageType::TransactionReceipts,
+            s::MessageType::GetBlockProof => MessageType::GetBlockProof,
+            s::MessageType::BlockProof => MessageType::BlockProof,
+            s::MessageType::GetHead => MessageType::GetHead,
+            s::MessageType::Head => MessageType::Head,
+            s::MessageType::VerAck => MessageType::VerAck,
+        }
+    }
+}
+
+/// Convert message limit settings. Limits configured for a message type replace its defaults.
+impl TryFrom<s::MessageLimitsSettings> for MessageLimits {
+    type Error = ConfigError;
+
+    fn try_from(settings: s::MessageLimitsSettings) -> Result<MessageLimits, ConfigError> {
+        let mut limits = MessageLimits::default();
+        if let Some(send_queue_size) = settings.send_queue_size {
+            limits.set_send_queue_size(send_queue_size);
+        }
+        for (ty, limit) in settings.messages {
+            let ty = s::MessageType::from_str(&ty)
+                .map_err(|_| ConfigError::InvalidMessageType(ty))?;
+            limits.set(ty.into(), MessageLimit::new(limit.per_minute, limit.max_size));
+        }
+        Ok(limits)
+    }
+}
+
+/// Convert block template settings. Transactions are free below the threshold of the mempool.
+#[cfg(feature = "rpc-server")]
+impl s::BlockTemplateSettings {
+    fn selection_policy(self, mempool: Option<&s::MempoolSettings>) -> DefaultSelectionPolicy {
+        DefaultSelectionPolicy {
+            fee_per_byte_min: self.fee_per_byte_min,
+            free_space: self.free_space,
+            free_fee_per_byte: mempool.and_then(|mempool| mempool.transaction_relay_fee_min)
+                .unwrap_or(MempoolConfig::DEFAULT_TRANSACTION_RELAY_FEE_MIN),
+            always_include: self.always_include.into_iter().collect(),
+            never_include: self.never_include.into_iter().collect(),
+            transactions_per_sender_max: self.transactions_per_sender_max,
         }
     }
 }
@@ -112,11 +272,103 @@ impl s::Seed {
             s::Seed::List(s::SeedList{list, public_key}) => {
                 Seed::List(Box::new(SeedList::new(Url::from_str(&list)?, public_key
                     .map(PublicKey::from_hex).transpose()?)))
+            },
+            s::Seed::Dns(s::SeedDns{dns, protocol, port}) => {
+                Seed::Dns(Box::new(DnsSeed::new(dns, protocol.into(), port)))
             }
         })
     }
 }
 
+impl s::ScoringSettings {
+    pub fn try_from(settings: s::ScoringSettings) -> Result<ScoringConfig, SeedError> {
+        let parse_peer_ids = |peer_ids: Vec<String>| peer_ids.iter()
+            .map(|peer_id| PeerId::from_str(peer_id).map_err(|_| PeerUriError::InvalidPeerId))
+            .collect::<Result<HashSet<PeerId>, PeerUriError>>();
+
+        let mut scoring = ScoringConfig::default();
+        scoring.preferred_peers = parse_peer_ids(settings.preferred_peers)?;
+        scoring.pinned_peers = parse_peer_ids(settings.pinned_peers)?;
+        if let Some(protocol_distribution_target) = settings.protocol_distribution_target {
+            scoring.protocol_distribution_target = protocol_distribution_target;
+        }
+        scoring.subnet_target = settings.subnet_target;
+        if let Some(latency_weight) = settings.latency_weight {
+            scoring.latency_weight = latency_weight.max(0.0).min(1.0);
+        }
+        Ok(scoring)
+    }
+}
+
+impl s::AllowedPeer {
+    pub fn try_from(allowed_peer: s::AllowedPeer) -> Result<AllowedPeer, SeedError> {
+        Ok(match allowed_peer {
+            s::AllowedPeer::Uri(s::SeedUri{uri}) => {
+                let peer_uri = PeerUri::from_str(&uri)?;
+                // Allowed peers are dialed like seed nodes, but must be identifiable.
+                if peer_uri.public_key().is_none() && peer_uri.peer_id().is_none() {
+                    return Err(PeerUriError::SeedNodeMissingPublicKey.into());
+                }
+                peer_uri.as_seed_peer_address()?;
+                AllowedPeer::Peer(Box::new(peer_uri))
+            },
+            s::AllowedPeer::PeerId(s::AllowedPeerId{peer_id}) => {
+                AllowedPeer::PeerId(PeerId::from_str(&peer_id).map_err(|_| PeerUriError::InvalidPeerId)?)
+            },
+        })
+    }
+}
+
+impl s::NetworkSettings {
+    /// Parses the seed nodes and checks that they have a public key if `require_seed_keys` is set.
+    pub fn seeds(&self) -> Result<Vec<Seed>, failure::Error> {
+        let seeds = self.seed_nodes.iter()
+            .map(|s| s::Seed::try_from(s.clone()))
+            .collect::<Result<Vec<Seed>, SeedError>>()?;
+        if self.require_seed_keys && seeds.iter().any(|s| match s {
+            Seed::Peer(uri) => uri.public_key().is_none(),
+            _ => false
+        }) {
+            Err(ConfigError::MissingPublicKey)?;
+        }
+        Ok(seeds)
+    }
+}
+
+impl s::LogSettings {
+    /// The log levels to use. Levels given on the command line take precedence.
+    pub fn log_levels(&self, cmdline_level: Option<LevelFilter>, cmdline_tags: &HashMap<String, LevelFilter>) -> LogLevels {
+        let mut tags = self.tags.clone();
+        tags.extend(cmdline_tags.iter().map(|(module, level)| (module.clone(), *level)));
+        LogLevels {
+            nimiq: cmdline_level.or(self.level).unwrap_or(DEFAULT_LEVEL),
+            tags,
+        }
+    }
+}
+
+#[cfg(feature = "rpc-server")]
+impl s::RpcServerSettings {
+    /// The configuration of the RPC server, except for the address it listens on.
+    pub fn json_rpc_config(&self, mempool: Option<&s::MempoolSettings>) -> Result<JsonRpcConfig, failure::Error> {
+        let credentials = match (&self.username, &self.password) {
+            (Some(username), Some(password)) => Some(Credentials::new(username, password)),
+            (None, None) => None,
+            _ => Err(ConfigError::MissingRpcCredentials)?
+        };
+        let block_template_policy = self.block_template.clone()
+            .unwrap_or_default()
+            .selection_policy(mempool);
+        Ok(JsonRpcConfig {
+            credentials,
+            methods: self.methods.iter().cloned().collect(),
+            allowip: (), // TODO
+            corsdomain: self.corsdomain.clone(),
+            block_template_policy: Arc::new(block_template_policy),
+        })
+    }
+}
+
 
 pub(crate) fn deserialize_coin<'de, D>(deserializer: D) -> Result<Coin, D::Error> where D: Deserializer<'de> {
     let value = u64::deserialize(deserializer)?;
@@ -131,8 +383,11 @@ pub(crate) fn deserialize_string<'de, D, T>(deserializer: D) -> Result<T, D::Err
     T::from_str(&value).map_err(Error::custom)
 }
 
-// NOTE: This is currently unused, but might be used in future.
-#[allow(dead_code)]
+pub(crate) fn deserialize_address_vec<'de, D>(deserializer: D) -> Result<Vec<Address>, D::Error> where D: Deserializer<'de> {
+    let values = Vec::<String>::deserialize(deserializer)?;
+    values.iter().map(|value| Address::from_any_str(value).map_err(|_| Error::custom(format!("Invalid address: {}", value)))).collect()
+}
+
 pub(crate) fn deserialize_string_vec<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
     where D: Deserializer<'de>,
           T: FromStr,
@@ -1,12 +1,14 @@
 use std::collections::HashMap;
 use std::fs::read_to_string;
+use std::net::IpAddr;
 use std::path::Path;
 use std::str::FromStr;
 
 use failure::Error;
 use log::LevelFilter;
 
-use network_primitives::address::NetAddress;
+use keys::Address;
+use network_primitives::address::{NetAddress, NetAddressRange};
 use primitives::coin::Coin;
 
 use crate::serialization::*;
@@ -15,7 +17,7 @@ pub const DEFAULT_REVERSE_PROXY_PORT: u16 = 8444;
 pub const DEFAULT_RPC_PORT: u16 = 8648;
 pub const DEFAULT_METRICS_PORT: u16 = 8649;
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
 pub(crate) struct Settings {
@@ -41,35 +43,51 @@ impl Settings {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Default)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Default)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct NetworkSettings {
     pub host: Option<String>,
     pub port: Option<u16>,
     #[serde(default)]
+    pub listen_addresses: Vec<IpAddr>,
+    #[serde(default)]
+    pub additional_hosts: Vec<String>,
+    #[serde(default)]
     pub protocol: Protocol,
     #[serde(default)]
     pub seed_nodes: Vec<Seed>,
     #[serde(default)]
+    pub require_seed_keys: bool,
+    pub dns_resolver: Option<String>,
+    pub capture_dir: Option<String>,
+    pub allowed_peers: Option<Vec<AllowedPeer>>,
+    #[serde(default)]
     pub user_agent: Option<String>,
     pub tls: Option<TlsSettings>,
+    pub proxy: Option<ProxySettings>,
+    pub mode: Option<ConnectionMode>,
+    #[serde(default)]
+    pub disable_inbound: bool,
+    pub limits: Option<MessageLimitsSettings>,
+    pub scoring: Option<ScoringSettings>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(untagged)]
 pub(crate) enum Seed {
     Uri(SeedUri),
     Info(SeedInfo),
     List(SeedList),
+    Dns(SeedDns),
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct SeedUri {
     pub uri: String
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct SeedInfo {
     pub host: String,
@@ -78,22 +96,46 @@ pub(crate) struct SeedInfo {
     pub peer_id: Option<String>
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct SeedList {
     pub list: String,
     pub public_key: Option<String>
 }
 
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SeedDns {
+    pub dns: String,
+    #[serde(default)]
+    pub protocol: Protocol,
+    pub port: Option<u16>,
+}
 
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum AllowedPeer {
+    Uri(SeedUri),
+    PeerId(AllowedPeerId),
+}
 
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AllowedPeerId {
+    pub peer_id: String
+}
+
+
+
+/// The protocols the client can be configured with. WebRTC is not among them, since this client
+/// doesn't implement WebRTC connections.
 #[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub(crate) enum Protocol {
     Wss,
     Ws,
     Dumb,
-    Rtc,
+    Tcp,
 }
 
 impl Default for Protocol {
@@ -102,21 +144,156 @@ impl Default for Protocol {
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ConnectionMode {
+    Normal,
+    Passive,
+    OutboundOnly,
+}
+
+impl Default for ConnectionMode {
+    fn default() -> Self {
+        ConnectionMode::Normal
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct TlsSettings {
     pub identity_file: String,
     pub identity_password: String,
 }
 
-#[derive(Clone, Debug, Deserialize, Default)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ProxySettings {
+    pub host: String,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(default)]
+    pub remote_dns: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MessageLimitsSettings {
+    pub send_queue_size: Option<usize>,
+    /// Limits keyed by the snake case name of the message type, e.g. `get_data`.
+    #[serde(default)]
+    pub messages: HashMap<String, MessageLimitSettings>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ScoringSettings {
+    #[serde(default)]
+    pub preferred_peers: Vec<String>,
+    #[serde(default)]
+    pub pinned_peers: Vec<String>,
+    pub protocol_distribution_target: Option<f64>,
+    pub subnet_target: Option<usize>,
+    pub latency_weight: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MessageLimitSettings {
+    pub per_minute: Option<usize>,
+    pub max_size: Option<usize>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum MessageType {
+    Version,
+    Inv,
+    GetData,
+    GetHeader,
+    NotFound,
+    GetBlocks,
+    Block,
+    Header,
+    Tx,
+    Mempool,
+    Reject,
+    Subscribe,
+    Addr,
+    GetAddr,
+    Ping,
+    Pong,
+    Signal,
+    GetChainProof,
+    ChainProof,
+    GetAccountsProof,
+    AccountsProof,
+    GetAccountsTreeChunk,
+    AccountsTreeChunk,
+    GetTransactionsProof,
+    TransactionsProof,
+    GetTransactionReceipts,
+    TransactionReceipts,
+    GetBlockProof,
+    BlockProof,
+    GetHead,
+    Head,
+    VerAck,
+}
+
+impl FromStr for MessageType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<MessageType, ()> {
+        Ok(match s {
+            "version" => MessageType::Version,
+            "inv" => MessageType::Inv,
+            "get_data" => MessageType::GetData,
+            "get_header" => MessageType::GetHeader,
+            "not_found" => MessageType::NotFound,
+            "get_blocks" => MessageType::GetBlocks,
+            "block" => MessageType::Block,
+            "header" => MessageType::Header,
+            "tx" => MessageType::Tx,
+            "mempool" => MessageType::Mempool,
+            "reject" => MessageType::Reject,
+            "subscribe" => MessageType::Subscribe,
+            "addr" => MessageType::Addr,
+            "get_addr" => MessageType::GetAddr,
+            "ping" => MessageType::Ping,
+            "pong" => MessageType::Pong,
+            "signal" => MessageType::Signal,
+            "get_chain_proof" => MessageType::GetChainProof,
+            "chain_proof" => MessageType::ChainProof,
+            "get_accounts_proof" => MessageType::GetAccountsProof,
+            "accounts_proof" => MessageType::AccountsProof,
+            "get_accounts_tree_chunk" => MessageType::GetAccountsTreeChunk,
+            "accounts_tree_chunk" => MessageType::AccountsTreeChunk,
+            "get_transactions_proof" => MessageType::GetTransactionsProof,
+            "transactions_proof" => MessageType::TransactionsProof,
+            "get_transaction_receipts" => MessageType::GetTransactionReceipts,
+            "transaction_receipts" => MessageType::TransactionReceipts,
+            "get_block_proof" => MessageType::GetBlockProof,
+            "block_proof" => MessageType::BlockProof,
+            "get_head" => MessageType::GetHead,
+            "head" => MessageType::Head,
+            "ver_ack" => MessageType::VerAck,
+            _ => Err(())?
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Default)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct ConsensusSettings {
     #[serde(rename = "type")]
     #[serde(default)]
     pub node_type: NodeType,
+    /// Defaults to the main network.
     #[serde(default)]
-    pub network: Network,
+    pub network: Option<Network>,
+    /// Network file of a custom network, can't be combined with `network`.
+    #[serde(default)]
+    pub network_file: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
@@ -152,6 +329,7 @@ pub(crate) enum Network {
     Main,
     Test,
     Dev,
+    Regtest,
 }
 
 impl Default for Network {
@@ -168,12 +346,13 @@ impl FromStr for Network {
             "main" => Network::Main,
             "test" => Network::Test,
             "dev" => Network::Dev,
+            "regtest" => Network::Regtest,
             _ => Err(())?
         })
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Default)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Default)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct RpcServerSettings {
     #[serde(deserialize_with = "deserialize_string_option")]
@@ -188,9 +367,27 @@ pub(crate) struct RpcServerSettings {
     pub methods: Vec<String>,
     pub username: Option<String>,
     pub password: Option<String>,
+    pub block_template: Option<BlockTemplateSettings>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BlockTemplateSettings {
+    #[serde(default)]
+    pub fee_per_byte_min: f64,
+    #[serde(default)]
+    pub free_space: usize,
+    #[serde(deserialize_with = "deserialize_address_vec")]
+    #[serde(default)]
+    pub always_include: Vec<Address>,
+    #[serde(deserialize_with = "deserialize_address_vec")]
+    #[serde(default)]
+    pub never_include: Vec<Address>,
+    #[serde(default)]
+    pub transactions_per_sender_max: Option<usize>,
 }
 
-#[derive(Clone, Debug, Deserialize, Default)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Default)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct MetricsServerSettings {
     #[serde(deserialize_with = "deserialize_string_option")]
@@ -200,19 +397,26 @@ pub(crate) struct MetricsServerSettings {
     pub password: Option<String>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct ReverseProxySettings {
     pub port: Option<u16>,
-    #[serde(deserialize_with = "deserialize_string")]
-    pub address: NetAddress,
+    /// A single trusted proxy, kept for existing configs.
+    #[serde(deserialize_with = "deserialize_string_option")]
+    #[serde(default)]
+    pub address: Option<NetAddress>,
+    #[serde(deserialize_with = "deserialize_string_vec")]
+    #[serde(default)]
+    pub trusted_proxies: Vec<NetAddressRange>,
     #[serde(default)]
     pub header: String,
     #[serde(default)]
+    pub proxy_protocol: bool,
+    #[serde(default)]
     pub with_tls_termination: bool,
 }
 
-#[derive(Clone, Debug, Deserialize, Default)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Default)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct LogSettings {
     #[serde(deserialize_with = "deserialize_string_option")]
@@ -229,7 +433,7 @@ pub(crate) struct LogSettings {
     pub file: Option<String>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct DatabaseSettings {
     pub path: Option<String>,
@@ -247,14 +451,22 @@ impl Default for DatabaseSettings {
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Default)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct MempoolSettings {
     pub filter: Option<MempoolFilterSettings>,
     pub blacklist_limit: Option<usize>,
+    #[serde(default)]
+    pub replace_by_fee: bool,
+    pub replacement_fee_bump: Option<f64>,
+    pub size_max: Option<usize>,
+    pub total_size_max: Option<usize>,
+    pub transactions_per_sender_max: Option<u32>,
+    pub free_transactions_per_sender_max: Option<u32>,
+    pub transaction_relay_fee_min: Option<f64>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct MempoolFilterSettings {
     #[serde(deserialize_with = "deserialize_coin")]
@@ -290,4 +502,130 @@ pub(crate) struct MempoolFilterSettings {
     #[serde(deserialize_with = "deserialize_coin")]
     #[serde(default)]
     pub sender_balance: Coin,
+    #[serde(deserialize_with = "deserialize_address_vec")]
+    #[serde(default)]
+    pub sender_blacklist: Vec<Address>,
+    #[serde(deserialize_with = "deserialize_address_vec")]
+    #[serde(default)]
+    pub sender_whitelist: Vec<Address>,
+    #[serde(deserialize_with = "deserialize_address_vec")]
+    #[serde(default)]
+    pub recipient_blacklist: Vec<Address>,
+    #[serde(deserialize_with = "deserialize_address_vec")]
+    #[serde(default)]
+    pub recipient_whitelist: Vec<Address>,
+    pub data_size_max: Option<usize>,
+    /// Rules by account type. toml can't deserialize enum keys, so these are parsed with
+    /// `AccountType::from_str`.
+    #[serde(default)]
+    pub account_types: HashMap<String, AccountTypeFilterSettings>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum AccountType {
+    Basic,
+    Vesting,
+    Htlc,
+}
+
+impl FromStr for AccountType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<AccountType, ()> {
+        Ok(match s.to_lowercase().as_str() {
+            "basic" => AccountType::Basic,
+            "vesting" => AccountType::Vesting,
+            "htlc" => AccountType::Htlc,
+            _ => Err(())?
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AccountTypeFilterSettings {
+    #[serde(default)]
+    pub reject: bool,
+    #[serde(default)]
+    pub tx_fee_per_byte: f64,
+    #[serde(deserialize_with = "deserialize_coin")]
+    #[serde(default)]
+    pub tx_value: Coin,
+    pub data_size_max: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use mempool::filter::Rules;
+    use network::message_limits::{MessageLimit, MessageLimits, MessageType as NetworkMessageType};
+    use primitives::account::AccountType as PrimitivesAccountType;
+
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../client.example.toml");
+
+    /// Uncomments the given section of the example config and its subsections.
+    fn uncomment_section(config: &str, section: &str) -> String {
+        let mut in_section = false;
+        config.lines().map(|line| {
+            if line.starts_with("#[") {
+                in_section = line[2..].starts_with(section);
+            }
+            if in_section && line.starts_with('#') && !line.starts_with("# ") {
+                line[1..].to_string()
+            } else {
+                line.to_string()
+            }
+        }).collect::<Vec<_>>().join("\n")
+    }
+
+    #[test]
+    fn it_parses_the_example_config() {
+        assert!(toml::from_str::<Settings>(EXAMPLE).is_ok());
+    }
+
+    #[test]
+    fn it_parses_mempool_filter_account_types() {
+        let settings: Settings = toml::from_str(&uncomment_section(EXAMPLE, "mempool.filter")).unwrap();
+        let filter = settings.mempool.unwrap().filter.unwrap();
+        assert_eq!(filter.account_types.keys().collect::<Vec<_>>(), vec!["vesting"]);
+
+        let rules = Rules::try_from(filter).unwrap();
+        let vesting = &rules.account_types[&PrimitivesAccountType::Vesting];
+        assert!(!vesting.reject);
+        assert_eq!(vesting.data_size_max, Some(64));
+    }
+
+    #[test]
+    fn it_parses_message_limits() {
+        let settings: Settings = toml::from_str(&uncomment_section(EXAMPLE, "network.limits")).unwrap();
+        let limits_settings = settings.network.limits.unwrap();
+        let mut types = limits_settings.messages.keys().collect::<Vec<_>>();
+        types.sort();
+        assert_eq!(types, vec!["get_data", "tx"]);
+
+        let limits = MessageLimits::try_from(limits_settings).unwrap();
+        assert_eq!(limits.get(NetworkMessageType::GetData), MessageLimit::new(Some(300), Some(65536)));
+        assert_eq!(limits.get(NetworkMessageType::Tx), MessageLimit::new(Some(1200), Some(262144)));
+    }
+
+    #[test]
+    fn it_rejects_unknown_message_types() {
+        let settings: Settings = toml::from_str("[network.limits.messages]\nget_everything = { per_minute = 1 }").unwrap();
+        assert!(MessageLimits::try_from(settings.network.limits.unwrap()).is_err());
+    }
+
+    #[test]
+    fn it_rejects_the_rtc_protocol() {
+        assert!(toml::from_str::<Settings>("[network]\nprotocol = \"ws\"").is_ok());
+        assert!(toml::from_str::<Settings>("[network]\nprotocol = \"rtc\"").is_err());
+    }
+
+    #[test]
+    fn it_rejects_unknown_account_types() {
+        let settings: Settings = toml::from_str("[mempool.filter.account_types.contract]\nreject = true").unwrap();
+        assert!(Rules::try_from(settings.mempool.unwrap().filter.unwrap()).is_err());
+    }
 }
@@ -69,7 +69,7 @@ impl Consensus {
         let network_time = Arc::new(NetworkTime::new());
         let blockchain = Arc::new(Blockchain::new(env, network_id, network_time.clone())?);
         let mempool = Mempool::new(blockchain.clone(), mempool_config);
-        let network = Network::new(blockchain.clone(), network_config, network_time, network_id)?;
+        let network = Network::new(env, blockchain.clone(), network_config, network_time, network_id)?;
         let accounts_chunk_cache = AccountsChunkCache::new(env, Arc::clone(&blockchain));
 
         let this = Arc::new(Consensus {
@@ -95,6 +95,10 @@ impl Consensus {
         Ok(this)
     }
 
+    pub fn established(&self) -> bool {
+        self.state.read().established
+    }
+
     fn init_listeners(this: &Arc<Consensus>) {
         unsafe { this.self_weak.replace(Arc::downgrade(this)) };
 
@@ -118,6 +122,8 @@ impl Consensus {
                 MempoolEvent::TransactionRestored(transaction) => this.on_transaction_added(transaction),
                 MempoolEvent::TransactionEvicted(transaction) => this.on_transaction_removed(transaction),
                 MempoolEvent::TransactionMined(transaction) => this.on_transaction_removed(transaction),
+                // The replacement is relayed through its `TransactionAdded` event.
+                MempoolEvent::TransactionReplaced(transaction, _) => this.on_transaction_removed(transaction),
             }
         });
 
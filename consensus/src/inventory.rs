@@ -262,6 +262,9 @@ impl InventoryAgent {
     const FREE_TRANSACTION_SIZE_PER_INTERVAL: usize = 15000; // ~100 legacy transactions
     const TRANSACTION_THROTTLE: Duration = Duration::from_millis(1000);
     const REQUEST_TRANSACTIONS_WAITING_MAX: usize = 5000;
+    /// Maximum number of block vectors waiting to be requested. Further vectors are dropped
+    /// until the queue has been drained, peers announce blocks again if we miss them.
+    const REQUEST_BLOCKS_WAITING_MAX: usize = 5000;
     const GET_BLOCKS_RATE_LIMIT: usize = 30; // per minute
     /// Time {ms} to wait between sending full inv vectors of transactions during Mempool request
     const MEMPOOL_THROTTLE: Duration = Duration::from_millis(1000); // 1 second
@@ -639,7 +642,7 @@ impl InventoryAgent {
     fn queue_vector(&self, vector: InvVector) {
         let mut state = self.state.write();
         match vector.ty {
-            InvVectorType::Block => state.blocks_to_request.enqueue(vector),
+            InvVectorType::Block => Self::queue_block_vector(&mut *state, vector),
             InvVectorType::Transaction => state.txs_to_request.enqueue(vector),
             InvVectorType::Error => () // XXX Get rid of this!
         }
@@ -648,7 +651,7 @@ impl InventoryAgent {
 
     fn queue_vectors(&self, state: &mut InventoryAgentState, block_vectors: Vec<InvVector>, tx_vectors: Vec<InvVector>) {
         for vector in block_vectors {
-            state.blocks_to_request.enqueue(vector);
+            Self::queue_block_vector(state, vector);
         }
 
         for vector in tx_vectors {
@@ -658,6 +661,14 @@ impl InventoryAgent {
         self.request_vectors_throttled(state);
     }
 
+    fn queue_block_vector(state: &mut InventoryAgentState, vector: InvVector) {
+        if state.blocks_to_request.len() < Self::REQUEST_BLOCKS_WAITING_MAX {
+            state.blocks_to_request.enqueue(vector);
+        } else {
+            trace!("Too many block vectors waiting to be requested, dropping {:?}", vector);
+        }
+    }
+
     fn request_vectors_throttled(&self, state: &mut InventoryAgentState) {
         self.timers.clear_delay(&InventoryAgentTimer::GetDataThrottle);
 
@@ -0,0 +1,33 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use nimiq_block::Block;
+use nimiq_blockchain::chain_info::ChainInfo;
+use nimiq_blockchain::super_block_counts::SuperBlockCounts;
+use nimiq_blockchain::transaction_store::TransactionInfo;
+use nimiq_database::FromDatabaseValue;
+use nimiq_fuzz::round_trip_database;
+use nimiq_hash::Blake2bHash;
+use nimiq_keys::Address;
+use nimiq_network::address::peer_address_store::StoredPeerAddress;
+use nimiq_network::ban_list::StoredBan;
+use nimiq_tree_primitives::accounts_tree_node::AccountsTreeNode;
+
+// The first byte selects the type of the value.
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+    let (ty, data) = (data[0], &data[1..]);
+    match ty % 9 {
+        0 => { round_trip_database::<Block>(data); },
+        1 => { round_trip_database::<AccountsTreeNode>(data); },
+        2 => { round_trip_database::<ChainInfo>(data); },
+        3 => { round_trip_database::<TransactionInfo>(data); },
+        4 => { round_trip_database::<SuperBlockCounts>(data); },
+        5 => { let _ = Address::copy_from_database(data); },
+        6 => { let _ = Blake2bHash::copy_from_database(data); },
+        7 => { round_trip_database::<StoredPeerAddress>(data); },
+        _ => { round_trip_database::<StoredBan>(data); },
+    }
+});
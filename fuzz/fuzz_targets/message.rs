@@ -0,0 +1,12 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use nimiq_fuzz::{fix_message_checksum, round_trip};
+use nimiq_messages::Message;
+
+fuzz_target!(|data: &[u8]| {
+    let mut data = data.to_vec();
+    if fix_message_checksum(&mut data) {
+        round_trip::<Message>(&data);
+    }
+});
@@ -0,0 +1,9 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use nimiq_network_primitives::address::PeerAddress;
+use nimiq_fuzz::round_trip;
+
+fuzz_target!(|data: &[u8]| {
+    round_trip::<PeerAddress>(data);
+});
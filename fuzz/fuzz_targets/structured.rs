@@ -0,0 +1,18 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use nimiq_fuzz::check_encoding;
+use nimiq_fuzz::generate::Source;
+
+// Generates valid values instead of decoding the input, to get past the checks at the start of
+// the decoders. The first byte selects the type of the value.
+fuzz_target!(|data: &[u8]| {
+    let mut source = Source::new(data);
+    match source.u8() % 5 {
+        0 => check_encoding(&source.message()),
+        1 => check_encoding(&source.block()),
+        2 => check_encoding(&source.block_header()),
+        3 => check_encoding(&source.transaction()),
+        _ => check_encoding(&source.peer_address()),
+    }
+});
@@ -0,0 +1,265 @@
+//! Structure-aware generators, which build valid values from fuzzer input.
+//!
+//! Random bytes rarely decode to anything interesting, since most of them fail the first check
+//! of a decoder. The generators use the input to pick the fields of a value instead, so that
+//! every input results in a value that is valid in encoding, though not in content.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use beserial::Serialize;
+
+use block::{Block, BlockBody, BlockHeader, BlockInterlink, TargetCompact};
+use block::proof::ChainProof;
+use hash::Blake2bHash;
+use keys::{Address, KeyPair, PrivateKey, PublicKey};
+use network_messages::{AddrMessage, ChallengeNonce, GetBlocksDirection, GetBlocksMessage, InvVector, InvVectorType, Message, TxMessage, VersionMessage};
+use network_primitives::address::{NetAddress, PeerAddress, PeerAddressType, PeerId};
+use network_primitives::services::ServiceFlags;
+use primitives::account::AccountType;
+use primitives::coin::Coin;
+use primitives::networks::NetworkId;
+use transaction::{SignatureProof, Transaction, TransactionFlags};
+
+/// Reads the fields of generated values from fuzzer input. Reads past the end of the input
+/// return zeros, so generation never fails.
+pub struct Source<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Source<'a> {
+    /// Upper bound for the length of generated collections, to keep values small.
+    const MAX_LEN: usize = 8;
+
+    pub fn new(data: &'a [u8]) -> Self {
+        Source { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn fill(&mut self, buffer: &mut [u8]) {
+        let n = buffer.len().min(self.data.len());
+        buffer[..n].copy_from_slice(&self.data[..n]);
+        for byte in buffer[n..].iter_mut() {
+            *byte = 0;
+        }
+        self.data = &self.data[n..];
+    }
+
+    pub fn u8(&mut self) -> u8 {
+        let mut buffer = [0u8; 1];
+        self.fill(&mut buffer);
+        buffer[0]
+    }
+
+    pub fn u16(&mut self) -> u16 {
+        u16::from(self.u8()) << 8 | u16::from(self.u8())
+    }
+
+    pub fn u32(&mut self) -> u32 {
+        u32::from(self.u16()) << 16 | u32::from(self.u16())
+    }
+
+    pub fn u64(&mut self) -> u64 {
+        u64::from(self.u32()) << 32 | u64::from(self.u32())
+    }
+
+    pub fn bool(&mut self) -> bool {
+        self.u8() & 1 == 1
+    }
+
+    /// A length of up to `max`.
+    pub fn len(&mut self, max: usize) -> usize {
+        self.u8() as usize % (max + 1)
+    }
+
+    pub fn bytes(&mut self, max: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; self.len(max)];
+        self.fill(&mut bytes);
+        bytes
+    }
+
+    pub fn vec<T, F: FnMut(&mut Self) -> T>(&mut self, max: usize, mut f: F) -> Vec<T> {
+        (0..self.len(max)).map(|_| f(self)).collect()
+    }
+
+    /// A lowercase alphanumeric string, e.g. for host names and user agents.
+    pub fn string(&mut self, max: usize) -> String {
+        const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789.-";
+        self.bytes(max).iter()
+            .map(|b| CHARS[*b as usize % CHARS.len()] as char)
+            .collect()
+    }
+
+    pub fn hash(&mut self) -> Blake2bHash {
+        let mut hash = [0u8; Blake2bHash::SIZE];
+        self.fill(&mut hash);
+        Blake2bHash::from(hash)
+    }
+
+    pub fn address(&mut self) -> Address {
+        let mut address = [0u8; Address::SIZE];
+        self.fill(&mut address);
+        Address::from(address)
+    }
+
+    /// A key pair, so that public keys and signatures are valid curve points.
+    pub fn key_pair(&mut self) -> KeyPair {
+        let mut private_key = [0u8; PrivateKey::SIZE];
+        self.fill(&mut private_key);
+        KeyPair::from(PrivateKey::from(private_key))
+    }
+
+    pub fn coin(&mut self) -> Coin {
+        Coin::from_u64_unchecked(self.u64() % (Coin::MAX_SAFE_VALUE + 1))
+    }
+
+    /// One of the built-in networks, as IDs of unregistered custom networks don't deserialize.
+    pub fn network_id(&mut self) -> NetworkId {
+        const NETWORK_IDS: [NetworkId; 6] = [NetworkId::Test, NetworkId::Dev, NetworkId::Bounty, NetworkId::Dummy, NetworkId::Regtest, NetworkId::Main];
+        NETWORK_IDS[self.u8() as usize % NETWORK_IDS.len()]
+    }
+
+    pub fn account_type(&mut self) -> AccountType {
+        match self.u8() % 3 {
+            0 => AccountType::Basic,
+            1 => AccountType::Vesting,
+            _ => AccountType::HTLC,
+        }
+    }
+
+    pub fn transaction(&mut self) -> Transaction {
+        let validity_start_height = self.u32();
+        let network_id = self.network_id();
+        let recipient = self.address();
+        let value = self.coin();
+        let fee = self.coin();
+
+        if self.bool() {
+            let key_pair = self.key_pair();
+            let mut transaction = Transaction::new_basic(Address::from(&key_pair.public), recipient, value, fee, validity_start_height, network_id);
+            let signature = key_pair.sign(&transaction.serialize_content());
+            transaction.proof = SignatureProof::from(key_pair.public, signature).serialize_to_vec();
+            transaction
+        } else {
+            let mut transaction = Transaction::new_basic(self.address(), recipient, value, fee, validity_start_height, network_id);
+            transaction.data = self.bytes(64);
+            transaction.sender_type = self.account_type();
+            transaction.recipient_type = self.account_type();
+            transaction.flags = TransactionFlags::from_bits_truncate(self.u8());
+            transaction.proof = self.bytes(128);
+            transaction
+        }
+    }
+
+    pub fn block_header(&mut self) -> BlockHeader {
+        BlockHeader {
+            version: self.u16(),
+            prev_hash: self.hash(),
+            interlink_hash: self.hash(),
+            body_hash: self.hash(),
+            accounts_hash: self.hash(),
+            n_bits: TargetCompact::from(self.u32()),
+            height: self.u32(),
+            timestamp: self.u32(),
+            nonce: self.u32(),
+        }
+    }
+
+    pub fn block(&mut self) -> Block {
+        let header = self.block_header();
+        // Draw from a few hashes, so that the interlink compression is exercised.
+        let choices = [header.prev_hash.clone(), self.hash(), self.hash()];
+        let hashes = self.vec(Self::MAX_LEN, |source| choices[source.u8() as usize % choices.len()].clone());
+        let interlink = BlockInterlink::new(hashes, &header.prev_hash);
+        let body = if self.bool() {
+            Some(BlockBody {
+                miner: self.address(),
+                extra_data: self.bytes(32),
+                transactions: self.vec(Self::MAX_LEN, Self::transaction),
+                pruned_accounts: Vec::new(),
+            })
+        } else {
+            None
+        };
+        Block { header, interlink, body }
+    }
+
+    pub fn net_address(&mut self) -> NetAddress {
+        match self.u8() % 4 {
+            0 => NetAddress::IPv4(Ipv4Addr::from(self.u32())),
+            1 => {
+                let mut ip = [0u8; 16];
+                self.fill(&mut ip);
+                NetAddress::IPv6(Ipv6Addr::from(ip))
+            },
+            2 => NetAddress::Unspecified,
+            _ => NetAddress::Unknown,
+        }
+    }
+
+    pub fn peer_address(&mut self) -> PeerAddress {
+        let ty = match self.u8() % 5 {
+            0 => PeerAddressType::Dumb,
+            1 => PeerAddressType::Ws(self.string(32), self.u16()),
+            2 => PeerAddressType::Wss(self.string(32), self.u16()),
+            3 => PeerAddressType::Rtc,
+            _ => PeerAddressType::Tcp(self.string(32), self.u16()),
+        };
+        let key_pair = self.key_pair();
+        let public_key: PublicKey = key_pair.public;
+        PeerAddress {
+            ty,
+            services: ServiceFlags::from_bits_truncate(self.u32()),
+            timestamp: self.u64(),
+            net_address: self.net_address(),
+            distance: self.u8(),
+            signature: Some(key_pair.sign(&[])),
+            peer_id: PeerId::from(&public_key),
+            public_key,
+        }
+    }
+
+    pub fn inv_vector(&mut self) -> InvVector {
+        let ty = match self.u8() % 3 {
+            0 => InvVectorType::Error,
+            1 => InvVectorType::Transaction,
+            _ => InvVectorType::Block,
+        };
+        InvVector::new(ty, self.hash())
+    }
+
+    pub fn message(&mut self) -> Message {
+        match self.u8() % 16 {
+            0 => {
+                let mut challenge_nonce = [0u8; 32];
+                self.fill(&mut challenge_nonce);
+                let challenge_nonce = ChallengeNonce::from(challenge_nonce);
+                let user_agent = if self.bool() { Some(self.string(32)) } else { None };
+                VersionMessage::new(self.peer_address(), self.hash(), self.hash(), challenge_nonce, user_agent)
+            },
+            1 => Message::Inv(self.vec(Self::MAX_LEN, Self::inv_vector)),
+            2 => Message::GetData(self.vec(Self::MAX_LEN, Self::inv_vector)),
+            3 => Message::GetHeader(self.vec(Self::MAX_LEN, Self::inv_vector)),
+            4 => Message::NotFound(self.vec(Self::MAX_LEN, Self::inv_vector)),
+            5 => Message::Block(Box::new(self.block())),
+            6 => Message::Header(Box::new(self.block_header())),
+            7 => TxMessage::new(self.transaction()),
+            8 => {
+                let direction = if self.bool() { GetBlocksDirection::Forward } else { GetBlocksDirection::Backward };
+                GetBlocksMessage::new(self.vec(Self::MAX_LEN, Self::hash), self.u16(), direction)
+            },
+            9 => Message::Mempool,
+            10 => AddrMessage::new(self.vec(Self::MAX_LEN, Self::peer_address)),
+            11 => Message::Ping(self.u32()),
+            12 => Message::Pong(self.u32()),
+            13 => Message::GetChainProof,
+            14 => Message::ChainProof(Box::new(ChainProof {
+                prefix: self.vec(Self::MAX_LEN, Self::block),
+                suffix: self.vec(Self::MAX_LEN, Self::block_header),
+            })),
+            _ => Message::Head(Box::new(self.block_header())),
+        }
+    }
+}
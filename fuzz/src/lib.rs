@@ -0,0 +1,84 @@
+//! Fuzzing targets for the decoders that are applied to untrusted input, i.e. network messages
+//! and their parts, and to database values.
+//!
+//! Run a target with [cargo-fuzz](https://github.com/rust-fuzz/cargo-fuzz) from this directory,
+//! e.g. `cargo +nightly fuzz run message`. The seed corpus in `corpus/` is built from the test
+//! vectors of the repository by `scripts/fuzz_corpus.py`.
+//!
+//! Every target decodes its input and, if that succeeds, checks that encoding the value gives
+//! bytes that decode to a value with the same encoding. The `structured` target instead builds
+//! valid values with the generators in `generate` and checks that they survive a round trip.
+
+extern crate beserial;
+extern crate nimiq_block as block;
+extern crate nimiq_blockchain as blockchain;
+extern crate nimiq_database as database;
+extern crate nimiq_hash as hash;
+extern crate nimiq_keys as keys;
+extern crate nimiq_messages as network_messages;
+extern crate nimiq_network_primitives as network_primitives;
+extern crate nimiq_primitives as primitives;
+extern crate nimiq_transaction as transaction;
+extern crate nimiq_tree_primitives as tree_primitives;
+extern crate nimiq_utils as utils;
+
+pub mod generate;
+
+use std::fmt::Debug;
+
+use beserial::{Deserialize, Serialize, uvar};
+use database::{FromDatabaseValue, IntoDatabaseValue};
+use utils::crc::Crc32Computer;
+
+/// Decodes `data` and checks that the value round-trips. Returns the value if `data` could be
+/// decoded.
+pub fn round_trip<T: Serialize + Deserialize + Debug>(data: &[u8]) -> Option<T> {
+    let value: T = Deserialize::deserialize(&mut &data[..]).ok()?;
+    check_encoding(&value);
+    Some(value)
+}
+
+/// Checks that the encoding of `value` decodes to a value with the same encoding.
+pub fn check_encoding<T: Serialize + Deserialize + Debug>(value: &T) {
+    let encoded = value.serialize_to_vec();
+    assert_eq!(encoded.len(), value.serialized_size(), "Wrong serialized size of {:?}", value);
+
+    let decoded: T = Deserialize::deserialize_from_vec(&encoded)
+        .unwrap_or_else(|e| panic!("Can't decode encoding of {:?}: {}", value, e));
+    assert_eq!(decoded.serialize_to_vec(), encoded, "Encoding of {:?} changed after decoding", value);
+}
+
+/// Like `round_trip`, but for values stored in the database.
+pub fn round_trip_database<T: FromDatabaseValue + IntoDatabaseValue>(data: &[u8]) -> Option<T> {
+    let value = T::copy_from_database(data).ok()?;
+    let encoded = encode_database(&value);
+    let decoded = T::copy_from_database(&encoded)
+        .unwrap_or_else(|e| panic!("Can't decode database value {:x?}: {}", encoded, e));
+    assert_eq!(encode_database(&decoded), encoded, "Database value {:x?} changed after decoding", encoded);
+    Some(value)
+}
+
+fn encode_database<T: IntoDatabaseValue>(value: &T) -> Vec<u8> {
+    let mut bytes = vec![0u8; value.database_byte_size()];
+    value.copy_into_database(&mut bytes);
+    bytes
+}
+
+/// Replaces the checksum of a message with the correct one, so that fuzzed messages aren't all
+/// rejected for their checksum. Returns false if `data` is too short to contain a message header.
+pub fn fix_message_checksum(data: &mut [u8]) -> bool {
+    // Magic, type as uvar, length and checksum.
+    let ty: uvar = match data.get(4..).map(|mut rest| Deserialize::deserialize(&mut rest)) {
+        Some(Ok(ty)) => ty,
+        _ => return false,
+    };
+    let checksum_start = 4 + Serialize::serialized_size(&ty) + 4;
+    if data.len() < checksum_start + 4 {
+        return false;
+    }
+
+    data[checksum_start..checksum_start + 4].copy_from_slice(&[0u8; 4]);
+    let checksum = Crc32Computer::default().update(data).result();
+    data[checksum_start..checksum_start + 4].copy_from_slice(&checksum.to_be_bytes());
+    true
+}
@@ -0,0 +1,152 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use beserial::Serialize;
+
+use nimiq_block::{Block, BlockHeader};
+use nimiq_block::proof::ChainProof;
+use nimiq_fuzz::{check_encoding, fix_message_checksum, round_trip, round_trip_database};
+use nimiq_fuzz::generate::Source;
+use nimiq_messages::Message;
+use nimiq_network::address::peer_address_state::PeerAddressInfo;
+use nimiq_network::address::peer_address_store::StoredPeerAddress;
+use nimiq_network::ban_list::{BanEntry, BanTarget, StoredBan};
+use nimiq_network_primitives::address::{PeerAddress, PeerId};
+use nimiq_transaction::Transaction;
+use nimiq_tree_primitives::accounts_proof::AccountsProof;
+use nimiq_tree_primitives::accounts_tree_node::AccountsTreeNode;
+
+const NUM_GENERATED: u64 = 500;
+
+fn seeds(target: &str) -> Vec<(String, Vec<u8>)> {
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("corpus").join(target);
+    let mut seeds: Vec<(String, Vec<u8>)> = fs::read_dir(&directory).unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            (path.file_name().unwrap().to_string_lossy().into_owned(), fs::read(&path).unwrap())
+        })
+        .collect();
+    assert!(!seeds.is_empty(), "No seeds in {}", directory.display());
+    seeds.sort();
+    seeds
+}
+
+fn sources() -> impl Iterator<Item=Vec<u8>> {
+    (0..NUM_GENERATED).map(|seed| {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut data = vec![0u8; rng.gen_range(0, 4096)];
+        rng.fill(&mut data[..]);
+        data
+    })
+}
+
+#[test]
+fn it_round_trips_the_message_seeds() {
+    for (name, mut data) in seeds("message") {
+        let original = data.clone();
+        assert!(fix_message_checksum(&mut data), "{}", name);
+        assert_eq!(data, original, "Checksum of {} changed", name);
+        assert!(round_trip::<Message>(&data).is_some(), "Can't decode {}", name);
+    }
+}
+
+#[test]
+fn it_round_trips_the_seeds() {
+    for (name, data) in seeds("block") {
+        assert!(round_trip::<Block>(&data).is_some(), "Can't decode {}", name);
+    }
+    for (name, data) in seeds("block_header") {
+        assert!(round_trip::<BlockHeader>(&data).is_some(), "Can't decode {}", name);
+    }
+    for (name, data) in seeds("transaction") {
+        // Test vectors of invalid transactions are seeds as well, but must not decode.
+        let valid = !name.starts_with("invalid_");
+        assert_eq!(round_trip::<Transaction>(&data).is_some(), valid, "Decoding {} gave the wrong result", name);
+    }
+    for (name, data) in seeds("accounts_proof") {
+        assert!(round_trip::<AccountsProof>(&data).is_some(), "Can't decode {}", name);
+    }
+    for (name, data) in seeds("chain_proof") {
+        assert!(round_trip::<ChainProof>(&data).is_some(), "Can't decode {}", name);
+    }
+    for (name, data) in seeds("peer_address") {
+        assert!(round_trip::<PeerAddress>(&data).is_some(), "Can't decode {}", name);
+    }
+}
+
+#[test]
+fn it_round_trips_the_database_seeds() {
+    for (name, data) in seeds("database_value") {
+        let decoded = match data[0] {
+            0 => round_trip_database::<Block>(&data[1..]).is_some(),
+            1 => round_trip_database::<AccountsTreeNode>(&data[1..]).is_some(),
+            ty => panic!("Unexpected type {} of {}", ty, name),
+        };
+        assert!(decoded, "Can't decode {}", name);
+    }
+}
+
+#[test]
+fn it_round_trips_stored_network_values() {
+    let mut num_peer_addresses = 0;
+    for data in sources() {
+        // Random bytes must not make the decoders panic.
+        round_trip_database::<StoredPeerAddress>(&data);
+        round_trip_database::<StoredBan>(&data);
+
+        let mut source = Source::new(&data);
+        let info = PeerAddressInfo::new(Arc::new(source.peer_address()));
+        if let Some(stored) = StoredPeerAddress::from_info(&info) {
+            let encoded = stored.serialize_to_vec();
+            assert!(round_trip_database::<StoredPeerAddress>(&encoded).is_some(), "Can't decode {:?}", stored);
+            num_peer_addresses += 1;
+        }
+
+        let target = match source.u8() % 3 {
+            0 => BanTarget::PeerId(PeerId::from(&source.key_pair().public)),
+            1 => BanTarget::Ip(source.net_address()),
+            _ => BanTarget::Subnet(source.net_address(), source.u8()),
+        };
+        let duration = if source.bool() { Some(Duration::from_secs(u64::from(source.u32()))) } else { None };
+        let entry = BanEntry::new(target, duration, source.string(32), None).unwrap();
+        let stored = StoredBan::from_entry(&entry);
+        let encoded = stored.serialize_to_vec();
+        assert!(round_trip_database::<StoredBan>(&encoded).is_some(), "Can't decode {:?}", stored);
+    }
+    assert!(num_peer_addresses > 0);
+}
+
+#[test]
+fn it_round_trips_generated_values() {
+    for data in sources() {
+        let mut source = Source::new(&data);
+        check_encoding(&source.transaction());
+        check_encoding(&source.block_header());
+        check_encoding(&source.block());
+        check_encoding(&source.peer_address());
+        check_encoding(&source.message());
+    }
+}
+
+#[test]
+fn it_generates_every_message_type() {
+    let mut types: Vec<_> = sources()
+        .map(|data| Source::new(&data).message().ty())
+        .collect();
+    types.sort();
+    types.dedup();
+    assert_eq!(types.len(), 16);
+}
+
+#[test]
+fn it_generates_from_empty_input() {
+    let mut source = Source::new(&[]);
+    assert!(source.is_empty());
+    check_encoding(&source.message());
+    check_encoding(&source.block());
+}
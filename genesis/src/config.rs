@@ -0,0 +1,271 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+use failure::Fail;
+
+use account::{Account, AccountError, BasicAccount, VestingContract};
+use accounts::Accounts;
+use beserial::Serialize;
+use block::{Block, BlockBody, BlockHeader, BlockInterlink};
+use database::WriteTransaction;
+use database::volatile::{VolatileDatabaseError, VolatileEnvironment};
+use hash::{Blake2bHash, Hash};
+use keys::Address;
+use primitives::coin::Coin;
+
+use crate::network_file::{GenesisEntry, NetworkFile, NetworkFileError, SeedListEntry};
+
+/// The easiest target, as used by the dev network.
+pub const DEFAULT_N_BITS: u32 = 0x1f010000;
+
+#[derive(Debug, Fail)]
+pub enum GenesisError {
+    #[fail(display = "{}", _0)]
+    IoError(#[cause] io::Error),
+    #[fail(display = "{}", _0)]
+    ParseError(#[cause] toml::de::Error),
+    #[fail(display = "Invalid address: {}", _0)]
+    InvalidAddress(String),
+    #[fail(display = "Invalid amount: {}", _0)]
+    InvalidAmount(u64),
+    #[fail(display = "Address {} is used for more than one account", _0)]
+    DuplicateAddress(String),
+    #[fail(display = "Too many genesis accounts")]
+    TooManyAccounts,
+    #[fail(display = "Extra data must be at most 255 bytes")]
+    ExtraDataTooLong,
+    #[fail(display = "{}", _0)]
+    DatabaseError(#[cause] VolatileDatabaseError),
+    #[fail(display = "{}", _0)]
+    AccountError(#[cause] AccountError),
+    #[fail(display = "{}", _0)]
+    NetworkFileError(#[cause] NetworkFileError),
+    #[fail(display = "No valid proof-of-work found for any nonce and timestamp")]
+    NoValidProofOfWork,
+}
+
+impl From<io::Error> for GenesisError {
+    fn from(e: io::Error) -> Self {
+        GenesisError::IoError(e)
+    }
+}
+
+impl From<toml::de::Error> for GenesisError {
+    fn from(e: toml::de::Error) -> Self {
+        GenesisError::ParseError(e)
+    }
+}
+
+impl From<VolatileDatabaseError> for GenesisError {
+    fn from(e: VolatileDatabaseError) -> Self {
+        GenesisError::DatabaseError(e)
+    }
+}
+
+impl From<AccountError> for GenesisError {
+    fn from(e: AccountError) -> Self {
+        GenesisError::AccountError(e)
+    }
+}
+
+impl From<NetworkFileError> for GenesisError {
+    fn from(e: NetworkFileError) -> Self {
+        GenesisError::NetworkFileError(e)
+    }
+}
+
+/// Describes the genesis of a custom network. See `genesis.example.toml`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisConfig {
+    pub network_id: u8,
+    pub name: String,
+    /// Unix timestamp in seconds. Defaults to the current time.
+    #[serde(default)]
+    pub timestamp: Option<u32>,
+    /// Receives the reward of the genesis block. Defaults to the zero address.
+    #[serde(default)]
+    pub miner: Option<String>,
+    #[serde(default)]
+    pub extra_data: String,
+    #[serde(default)]
+    pub n_bits: Option<u32>,
+    #[serde(default)]
+    pub seed_peers: Vec<String>,
+    #[serde(default)]
+    pub seed_lists: Vec<SeedListEntry>,
+    #[serde(default)]
+    pub accounts: Vec<BasicAccountConfig>,
+    #[serde(default)]
+    pub vesting_contracts: Vec<VestingContractConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BasicAccountConfig {
+    pub address: String,
+    /// Balance in Luna
+    pub balance: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VestingContractConfig {
+    pub address: String,
+    pub owner: String,
+    /// Balance in Luna
+    pub balance: u64,
+    /// Block height at which vesting starts
+    pub start: u32,
+    pub step_blocks: u32,
+    /// Amount in Luna that becomes available every `step_blocks` blocks
+    pub step_amount: u64,
+    /// Defaults to the balance.
+    #[serde(default)]
+    pub total_amount: Option<u64>,
+}
+
+impl GenesisConfig {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, GenesisError> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Returns the initial accounts, basic accounts first.
+    pub fn genesis_accounts(&self) -> Result<Vec<(Address, Account)>, GenesisError> {
+        let mut genesis_accounts = Vec::with_capacity(self.accounts.len() + self.vesting_contracts.len());
+        for account in self.accounts.iter() {
+            let balance = parse_coin(account.balance)?;
+            genesis_accounts.push((parse_address(&account.address)?, Account::Basic(BasicAccount { balance })));
+        }
+        for contract in self.vesting_contracts.iter() {
+            let vesting_contract = VestingContract::new(
+                parse_coin(contract.balance)?,
+                parse_address(&contract.owner)?,
+                contract.start,
+                contract.step_blocks,
+                parse_coin(contract.step_amount)?,
+                parse_coin(contract.total_amount.unwrap_or(contract.balance))?,
+            );
+            genesis_accounts.push((parse_address(&contract.address)?, Account::Vesting(vesting_contract)));
+        }
+
+        let mut addresses = HashSet::new();
+        for (address, _) in genesis_accounts.iter() {
+            if !addresses.insert(address.clone()) {
+                return Err(GenesisError::DuplicateAddress(address.to_user_friendly_address()));
+            }
+        }
+        if genesis_accounts.len() > u16::max_value() as usize {
+            return Err(GenesisError::TooManyAccounts);
+        }
+        Ok(genesis_accounts)
+    }
+
+    /// Creates the genesis block for the given accounts. Its proof-of-work still needs to be mined.
+    pub fn genesis_block(&self, genesis_accounts: &[(Address, Account)]) -> Result<Block, GenesisError> {
+        if self.extra_data.len() > 255 {
+            return Err(GenesisError::ExtraDataTooLong);
+        }
+        let miner = match self.miner {
+            Some(ref miner) => parse_address(miner)?,
+            None => Address::from([0u8; Address::SIZE]),
+        };
+        let body = BlockBody {
+            miner,
+            extra_data: self.extra_data.as_bytes().to_vec(),
+            transactions: vec![],
+            pruned_accounts: vec![],
+        };
+
+        let height = 1;
+        let accounts_hash = Self::accounts_hash(genesis_accounts, &body, height)?;
+
+        let timestamp = self.timestamp.unwrap_or_else(|| {
+            SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as u32
+        });
+        let prev_hash: Blake2bHash = [0u8; 32].into();
+        let header = BlockHeader {
+            version: Block::VERSION,
+            prev_hash: prev_hash.clone(),
+            interlink_hash: [0u8; 32].into(),
+            body_hash: body.hash(),
+            accounts_hash,
+            n_bits: self.n_bits.unwrap_or(DEFAULT_N_BITS).into(),
+            height,
+            timestamp,
+            nonce: 0,
+        };
+
+        Ok(Block {
+            header,
+            interlink: BlockInterlink::new(vec![], &prev_hash),
+            body: Some(body),
+        })
+    }
+
+    /// Creates the genesis block, mines it and puts everything into a network file.
+    pub fn build(&self) -> Result<NetworkFile, GenesisError> {
+        let genesis_accounts = self.genesis_accounts()?;
+        let mut genesis_block = self.genesis_block(&genesis_accounts)?;
+        mine(&mut genesis_block.header)?;
+
+        let network_file = NetworkFile {
+            network_id: self.network_id,
+            name: self.name.clone(),
+            seed_peers: self.seed_peers.clone(),
+            seed_lists: self.seed_lists.clone(),
+            genesis: GenesisEntry::from_block(&genesis_block, encode_genesis_accounts(&genesis_accounts)),
+        };
+
+        // Make sure that nodes will accept the network file.
+        network_file.to_network_info()?;
+        Ok(network_file)
+    }
+
+    fn accounts_hash(genesis_accounts: &[(Address, Account)], body: &BlockBody, height: u32) -> Result<Blake2bHash, GenesisError> {
+        let env = VolatileEnvironment::new(10)?;
+        let accounts = Accounts::new(&env);
+        let mut txn = WriteTransaction::new(&env);
+        accounts.init_with(&mut txn, genesis_accounts.to_vec(), body, height)?;
+        let hash = accounts.hash(Some(&txn));
+        txn.abort();
+        Ok(hash)
+    }
+}
+
+/// Increments the nonce until the header has a valid proof-of-work. Once all nonces have been
+/// tried, the timestamp is incremented and the nonces are tried again.
+pub fn mine(header: &mut BlockHeader) -> Result<(), GenesisError> {
+    while !header.verify_proof_of_work() {
+        header.nonce = match header.nonce.checked_add(1) {
+            Some(nonce) => nonce,
+            None => {
+                header.timestamp = header.timestamp.checked_add(1)
+                    .ok_or(GenesisError::NoValidProofOfWork)?;
+                0
+            },
+        };
+    }
+    Ok(())
+}
+
+/// Encodes accounts in the format of `NetworkInfo::genesis_accounts`.
+pub fn encode_genesis_accounts(genesis_accounts: &[(Address, Account)]) -> String {
+    let mut bytes = (genesis_accounts.len() as u16).serialize_to_vec();
+    for (address, account) in genesis_accounts {
+        bytes.append(&mut address.serialize_to_vec());
+        bytes.append(&mut account.serialize_to_vec());
+    }
+    hex::encode(bytes)
+}
+
+fn parse_address(s: &str) -> Result<Address, GenesisError> {
+    Address::from_any_str(s).map_err(|_| GenesisError::InvalidAddress(s.to_string()))
+}
+
+fn parse_coin(luna: u64) -> Result<Coin, GenesisError> {
+    Coin::from_u64(luna).map_err(|_| GenesisError::InvalidAmount(luna))
+}
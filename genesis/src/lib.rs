@@ -0,0 +1,17 @@
+#[macro_use]
+extern crate serde_derive;
+
+extern crate nimiq_account as account;
+extern crate nimiq_accounts as accounts;
+extern crate nimiq_block as block;
+extern crate nimiq_database as database;
+extern crate nimiq_hash as hash;
+extern crate nimiq_keys as keys;
+extern crate nimiq_network_primitives as network_primitives;
+extern crate nimiq_primitives as primitives;
+
+pub mod config;
+pub mod network_file;
+
+pub use self::config::{GenesisConfig, GenesisError};
+pub use self::network_file::{NetworkFile, NetworkFileError};
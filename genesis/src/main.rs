@@ -0,0 +1,48 @@
+//! Creates the network file of a custom network from a genesis config, see `genesis.example.toml`.
+//! The network file is written to stdout unless an output file is given.
+
+use std::process;
+
+use clap::{App, Arg};
+use failure::Error;
+
+use nimiq_genesis::GenesisConfig;
+
+fn run() -> Result<(), Error> {
+    let matches = App::new("nimiq-genesis")
+        .version("0.1.0")
+        .about("Creates the genesis block and network file of a custom Nimiq network")
+        .author("The Nimiq Core Development Team <info@nimiq.com>")
+        .arg(Arg::with_name("config")
+            .value_name("CONFIG")
+            .help("Genesis config file")
+            .required(true))
+        .arg(Arg::with_name("output")
+            .short("o")
+            .long("output")
+            .value_name("FILE")
+            .help("Write the network file to FILE")
+            .takes_value(true))
+        .get_matches();
+
+    let config = GenesisConfig::from_file(matches.value_of("config").unwrap())?;
+    eprintln!("Mining genesis block of network '{}', this may take a while.", config.name);
+    let network_file = config.build()?;
+    eprintln!("Genesis hash: {}", network_file.genesis.hash);
+
+    match matches.value_of("output") {
+        Some(output) => network_file.to_file(output)?,
+        None => print!("{}", network_file.to_toml()?),
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        for cause in e.iter_causes() {
+            eprintln!("Caused by: {}", cause);
+        }
+        process::exit(1);
+    }
+}
@@ -0,0 +1,277 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use failure::Fail;
+use hex::FromHex;
+use url::Url;
+
+use accounts::Accounts;
+use block::{Block, BlockBody, BlockHeader, BlockInterlink};
+use database::WriteTransaction;
+use database::volatile::{VolatileDatabaseError, VolatileEnvironment};
+use hash::{Blake2bHash, Hash};
+use keys::{Address, PublicKey};
+use network_primitives::address::peer_uri::PeerUri;
+use network_primitives::address::seed_list::SeedList;
+use network_primitives::networks::NetworkInfo;
+use primitives::networks::NetworkId;
+
+#[derive(Debug, Fail)]
+pub enum NetworkFileError {
+    #[fail(display = "{}", _0)]
+    IoError(#[cause] io::Error),
+    #[fail(display = "{}", _0)]
+    ParseError(#[cause] toml::de::Error),
+    #[fail(display = "{}", _0)]
+    SerializeError(#[cause] toml::ser::Error),
+    #[fail(display = "Network ID {} is reserved for a built-in network", _0)]
+    ReservedNetworkId(u8),
+    #[fail(display = "Invalid seed peer: {}", _0)]
+    InvalidSeedPeer(String),
+    #[fail(display = "Invalid seed list: {}", _0)]
+    InvalidSeedList(String),
+    #[fail(display = "Invalid genesis block: {}", _0)]
+    InvalidGenesisBlock(&'static str),
+    #[fail(display = "Invalid genesis accounts")]
+    InvalidGenesisAccounts,
+    #[fail(display = "The genesis hash doesn't match the genesis block")]
+    GenesisHashMismatch,
+    #[fail(display = "The accounts hash of the genesis block doesn't match the genesis accounts")]
+    AccountsHashMismatch,
+    #[fail(display = "{}", _0)]
+    DatabaseError(#[cause] VolatileDatabaseError),
+}
+
+impl From<io::Error> for NetworkFileError {
+    fn from(e: io::Error) -> Self {
+        NetworkFileError::IoError(e)
+    }
+}
+
+impl From<toml::de::Error> for NetworkFileError {
+    fn from(e: toml::de::Error) -> Self {
+        NetworkFileError::ParseError(e)
+    }
+}
+
+impl From<toml::ser::Error> for NetworkFileError {
+    fn from(e: toml::ser::Error) -> Self {
+        NetworkFileError::SerializeError(e)
+    }
+}
+
+impl From<VolatileDatabaseError> for NetworkFileError {
+    fn from(e: VolatileDatabaseError) -> Self {
+        NetworkFileError::DatabaseError(e)
+    }
+}
+
+/// A custom network as stored in a network file. Use the `nimiq-genesis` tool to create one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkFile {
+    /// Any ID that isn't used by one of the built-in networks.
+    pub network_id: u8,
+    pub name: String,
+    /// Seed peers as URIs, e.g. `wss://seed.example.com:8443/<public key>`.
+    #[serde(default)]
+    pub seed_peers: Vec<String>,
+    #[serde(default)]
+    pub seed_lists: Vec<SeedListEntry>,
+    pub genesis: GenesisEntry,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedListEntry {
+    pub url: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisEntry {
+    pub hash: String,
+    /// The genesis accounts in the format of `NetworkInfo::genesis_accounts`.
+    pub accounts: String,
+    pub header: HeaderEntry,
+    pub body: BodyEntry,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeaderEntry {
+    pub version: u16,
+    pub prev_hash: String,
+    pub interlink_hash: String,
+    pub body_hash: String,
+    pub accounts_hash: String,
+    pub n_bits: u32,
+    pub height: u32,
+    pub timestamp: u32,
+    pub nonce: u32,
+}
+
+/// The genesis block body. It can't contain transactions.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BodyEntry {
+    pub miner: String,
+    /// Hex-encoded
+    #[serde(default)]
+    pub extra_data: String,
+}
+
+impl NetworkFile {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, NetworkFileError> {
+        Self::from_str(&fs::read_to_string(path)?)
+    }
+
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), NetworkFileError> {
+        Ok(fs::write(path, self.to_toml()?)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String, NetworkFileError> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Checks the network file and turns it into a `NetworkInfo` that can be registered with
+    /// `register_custom_network`.
+    pub fn to_network_info(&self) -> Result<NetworkInfo, NetworkFileError> {
+        let network_id = NetworkId::from(self.network_id);
+        if !network_id.is_custom() {
+            return Err(NetworkFileError::ReservedNetworkId(self.network_id));
+        }
+
+        let seed_peers = self.seed_peers.iter()
+            .map(|uri| PeerUri::from_str(uri)
+                .and_then(|peer_uri| peer_uri.as_seed_peer_address())
+                .map_err(|_| NetworkFileError::InvalidSeedPeer(uri.clone())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let seed_lists = self.seed_lists.iter()
+            .map(|seed_list| seed_list.to_seed_list())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let genesis_block = self.genesis.to_block()?;
+        let genesis_hash = parse_hash(&self.genesis.hash, "invalid hash")?;
+        if genesis_block.header.hash::<Blake2bHash>() != genesis_hash {
+            return Err(NetworkFileError::GenesisHashMismatch);
+        }
+        if !genesis_block.header.verify_proof_of_work() {
+            return Err(NetworkFileError::InvalidGenesisBlock("invalid proof-of-work"));
+        }
+
+        // Check the accounts right away, the blockchain would panic on them otherwise.
+        let genesis_accounts = hex::decode(&self.genesis.accounts).ok()
+            .and_then(|bytes| Accounts::parse_genesis_accounts(&bytes).ok())
+            .ok_or(NetworkFileError::InvalidGenesisAccounts)?;
+        let env = VolatileEnvironment::new(10)?;
+        let accounts = Accounts::new(&env);
+        let mut txn = WriteTransaction::new(&env);
+        let body = genesis_block.body.as_ref().expect("Genesis block without body");
+        accounts.init_with(&mut txn, genesis_accounts, body, genesis_block.header.height)
+            .map_err(|_| NetworkFileError::InvalidGenesisAccounts)?;
+        let accounts_hash = accounts.hash(Some(&txn));
+        txn.abort();
+        if accounts_hash != genesis_block.header.accounts_hash {
+            return Err(NetworkFileError::AccountsHashMismatch);
+        }
+
+        Ok(NetworkInfo {
+            network_id,
+            name: self.name.clone(),
+            seed_peers,
+            seed_lists,
+            dns_seeds: vec![],
+            genesis_block,
+            genesis_hash,
+            genesis_accounts: self.genesis.accounts.clone(),
+        })
+    }
+}
+
+impl FromStr for NetworkFile {
+    type Err = NetworkFileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+impl SeedListEntry {
+    fn to_seed_list(&self) -> Result<SeedList, NetworkFileError> {
+        let url = Url::parse(&self.url)
+            .map_err(|_| NetworkFileError::InvalidSeedList(self.url.clone()))?;
+        let public_key = self.public_key.as_ref()
+            .map(|public_key| PublicKey::from_hex(public_key))
+            .transpose()
+            .map_err(|_| NetworkFileError::InvalidSeedList(self.url.clone()))?;
+        Ok(SeedList::new(url, public_key))
+    }
+}
+
+impl GenesisEntry {
+    pub fn from_block(block: &Block, accounts: String) -> Self {
+        let header = &block.header;
+        let body = block.body.as_ref().expect("Genesis block without body");
+        GenesisEntry {
+            hash: header.hash::<Blake2bHash>().to_hex(),
+            accounts,
+            header: HeaderEntry {
+                version: header.version,
+                prev_hash: header.prev_hash.to_hex(),
+                interlink_hash: header.interlink_hash.to_hex(),
+                body_hash: header.body_hash.to_hex(),
+                accounts_hash: header.accounts_hash.to_hex(),
+                n_bits: header.n_bits.into(),
+                height: header.height,
+                timestamp: header.timestamp,
+                nonce: header.nonce,
+            },
+            body: BodyEntry {
+                miner: body.miner.to_user_friendly_address(),
+                extra_data: hex::encode(&body.extra_data),
+            },
+        }
+    }
+
+    pub fn to_block(&self) -> Result<Block, NetworkFileError> {
+        let header = BlockHeader {
+            version: self.header.version,
+            prev_hash: parse_hash(&self.header.prev_hash, "invalid previous hash")?,
+            interlink_hash: parse_hash(&self.header.interlink_hash, "invalid interlink hash")?,
+            body_hash: parse_hash(&self.header.body_hash, "invalid body hash")?,
+            accounts_hash: parse_hash(&self.header.accounts_hash, "invalid accounts hash")?,
+            n_bits: self.header.n_bits.into(),
+            height: self.header.height,
+            timestamp: self.header.timestamp,
+            nonce: self.header.nonce,
+        };
+        let body = BlockBody {
+            miner: Address::from_any_str(&self.body.miner)
+                .map_err(|_| NetworkFileError::InvalidGenesisBlock("invalid miner address"))?,
+            extra_data: hex::decode(&self.body.extra_data)
+                .map_err(|_| NetworkFileError::InvalidGenesisBlock("extra data must be hex-encoded"))?,
+            transactions: vec![],
+            pruned_accounts: vec![],
+        };
+        if header.body_hash != body.hash::<Blake2bHash>() {
+            return Err(NetworkFileError::InvalidGenesisBlock("body hash mismatch"));
+        }
+
+        Ok(Block {
+            interlink: BlockInterlink::new(vec![], &header.prev_hash),
+            header,
+            body: Some(body),
+        })
+    }
+}
+
+fn parse_hash(s: &str, error: &'static str) -> Result<Blake2bHash, NetworkFileError> {
+    Blake2bHash::from_str(s).map_err(|_| NetworkFileError::InvalidGenesisBlock(error))
+}
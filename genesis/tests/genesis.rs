@@ -0,0 +1,143 @@
+use std::str::FromStr;
+
+use beserial::Deserialize;
+use nimiq_account::Account;
+use nimiq_accounts::Accounts;
+use nimiq_block::TargetCompact;
+use nimiq_genesis::config::{encode_genesis_accounts, mine, BasicAccountConfig, VestingContractConfig};
+use nimiq_genesis::network_file::GenesisEntry;
+use nimiq_genesis::{GenesisConfig, GenesisError, NetworkFile, NetworkFileError};
+use nimiq_hash::{Blake2bHash, Hash};
+use nimiq_network_primitives::networks::{get_network_info, register_custom_network, NetworkId};
+
+/// A genesis config with the accounts and parameters of the dev network.
+fn dev_config() -> GenesisConfig {
+    let dev = get_network_info(NetworkId::Dev).unwrap();
+    let genesis_accounts = Accounts::parse_genesis_accounts(&hex::decode(&dev.genesis_accounts).unwrap()).unwrap();
+
+    let mut config = GenesisConfig {
+        network_id: 100,
+        name: "dev-copy".to_string(),
+        timestamp: Some(dev.genesis_block.header.timestamp),
+        miner: None,
+        extra_data: "DevNet".to_string(),
+        n_bits: None,
+        seed_peers: vec![],
+        seed_lists: vec![],
+        accounts: vec![],
+        vesting_contracts: vec![],
+    };
+    for (address, account) in genesis_accounts {
+        match account {
+            Account::Basic(account) => config.accounts.push(BasicAccountConfig {
+                address: address.to_user_friendly_address(),
+                balance: account.balance.into(),
+            }),
+            Account::Vesting(contract) => config.vesting_contracts.push(VestingContractConfig {
+                address: address.to_user_friendly_address(),
+                owner: contract.owner.to_user_friendly_address(),
+                balance: contract.balance.into(),
+                start: contract.vesting_start,
+                step_blocks: contract.vesting_step_blocks,
+                step_amount: contract.vesting_step_amount.into(),
+                total_amount: Some(contract.vesting_total_amount.into()),
+            }),
+            Account::HTLC(_) => unreachable!(),
+        }
+    }
+    config
+}
+
+#[test]
+fn it_reproduces_the_dev_genesis_block() {
+    let dev = get_network_info(NetworkId::Dev).unwrap();
+    let config = dev_config();
+    let mut genesis_block = config.genesis_block(&config.genesis_accounts().unwrap()).unwrap();
+    assert_eq!(genesis_block.header.accounts_hash, dev.genesis_block.header.accounts_hash);
+    assert_eq!(genesis_block.header.body_hash, dev.genesis_block.header.body_hash);
+
+    genesis_block.header.nonce = dev.genesis_block.header.nonce;
+    assert_eq!(genesis_block.header.hash::<Blake2bHash>(), dev.genesis_hash);
+}
+
+#[test]
+fn it_loads_and_registers_network_files() {
+    let dev = get_network_info(NetworkId::Dev).unwrap();
+    let config = dev_config();
+    let genesis_accounts = config.genesis_accounts().unwrap();
+    let mut genesis_block = config.genesis_block(&genesis_accounts).unwrap();
+    genesis_block.header.nonce = dev.genesis_block.header.nonce;
+
+    let network_file = NetworkFile {
+        network_id: config.network_id,
+        name: config.name.clone(),
+        seed_peers: vec!["wss://seed.consortium.example:8443/e65e39616662f2c16d62dc08915e5a1d104619db8c2b9cf9b389f96c8dce9837".to_string()],
+        seed_lists: vec![],
+        genesis: GenesisEntry::from_block(&genesis_block, encode_genesis_accounts(&genesis_accounts)),
+    };
+    let mut network_file = NetworkFile::from_str(&network_file.to_toml().unwrap()).unwrap();
+    let network_info = network_file.to_network_info().unwrap();
+    assert_eq!(network_info.network_id, NetworkId::from(100));
+    assert_eq!(network_info.genesis_hash, dev.genesis_hash);
+    assert_eq!(network_info.seed_peers.len(), 1);
+
+    assert!(NetworkId::deserialize_from_vec(&vec![100]).is_err());
+    register_custom_network(network_info).unwrap();
+    assert_eq!(get_network_info(NetworkId::from(100)).unwrap().name, "dev-copy");
+    assert_eq!(NetworkId::deserialize_from_vec(&vec![100]).unwrap(), NetworkId::from(100));
+    assert!(NetworkId::deserialize_from_vec(&vec![101]).is_err());
+    assert!(register_custom_network(network_file.to_network_info().unwrap()).is_err());
+
+    network_file.network_id = 42;
+    match network_file.to_network_info() {
+        Err(NetworkFileError::ReservedNetworkId(42)) => {},
+        _ => panic!("Network file with a reserved network ID was accepted"),
+    }
+}
+
+#[test]
+fn it_rejects_network_files_with_other_accounts() {
+    let dev = get_network_info(NetworkId::Dev).unwrap();
+    let config = dev_config();
+    let genesis_accounts = config.genesis_accounts().unwrap();
+    let mut genesis_block = config.genesis_block(&genesis_accounts).unwrap();
+    genesis_block.header.nonce = dev.genesis_block.header.nonce;
+
+    let mut network_file = NetworkFile {
+        network_id: config.network_id,
+        name: config.name.clone(),
+        seed_peers: vec![],
+        seed_lists: vec![],
+        genesis: GenesisEntry::from_block(&genesis_block, encode_genesis_accounts(&genesis_accounts)),
+    };
+    assert!(network_file.to_network_info().is_ok());
+
+    network_file.genesis.accounts = encode_genesis_accounts(&genesis_accounts[1..]);
+    match network_file.to_network_info() {
+        Err(NetworkFileError::AccountsHashMismatch) => {},
+        _ => panic!("Network file with accounts that don't match the accounts hash was accepted"),
+    }
+}
+
+#[test]
+fn it_moves_the_timestamp_once_all_nonces_have_been_tried() {
+    let mut header = get_network_info(NetworkId::Regtest).unwrap().genesis_block.header.clone();
+    header.nonce = u32::max_value();
+    while header.verify_proof_of_work() {
+        header.timestamp += 1;
+    }
+    let timestamp = header.timestamp;
+
+    mine(&mut header).unwrap();
+    assert!(header.verify_proof_of_work());
+    assert_eq!(header.timestamp, timestamp + 1);
+
+    // Nothing meets the hardest target.
+    header.n_bits = TargetCompact::from(0x0100_0001);
+    header.nonce = u32::max_value();
+    header.timestamp = u32::max_value();
+    match mine(&mut header) {
+        Err(GenesisError::NoValidProofOfWork) => {},
+        _ => panic!("Mining didn't give up"),
+    }
+}
@@ -1,13 +1,16 @@
 use std::sync::Arc;
 use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 
 use futures::{Async, Future, Poll};
 
 use consensus::consensus::Consensus;
 use database::Environment;
+use network::message_limits::MessageLimits;
 use network::network::Network;
-use network::network_config::{NetworkConfig, ReverseProxyConfig, Seed, PeerKeyStore};
-use network_primitives::address::NetAddress;
+use network::network_config::{AllowedPeer, ClientAddressSource, ConnectionMode, NetworkConfig, ProxyConfig, ReverseProxyConfig, ScoringConfig, Seed, PeerKeyStore};
+use network_primitives::address::NetAddressRange;
 use primitives::networks::NetworkId;
 use network_primitives::protocol::Protocol;
 use mempool::MempoolConfig;
@@ -28,10 +31,19 @@ pub struct ClientBuilder {
     peer_key_store: PeerKeyStore,
     hostname: Option<String>,
     port: Option<u16>,
+    listen_addresses: Vec<IpAddr>,
+    additional_hosts: Vec<String>,
     user_agent: String,
     network_id: NetworkId,
     reverse_proxy_config: Option<ReverseProxyConfig>,
     additional_seeds: Vec<Seed>,
+    allowed_peers: Option<Vec<AllowedPeer>>,
+    proxy: Option<ProxyConfig>,
+    connection_mode: ConnectionMode,
+    message_limits: Option<MessageLimits>,
+    scoring: Option<ScoringConfig>,
+    dns_resolver: Option<SocketAddr>,
+    capture_dir: Option<PathBuf>,
     identity_file: Option<String>,
     identity_password: Option<String>,
     mempool_config: Option<MempoolConfig>
@@ -45,10 +57,19 @@ impl ClientBuilder {
             peer_key_store,
             hostname: None,
             port: None,
+            listen_addresses: Vec::new(),
+            additional_hosts: Vec::new(),
             user_agent: DEFAULT_USER_AGENT.clone(),
             network_id: NetworkId::Main,
             reverse_proxy_config: None,
             additional_seeds: Vec::new(),
+            allowed_peers: None,
+            proxy: None,
+            connection_mode: ConnectionMode::default(),
+            message_limits: None,
+            scoring: None,
+            dns_resolver: None,
+            capture_dir: None,
             identity_file: None,
             identity_password: None,
             mempool_config: None
@@ -79,8 +100,64 @@ impl ClientBuilder {
         self
     }
 
-    pub fn with_reverse_proxy(&mut self, port: u16, address: NetAddress, header: String, with_tls_termination: bool) -> &mut Self {
-        self.reverse_proxy_config = Some(ReverseProxyConfig{port, address, header, with_tls_termination});
+    /// Runs the node in private network mode, where it only connects to the allowed peers.
+    pub fn with_allowed_peers(&mut self, allowed_peers: Vec<AllowedPeer>) -> &mut Self {
+        self.allowed_peers.get_or_insert_with(Vec::new).extend(allowed_peers);
+        self
+    }
+
+    /// Routes outbound connections through a SOCKS5 proxy.
+    pub fn with_proxy(&mut self, proxy: ProxyConfig) -> &mut Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Makes the node passive, i.e. never dial peers, or outbound-only, i.e. not listen.
+    pub fn with_connection_mode(&mut self, connection_mode: ConnectionMode) -> &mut Self {
+        self.connection_mode = connection_mode;
+        self
+    }
+
+    /// Limits the messages peers may send us, instead of using the default limits.
+    pub fn with_message_limits(&mut self, message_limits: MessageLimits) -> &mut Self {
+        self.message_limits = Some(message_limits);
+        self
+    }
+
+    /// Sets the preferred and pinned peers and the targets for scoring peer connections.
+    pub fn with_scoring(&mut self, scoring: ScoringConfig) -> &mut Self {
+        self.scoring = Some(scoring);
+        self
+    }
+
+    /// Listens on the given interfaces instead of all of them.
+    pub fn with_listen_addresses(&mut self, listen_addresses: Vec<IpAddr>) -> &mut Self {
+        self.listen_addresses = listen_addresses;
+        self
+    }
+
+    /// Advertises the given hosts besides the hostname, see `NetworkConfig::additional_hosts`.
+    pub fn with_additional_hosts(&mut self, additional_hosts: Vec<String>) -> &mut Self {
+        self.additional_hosts = additional_hosts;
+        self
+    }
+
+    /// Resolves DNS seeds with the given name server instead of the system's name server.
+    pub fn with_dns_resolver(&mut self, dns_resolver: SocketAddr) -> &mut Self {
+        self.dns_resolver = Some(dns_resolver);
+        self
+    }
+
+    /// Allows traffic captures, which are written to the given directory.
+    pub fn with_capture_dir(&mut self, capture_dir: PathBuf) -> &mut Self {
+        self.capture_dir = Some(capture_dir);
+        self
+    }
+
+    /// Runs the node behind reverse proxies at the given addresses, which pass on the address of
+    /// the client in an HTTP header or with the PROXY protocol.
+    pub fn with_reverse_proxy(&mut self, port: u16, trusted_proxies: Vec<NetAddressRange>, client_address: ClientAddressSource, with_tls_termination: bool) -> &mut Self {
+        self.reverse_proxy_config = Some(ReverseProxyConfig{port, trusted_proxies, client_address, with_tls_termination});
         self
     }
 
@@ -113,11 +190,20 @@ impl ClientBuilder {
             protocol,
             hostname,
             port,
+            listen_addresses,
+            additional_hosts,
             reverse_proxy_config,
             identity_file,
             identity_password,
             user_agent,
             additional_seeds,
+            allowed_peers,
+            proxy,
+            connection_mode,
+            message_limits,
+            scoring,
+            dns_resolver,
+            capture_dir,
         } = self;
 
         // build network config
@@ -142,12 +228,40 @@ impl ClientBuilder {
                 let identity_password = identity_password.ok_or(ClientError::MissingIdentityFile)?;
                 NetworkConfig::new_wss_network_config(hostname, port, identity_file, identity_password, reverse_proxy_config)
             },
+            Protocol::Tcp => {
+                if reverse_proxy_config.is_some() { return Err(ClientError::ConfigureReverseProxyError) }
+                let hostname = hostname.ok_or(ClientError::MissingHostname)?;
+                let port = port.unwrap_or(protocol.default_port()
+                    .ok_or(ClientError::MissingPort)?);
+                NetworkConfig::new_tcp_network_config(hostname, port)
+            },
             Protocol::Rtc => {
                 return Err(ClientError::RtcNotImplemented)
             },
         };
+        network_config.set_listen_addresses(listen_addresses);
+        network_config.set_additional_hosts(additional_hosts);
         network_config.set_user_agent(user_agent);
         network_config.set_additional_seeds(additional_seeds);
+        if let Some(allowed_peers) = allowed_peers {
+            network_config.set_allowed_peers(allowed_peers);
+        }
+        if let Some(proxy) = proxy {
+            network_config.set_proxy(proxy);
+        }
+        network_config.set_connection_mode(connection_mode);
+        if let Some(message_limits) = message_limits {
+            network_config.set_message_limits(message_limits);
+        }
+        if let Some(scoring) = scoring {
+            network_config.set_scoring(scoring);
+        }
+        if let Some(dns_resolver) = dns_resolver {
+            network_config.set_dns_resolver(dns_resolver);
+        }
+        if let Some(capture_dir) = capture_dir {
+            network_config.set_capture_dir(capture_dir);
+        }
         network_config.init_persistent(&peer_key_store)?;
 
         let mempool_config = mempool_config.unwrap_or_else(MempoolConfig::default);
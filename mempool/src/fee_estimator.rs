@@ -0,0 +1,100 @@
+use beserial::Serialize;
+use block::{Block, BlockBody, BlockHeader};
+use transaction::Transaction;
+
+/// Estimates the fee per byte a transaction needs to pay to be included within a given number of
+/// blocks. The estimate combines the fee distribution of the pending transactions in the mempool
+/// with the fees that were paid by transactions included in recently mined blocks.
+#[derive(Debug, Clone)]
+pub struct FeeEstimator {
+    history_length: u32,
+}
+
+impl FeeEstimator {
+    /// Number of recent blocks taken into account by default.
+    pub const DEFAULT_HISTORY_LENGTH: u32 = 10;
+
+    /// Blocks that are filled to less than this fraction of the available space are considered
+    /// to have had room for any transaction, regardless of its fee.
+    const FULL_BLOCK_RATIO: f64 = 0.9;
+
+    pub fn new(history_length: u32) -> Self {
+        FeeEstimator { history_length }
+    }
+
+    pub fn history_length(&self) -> u32 {
+        self.history_length
+    }
+
+    /// Space in bytes available for transactions and pruned accounts in a block.
+    /// The interlink size varies from block to block and is not taken into account here.
+    pub fn block_space() -> usize {
+        Block::MAX_SIZE
+            - BlockHeader::SIZE
+            - BlockBody::get_metadata_size(0)
+    }
+
+    /// Returns the recommended fee per byte for a transaction that should be included within
+    /// `target_blocks` blocks.
+    ///
+    /// `pending` must yield the pending transactions sorted by fee per byte in ascending order
+    /// (i.e. in mempool order), `recent_blocks` are the most recent blocks of the main chain
+    /// including their bodies.
+    pub fn estimate<'a, I>(&self, target_blocks: u32, pending: I, recent_blocks: &[Block]) -> f64
+        where I: DoubleEndedIterator<Item=&'a Transaction> {
+        let target_blocks = target_blocks.max(1);
+        let pending_estimate = Self::estimate_from_pending(target_blocks, pending);
+        let history_estimate = Self::estimate_from_blocks(target_blocks, recent_blocks);
+        pending_estimate.max(history_estimate)
+    }
+
+    /// The fee per byte of the first pending transaction that would not fit into the next
+    /// `target_blocks` blocks anymore. A new transaction needs to outbid it to be included in time.
+    fn estimate_from_pending<'a, I>(target_blocks: u32, pending: I) -> f64
+        where I: DoubleEndedIterator<Item=&'a Transaction> {
+        let space = Self::block_space() * target_blocks as usize;
+        let mut size = 0;
+        for tx in pending.rev() {
+            size += tx.serialized_size();
+            if size > space {
+                return tx.fee_per_byte();
+            }
+        }
+        0f64
+    }
+
+    /// Looks at the lowest fee per byte that made it into each of the recent blocks. Blocks that
+    /// were not full would have accepted any fee. The shorter the target, the higher the percentile
+    /// of these per-block minimums that is returned: A target of one block requires the fee to beat
+    /// all recent blocks, a target of two blocks the median, and so on.
+    fn estimate_from_blocks(target_blocks: u32, recent_blocks: &[Block]) -> f64 {
+        let mut min_fees: Vec<f64> = recent_blocks.iter()
+            .filter_map(|block| block.body.as_ref())
+            .map(|body| {
+                let used = body.serialized_size() - BlockBody::get_metadata_size(body.extra_data.len());
+                if (used as f64) < Self::block_space() as f64 * Self::FULL_BLOCK_RATIO {
+                    return 0f64;
+                }
+                body.transactions.iter()
+                    .map(Transaction::fee_per_byte)
+                    .fold(None, |min: Option<f64>, fee| Some(min.map_or(fee, |min| min.min(fee))))
+                    .unwrap_or(0f64)
+            })
+            .collect();
+
+        if min_fees.is_empty() {
+            return 0f64;
+        }
+
+        // Sort descending.
+        min_fees.sort_unstable_by(|a, b| b.partial_cmp(a).unwrap());
+        let index = min_fees.len() * (target_blocks as usize - 1) / target_blocks as usize;
+        min_fees[index]
+    }
+}
+
+impl Default for FeeEstimator {
+    fn default() -> Self {
+        FeeEstimator::new(Self::DEFAULT_HISTORY_LENGTH)
+    }
+}
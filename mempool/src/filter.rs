@@ -1,5 +1,8 @@
-use account::Account;
+use std::collections::{BTreeMap, HashSet};
+
+use account::{Account, AccountType};
 use collections::LimitHashSet;
+use keys::Address;
 use nimiq_hash::Blake2bHash;
 use primitives::coin::Coin;
 use transaction::{Transaction, TransactionFlags};
@@ -7,6 +10,8 @@ use transaction::{Transaction, TransactionFlags};
 #[derive(Debug)]
 pub struct MempoolFilter {
     blacklist: LimitHashSet<Blake2bHash>,
+    /// Hashes of transactions rejected by the current rules.
+    rejected: LimitHashSet<Blake2bHash>,
     rules: Rules,
 }
 
@@ -16,6 +21,7 @@ impl MempoolFilter {
     pub fn new(rules: Rules, blacklist_limit: usize) -> Self {
         MempoolFilter {
             blacklist: LimitHashSet::new(blacklist_limit),
+            rejected: LimitHashSet::new(blacklist_limit),
             rules,
         }
     }
@@ -25,16 +31,65 @@ impl MempoolFilter {
         self
     }
 
+    /// Blacklists a transaction that was rejected by the filter rules. Unlike other blacklisted
+    /// hashes, it is removed from the blacklist when the rules change.
+    pub fn reject(&mut self, hash: Blake2bHash) -> &mut Self {
+        self.rejected.insert(hash);
+        self
+    }
+
     pub fn remove(&mut self, hash: &Blake2bHash) -> &mut Self {
         self.blacklist.remove(hash);
+        self.rejected.remove(hash);
         self
     }
 
     pub fn blacklisted(&self, hash: &Blake2bHash) -> bool {
-        self.blacklist.contains(hash)
+        self.blacklist.contains(hash) || self.rejected.contains(hash)
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Replaces the filter rules. Transactions rejected under the old rules are removed from the
+    /// blacklist, so that they are checked against the new rules.
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+        self.rejected.clear();
     }
 
     pub fn accepts_transaction(&self, tx: &Transaction) -> bool {
+        self.accepts_addresses(tx) &&
+            self.rules.data_size_max.map_or(true, |max| tx.data.len() <= max) &&
+            self.accepts_account_types(tx) &&
+            self.accepts_values(tx)
+    }
+
+    fn accepts_addresses(&self, tx: &Transaction) -> bool {
+        !self.rules.sender_blacklist.contains(&tx.sender) &&
+            !self.rules.recipient_blacklist.contains(&tx.recipient) &&
+            (self.rules.sender_whitelist.is_empty() || self.rules.sender_whitelist.contains(&tx.sender)) &&
+            (self.rules.recipient_whitelist.is_empty() || self.rules.recipient_whitelist.contains(&tx.recipient))
+    }
+
+    fn accepts_account_types(&self, tx: &Transaction) -> bool {
+        let mut account_types = vec![tx.sender_type];
+        if tx.recipient_type != tx.sender_type {
+            account_types.push(tx.recipient_type);
+        }
+
+        account_types.iter()
+            .filter_map(|account_type| self.rules.account_types.get(account_type))
+            .all(|rules| {
+                !rules.reject &&
+                    tx.fee_per_byte() >= rules.tx_fee_per_byte &&
+                    tx.value >= rules.tx_value &&
+                    rules.data_size_max.map_or(true, |max| tx.data.len() <= max)
+            })
+    }
+
+    fn accepts_values(&self, tx: &Transaction) -> bool {
          tx.fee >= self.rules.tx_fee &&
              tx.value >= self.rules.tx_value &&
              // Unchecked addition of coins.
@@ -86,6 +141,18 @@ pub struct Rules {
     pub creation_value: Coin,
     pub recipient_balance: Coin,
     pub sender_balance: Coin,
+    /// Transactions from these addresses are rejected.
+    pub sender_blacklist: HashSet<Address>,
+    /// If not empty, only transactions from these addresses are accepted.
+    pub sender_whitelist: HashSet<Address>,
+    /// Transactions to these addresses are rejected.
+    pub recipient_blacklist: HashSet<Address>,
+    /// If not empty, only transactions to these addresses are accepted.
+    pub recipient_whitelist: HashSet<Address>,
+    /// Maximum size of the data field in bytes.
+    pub data_size_max: Option<usize>,
+    /// Additional rules for transactions from or to accounts of a certain type.
+    pub account_types: BTreeMap<AccountType, AccountTypeRules>,
 }
 
 impl Default for Rules {
@@ -103,6 +170,33 @@ impl Default for Rules {
             creation_value: Coin::ZERO,
             sender_balance: Coin::ZERO,
             recipient_balance: Coin::ZERO,
+            sender_blacklist: HashSet::new(),
+            sender_whitelist: HashSet::new(),
+            recipient_blacklist: HashSet::new(),
+            recipient_whitelist: HashSet::new(),
+            data_size_max: None,
+            account_types: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AccountTypeRules {
+    /// Reject all transactions from or to this account type.
+    pub reject: bool,
+    pub tx_fee_per_byte: f64,
+    pub tx_value: Coin,
+    /// Maximum size of the data field in bytes.
+    pub data_size_max: Option<usize>,
+}
+
+impl Default for AccountTypeRules {
+    fn default() -> AccountTypeRules {
+        AccountTypeRules {
+            reject: false,
+            tx_fee_per_byte: 0.0,
+            tx_value: Coin::ZERO,
+            data_size_max: None,
         }
     }
 }
@@ -27,8 +27,10 @@ use keys::Address;
 use transaction::{Transaction, TransactionFlags};
 use utils::observer::Notifier;
 
+use crate::fee_estimator::FeeEstimator;
 use crate::filter::{MempoolFilter, Rules};
 
+pub mod fee_estimator;
 pub mod filter;
 
 pub struct Mempool<'env> {
@@ -36,6 +38,14 @@ pub struct Mempool<'env> {
     pub notifier: RwLock<Notifier<'env, MempoolEvent>>,
     state: RwLock<MempoolState>,
     mut_lock: Mutex<()>,
+    fee_estimator: FeeEstimator,
+    replace_by_fee: bool,
+    replacement_fee_bump: f64,
+    size_max: usize,
+    total_size_max: usize,
+    transactions_per_sender_max: u32,
+    free_transactions_per_sender_max: u32,
+    transaction_relay_fee_min: f64,
 }
 
 struct MempoolState {
@@ -43,6 +53,7 @@ struct MempoolState {
     transactions_by_sender: HashMap<Address, BTreeSet<Arc<Transaction>>>,
     transactions_by_recipient: HashMap<Address, BTreeSet<Arc<Transaction>>>,
     transactions_sorted_fee: BTreeSet<Arc<Transaction>>, // sorted by fee, ascending
+    total_size: usize, // serialized size of all transactions in bytes
     filter: MempoolFilter,
 }
 
@@ -52,19 +63,51 @@ pub enum MempoolEvent {
     TransactionRestored(Arc<Transaction>),
     TransactionMined(Arc<Transaction>),
     TransactionEvicted(Arc<Transaction>),
+    /// A pending transaction (first) was replaced by a transaction paying a higher fee (second).
+    TransactionReplaced(Arc<Transaction>, Arc<Transaction>),
 }
 
 #[derive(Debug, Clone)]
 pub struct MempoolConfig {
     pub filter_rules: Rules,
     pub filter_limit: usize,
+    /// Allow pending transactions to be replaced by a transaction with a higher fee.
+    pub replace_by_fee: bool,
+    /// Minimum increase of the fee per byte (sat/byte) for a transaction to replace a pending one.
+    pub replacement_fee_bump: f64,
+    /// Maximum number of transactions in the mempool.
+    pub size_max: usize,
+    /// Maximum serialized size (bytes) of all transactions in the mempool.
+    pub total_size_max: usize,
+    /// Maximum number of transactions per sender.
+    pub transactions_per_sender_max: u32,
+    /// Maximum number of "free" transactions per sender.
+    pub free_transactions_per_sender_max: u32,
+    /// Fee threshold in sat/byte below which transactions are considered "free".
+    pub transaction_relay_fee_min: f64,
+}
+
+impl MempoolConfig {
+    pub const DEFAULT_REPLACEMENT_FEE_BUMP: f64 = 1f64;
+    pub const DEFAULT_SIZE_MAX: usize = 100_000;
+    pub const DEFAULT_TOTAL_SIZE_MAX: usize = 25_000_000; // 25 MB
+    pub const DEFAULT_TRANSACTIONS_PER_SENDER_MAX: u32 = 500;
+    pub const DEFAULT_FREE_TRANSACTIONS_PER_SENDER_MAX: u32 = 10;
+    pub const DEFAULT_TRANSACTION_RELAY_FEE_MIN: f64 = 1f64;
 }
 
 impl Default for MempoolConfig {
     fn default() -> MempoolConfig {
         MempoolConfig {
             filter_rules: Rules::default(),
-            filter_limit: MempoolFilter::DEFAULT_BLACKLIST_SIZE
+            filter_limit: MempoolFilter::DEFAULT_BLACKLIST_SIZE,
+            replace_by_fee: false,
+            replacement_fee_bump: MempoolConfig::DEFAULT_REPLACEMENT_FEE_BUMP,
+            size_max: MempoolConfig::DEFAULT_SIZE_MAX,
+            total_size_max: MempoolConfig::DEFAULT_TOTAL_SIZE_MAX,
+            transactions_per_sender_max: MempoolConfig::DEFAULT_TRANSACTIONS_PER_SENDER_MAX,
+            free_transactions_per_sender_max: MempoolConfig::DEFAULT_FREE_TRANSACTIONS_PER_SENDER_MAX,
+            transaction_relay_fee_min: MempoolConfig::DEFAULT_TRANSACTION_RELAY_FEE_MIN,
         }
     }
 }
@@ -79,9 +122,18 @@ impl<'env> Mempool<'env> {
                 transactions_by_sender: HashMap::new(),
                 transactions_by_recipient: HashMap::new(),
                 transactions_sorted_fee: BTreeSet::new(),
+                total_size: 0,
                 filter: MempoolFilter::new(config.filter_rules, config.filter_limit),
             }),
             mut_lock: Mutex::new(()),
+            fee_estimator: FeeEstimator::default(),
+            replace_by_fee: config.replace_by_fee,
+            replacement_fee_bump: config.replacement_fee_bump.max(0f64),
+            size_max: config.size_max,
+            total_size_max: config.total_size_max,
+            transactions_per_sender_max: config.transactions_per_sender_max,
+            free_transactions_per_sender_max: config.free_transactions_per_sender_max,
+            transaction_relay_fee_min: config.transaction_relay_fee_min,
         });
 
         let arc_self = arc.clone();
@@ -93,6 +145,44 @@ impl<'env> Mempool<'env> {
         self.state.read().filter.blacklisted(hash)
     }
 
+    pub fn filter_rules(&self) -> Rules {
+        self.state.read().filter.rules().clone()
+    }
+
+    /// Replaces the filter rules and evicts all pending transactions that are not accepted by
+    /// the new rules. Rules that depend on account balances are only applied to new transactions.
+    pub fn set_filter_rules(&self, rules: Rules) {
+        // Only one mutating operation at a time.
+        let _lock = self.mut_lock.lock();
+
+        let mut txs_filtered = Vec::new();
+        {
+            let mut state = self.state.write();
+            state.filter.set_rules(rules);
+
+            for tx in state.transactions_sorted_fee.iter() {
+                if !state.filter.accepts_transaction(tx) {
+                    txs_filtered.push(tx.clone());
+                }
+            }
+
+            // Evicted transactions are not blacklisted, so they are accepted again if the
+            // rules are relaxed later.
+            for tx in txs_filtered.iter() {
+                Mempool::remove_transaction(&mut state, tx);
+            }
+        }
+
+        if !txs_filtered.is_empty() {
+            debug!("Evicted {} transactions after filter rules changed", txs_filtered.len());
+        }
+
+        // Notify listeners.
+        for tx in txs_filtered {
+            self.notifier.read().notify(MempoolEvent::TransactionEvicted(tx));
+        }
+    }
+
     pub fn push_transaction(&self, mut transaction: Transaction) -> ReturnCode {
         let hash: Blake2bHash = transaction.hash();
 
@@ -105,13 +195,20 @@ impl<'env> Mempool<'env> {
         // Transactions that are invalidated by the new transaction are stored here.
         let mut txs_to_remove = Vec::new();
 
+        // The pending transaction that is replaced by the new transaction, if any.
+        let mut replaced_tx = None;
+
         {
             let state = self.state.upgradable_read();
 
             // Check transaction against rules and blacklist
-            if !state.filter.accepts_transaction(&transaction) || state.filter.blacklisted(&hash) {
+            if state.filter.blacklisted(&hash) {
+                trace!("Transaction is blacklisted: {}", hash);
+                return ReturnCode::Filtered;
+            }
+            if !state.filter.accepts_transaction(&transaction) {
                 let mut state = RwLockUpgradableReadGuard::upgrade(state);
-                state.filter.blacklist(hash);
+                state.filter.reject(hash);
                 trace!("Transaction was filtered: {}", transaction.hash::<Blake2bHash>());
                 return ReturnCode::Filtered;
             }
@@ -126,15 +223,27 @@ impl<'env> Mempool<'env> {
                 return ReturnCode::Invalid;
             }
 
-            // Check limit for free transactions.
             let txs_by_sender_opt = state.transactions_by_sender.get(&transaction.sender);
-            if transaction.fee_per_byte() < TRANSACTION_RELAY_FEE_MIN {
+
+            // Check if the new transaction replaces a pending one.
+            if self.replace_by_fee {
+                if let Some(tx) = txs_by_sender_opt.and_then(|txs| txs.iter().find(|tx| Mempool::is_replacement(tx, &transaction))) {
+                    if transaction.fee_per_byte() <= tx.fee_per_byte() + self.replacement_fee_bump {
+                        return ReturnCode::FeeTooLow;
+                    }
+                    replaced_tx = Some(Arc::clone(tx));
+                }
+            }
+            let is_replaced = |tx: &Arc<Transaction>| replaced_tx.as_ref().map_or(false, |r| Arc::ptr_eq(r, tx));
+
+            // Check limit for free transactions.
+            if transaction.fee_per_byte() < self.transaction_relay_fee_min {
                 let mut num_free_tx = 0;
                 if let Some(transactions) = txs_by_sender_opt {
-                    for tx in transactions {
-                        if tx.fee_per_byte() < TRANSACTION_RELAY_FEE_MIN {
+                    for tx in transactions.iter().filter(|tx| !is_replaced(*tx)) {
+                        if tx.fee_per_byte() < self.transaction_relay_fee_min {
                             num_free_tx += 1;
-                            if num_free_tx >= FREE_TRANSACTIONS_PER_SENDER_MAX {
+                            if num_free_tx >= self.free_transactions_per_sender_max {
                                 return ReturnCode::FeeTooLow;
                             }
                         } else {
@@ -179,7 +288,7 @@ impl<'env> Mempool<'env> {
                     Ok(r) => {
                         // Check recipient account against filter rules.
                         if !state.filter.accepts_recipient_account(&transaction, &recipient_account, &r) {
-                            self.state.write().filter.blacklist(hash);
+                            self.state.write().filter.reject(hash);
                             return ReturnCode::Filtered;
                         }
                     }
@@ -206,6 +315,7 @@ impl<'env> Mempool<'env> {
 
             // First apply all transactions with a higher fee/byte.
             // These are not affected by the new transaction and should never fail to apply.
+            // The replaced transaction (if any) always has a lower fee/byte and is skipped further below.
             let mut tx_opt = tx_iter.next_back();
             while let Some(tx) = tx_opt {
                 // Break on the first transaction with a lower fee/byte.
@@ -224,7 +334,7 @@ impl<'env> Mempool<'env> {
             }
 
             // If we are already at the transaction limit, reject the new transaction.
-            if tx_count >= TRANSACTIONS_PER_SENDER_MAX {
+            if tx_count >= self.transactions_per_sender_max {
                 return ReturnCode::FeeTooLow;
             }
 
@@ -237,7 +347,7 @@ impl<'env> Mempool<'env> {
 
             // Check sender account against filter rules.
             if !state.filter.accepts_sender_account(&transaction, &old_sender_account, &sender_account) {
-                self.state.write().filter.blacklist(hash);
+                self.state.write().filter.reject(hash);
                 return ReturnCode::Filtered;
             }
 
@@ -246,7 +356,9 @@ impl<'env> Mempool<'env> {
             // Finally, check the remaining transactions with lower fee/byte and evict them if necessary.
             // tx_opt already contains the first lower/fee byte transaction to check (if there is one remaining).
             while let Some(tx) = tx_opt {
-                if tx_count < TRANSACTIONS_PER_SENDER_MAX {
+                if is_replaced(tx) {
+                    // The replaced transaction is removed below.
+                } else if tx_count < self.transactions_per_sender_max {
                     if let Ok(account) = sender_account.with_outgoing_transaction(tx, block_height) {
                         sender_account = account;
                         tx_count += 1;
@@ -269,6 +381,12 @@ impl<'env> Mempool<'env> {
             let mut state = self.state.write();
             Mempool::add_transaction(&mut state, hash.clone(), tx_arc.clone());
 
+            // Remove the replaced transaction and make sure we don't accept it again.
+            if let Some(ref tx) = replaced_tx {
+                Mempool::remove_transaction(&mut state, tx);
+                state.filter.blacklist(tx.hash());
+            }
+
             // Evict transactions that were invalidated by the new transaction.
             for tx in txs_to_remove.iter() {
                 Mempool::remove_transaction(&mut *state, tx);
@@ -277,17 +395,18 @@ impl<'env> Mempool<'env> {
             // Rename variable.
             removed_transactions = txs_to_remove;
 
-            // Remove the lowest fee transaction if mempool max size is reached.
-            if state.transactions_sorted_fee.len() > SIZE_MAX {
-                let tx = state.transactions_sorted_fee.iter().next().unwrap().clone();
-                Mempool::remove_transaction(&mut state, &tx);
-                removed_transactions.push(tx);
-            }
+            // Remove the lowest fee transactions if mempool max size is reached.
+            removed_transactions.extend(self.evict_lowest_fee(&mut state));
         }
 
         // Drop the lock on blockchain::push
         drop(_push_lock);
 
+        // Tell listeners about the transaction we replaced.
+        if let Some(tx) = replaced_tx {
+            self.notifier.read().notify(MempoolEvent::TransactionReplaced(tx, Arc::clone(&tx_arc)));
+        }
+
         // Tell listeners about the new transaction we received.
         self.notifier.read().notify(MempoolEvent::TransactionAdded(hash, tx_arc));
 
@@ -303,6 +422,24 @@ impl<'env> Mempool<'env> {
         self.state.read().transactions_by_hash.contains_key(hash)
     }
 
+    /// Number of transactions in the mempool.
+    pub fn size(&self) -> usize {
+        self.state.read().transactions_by_hash.len()
+    }
+
+    /// Serialized size of all transactions in the mempool in bytes.
+    pub fn total_size(&self) -> usize {
+        self.state.read().total_size
+    }
+
+    pub fn size_max(&self) -> usize {
+        self.size_max
+    }
+
+    pub fn total_size_max(&self) -> usize {
+        self.total_size_max
+    }
+
     pub fn get_transaction(&self, hash: &Blake2bHash) -> Option<Arc<Transaction>> {
         self.state.read().transactions_by_hash.get(hash).cloned()
     }
@@ -333,6 +470,22 @@ impl<'env> Mempool<'env> {
         txs
     }
 
+    /// Returns the recommended fee per byte for a transaction to be included within the next
+    /// `target_blocks` blocks.
+    pub fn estimate_fee(&self, target_blocks: u32) -> f64 {
+        let mut recent_blocks = Vec::new();
+        let head_height = self.blockchain.height();
+        let history_length = self.fee_estimator.history_length().min(head_height);
+        for height in (head_height - history_length + 1)..=head_height {
+            if let Some(block) = self.blockchain.get_block_at(height, true) {
+                recent_blocks.push(block);
+            }
+        }
+
+        let state = self.state.read();
+        self.fee_estimator.estimate(target_blocks, state.transactions_sorted_fee.iter().map(|tx| tx.as_ref()), &recent_blocks)
+    }
+
     pub fn get_transactions_by_addresses(&self, addresses: HashSet<Address>, max_count: usize) -> Vec<Arc<Transaction>> {
         let mut txs = Vec::new();
 
@@ -499,7 +652,7 @@ impl<'env> Mempool<'env> {
                         }
                     };
 
-                    let (txs_to_add, txs_to_remove) = Mempool::merge_transactions(&accounts, sender, block_height, existing_txs, &restored_txs);
+                    let (txs_to_add, txs_to_remove) = self.merge_transactions(&accounts, sender, block_height, existing_txs, &restored_txs);
                     for tx in txs_to_add {
                         let transaction = Arc::new(tx.clone());
                         Mempool::add_transaction(&mut state, tx.hash(), transaction.clone());
@@ -512,18 +665,7 @@ impl<'env> Mempool<'env> {
                 }
 
                 // Evict lowest fee transactions if the mempool has grown too large.
-                let size = state.transactions_sorted_fee.len();
-                if size > SIZE_MAX {
-                    let mut txs_to_remove = Vec::with_capacity(size - SIZE_MAX);
-                    let mut iter = state.transactions_sorted_fee.iter();
-                    for _ in 0..size - SIZE_MAX {
-                        txs_to_remove.push(iter.next().unwrap().clone());
-                    }
-                    for tx in txs_to_remove.iter() {
-                        Mempool::remove_transaction(&mut state, tx);
-                    }
-                    removed_transactions.extend(txs_to_remove);
-                }
+                removed_transactions.extend(self.evict_lowest_fee(&mut state));
             }
         }
 
@@ -537,8 +679,34 @@ impl<'env> Mempool<'env> {
         }
     }
 
+    /// Checks whether `new_tx` is a replacement for the pending transaction `old_tx`, i.e. it has
+    /// the same sender, recipient, value and validity window.
+    fn is_replacement(old_tx: &Transaction, new_tx: &Transaction) -> bool {
+        old_tx.sender == new_tx.sender
+            && old_tx.recipient == new_tx.recipient
+            && old_tx.value == new_tx.value
+            && old_tx.validity_start_height == new_tx.validity_start_height
+    }
+
+    /// Removes the transactions with the lowest fee per byte until the mempool is within its
+    /// limits for the number of transactions and their total size.
+    fn evict_lowest_fee(&self, state: &mut MempoolState) -> Vec<Arc<Transaction>> {
+        let mut txs_evicted = Vec::new();
+        while state.transactions_sorted_fee.len() > self.size_max || state.total_size > self.total_size_max {
+            let tx = match state.transactions_sorted_fee.iter().next() {
+                Some(tx) => tx.clone(),
+                None => break,
+            };
+            Mempool::remove_transaction(state, &tx);
+            txs_evicted.push(tx);
+        }
+        txs_evicted
+    }
+
     fn add_transaction(state: &mut MempoolState, hash: Blake2bHash, tx: Arc<Transaction>) {
-        state.transactions_by_hash.insert(hash, tx.clone());
+        if state.transactions_by_hash.insert(hash, tx.clone()).is_none() {
+            state.total_size += tx.serialized_size();
+        }
         state.transactions_sorted_fee.insert(tx.clone());
 
         let txs_by_recipient = state.transactions_by_recipient
@@ -553,7 +721,9 @@ impl<'env> Mempool<'env> {
     }
 
     fn remove_transaction(state: &mut MempoolState, tx: &Transaction) {
-        state.transactions_by_hash.remove(&tx.hash());
+        if state.transactions_by_hash.remove(&tx.hash()).is_some() {
+            state.total_size -= tx.serialized_size();
+        }
         state.transactions_sorted_fee.remove(tx);
 
         let mut remove_key = false;
@@ -575,7 +745,7 @@ impl<'env> Mempool<'env> {
         }
     }
 
-    fn merge_transactions<'a>(accounts: &Accounts, sender: &Address, block_height: u32, old_txs: &BTreeSet<Arc<Transaction>>, new_txs: &BTreeSet<&'a Transaction>) -> (Vec<&'a Transaction>, Vec<Arc<Transaction>>) {
+    fn merge_transactions<'a>(&self, accounts: &Accounts, sender: &Address, block_height: u32, old_txs: &BTreeSet<Arc<Transaction>>, new_txs: &BTreeSet<&'a Transaction>) -> (Vec<&'a Transaction>, Vec<Arc<Transaction>>) {
         let mut txs_to_add = Vec::new();
         let mut txs_to_remove = Vec::new();
 
@@ -596,7 +766,7 @@ impl<'env> Mempool<'env> {
             };
 
             if new_is_next {
-                if tx_count < TRANSACTIONS_PER_SENDER_MAX {
+                if tx_count < self.transactions_per_sender_max {
                     let tx = new_tx.unwrap();
                     if let Ok(account) = sender_account.with_outgoing_transaction(*tx, block_height) {
                         sender_account = account;
@@ -607,7 +777,7 @@ impl<'env> Mempool<'env> {
                 new_tx = iter_new.next_back();
             } else {
                 let tx = old_tx.unwrap();
-                if tx_count < TRANSACTIONS_PER_SENDER_MAX {
+                if tx_count < self.transactions_per_sender_max {
                     if let Ok(account) = sender_account.with_outgoing_transaction(tx, block_height) {
                         sender_account = account;
                         tx_count += 1;
@@ -633,15 +803,3 @@ pub enum ReturnCode {
     Known,
     Filtered,
 }
-
-/// Fee threshold in sat/byte below which transactions are considered "free".
-const TRANSACTION_RELAY_FEE_MIN : f64 = 1f64;
-
-/// Maximum number of transactions per sender.
-const TRANSACTIONS_PER_SENDER_MAX : u32 = 500;
-
-/// Maximum number of "free" transactions per sender.
-const FREE_TRANSACTIONS_PER_SENDER_MAX : u32 = 10;
-
-/// Maximum number of transactions in the mempool.
-pub const SIZE_MAX : usize = 100_000;
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use beserial::Serialize;
+
+use nimiq_block::{Block, BlockBody, BlockHeader, BlockInterlink};
+use nimiq_blockchain::Blockchain;
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_keys::Address;
+use nimiq_mempool::{Mempool, MempoolConfig};
+use nimiq_mempool::fee_estimator::FeeEstimator;
+use nimiq_network_primitives::time::NetworkTime;
+use nimiq_primitives::coin::Coin;
+use nimiq_primitives::networks::NetworkId;
+use nimiq_transaction::Transaction;
+
+/// Creates `count` unsigned transactions with the given fee per byte.
+fn transactions(count: usize, fee_per_byte: u64, sender: u8) -> Vec<Transaction> {
+    (0..count).map(|i| {
+        let mut tx = Transaction::new_basic(
+            Address::from([sender; Address::SIZE]),
+            Address::from([(i % 256) as u8; Address::SIZE]),
+            Coin::from_u64(1000 + i as u64).unwrap(),
+            Coin::ZERO,
+            1,
+            NetworkId::Dev,
+        );
+        tx.fee = Coin::from_u64(fee_per_byte * tx.serialized_size() as u64).unwrap();
+        tx
+    }).collect()
+}
+
+/// Creates a block whose body contains the given transactions. The block is not valid on any chain.
+fn block(transactions: Vec<Transaction>) -> Block {
+    Block {
+        header: BlockHeader::default(),
+        interlink: BlockInterlink::default(),
+        body: Some(BlockBody {
+            miner: Address::from([0u8; Address::SIZE]),
+            extra_data: Vec::new(),
+            transactions,
+            pruned_accounts: Vec::new(),
+        }),
+    }
+}
+
+/// Number of the transactions created above that fit into a block.
+fn transactions_per_block() -> usize {
+    FeeEstimator::block_space() / transactions(1, 0, 0)[0].serialized_size()
+}
+
+#[test]
+fn it_estimates_zero_without_competition() {
+    let estimator = FeeEstimator::default();
+    let pending = transactions(10, 2, 1);
+    let chain = vec![block(transactions(10, 5, 2)), block(Vec::new())];
+
+    assert_eq!(estimator.estimate(1, pending.iter(), &chain), 0f64);
+    assert_eq!(estimator.estimate(1, pending.iter(), &[]), 0f64);
+}
+
+#[test]
+fn it_estimates_from_pending_transactions() {
+    let estimator = FeeEstimator::default();
+    let per_block = transactions_per_block();
+
+    // One block worth of high fee transactions, one block worth of medium fee transactions
+    // and some low fee transactions, sorted ascending by fee per byte.
+    let mut pending = transactions(10, 1, 1);
+    pending.extend(transactions(per_block, 5, 2));
+    pending.extend(transactions(per_block, 10, 3));
+
+    assert_eq!(estimator.estimate(1, pending.iter(), &[]), 5f64);
+    assert_eq!(estimator.estimate(2, pending.iter(), &[]), 1f64);
+    assert_eq!(estimator.estimate(3, pending.iter(), &[]), 0f64);
+}
+
+#[test]
+fn it_estimates_from_recent_blocks() {
+    let estimator = FeeEstimator::default();
+    let per_block = transactions_per_block();
+
+    // Synthetic chain of full blocks with increasing minimum fees and one half-empty block.
+    let chain = vec![
+        block(transactions(per_block, 2, 1)),
+        block(transactions(per_block, 4, 2)),
+        block(transactions(per_block / 2, 100, 3)),
+        block(transactions(per_block, 8, 4)),
+    ];
+
+    // Minimums sorted descending: 8, 4, 2, 0
+    assert_eq!(estimator.estimate(1, Vec::<Transaction>::new().iter(), &chain), 8f64);
+    assert_eq!(estimator.estimate(2, Vec::<Transaction>::new().iter(), &chain), 2f64);
+    assert_eq!(estimator.estimate(4, Vec::<Transaction>::new().iter(), &chain), 0f64);
+
+    // Target of zero blocks is treated like one block.
+    assert_eq!(estimator.estimate(0, Vec::<Transaction>::new().iter(), &chain), 8f64);
+}
+
+#[test]
+fn it_uses_the_higher_estimate() {
+    let estimator = FeeEstimator::default();
+    let per_block = transactions_per_block();
+
+    let chain = vec![block(transactions(per_block, 3, 1))];
+    let mut pending = transactions(10, 6, 2);
+    pending.extend(transactions(per_block, 7, 3));
+
+    assert_eq!(estimator.estimate(1, pending.iter(), &chain), 6f64);
+    assert_eq!(estimator.estimate(2, pending.iter(), &chain), 3f64);
+}
+
+#[test]
+fn mempool_estimates_fee_on_genesis() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain, MempoolConfig::default());
+
+    assert_eq!(mempool.estimate_fee(1), 0f64);
+    assert_eq!(mempool.estimate_fee(10), 0f64);
+}
@@ -1,6 +1,7 @@
+use nimiq_account::AccountType;
 use nimiq_hash::{Hash, Blake2bHash};
 use nimiq_keys::Address;
-use nimiq_mempool::filter::{MempoolFilter, Rules};
+use nimiq_mempool::filter::{AccountTypeRules, MempoolFilter, Rules};
 use nimiq_primitives::coin::Coin;
 use nimiq_primitives::networks::NetworkId;
 use nimiq_transaction::Transaction;
@@ -23,6 +24,15 @@ fn it_can_blacklist_transactions() {
     assert!(f.blacklisted(&hash));
     f.remove(&hash);
     assert!(!f.blacklisted(&hash));
+
+    // Rejected transactions are removed from the blacklist when the rules change.
+    f.blacklist(hash.clone());
+    let rejected = Blake2bHash::from([1u8; 32]);
+    f.reject(rejected.clone());
+    assert!(f.blacklisted(&rejected));
+    f.set_rules(Rules::default());
+    assert!(!f.blacklisted(&rejected));
+    assert!(f.blacklisted(&hash));
 }
 
 #[test]
@@ -45,3 +55,80 @@ fn it_accepts_and_rejects_transactions() {
     tx.fee = Coin::from_u64(1).unwrap();
     assert!(f.accepts_transaction(&tx));
 }
+
+#[test]
+fn it_applies_address_rules() {
+    let sender = Address::from([1u8; Address::SIZE]);
+    let recipient = Address::from([2u8; Address::SIZE]);
+    let other = Address::from([3u8; Address::SIZE]);
+    let tx = Transaction::new_basic(sender.clone(), recipient.clone(), Coin::from_u64(10).unwrap(), Coin::ZERO, 0, NetworkId::Main);
+
+    let mut rules = Rules::default();
+    rules.sender_blacklist.insert(sender.clone());
+    assert!(!MempoolFilter::new(rules, MempoolFilter::DEFAULT_BLACKLIST_SIZE).accepts_transaction(&tx));
+
+    let mut rules = Rules::default();
+    rules.recipient_blacklist.insert(recipient.clone());
+    assert!(!MempoolFilter::new(rules, MempoolFilter::DEFAULT_BLACKLIST_SIZE).accepts_transaction(&tx));
+
+    let mut rules = Rules::default();
+    rules.sender_whitelist.insert(other.clone());
+    assert!(!MempoolFilter::new(rules, MempoolFilter::DEFAULT_BLACKLIST_SIZE).accepts_transaction(&tx));
+
+    let mut rules = Rules::default();
+    rules.sender_whitelist.insert(sender);
+    rules.recipient_whitelist.insert(other);
+    rules.recipient_whitelist.insert(recipient);
+    assert!(MempoolFilter::new(rules, MempoolFilter::DEFAULT_BLACKLIST_SIZE).accepts_transaction(&tx));
+}
+
+#[test]
+fn it_applies_data_size_rules() {
+    let mut tx = Transaction::new_basic(
+        Address::from([1u8; Address::SIZE]),
+        Address::from([2u8; Address::SIZE]),
+        Coin::from_u64(10).unwrap(),
+        Coin::ZERO,
+        0,
+        NetworkId::Main,
+    );
+    tx.data = vec![0u8; 32];
+
+    let mut rules = Rules::default();
+    rules.data_size_max = Some(16);
+    let mut f = MempoolFilter::new(rules.clone(), MempoolFilter::DEFAULT_BLACKLIST_SIZE);
+    assert!(!f.accepts_transaction(&tx));
+
+    rules.data_size_max = Some(32);
+    f.set_rules(rules);
+    assert!(f.accepts_transaction(&tx));
+}
+
+#[test]
+fn it_applies_account_type_rules() {
+    let mut tx = Transaction::new_basic(
+        Address::from([1u8; Address::SIZE]),
+        Address::from([2u8; Address::SIZE]),
+        Coin::from_u64(10).unwrap(),
+        Coin::ZERO,
+        0,
+        NetworkId::Main,
+    );
+    tx.recipient_type = AccountType::Vesting;
+
+    let mut rules = Rules::default();
+    rules.account_types.insert(AccountType::Vesting, AccountTypeRules { reject: true, ..Default::default() });
+    let mut f = MempoolFilter::new(rules, MempoolFilter::DEFAULT_BLACKLIST_SIZE);
+    assert!(!f.accepts_transaction(&tx));
+
+    let mut rules = Rules::default();
+    rules.account_types.insert(AccountType::Vesting, AccountTypeRules { tx_value: Coin::from_u64(100).unwrap(), ..Default::default() });
+    f.set_rules(rules);
+    assert!(!f.accepts_transaction(&tx));
+
+    // Rules for account types that are not involved don't apply.
+    let mut rules = Rules::default();
+    rules.account_types.insert(AccountType::HTLC, AccountTypeRules { reject: true, ..Default::default() });
+    f.set_rules(rules);
+    assert!(f.accepts_transaction(&tx));
+}
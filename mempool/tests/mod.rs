@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use hex;
 
@@ -7,10 +7,10 @@ use nimiq_block::BlockBody;
 use nimiq_blockchain::Blockchain;
 use nimiq_database::volatile::VolatileEnvironment;
 use nimiq_database::WriteTransaction;
-use nimiq_hash::Hash;
+use nimiq_hash::{Blake2bHash, Hash};
 use nimiq_keys::Address;
 use nimiq_keys::KeyPair;
-use nimiq_mempool::{Mempool, ReturnCode, MempoolConfig};
+use nimiq_mempool::{Mempool, MempoolEvent, ReturnCode, MempoolConfig};
 use nimiq_network_primitives::time::NetworkTime;
 use nimiq_primitives::coin::Coin;
 use nimiq_primitives::networks::NetworkId;
@@ -159,3 +159,213 @@ fn reject_free_tx_beyond_limit() {
         }
     }
 }
+
+#[test]
+fn replace_tx_with_higher_fee() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let config = MempoolConfig { replace_by_fee: true, ..MempoolConfig::default() };
+    let mempool = Mempool::new(blockchain.clone(), config);
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_moved = events.clone();
+    mempool.notifier.write().register(move |e: &MempoolEvent| events_moved.lock().unwrap().push(e.clone()));
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.state().accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit();
+
+    // Generate, sign and push a free transaction from address_a
+    let mut tx1 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from_u64(10).unwrap(), Coin::from_u64(0).unwrap(), 1, NetworkId::Main );
+    let signature_proof1 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx1.serialize_content()));
+    tx1.proof = signature_proof1.serialize_to_vec();
+    let hash1 = tx1.hash();
+    assert_eq!(mempool.push_transaction(tx1), ReturnCode::Accepted);
+
+    // A replacement that doesn't pay enough more is rejected
+    let mut tx2 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from_u64(10).unwrap(), Coin::from_u64(100).unwrap(), 1, NetworkId::Main );
+    let signature_proof2 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx2.serialize_content()));
+    tx2.proof = signature_proof2.serialize_to_vec();
+    assert_eq!(mempool.push_transaction(tx2), ReturnCode::FeeTooLow);
+
+    // A replacement with a sufficient fee bump replaces the first transaction
+    let mut tx3 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from_u64(10).unwrap(), Coin::from_u64(300).unwrap(), 1, NetworkId::Main );
+    let signature_proof3 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx3.serialize_content()));
+    tx3.proof = signature_proof3.serialize_to_vec();
+    let hash3 = tx3.hash();
+    assert_eq!(mempool.push_transaction(tx3), ReturnCode::Accepted);
+
+    assert!(!mempool.contains(&hash1));
+    assert!(mempool.contains(&hash3));
+    assert!(mempool.is_filtered(&hash1));
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 3);
+    match &events[1] {
+        MempoolEvent::TransactionReplaced(old, new) => {
+            assert_eq!(old.hash::<Blake2bHash>(), hash1);
+            assert_eq!(new.hash::<Blake2bHash>(), hash3);
+        },
+        e => panic!("Unexpected event: {:?}", e),
+    }
+    match &events[2] {
+        MempoolEvent::TransactionAdded(hash, _) => assert_eq!(hash, &hash3),
+        e => panic!("Unexpected event: {:?}", e),
+    }
+}
+
+#[test]
+fn keep_both_tx_without_replace_by_fee() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain.clone(), MempoolConfig::default());
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.state().accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit();
+
+    let mut tx1 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from_u64(10).unwrap(), Coin::from_u64(0).unwrap(), 1, NetworkId::Main );
+    let signature_proof1 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx1.serialize_content()));
+    tx1.proof = signature_proof1.serialize_to_vec();
+    let hash1 = tx1.hash();
+    assert_eq!(mempool.push_transaction(tx1), ReturnCode::Accepted);
+
+    let mut tx2 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from_u64(10).unwrap(), Coin::from_u64(300).unwrap(), 1, NetworkId::Main );
+    let signature_proof2 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx2.serialize_content()));
+    tx2.proof = signature_proof2.serialize_to_vec();
+    let hash2 = tx2.hash();
+    assert_eq!(mempool.push_transaction(tx2), ReturnCode::Accepted);
+
+    assert!(mempool.contains(&hash1));
+    assert!(mempool.contains(&hash2));
+}
+
+#[test]
+fn evict_lowest_fee_tx_beyond_total_size() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let config = MempoolConfig { total_size_max: 2 * Transaction::MIN_SIZE, ..MempoolConfig::default() };
+    let mempool = Mempool::new(blockchain.clone(), config);
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.state().accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit();
+
+    let mut hashes = Vec::new();
+    for &fee in [200, 0, 400].iter() {
+        let mut tx = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from_u64(10 + fee).unwrap(), Coin::from_u64(fee).unwrap(), 1, NetworkId::Main );
+        let signature_proof = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx.serialize_content()));
+        tx.proof = signature_proof.serialize_to_vec();
+        hashes.push(tx.hash::<Blake2bHash>());
+        assert_eq!(mempool.push_transaction(tx), ReturnCode::Accepted);
+    }
+
+    // The free transaction was evicted to stay within the size limit.
+    assert_eq!(mempool.size(), 2);
+    assert_eq!(mempool.total_size(), 2 * Transaction::MIN_SIZE);
+    assert!(mempool.contains(&hashes[0]));
+    assert!(!mempool.contains(&hashes[1]));
+    assert!(mempool.contains(&hashes[2]));
+}
+
+#[test]
+fn reject_free_tx_beyond_configured_limit() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let config = MempoolConfig { free_transactions_per_sender_max: 2, ..MempoolConfig::default() };
+    let mempool = Mempool::new(blockchain.clone(), config);
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.state().accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit();
+
+    for i in 0..2 + 1 {
+        let mut tx1 = Transaction::new_basic( address_a.clone(), address_b.clone(), Coin::from_u64(1 + i).unwrap(), Coin::from_u64(0).unwrap(), 1, NetworkId::Main );
+        let signature_proof1 = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx1.serialize_content()));
+        tx1.proof = signature_proof1.serialize_to_vec();
+        if i < 2 {
+            assert_eq!(mempool.push_transaction(tx1), ReturnCode::Accepted);
+        } else {
+            assert_eq!(mempool.push_transaction(tx1), ReturnCode::FeeTooLow);
+        }
+    }
+}
+
+#[test]
+fn evict_pending_tx_when_filter_rules_change() {
+    let env = VolatileEnvironment::new(10).unwrap();
+    let blockchain = Arc::new(Blockchain::new(&env, NetworkId::Main, Arc::new(NetworkTime::new())).unwrap());
+    let mempool = Mempool::new(blockchain.clone(), MempoolConfig::default());
+
+    let keypair_a = KeyPair::generate();
+    let address_a = Address::from(&keypair_a.public);
+    let address_b = Address::from([2u8; Address::SIZE]);
+    let address_c = Address::from([3u8; Address::SIZE]);
+
+    // Give address_a balance
+    let body = BlockBody { miner: address_a.clone(), extra_data: Vec::new(), transactions: Vec::new(), pruned_accounts: Vec::new() };
+    let mut txn = WriteTransaction::new(&env);
+    blockchain.state().accounts().commit_block_body(&mut txn, &body, 1).unwrap();
+    txn.commit();
+
+    let mut txs = Vec::new();
+    let mut hashes = Vec::new();
+    for recipient in [address_b.clone(), address_c.clone()].iter() {
+        let mut tx = Transaction::new_basic( address_a.clone(), recipient.clone(), Coin::from_u64(10).unwrap(), Coin::from_u64(200).unwrap(), 1, NetworkId::Main );
+        let signature_proof = SignatureProof::from(keypair_a.public.clone(), keypair_a.sign(&tx.serialize_content()));
+        tx.proof = signature_proof.serialize_to_vec();
+        hashes.push(tx.hash::<Blake2bHash>());
+        txs.push(tx.clone());
+        assert_eq!(mempool.push_transaction(tx), ReturnCode::Accepted);
+    }
+
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+    let evicted1 = Arc::clone(&evicted);
+    mempool.notifier.write().register(move |e: &MempoolEvent| {
+        if let MempoolEvent::TransactionEvicted(tx) = e {
+            evicted1.lock().unwrap().push(tx.hash::<Blake2bHash>());
+        }
+    });
+
+    let mut rules = mempool.filter_rules();
+    rules.recipient_blacklist.insert(address_c.clone());
+    mempool.set_filter_rules(rules);
+
+    assert!(mempool.contains(&hashes[0]));
+    assert!(!mempool.contains(&hashes[1]));
+    assert_eq!(*evicted.lock().unwrap(), vec![hashes[1].clone()]);
+
+    // Pushing it again under the new rules blacklists it until the rules are relaxed.
+    assert_eq!(mempool.push_transaction(txs[1].clone()), ReturnCode::Filtered);
+    assert!(mempool.is_filtered(&hashes[1]));
+
+    let mut rules = mempool.filter_rules();
+    rules.recipient_blacklist.remove(&address_c);
+    mempool.set_filter_rules(rules);
+    assert!(!mempool.is_filtered(&hashes[1]));
+    assert_eq!(mempool.push_transaction(txs[1].clone()), ReturnCode::Accepted);
+}
@@ -216,6 +216,15 @@ impl Message {
 
         Ok(n as usize)
     }
+
+    pub fn peek_type(buffer: &[u8]) -> Result<MessageType, SerializingError> {
+        let mut c = Cursor::new(buffer);
+
+        // skip 4 bytes of magic
+        c.seek(SeekFrom::Start(4))?;
+
+        MessageType::deserialize(&mut c)
+    }
 }
 
 const MAGIC: u32 = 0x4204_2042;
@@ -305,3 +305,13 @@ fn reserialize_messages() {
         assert!(message.serialize_to_vec() == vec);
     }
 }
+
+#[test]
+fn peek_message_header() {
+    for message in MESSAGES.iter() {
+        let vec = ::hex::decode(message).unwrap();
+        let message: Message = Deserialize::deserialize(&mut &vec[..]).unwrap();
+        assert_eq!(Message::peek_type(&vec).unwrap(), message.ty());
+        assert_eq!(Message::peek_length(&vec).unwrap(), vec.len());
+    }
+}
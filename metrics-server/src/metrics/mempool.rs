@@ -1,11 +1,10 @@
 use std::io;
 use std::sync::Arc;
 
-use mempool::{Mempool, SIZE_MAX};
+use mempool::Mempool;
 
 use crate::server;
 use crate::server::SerializationType;
-use beserial::Serialize;
 
 pub struct MempoolMetrics {
     mempool: Arc<Mempool<'static>>,
@@ -21,7 +20,7 @@ impl MempoolMetrics {
 
 impl server::Metrics for MempoolMetrics {
     fn metrics(&self, serializer: &mut server::MetricsSerializer<SerializationType>) -> Result<(), io::Error> {
-        let txs = self.mempool.get_transactions(SIZE_MAX, 0f64);
+        let txs = self.mempool.get_transactions(usize::max_value(), 0f64);
         let group = [0usize, 1, 2, 5, 10, 20, 50, 100, 200, 500, 1000, 2000, 5000, 10000];
         for i in 1..group.len() {
             let lower_bound = group[i - 1];
@@ -38,10 +37,9 @@ impl server::Metrics for MempoolMetrics {
             txs.iter().filter(|tx| (tx.fee_per_byte() as usize) >= lower_bound).count(),
             attributes!{"fee_per_byte" => format!(">={}", lower_bound)}
         )?;
-        serializer.metric(
-            "mempool_size",
-            txs.iter().map(|tx| tx.serialized_size()).sum::<usize>(),
-        )?;
+        serializer.metric("mempool_size", self.mempool.total_size())?;
+        serializer.metric("mempool_size_max", self.mempool.total_size_max())?;
+        serializer.metric("mempool_transactions_max", self.mempool.size_max())?;
 
         Ok(())
     }
@@ -66,6 +66,20 @@ impl server::Metrics for NetworkMetrics {
         )?;
 
         serializer.metric("network_time_now", self.network.network_time.now())?;
+        {
+            let time_offset = self.network.time_offset();
+            serializer.metric("network_time_offset", time_offset.offset)?;
+            serializer.metric_with_attributes(
+                "network_time_samples",
+                time_offset.samples.len(),
+                attributes!{"type" => "accepted"}
+            )?;
+            serializer.metric_with_attributes(
+                "network_time_samples",
+                time_offset.outliers.len(),
+                attributes!{"type" => "outlier"}
+            )?;
+        }
         serializer.metric_with_attributes(
             "network_bytes",
             network_metrics.bytes_sent(),
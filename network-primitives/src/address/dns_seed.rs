@@ -0,0 +1,34 @@
+use crate::protocol::Protocol;
+
+/// A host name whose DNS records point to seed nodes.
+///
+/// TXT records hold peer URIs including the public key of the seed. A and AAAA records are
+/// connected to as keyless seeds using the protocol and port given here.
+#[derive(Clone, Debug)]
+pub struct DnsSeed {
+    host: String,
+    protocol: Protocol,
+    port: Option<u16>,
+}
+
+impl DnsSeed {
+    pub fn new(host: String, protocol: Protocol, port: Option<u16>) -> Self {
+        Self {
+            host,
+            protocol,
+            port,
+        }
+    }
+
+    pub fn host(&self) -> &String {
+        &self.host
+    }
+
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+}
@@ -1,3 +1,4 @@
+pub mod dns_seed;
 pub mod net_address;
 pub mod peer_address;
 pub mod peer_uri;
@@ -7,6 +8,7 @@ pub use self::net_address::*;
 pub use self::peer_address::*;
 pub use self::peer_uri::PeerUri;
 pub use self::seed_list::SeedList;
+pub use self::dns_seed::DnsSeed;
 
 use hex::FromHex;
 
@@ -45,6 +45,21 @@ impl NetAddress {
         }
     }
 
+    /// Returns the IPv4 address for IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`), as seen by
+    /// sockets listening on both IPv4 and IPv6, and the address itself otherwise.
+    pub fn canonical(&self) -> Self {
+        match self {
+            NetAddress::IPv6(ip) => {
+                let segments = ip.segments();
+                match ip.to_ipv4() {
+                    Some(ipv4) if segments[..5] == [0u16; 5] && segments[5] == 0xffff => NetAddress::IPv4(ipv4),
+                    _ => *self,
+                }
+            },
+            _ => *self,
+        }
+    }
+
     pub fn is_pseudo(&self) -> bool {
         let ty = self.get_type();
         ty == NetAddressType::Unknown || ty == NetAddressType::Unspecified
@@ -140,6 +155,15 @@ impl fmt::Display for NetAddress {
     }
 }
 
+impl From<IpAddr> for NetAddress {
+    fn from(addr: IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(addr) => NetAddress::IPv4(addr),
+            IpAddr::V6(addr) => NetAddress::IPv6(addr),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Fail)]
 #[fail(display = "{}", _0)]
 pub struct NetAddressParseError(#[cause] AddrParseError);
@@ -154,4 +178,81 @@ impl FromStr for NetAddress {
             IpAddr::V6(addr) => Ok(NetAddress::IPv6(addr)),
         }
     }
-}
\ No newline at end of file
+}
+/// A range of IP addresses in CIDR notation, e.g. `10.0.0.0/8`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct NetAddressRange {
+    address: NetAddress,
+    bit_count: u8,
+}
+
+impl NetAddressRange {
+    /// Creates a range from an address and the number of prefix bits. Ranges of IPv4-mapped IPv6
+    /// addresses are turned into IPv4 ranges.
+    pub fn new(address: NetAddress, bit_count: u8) -> Self {
+        let (address, bit_count) = match (address, address.canonical()) {
+            (NetAddress::IPv6(_), canonical @ NetAddress::IPv4(_)) if bit_count >= 96 => (canonical, bit_count - 96),
+            _ => (address, bit_count),
+        };
+        NetAddressRange {
+            address: address.subnet(bit_count),
+            bit_count,
+        }
+    }
+
+    pub fn address(&self) -> NetAddress {
+        self.address
+    }
+
+    pub fn bit_count(&self) -> u8 {
+        self.bit_count
+    }
+
+    /// Whether the address is in this range. IPv4-mapped IPv6 addresses are matched against IPv4
+    /// ranges.
+    pub fn contains(&self, net_address: &NetAddress) -> bool {
+        let net_address = net_address.canonical();
+        self.address.get_type() == net_address.get_type() && self.address == net_address.subnet(self.bit_count)
+    }
+}
+
+impl From<NetAddress> for NetAddressRange {
+    /// The range that only contains the given address.
+    fn from(address: NetAddress) -> Self {
+        let bit_count = match address {
+            NetAddress::IPv4(_) => 32,
+            NetAddress::IPv6(_) => 128,
+            _ => 0,
+        };
+        NetAddressRange::new(address, bit_count)
+    }
+}
+
+impl fmt::Display for NetAddressRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.bit_count)
+    }
+}
+
+#[derive(Debug, Clone, Fail)]
+#[fail(display = "Expected an IP address or a subnet in CIDR notation")]
+pub struct NetAddressRangeParseError;
+
+impl FromStr for NetAddressRange {
+    type Err = NetAddressRangeParseError;
+
+    /// Parses a subnet like `192.168.0.0/16` or a single IP address.
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        let (address, bit_count) = match s.find('/') {
+            Some(pos) => (&s[..pos], Some(u8::from_str(&s[pos + 1..]).map_err(|_| NetAddressRangeParseError)?)),
+            None => (s, None),
+        };
+        let address = NetAddress::from_str(address).map_err(|_| NetAddressRangeParseError)?;
+        let max_bit_count = if address.get_type() == NetAddressType::IPv4 { 32 } else { 128 };
+        match bit_count {
+            Some(bit_count) if bit_count > max_bit_count => Err(NetAddressRangeParseError),
+            Some(bit_count) => Ok(NetAddressRange::new(address, bit_count)),
+            None => Ok(NetAddressRange::from(address)),
+        }
+    }
+}
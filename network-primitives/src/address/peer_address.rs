@@ -1,4 +1,5 @@
 use beserial::{Deserialize, DeserializeWithLength, ReadBytesExt, Serialize, SerializeWithLength, SerializingError, WriteBytesExt};
+use hash::{Blake2bHasher, Hasher as _};
 use keys::{PublicKey, Signature};
 use std::fmt;
 use std::hash::Hash;
@@ -7,7 +8,7 @@ use std::time::Duration;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 use std::vec::Vec;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv6Addr};
 use std::str::FromStr;
 
 use crate::address::{NetAddress, PeerId, PeerUri};
@@ -21,6 +22,7 @@ pub enum PeerAddressType {
     Ws(String, u16),
     Wss(String, u16),
     Rtc,
+    Tcp(String, u16),
 }
 
 impl PeerAddressType {
@@ -29,11 +31,19 @@ impl PeerAddressType {
             PeerAddressType::Dumb => Protocol::Dumb,
             PeerAddressType::Ws(_, _) => Protocol::Ws,
             PeerAddressType::Wss(_, _) => Protocol::Wss,
-            PeerAddressType::Rtc => Protocol::Rtc
+            PeerAddressType::Rtc => Protocol::Rtc,
+            PeerAddressType::Tcp(_, _) => Protocol::Tcp,
         }
     }
 }
 
+/// Placeholder for the public key of seeds configured without one. This is the encoding of the
+/// identity point, which is not the public key of any key pair.
+const UNKNOWN_PUBLIC_KEY: [u8; PublicKey::SIZE] = [
+    1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+
 #[derive(Debug, Clone)]
 pub struct PeerAddress {
     pub ty: PeerAddressType,
@@ -64,7 +74,8 @@ impl Serialize for PeerAddress {
             PeerAddressType::Dumb => 0,
             PeerAddressType::Ws(host, port) => host.serialize::<u8, W>(writer)? + port.serialize(writer)?,
             PeerAddressType::Wss(host, port) => host.serialize::<u8, W>(writer)? + port.serialize(writer)?,
-            PeerAddressType::Rtc => 0
+            PeerAddressType::Rtc => 0,
+            PeerAddressType::Tcp(host, port) => host.serialize::<u8, W>(writer)? + port.serialize(writer)?,
         };
         Ok(size)
     }
@@ -82,7 +93,8 @@ impl Serialize for PeerAddress {
             PeerAddressType::Dumb => 0,
             PeerAddressType::Ws(host, port) => host.serialized_size::<u8>() + port.serialized_size(),
             PeerAddressType::Wss(host, port) => host.serialized_size::<u8>() + port.serialized_size(),
-            PeerAddressType::Rtc => 0
+            PeerAddressType::Rtc => 0,
+            PeerAddressType::Tcp(host, port) => host.serialized_size::<u8>() + port.serialized_size(),
         };
         size
     }
@@ -101,7 +113,8 @@ impl Deserialize for PeerAddress {
             Protocol::Dumb => PeerAddressType::Dumb,
             Protocol::Ws => PeerAddressType::Ws(DeserializeWithLength::deserialize::<u8, R>(reader)?, Deserialize::deserialize(reader)?),
             Protocol::Wss => PeerAddressType::Wss(DeserializeWithLength::deserialize::<u8, R>(reader)?, Deserialize::deserialize(reader)?),
-            Protocol::Rtc => PeerAddressType::Rtc
+            Protocol::Rtc => PeerAddressType::Rtc,
+            Protocol::Tcp => PeerAddressType::Tcp(DeserializeWithLength::deserialize::<u8, R>(reader)?, Deserialize::deserialize(reader)?),
         };
         let peer_id = PeerId::from(&public_key);
         Ok(PeerAddress{ ty: type_special, services, timestamp, net_address, public_key, distance, signature: Some(signature), peer_id})
@@ -116,6 +129,45 @@ impl PeerAddress {
         false
     }
 
+    /// Creates the address of a seed node that was configured without a public key. The key is
+    /// learned when connecting to the seed. Until then, the address uses a placeholder key and
+    /// either the configured peer ID or one derived from the host and port.
+    pub fn new_keyless_seed(ty: PeerAddressType, peer_id: Option<PeerId>) -> Self {
+        let peer_id = peer_id.unwrap_or_else(|| Self::keyless_seed_peer_id(&ty));
+        PeerAddress {
+            ty,
+            services: ServiceFlags::FULL,
+            timestamp: 0,
+            net_address: NetAddress::Unspecified,
+            public_key: PublicKey::from(&UNKNOWN_PUBLIC_KEY),
+            distance: 0,
+            signature: None,
+            peer_id,
+        }
+    }
+
+    /// Whether the public key of this address is known. This is only false for keyless seeds.
+    pub fn has_public_key(&self) -> bool {
+        self.public_key.as_bytes() != &UNKNOWN_PUBLIC_KEY
+    }
+
+    pub fn is_keyless_seed(&self) -> bool {
+        self.is_seed() && !self.has_public_key()
+    }
+
+    /// Checks whether `peer_address`, as announced by the peer, can belong to this keyless seed.
+    /// If a peer ID was configured for the seed, the announced address must have that peer ID.
+    pub fn matches_keyless_seed(&self, peer_address: &PeerAddress) -> bool {
+        self.is_keyless_seed()
+            && peer_address.has_public_key()
+            && self.protocol() == peer_address.protocol()
+            && (self.peer_id == peer_address.peer_id || self.peer_id == Self::keyless_seed_peer_id(&self.ty))
+    }
+
+    fn keyless_seed_peer_id(ty: &PeerAddressType) -> PeerId {
+        PeerId::from(Blake2bHasher::default().digest(&ty.serialize_to_vec()))
+    }
+
     pub fn as_uri(&self) -> PeerUri {
         // TODO: Do this without cloning. Not urgent, since we don't use this too much.
         PeerUri::from(self.clone())
@@ -127,10 +179,16 @@ impl PeerAddress {
             return None;
         }
 
-        let public_key: String = ::hex::encode(&self.public_key.as_bytes());
+        // Keyless seeds are written without the public key.
+        let public_key: String = if self.has_public_key() {
+            format!("/{}", ::hex::encode(&self.public_key.as_bytes()))
+        } else {
+            String::new()
+        };
         match self.ty {
-            PeerAddressType::Ws(ref host, ref port) => Some(format!("ws://{}:{}/{}", host, port, public_key)),
-            PeerAddressType::Wss(ref host, ref port) => Some(format!("wss://{}:{}/{}", host, port, public_key)),
+            PeerAddressType::Ws(ref host, ref port) => Some(format!("ws://{}:{}{}", host, port, public_key)),
+            PeerAddressType::Wss(ref host, ref port) => Some(format!("wss://{}:{}{}", host, port, public_key)),
+            PeerAddressType::Tcp(ref host, ref port) => Some(format!("tcp://{}:{}{}", host, port, public_key)),
             _ => None, // Seed nodes should never be PeerAddressType::RTC or PeerAddressType::Dumb
         }
     }
@@ -141,7 +199,7 @@ impl PeerAddress {
         res.append(&mut self.timestamp.serialize_to_vec());
 
         match &self.ty {
-            PeerAddressType::Ws(host, port) | PeerAddressType::Wss(host, port) => {
+            PeerAddressType::Ws(host, port) | PeerAddressType::Wss(host, port) | PeerAddressType::Tcp(host, port) => {
                 res.append(&mut host.serialize_to_vec::<u8>());
                 res.append(&mut port.serialize_to_vec());
             }
@@ -165,6 +223,7 @@ impl PeerAddress {
             match (age, self.protocol()) {
                 (Some(age), Protocol::Ws) =>  return age > MAX_AGE_WEBSOCKET,
                 (Some(age), Protocol::Wss) =>  return age > MAX_AGE_WEBSOCKET,
+                (Some(age), Protocol::Tcp) =>  return age > MAX_AGE_WEBSOCKET,
                 (Some(age), Protocol::Rtc) =>  return age > MAX_AGE_WEBRTC,
                 (Some(age), Protocol::Dumb) =>  return age > MAX_AGE_DUMB,
                 (None, _) => return false,
@@ -175,9 +234,23 @@ impl PeerAddress {
 
     pub fn is_globally_reachable(&self, legacy_mode: bool) -> bool {
         match &self.ty {
-            PeerAddressType::Ws(host, _) => {
+            PeerAddressType::Ws(host, _) | PeerAddressType::Tcp(host, _) => {
+                // IPv6 addresses must be enclosed in brackets, as in URIs.
+                let bracketed = host.starts_with('[') && host.ends_with(']');
+                let ip = if bracketed {
+                    match Ipv6Addr::from_str(&host[1..host.len()-1]) {
+                        Ok(ipv6) => Some(IpAddr::V6(ipv6)),
+                        Err(_) => return false,
+                    }
+                } else {
+                    match IpAddr::from_str(&host[..]) {
+                        Ok(IpAddr::V6(_)) => return false,
+                        Ok(ipv4) => Some(ipv4),
+                        Err(_) => None,
+                    }
+                };
                 // If host is an ip, check if it's globally reachable
-                if let Ok(ip) = IpAddr::from_str(&host[..]) {
+                if let Some(ip) = ip {
                     if legacy_mode {
                         if !is_ip_globally_reachable_legacy(&ip) {
                             return false;
@@ -193,6 +266,10 @@ impl PeerAddress {
                             }
                         }
                     }
+                    // IPv6 addresses have no dots, so they can't pass the domain check below.
+                    if bracketed {
+                        return true;
+                    }
                 }
             },
             PeerAddressType::Wss(host, _) => {
@@ -204,7 +281,7 @@ impl PeerAddress {
             _ => {}
         }
         match &self.ty {
-            PeerAddressType::Wss(host, _) | PeerAddressType::Ws(host, _) => {
+            PeerAddressType::Wss(host, _) | PeerAddressType::Ws(host, _) | PeerAddressType::Tcp(host, _) => {
                 // "the use of dotless domains is prohibited [in new gTLDs]" [ https://www.icann.org/resources/board-material/resolutions-new-gtld-2013-08-13-en#1 ]. Old gTLDs rarely use them.
                 if !host[1..host.len()-1].contains('.') {
                     return false;
@@ -244,7 +321,8 @@ impl Hash for PeerAddress {
             PeerAddressType::Dumb => format!("dumb:///{}", peer_id),
             PeerAddressType::Ws(_, _) => format!("ws:///{}", peer_id),
             PeerAddressType::Wss(_, _) => format!("wss:///{}", peer_id),
-            PeerAddressType::Rtc => format!("rtc:///{}", peer_id)
+            PeerAddressType::Rtc => format!("rtc:///{}", peer_id),
+            PeerAddressType::Tcp(_, _) => format!("tcp:///{}", peer_id),
         };
         peer_id_uri.hash(state);
     }
@@ -263,7 +341,8 @@ impl Deserialize for PeerAddressType {
             Protocol::Dumb => Ok(PeerAddressType::Dumb),
             Protocol::Ws => Ok(PeerAddressType::Ws(DeserializeWithLength::deserialize::<u8, R>(reader)?, Deserialize::deserialize(reader)?)),
             Protocol::Wss => Ok(PeerAddressType::Wss(DeserializeWithLength::deserialize::<u8, R>(reader)?, Deserialize::deserialize(reader)?)),
-            Protocol::Rtc => Ok(PeerAddressType::Rtc)
+            Protocol::Rtc => Ok(PeerAddressType::Rtc),
+            Protocol::Tcp => Ok(PeerAddressType::Tcp(DeserializeWithLength::deserialize::<u8, R>(reader)?, Deserialize::deserialize(reader)?)),
         }
     }
 }
@@ -274,7 +353,8 @@ impl Serialize for PeerAddressType {
             PeerAddressType::Dumb => Protocol::Dumb.serialize(writer)?,
             PeerAddressType::Ws(host, port) => Protocol::Ws.serialize(writer)? + host.serialize::<u8, W>(writer)? + port.serialize(writer)?,
             PeerAddressType::Wss(host, port) => Protocol::Wss.serialize(writer)? + host.serialize::<u8, W>(writer)? + port.serialize(writer)?,
-            PeerAddressType::Rtc => Protocol::Rtc.serialize(writer)?
+            PeerAddressType::Rtc => Protocol::Rtc.serialize(writer)?,
+            PeerAddressType::Tcp(host, port) => Protocol::Tcp.serialize(writer)? + host.serialize::<u8, W>(writer)? + port.serialize(writer)?,
         })
     }
 
@@ -282,6 +362,7 @@ impl Serialize for PeerAddressType {
         Protocol::Dumb.serialized_size() + match self {
             PeerAddressType::Ws(host, port) => host.serialized_size::<u8>() + port.serialized_size(),
             PeerAddressType::Wss(host, port) => host.serialized_size::<u8>() + port.serialized_size(),
+            PeerAddressType::Tcp(host, port) => host.serialized_size::<u8>() + port.serialized_size(),
             _ => 0
         }
     }
@@ -41,7 +41,7 @@ pub enum PeerUriError {
     InvalidPublicKey(#[cause] keys::ParseError),
     #[fail(display = "Seed node is missing the public key")]
     SeedNodeMissingPublicKey,
-    #[fail(display = "The only allowed protocols for seed nodes are Wss and Ws")]
+    #[fail(display = "The only allowed protocols for seed nodes are Wss, Ws and Tcp")]
     SeedNodeWithInvalidProtocol,
 }
 
@@ -72,6 +72,7 @@ impl FromStr for Protocol {
             "ws" => Ok(Protocol::Ws),
             "wss" => Ok(Protocol::Wss),
             "rtc" => Ok(Protocol::Rtc),
+            "tcp" => Ok(Protocol::Tcp),
             _ => Err(PeerUriError::UnknownProtocol)
         }
     }
@@ -84,6 +85,7 @@ impl fmt::Display for Protocol {
             Protocol::Ws => "ws",
             Protocol::Wss => "wss",
             Protocol::Rtc => "rtc",
+            Protocol::Tcp => "tcp",
         })
     }
 }
@@ -113,7 +115,7 @@ impl<'a> fmt::Display for PeerUri {
                 write!(f, "{}://{}", self.protocol, self.peer_id()
                     .expect("No peer ID for dumb/rtc URI"))?;
             },
-            Protocol::Ws | Protocol::Wss => {
+            Protocol::Ws | Protocol::Wss | Protocol::Tcp => {
                 write!(f, "{}://{}", self.protocol, self.hostname.as_ref().unwrap())?;
                 self.port.map(|p| write!(f, ":{}", p)).transpose()?;
                 self.peer_id().or_else(|| self.public_key()).map(|p| write!(f, "/{}", p)).transpose()?;
@@ -146,7 +148,7 @@ impl PeerUri {
         // or None if there was no path segments at all. If there are multiple segments, returns
         // with an error.
         //
-        // For Dumb and Rtc this must be None (checked later). For Ws and Wss this is the peer_id.
+        // For Dumb and Rtc this must be None (checked later). For Ws, Wss and Tcp this is the peer_id.
         let path_segment = url.path_segments()
             .and_then(|segments| {
                 let segments = segments.collect::<Vec<&str>>();
@@ -174,7 +176,7 @@ impl PeerUri {
                     public_key: None
                 })
             },
-            Protocol::Ws | Protocol::Wss => {
+            Protocol::Ws | Protocol::Wss | Protocol::Tcp => {
                 let host = String::from(url.host_str().ok_or_else(|| PeerUriError::MissingHostname)?);
                 let (peer_id, public_key) = match path_segment {
                     Some(ref peer_id) if peer_id.len() == 2 * PeerId::SIZE => (path_segment, None),
@@ -199,40 +201,34 @@ impl PeerUri {
     pub fn peer_id(&self) -> Option<&String> { self.peer_id.as_ref() }
     pub fn public_key(&self) -> Option<&String> { self.public_key.as_ref() }
 
+    /// Returns the address of the seed node given by this URI. If the URI doesn't contain a
+    /// public key, this is a keyless seed whose key is learned when connecting to it.
     pub fn as_seed_peer_address(&self) -> Result<PeerAddress, PeerUriError> {
-        // TODO: May be we want to allow seed nodes without public key?
-        if self.public_key().is_none() {
-            return Err(PeerUriError::SeedNodeMissingPublicKey);
-        }
+        let ty = match self.protocol() {
+            Protocol::Wss => PeerAddressType::Wss(self.hostname().expect("Mandatory for Wss").to_string(), self.port().unwrap_or(443)),
+            Protocol::Ws => PeerAddressType::Ws(self.hostname().expect("Mandatory for Ws").to_string(), self.port().unwrap_or(80)),
+            Protocol::Tcp => PeerAddressType::Tcp(self.hostname().expect("Mandatory for Tcp").to_string(), self.port().unwrap_or_else(|| Protocol::Tcp.default_port().unwrap())),
+            _ => return Err(PeerUriError::SeedNodeWithInvalidProtocol),
+        };
 
-        let public_key = match PublicKey::from_hex(self.public_key().expect("Checked in previous step")) {
-            Ok(public_key) => public_key,
-            Err(e) => return Err(PeerUriError::from(e)),
+        let public_key = match self.public_key() {
+            Some(public_key) => PublicKey::from_hex(public_key)?,
+            None => {
+                let peer_id = self.peer_id().map(|peer_id| PeerId::from_str(peer_id)).transpose()?;
+                return Ok(PeerAddress::new_keyless_seed(ty, peer_id));
+            },
         };
 
-        match self.protocol() {
-            Protocol::Wss => Ok(PeerAddress {
-                ty: PeerAddressType::Wss(self.hostname().expect("Mandatory for Wss").to_string(), self.port().unwrap_or(443)),
-                services: ServiceFlags::FULL,
-                timestamp: 0,
-                net_address: NetAddress::Unspecified,
-                public_key,
-                distance: 0,
-                signature: None,
-                peer_id: PeerId::from(&public_key),
-            }),
-            Protocol::Ws => Ok(PeerAddress {
-                ty: PeerAddressType::Ws(self.hostname().expect("Mandatory for Ws").to_string(), self.port().unwrap_or(80)),
-                services: ServiceFlags::FULL,
-                timestamp: 0,
-                net_address: NetAddress::Unspecified,
-                public_key,
-                distance: 0,
-                signature: None,
-                peer_id: PeerId::from(&public_key),
-            }),
-            _ => Err(PeerUriError::SeedNodeWithInvalidProtocol),
-        }
+        Ok(PeerAddress {
+            ty,
+            services: ServiceFlags::FULL,
+            timestamp: 0,
+            net_address: NetAddress::Unspecified,
+            public_key,
+            distance: 0,
+            signature: None,
+            peer_id: PeerId::from(&public_key),
+        })
     }
 }
 
@@ -245,7 +241,7 @@ impl From<PeerAddress> for PeerUri {
             PeerAddressType::Dumb | PeerAddressType::Rtc => {
                 PeerUri { protocol, peer_id, hostname: None, port: None, public_key: None }
             },
-            PeerAddressType::Ws(host, port) | PeerAddressType::Wss(host, port) => {
+            PeerAddressType::Ws(host, port) | PeerAddressType::Wss(host, port) | PeerAddressType::Tcp(host, port) => {
                 PeerUri { protocol, peer_id, hostname: Some(host), port: Some(port), public_key: None }
             }
         }
@@ -1,10 +1,12 @@
 #![allow(clippy::unreadable_literal)]
 
 use std::collections::HashMap;
+use std::sync::RwLock;
 
+use failure::Fail;
 use hex::FromHex;
 
-use hash::Blake2bHash;
+use hash::{Blake2bHash, Hash};
 use keys::Address;
 use keys::PublicKey;
 use block::{Block, BlockBody, BlockHeader, BlockInterlink};
@@ -14,6 +16,7 @@ use crate::address::net_address::NetAddress;
 use crate::address::peer_address::PeerAddress;
 use crate::address::peer_address::PeerAddressType;
 use crate::address::PeerId;
+use crate::address::dns_seed::DnsSeed;
 use crate::address::seed_list::SeedList;
 use crate::services::ServiceFlags;
 
@@ -23,6 +26,7 @@ pub struct NetworkInfo {
     pub name: String,
     pub seed_peers: Vec<PeerAddress>,
     pub seed_lists: Vec<SeedList>,
+    pub dns_seeds: Vec<DnsSeed>,
     pub genesis_block: Block,
     pub genesis_hash: Blake2bHash,
     pub genesis_accounts: String, // FIXME
@@ -79,6 +83,7 @@ lazy_static! {
                 seed_lists: vec![
                     create_seed_list("https://nimiq.community/seeds.txt", "8b4ae04557f490102036ce3e570b39058c92fc5669083fb9bbb6effc91dc3c71")
                 ],
+                dns_seeds: vec![],
                 genesis_block: Block {
                     header: BlockHeader {
                         version: 1,
@@ -1222,6 +1227,7 @@ lazy_static! {
                 ],
                 seed_lists: vec![
                 ],
+                dns_seeds: vec![],
                 genesis_block: Block {
                     header: BlockHeader {
                         version: 1,
@@ -1402,6 +1408,7 @@ lazy_static! {
                 ],
                 seed_lists: vec![
                 ],
+                dns_seeds: vec![],
                 genesis_block: Block {
                     header: BlockHeader {
                         version: 1,
@@ -1575,8 +1582,68 @@ lazy_static! {
             }
         );
 
+        // The regtest network reuses the dev network's genesis accounts. Its blocks are mined at a
+        // minimal difficulty, which meets the target on every other attempt.
+        let regtest = {
+            let dev = &m[&NetworkId::Dev];
+            let mut genesis_block = dev.genesis_block.clone();
+            genesis_block.header.n_bits = 0x207f_ffff.into();
+            genesis_block.header.timestamp = 1546300800;
+            genesis_block.header.nonce = 0;
+            NetworkInfo {
+                network_id: NetworkId::Regtest,
+                name: "regtest".into(),
+                seed_peers: vec![],
+                seed_lists: vec![],
+                dns_seeds: vec![],
+                genesis_block,
+                genesis_hash: "841e27fe10191caf3ec1427d5750a422d8e2ee0e9395f2030ab2fec784034939".into(),
+                genesis_accounts: dev.genesis_accounts.clone(),
+            }
+        };
+        add(&mut m, regtest);
+
         m
     };
 }
 
-pub fn get_network_info<'a>(network_id: NetworkId) -> Option<&'a NetworkInfo> { NETWORK_MAP.get(&network_id) }
+lazy_static! {
+    static ref CUSTOM_NETWORK_MAP: RwLock<HashMap<NetworkId, &'static NetworkInfo>> = RwLock::new(HashMap::new());
+}
+
+#[derive(Debug, Fail)]
+pub enum NetworkRegistrationError {
+    #[fail(display = "Network ID {} is reserved for a built-in network", _0)]
+    ReservedNetworkId(u8),
+    #[fail(display = "A network with ID {} is already registered", _0)]
+    AlreadyRegistered(u8),
+    #[fail(display = "The genesis hash doesn't match the genesis block")]
+    GenesisHashMismatch,
+}
+
+pub fn get_network_info<'a>(network_id: NetworkId) -> Option<&'a NetworkInfo> {
+    if network_id.is_custom() {
+        return CUSTOM_NETWORK_MAP.read().unwrap().get(&network_id).cloned();
+    }
+    NETWORK_MAP.get(&network_id)
+}
+
+/// Makes a custom network available through `get_network_info`. This needs to happen before the
+/// blockchain of that network is opened. Registered networks live until the process exits.
+pub fn register_custom_network(network_info: NetworkInfo) -> Result<(), NetworkRegistrationError> {
+    let network_id = network_info.network_id;
+    if !network_id.is_custom() {
+        return Err(NetworkRegistrationError::ReservedNetworkId(network_id.into()));
+    }
+    if network_info.genesis_block.header.hash::<Blake2bHash>() != network_info.genesis_hash {
+        return Err(NetworkRegistrationError::GenesisHashMismatch);
+    }
+
+    let mut custom_networks = CUSTOM_NETWORK_MAP.write().unwrap();
+    if custom_networks.contains_key(&network_id) {
+        return Err(NetworkRegistrationError::AlreadyRegistered(network_id.into()));
+    }
+    custom_networks.insert(network_id, Box::leak(Box::new(network_info)));
+    network_id.register_custom();
+    Ok(())
+}
@@ -6,7 +6,9 @@ pub enum Protocol {
     Dumb = 0,
     Wss = 1,
     Rtc = 2,
-    Ws = 4
+    Ws = 4,
+    /// Raw TCP with an encrypted and mutually authenticated channel. Meant for private clusters.
+    Tcp = 8,
 }
 
 impl From<ProtocolFlags> for Vec<Protocol> {
@@ -24,6 +26,9 @@ impl From<ProtocolFlags> for Vec<Protocol> {
         if flags.contains(ProtocolFlags::WS) {
             v.push(Protocol::Ws);
         }
+        if flags.contains(ProtocolFlags::TCP) {
+            v.push(Protocol::Tcp);
+        }
         v
     }
 }
@@ -35,6 +40,7 @@ bitflags! {
         const WSS   = 0b0000_0001;
         const RTC   = 0b0000_0010;
         const WS    = 0b0000_0100;
+        const TCP   = 0b0000_1000;
     }
 }
 
@@ -45,6 +51,7 @@ impl From<Protocol> for ProtocolFlags {
             Protocol::Rtc => ProtocolFlags::RTC,
             Protocol::Wss => ProtocolFlags::WSS,
             Protocol::Ws => ProtocolFlags::WS,
+            Protocol::Tcp => ProtocolFlags::TCP,
         }
     }
 }
@@ -63,6 +70,7 @@ impl Protocol {
     pub fn default_port(self) -> Option<u16> {
         match self {
             Protocol::Ws | Protocol::Wss => Some(8443),
+            Protocol::Tcp => Some(8444),
             _ => None
         }
     }
@@ -108,7 +108,9 @@ impl Serialize for Subscription {
                 }
                 size += Serialize::serialize(&(addresses.len() as u16), writer)?;
 
-                // Serialize addresses
+                // Serialize addresses, sorted so that the encoding is deterministic
+                let mut addresses: Vec<&Address> = addresses.iter().collect();
+                addresses.sort();
                 for address in addresses {
                     size += Serialize::serialize(address, writer)?;
                 }
@@ -1,3 +1,4 @@
+use std::cmp;
 use std::time::SystemTime;
 use std::time::Duration;
 use utils::time::systemtime_to_timestamp;
@@ -6,7 +7,9 @@ use atomic::Ordering;
 
 #[derive(Debug, Default)]
 pub struct NetworkTime {
-    offset: Atomic<i64>
+    offset: Atomic<i64>,
+    /// A fixed time in milliseconds that overrides the system clock and the offset, for testing.
+    fixed_time: Atomic<Option<u64>>,
 }
 
 impl NetworkTime {
@@ -16,7 +19,8 @@ impl NetworkTime {
 
     pub fn with_offset(offset: i64) -> Self {
         NetworkTime {
-            offset: Atomic::new(offset)
+            offset: Atomic::new(offset),
+            fixed_time: Atomic::new(None),
         }
     }
 
@@ -24,7 +28,25 @@ impl NetworkTime {
         self.offset.store(new_offset, Ordering::Relaxed);
     }
 
+    pub fn offset(&self) -> i64 {
+        self.offset.load(Ordering::Relaxed)
+    }
+
+    /// Freezes the clock at the given unix timestamp in milliseconds, or lets it run again if `None`
+    /// is given. The offset to the peers' clocks is ignored while the clock is frozen.
+    pub fn set_fixed_time(&self, fixed_time: Option<u64>) {
+        self.fixed_time.store(fixed_time, Ordering::Relaxed);
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        self.fixed_time.load(Ordering::Relaxed).is_some()
+    }
+
     pub fn now(&self) -> u64 {
+        if let Some(fixed_time) = self.fixed_time.load(Ordering::Relaxed) {
+            return fixed_time;
+        }
+
         let offset = self.offset.load(Ordering::Relaxed);
         let abs_offset = offset.abs() as u64;
         let system_time = if offset > 0 {
@@ -36,3 +58,97 @@ impl NetworkTime {
         systemtime_to_timestamp(system_time)
     }
 }
+
+/// An estimate of the offset of the local clock to the clocks of the peers, in milliseconds.
+///
+/// The estimate ignores samples that are implausibly far off or far from the other samples, and
+/// takes the trimmed mean of the remaining ones as the offset the peers agree on. The offset of
+/// the network time only moves towards that target in bounded steps, so that a few peers can't
+/// shift the clock far in one go.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimeOffsetEstimate {
+    /// The offset that the network time should use.
+    pub offset: i64,
+    /// The offset the peers agree on, or `None` if there are too few samples to tell.
+    pub target: Option<i64>,
+    /// The sorted samples that were taken into account.
+    pub samples: Vec<i64>,
+    /// The sorted samples that were rejected as outliers.
+    pub outliers: Vec<i64>,
+}
+
+impl TimeOffsetEstimate {
+    /// Minimum number of samples needed to adjust the offset.
+    pub const MIN_SAMPLES: usize = 3;
+    /// Samples further off than this are rejected right away (70 minutes).
+    pub const MAX_SAMPLE_OFFSET: i64 = 70 * 60 * 1000;
+    /// Samples further from the median than this many median absolute deviations are rejected.
+    pub const OUTLIER_DEVIATIONS: i64 = 3;
+    /// Samples within this distance of the median are never rejected, so that a tight cluster of
+    /// samples doesn't turn slight differences into outliers.
+    pub const OUTLIER_MIN_DISTANCE: i64 = 2000;
+    /// Fraction of samples dropped from each end before taking the mean.
+    pub const TRIM_FRACTION: f64 = 0.2;
+    /// Maximum change of the offset per update.
+    pub const MAX_ADJUSTMENT: i64 = 30 * 1000;
+    /// The local clock is considered to be off if the peers agree on an offset beyond this
+    /// (5 minutes).
+    pub const WARNING_OFFSET: i64 = 5 * 60 * 1000;
+
+    /// Estimates the offset from the offsets of the peers' clocks, starting from the current offset.
+    pub fn new(current_offset: i64, mut offsets: Vec<i64>) -> Self {
+        offsets.sort();
+        let (plausible, mut outliers): (Vec<i64>, Vec<i64>) = offsets.into_iter()
+            .partition(|offset| offset.abs() <= Self::MAX_SAMPLE_OFFSET);
+
+        let samples = if plausible.len() >= Self::MIN_SAMPLES {
+            let center = median(&plausible);
+            let mut deviations: Vec<i64> = plausible.iter().map(|offset| (offset - center).abs()).collect();
+            deviations.sort();
+            let max_distance = cmp::max(Self::OUTLIER_DEVIATIONS * median(&deviations), Self::OUTLIER_MIN_DISTANCE);
+            let (samples, rejected): (Vec<i64>, Vec<i64>) = plausible.into_iter()
+                .partition(|offset| (offset - center).abs() <= max_distance);
+            outliers.extend(rejected);
+            outliers.sort();
+            samples
+        } else {
+            plausible
+        };
+
+        let target = if samples.len() >= Self::MIN_SAMPLES {
+            Some(trimmed_mean(&samples, Self::TRIM_FRACTION))
+        } else {
+            None
+        };
+
+        let offset = match target {
+            Some(target) => current_offset + cmp::min(cmp::max(target - current_offset, -Self::MAX_ADJUSTMENT), Self::MAX_ADJUSTMENT),
+            None => current_offset,
+        };
+
+        TimeOffsetEstimate { offset, target, samples, outliers }
+    }
+
+    /// Whether the peers agree that the local clock is far off.
+    pub fn is_clock_off(&self) -> bool {
+        self.target.map_or(false, |target| target.abs() > Self::WARNING_OFFSET)
+    }
+}
+
+/// The median of sorted, non-empty `values`.
+fn median(values: &[i64]) -> i64 {
+    let len = values.len();
+    if len % 2 == 0 {
+        (values[len / 2 - 1] + values[len / 2]) / 2
+    } else {
+        values[len / 2]
+    }
+}
+
+/// The mean of sorted, non-empty `values` without the given fraction of values at each end.
+fn trimmed_mean(values: &[i64], fraction: f64) -> i64 {
+    let trim = (values.len() as f64 * fraction) as usize;
+    let trimmed = &values[trim..values.len() - trim];
+    let sum: i128 = trimmed.iter().map(|value| i128::from(*value)).sum();
+    (sum / trimmed.len() as i128) as i64
+}
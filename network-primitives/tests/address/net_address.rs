@@ -0,0 +1,58 @@
+use std::str::FromStr;
+
+use network_primitives::address::{NetAddress, NetAddressRange};
+
+fn ip(s: &str) -> NetAddress {
+    NetAddress::from_str(s).unwrap()
+}
+
+#[test]
+fn it_parses_ranges() {
+    let range = NetAddressRange::from_str("192.168.10.40/16").unwrap();
+    assert_eq!(range.address(), ip("192.168.0.0"));
+    assert_eq!(range.bit_count(), 16);
+    assert_eq!(range.to_string(), "192.168.0.0/16");
+
+    let range = NetAddressRange::from_str("2001:db8::1").unwrap();
+    assert_eq!(range.address(), ip("2001:db8::1"));
+    assert_eq!(range.bit_count(), 128);
+
+    assert!(NetAddressRange::from_str("192.168.0.0/33").is_err());
+    assert!(NetAddressRange::from_str("192.168.0.0/").is_err());
+    assert!(NetAddressRange::from_str("localhost").is_err());
+}
+
+#[test]
+fn it_matches_addresses() {
+    let range = NetAddressRange::from_str("10.1.0.0/16").unwrap();
+    assert!(range.contains(&ip("10.1.2.3")));
+    assert!(!range.contains(&ip("10.2.0.1")));
+    assert!(!range.contains(&ip("2001:db8::1")));
+    // As seen by a socket listening on both IPv4 and IPv6.
+    assert!(range.contains(&ip("::ffff:10.1.2.3")));
+    assert!(!range.contains(&ip("::10.1.2.3")));
+
+    let range = NetAddressRange::from_str("2001:db8::/32").unwrap();
+    assert!(range.contains(&ip("2001:db8:1::1")));
+    assert!(!range.contains(&ip("2001:db9::1")));
+    assert!(!range.contains(&ip("32.1.13.184")));
+
+    let range = NetAddressRange::from(ip("127.0.0.1"));
+    assert!(range.contains(&ip("127.0.0.1")));
+    assert!(!range.contains(&ip("127.0.0.2")));
+
+    // Ranges of IPv4-mapped addresses also match the IPv4 addresses.
+    let range = NetAddressRange::from_str("::ffff:192.168.10.40").unwrap();
+    assert_eq!(range.to_string(), "192.168.10.40/32");
+    assert!(range.contains(&ip("192.168.10.40")));
+    assert!(range.contains(&ip("::ffff:192.168.10.40")));
+}
+
+#[test]
+fn it_canonicalizes_ipv4_mapped_addresses() {
+    assert_eq!(ip("::ffff:10.1.2.3").canonical(), ip("10.1.2.3"));
+    assert_eq!(ip("::10.1.2.3").canonical(), ip("::10.1.2.3"));
+    assert_eq!(ip("2001:db8::1").canonical(), ip("2001:db8::1"));
+    assert_eq!(ip("10.1.2.3").canonical(), ip("10.1.2.3"));
+    assert_eq!(NetAddress::Unknown.canonical(), NetAddress::Unknown);
+}
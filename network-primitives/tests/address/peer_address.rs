@@ -0,0 +1,27 @@
+use nimiq_keys::KeyPair;
+
+use network_primitives::address::{NetAddress, PeerAddress, PeerAddressType, PeerId};
+use network_primitives::services::ServiceFlags;
+
+fn peer_address(ty: PeerAddressType) -> PeerAddress {
+    let public_key = KeyPair::generate().public;
+    PeerAddress { ty, services: ServiceFlags::FULL, timestamp: 0, net_address: NetAddress::Unspecified, public_key, distance: 0, signature: None, peer_id: PeerId::from(&public_key) }
+}
+
+#[test]
+fn it_checks_tcp_addresses_for_global_reachability() {
+    assert!(peer_address(PeerAddressType::Tcp("node.nimiq.com".to_string(), 8443)).is_globally_reachable(false));
+    assert!(peer_address(PeerAddressType::Tcp("8.8.8.8".to_string(), 8443)).is_globally_reachable(false));
+    assert!(!peer_address(PeerAddressType::Tcp("10.0.0.1".to_string(), 8443)).is_globally_reachable(false));
+    assert!(!peer_address(PeerAddressType::Tcp("127.0.0.1".to_string(), 8443)).is_globally_reachable(false));
+    assert!(!peer_address(PeerAddressType::Tcp("localhost".to_string(), 8443)).is_globally_reachable(false));
+}
+
+#[test]
+fn it_requires_brackets_around_ipv6_hosts() {
+    assert!(peer_address(PeerAddressType::Ws("[2a01:4f8::1]".to_string(), 8443)).is_globally_reachable(false));
+    assert!(!peer_address(PeerAddressType::Ws("2a01:4f8::1".to_string(), 8443)).is_globally_reachable(false));
+    assert!(!peer_address(PeerAddressType::Ws("[::1]".to_string(), 8443)).is_globally_reachable(false));
+    assert!(!peer_address(PeerAddressType::Ws("[8.8.8.8]".to_string(), 8443)).is_globally_reachable(false));
+    assert!(!peer_address(PeerAddressType::Wss("[2a01:4f8::1]".to_string(), 8443)).is_globally_reachable(false));
+}
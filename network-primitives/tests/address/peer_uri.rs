@@ -68,3 +68,47 @@ fn test_parse_uri_ws() {
     assert_eq!(uri.peer_id(), Some(String::from("2b3f0f59334ef71ee7869b451139587f")).as_ref());
 }
 
+
+#[test]
+fn test_parse_uri_tcp_seed() {
+    let uri = PeerUri::from_str("tcp://10.0.0.2:8444/e65e39616662f2c16d62dc08915e5a1d104619db8c2b9cf9b389f96c8dce9837").unwrap();
+    assert_eq!(uri.protocol(), Protocol::Tcp);
+    assert_eq!(uri.hostname(), Some(String::from("10.0.0.2")).as_ref());
+    assert_eq!(uri.port(), Some(8444));
+
+    let peer_address = uri.as_seed_peer_address().unwrap();
+    assert_eq!(peer_address.protocol(), Protocol::Tcp);
+    assert_eq!(peer_address.to_seed_string(), Some(String::from("tcp://10.0.0.2:8444/e65e39616662f2c16d62dc08915e5a1d104619db8c2b9cf9b389f96c8dce9837")));
+}
+
+#[test]
+fn test_keyless_seed() {
+    let uri = PeerUri::from_str("wss://seed.nimiq.example:8443").unwrap();
+    let seed = uri.as_seed_peer_address().unwrap();
+    assert!(seed.is_keyless_seed());
+    assert_eq!(seed.to_seed_string(), Some(String::from("wss://seed.nimiq.example:8443")));
+
+    // The seed accepts any key until it is pinned.
+    let keyed_uri = PeerUri::from_str("wss://seed.nimiq.example:8443/e65e39616662f2c16d62dc08915e5a1d104619db8c2b9cf9b389f96c8dce9837").unwrap();
+    let keyed_seed = keyed_uri.as_seed_peer_address().unwrap();
+    assert!(!keyed_seed.is_keyless_seed());
+    assert!(seed.matches_keyless_seed(&keyed_seed));
+    assert!(!keyed_seed.matches_keyless_seed(&seed));
+}
+
+#[test]
+fn test_keyless_seed_with_peer_id() {
+    let keyed_uri = PeerUri::from_str("wss://seed.nimiq.example:8443/e65e39616662f2c16d62dc08915e5a1d104619db8c2b9cf9b389f96c8dce9837").unwrap();
+    let keyed_seed = keyed_uri.as_seed_peer_address().unwrap();
+
+    let uri = PeerUri::from_str(&format!("wss://seed.nimiq.example:8443/{}", keyed_seed.peer_id.to_hex())).unwrap();
+    let seed = uri.as_seed_peer_address().unwrap();
+    assert!(seed.is_keyless_seed());
+    assert_eq!(seed.peer_id, keyed_seed.peer_id);
+    assert!(seed.matches_keyless_seed(&keyed_seed));
+
+    // A configured peer ID must match the key the seed announces.
+    let other_uri = PeerUri::from_str("wss://seed.nimiq.example:8443/f1240638c6dd670467f22a04b58f7740").unwrap();
+    let other_seed = other_uri.as_seed_peer_address().unwrap();
+    assert!(!other_seed.matches_keyless_seed(&keyed_seed));
+}
@@ -4,4 +4,6 @@ extern crate nimiq_network_primitives as network_primitives;
 mod networks;
 #[cfg(feature = "subscription")]
 mod subscription;
+#[cfg(feature = "time")]
+mod time;
 mod address;
@@ -9,3 +9,12 @@ fn it_has_expected_main_hash() {
         &hex::decode("264AAF8A4F9828A76C550635DA078EB466306A189FCC03710BEE9F649C869D12").unwrap()[..]
     )
 }
+
+#[test]
+fn it_has_expected_regtest_hash() {
+    let network_info = get_network_info(NetworkId::Regtest).unwrap();
+    assert_eq!(network_info.genesis_block.header.hash::<Blake2bHash>(), network_info.genesis_hash);
+    assert!(network_info.genesis_block.header.verify_proof_of_work());
+    assert!(network_info.seed_peers.is_empty());
+}
+
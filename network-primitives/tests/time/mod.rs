@@ -0,0 +1,71 @@
+use nimiq_network_primitives::time::{NetworkTime, TimeOffsetEstimate};
+
+#[test]
+fn it_can_freeze_the_clock() {
+    let network_time = NetworkTime::with_offset(-1_000_000);
+    network_time.set_fixed_time(Some(1_546_300_800_000));
+    assert!(network_time.is_fixed());
+    assert_eq!(network_time.now(), 1_546_300_800_000);
+
+    network_time.set_offset(5000);
+    assert_eq!(network_time.now(), 1_546_300_800_000);
+
+    network_time.set_fixed_time(None);
+    assert!(!network_time.is_fixed());
+    assert!(network_time.now() > 1_546_300_800_000);
+}
+
+#[test]
+fn it_needs_enough_samples() {
+    let estimate = TimeOffsetEstimate::new(1000, vec![5000, 6000]);
+    assert_eq!(estimate.target, None);
+    assert_eq!(estimate.offset, 1000);
+    assert_eq!(estimate.samples, vec![5000, 6000]);
+}
+
+#[test]
+fn it_takes_the_trimmed_mean() {
+    let estimate = TimeOffsetEstimate::new(0, vec![1000, -1000, 2000, 0, 3000]);
+    assert_eq!(estimate.target, Some(1000));
+    assert_eq!(estimate.offset, 1000);
+    assert!(estimate.outliers.is_empty());
+
+    // The smallest and largest sample are dropped.
+    let estimate = TimeOffsetEstimate::new(0, vec![0, 100, 200, 300, 2100]);
+    assert_eq!(estimate.target, Some(200));
+}
+
+#[test]
+fn it_rejects_outliers() {
+    let estimate = TimeOffsetEstimate::new(0, vec![100, 200, 150, 50, 3_600_000, -80 * 60 * 1000]);
+    assert_eq!(estimate.samples, vec![50, 100, 150, 200]);
+    assert_eq!(estimate.outliers, vec![-80 * 60 * 1000, 3_600_000]);
+    assert_eq!(estimate.target, Some(125));
+
+    // Too few samples remain.
+    let estimate = TimeOffsetEstimate::new(0, vec![100, 200, 100 * 60 * 1000]);
+    assert_eq!(estimate.target, None);
+    assert_eq!(estimate.offset, 0);
+}
+
+#[test]
+fn it_bounds_the_adjustment() {
+    let offsets = vec![600_000, 600_100, 599_900];
+    let estimate = TimeOffsetEstimate::new(0, offsets.clone());
+    assert_eq!(estimate.target, Some(600_000));
+    assert_eq!(estimate.offset, TimeOffsetEstimate::MAX_ADJUSTMENT);
+    assert!(estimate.is_clock_off());
+
+    let estimate = TimeOffsetEstimate::new(590_000, offsets);
+    assert_eq!(estimate.offset, 600_000);
+
+    let estimate = TimeOffsetEstimate::new(0, vec![-600_000, -600_000, -600_000]);
+    assert_eq!(estimate.offset, -TimeOffsetEstimate::MAX_ADJUSTMENT);
+}
+
+#[test]
+fn it_doesnt_warn_about_small_offsets() {
+    let estimate = TimeOffsetEstimate::new(0, vec![10_000, 12_000, 11_000]);
+    assert!(!estimate.is_clock_off());
+    assert!(!TimeOffsetEstimate::default().is_clock_off());
+}
@@ -11,6 +11,7 @@ use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 use std::time::{Duration, Instant, SystemTime};
 
+use database::Environment;
 use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use rand::{Rng, rngs::OsRng};
 
@@ -25,13 +26,15 @@ use utils::observer::Notifier;
 use utils::time::systemtime_to_timestamp;
 use utils::timers::Timers;
 
+use crate::ban_list::BanTarget;
 use crate::connection::close_type::CloseType;
-use crate::network_config::{NetworkConfig, Seed};
+use crate::network_config::{AllowedPeer, NetworkConfig, Seed};
 use crate::peer_channel::PeerChannel;
 
 use super::peer_address_seeder::{PeerAddressSeeder, PeerAddressSeederEvent};
 use super::peer_address_state::PeerAddressInfo;
 use super::peer_address_state::PeerAddressState;
+use super::peer_address_store::PeerAddressStore;
 use crate::error::Error;
 
 pub struct PeerAddressBookState {
@@ -39,6 +42,7 @@ pub struct PeerAddressBookState {
     ws_addresses: HashSet<Arc<PeerAddress>>,
     wss_addresses: HashSet<Arc<PeerAddress>>,
     rtc_addresses: HashSet<Arc<PeerAddress>>,
+    tcp_addresses: HashSet<Arc<PeerAddress>>,
     address_by_peer_id: HashMap<PeerId, Arc<PeerAddress>>,
     addresses_by_net_address: HashMap<NetAddress, HashSet<Arc<PeerAddress>>>,
 }
@@ -64,6 +68,10 @@ impl PeerAddressBookState {
         self.rtc_addresses.iter()
     }
 
+    pub fn tcp_address_iter(&self) -> Iter<Arc<PeerAddress>> {
+        self.tcp_addresses.iter()
+    }
+
     pub fn address_iter_for_protocol_mask(&self, protocol_mask: ProtocolFlags) -> QueryIterator {
         if protocol_mask == ProtocolFlags::WSS {
             QueryIterator::Iter(self.wss_address_iter())
@@ -77,6 +85,8 @@ impl PeerAddressBookState {
             QueryIterator::Alternate(Alternate::new(self.rtc_address_iter(), self.ws_address_iter()))
         } else if protocol_mask == ProtocolFlags::RTC | ProtocolFlags::WSS {
             QueryIterator::Alternate(Alternate::new(self.rtc_address_iter(), self.wss_address_iter()))
+        } else if protocol_mask == ProtocolFlags::TCP {
+            QueryIterator::Iter(self.tcp_address_iter())
         } else {
             QueryIterator::Keys(self.address_iter())
         }
@@ -95,6 +105,8 @@ impl PeerAddressBookState {
             self.known_rtc_addresses_count() + self.known_ws_addresses_count()
         } else if protocol_mask == ProtocolFlags::RTC | ProtocolFlags::WSS {
             self.known_rtc_addresses_count() + self.known_wss_addresses_count()
+        } else if protocol_mask == ProtocolFlags::TCP {
+            self.known_tcp_addresses_count()
         } else {
             self.known_addresses_count()
         }
@@ -143,6 +155,9 @@ impl PeerAddressBookState {
             Protocol::Rtc => {
                 self.rtc_addresses.insert(Arc::clone(&info.peer_address));
             },
+            Protocol::Tcp => {
+                self.tcp_addresses.insert(Arc::clone(&info.peer_address));
+            },
             Protocol::Dumb => { } // Dumb addresses are ignored.
         };
 
@@ -159,14 +174,28 @@ impl PeerAddressBookState {
             }
         }
 
+        self.remove_from_indices(&peer_address);
+
+        if let Some(info) = self.get_info(&peer_address) {
+            // Don't delete bans.
+            if info.state == PeerAddressState::Banned {
+                return;
+            }
+
+            // Delete the address.
+            self.info_by_address.remove(&peer_address);
+        }
+    }
+
+    fn remove_from_indices(&mut self, peer_address: &Arc<PeerAddress>) {
         // Delete from peer id index.
         self.address_by_peer_id.remove(&peer_address.peer_id);
 
         // Delete from net address index.
-        if let Some(info) = self.info_by_address.get_mut(&peer_address) {
+        if let Some(info) = self.info_by_address.get_mut(peer_address) {
             for net_address in &info.added_by {
                 if let Some(addresses) = self.addresses_by_net_address.get_mut(&net_address) {
-                    addresses.remove(&peer_address);
+                    addresses.remove(peer_address);
                 }
             }
         }
@@ -174,25 +203,39 @@ impl PeerAddressBookState {
         // Remove from protocol index.
         match peer_address.protocol() {
             Protocol::Ws => {
-                self.ws_addresses.remove(&peer_address);
+                self.ws_addresses.remove(peer_address);
             },
             Protocol::Wss => {
-                self.wss_addresses.remove(&peer_address);
+                self.wss_addresses.remove(peer_address);
             },
             Protocol::Rtc => {
-                self.rtc_addresses.remove(&peer_address);
+                self.rtc_addresses.remove(peer_address);
+            },
+            Protocol::Tcp => {
+                self.tcp_addresses.remove(peer_address);
             },
             _ => {}
         }
+    }
 
-        if let Some(info) = self.get_info(&peer_address) {
-            // Don't delete bans.
-            if info.state == PeerAddressState::Banned {
-                return;
-            }
-
-            // Delete the address.
-            self.info_by_address.remove(&peer_address);
+    /// Replaces a keyless seed with the address it authenticated with. The pinned address is a
+    /// seed as well, so that it is never removed and only connections with that key are accepted.
+    fn pin_seed(&mut self, seed: &Arc<PeerAddress>, peer_address: &PeerAddress) {
+        self.remove_from_indices(seed);
+        self.info_by_address.remove(seed);
+
+        let pinned = Arc::new(PeerAddress {
+            ty: seed.ty.clone(),
+            services: peer_address.services,
+            timestamp: 0,
+            net_address: peer_address.net_address,
+            public_key: peer_address.public_key,
+            distance: 0,
+            signature: None,
+            peer_id: peer_address.peer_id.clone(),
+        });
+        if !self.info_by_address.contains_key(&pinned) {
+            self.add_to_store(PeerAddressInfo::new(pinned));
         }
     }
 
@@ -257,6 +300,7 @@ impl PeerAddressBookState {
     pub fn known_ws_addresses_count(&self) -> usize { self.ws_addresses.len() }
     pub fn known_wss_addresses_count(&self) -> usize { self.wss_addresses.len() }
     pub fn known_rtc_addresses_count(&self) -> usize { self.rtc_addresses.len() }
+    pub fn known_tcp_addresses_count(&self) -> usize { self.tcp_addresses.len() }
 }
 
 pub struct PeerAddressBook {
@@ -266,6 +310,7 @@ pub struct PeerAddressBook {
     network_id: NetworkId,
     timers: Timers<PeerAddressBookTimer>,
     change_lock: Mutex<()>,
+    store: PeerAddressStore,
     pub notifier: Notifier<'static, PeerAddressBookEvent>,
 }
 
@@ -273,6 +318,7 @@ pub struct PeerAddressBook {
 enum PeerAddressBookTimer {
     ExternalSeeding,
     Housekeeping,
+    Persist,
 }
 
 pub enum PeerAddressBookEvent {
@@ -281,13 +327,14 @@ pub enum PeerAddressBookEvent {
 }
 
 impl PeerAddressBook {
-    pub fn new(network_config: Arc<NetworkConfig>, network_id: NetworkId) -> Result<Self, Error> {
+    pub fn new(network_config: Arc<NetworkConfig>, network_id: NetworkId, env: &'static Environment) -> Result<Self, Error> {
         let this = Self {
             state: RwLock::new(PeerAddressBookState {
                 info_by_address: HashMap::new(),
                 ws_addresses: HashSet::new(),
                 wss_addresses: HashSet::new(),
                 rtc_addresses: HashSet::new(),
+                tcp_addresses: HashSet::new(),
                 address_by_peer_id: HashMap::new(),
                 addresses_by_net_address: HashMap::new(),
             }),
@@ -296,13 +343,16 @@ impl PeerAddressBook {
             network_config,
             timers: Timers::new(),
             change_lock: Mutex::new(()),
+            store: PeerAddressStore::new(env),
             notifier: Notifier::new(),
         };
 
-        // Init hardcoded seed peers.
+        // Init hardcoded seed peers. Private networks only connect to the allowed peers.
         if let Some(network_info) = get_network_info(network_id) {
-            // TODO: This may be optimized by using Vec<Arc<>> instead of cloning
-            this.add(None, network_info.seed_peers.clone());
+            if !this.network_config.is_private() {
+                // TODO: This may be optimized by using Vec<Arc<>> instead of cloning
+                this.add(None, network_info.seed_peers.clone());
+            }
         } else {
             return Err(Error::InvalidNetworkInfo(network_id));
         }
@@ -312,16 +362,32 @@ impl PeerAddressBook {
         .filter_map(|seed| {
             match seed {
                 Seed::Peer(peer_uri) => Some(peer_uri.as_seed_peer_address().expect("This should be checked before adding the seed peer to network_config")),
-                Seed::List(_) => None,
+                Seed::List(_) | Seed::Dns(_) => None,
             }
         }).collect();
         this.add(None, additional_seeds);
 
+        // Allowed peers with a known address are treated like seeds, so that they are never
+        // removed from the address book and we keep reconnecting to them.
+        if let Some(allowed_peers) = this.network_config.allowed_peers() {
+            let allowed_seeds: Vec<PeerAddress> = allowed_peers.iter()
+                .filter_map(|allowed_peer| {
+                    match allowed_peer {
+                        AllowedPeer::Peer(peer_uri) => Some(peer_uri.as_seed_peer_address().expect("This should be checked before adding the allowed peer to network_config")),
+                        AllowedPeer::PeerId(_) => None,
+                    }
+                }).collect();
+            this.add(None, allowed_seeds);
+        }
+
         Ok(this)
     }
 
     /// Initialises async stuff.
     pub fn initialize(this: &Arc<Self>) -> Result<(), Error> {
+        // Restore the addresses known before the last shutdown.
+        this.restore();
+
         // Setup housekeeping interval.
         let weak = Arc::downgrade(this);
         this.timers.set_interval(PeerAddressBookTimer::Housekeeping, move || {
@@ -329,6 +395,20 @@ impl PeerAddressBook {
             this.housekeeping();
         }, HOUSEKEEPING_INTERVAL);
 
+        // Write the known addresses to the database periodically.
+        let weak = Arc::downgrade(this);
+        this.timers.set_interval(PeerAddressBookTimer::Persist, move || {
+            let this = upgrade_weak!(weak);
+            this.persist();
+        }, PERSIST_INTERVAL);
+
+        // Private networks don't use seed lists.
+        if this.network_config.is_private() {
+            this.seeded.store(true, Ordering::Release);
+            this.notifier.notify(PeerAddressBookEvent::Seeded);
+            return Ok(());
+        }
+
         // Collect more seed peers from seed lists.
         let weak = Arc::downgrade(this);
         this.timers.set_delay(PeerAddressBookTimer::ExternalSeeding, move || {
@@ -357,6 +437,92 @@ impl PeerAddressBook {
         Ok(())
     }
 
+    /// Adds seeds while the node is running, e.g. after the config file was reloaded. Seed lists
+    /// and DNS seeds are fetched in the background, private networks only add seed peers.
+    pub fn add_seeds(this: &Arc<Self>, seeds: &[Seed]) {
+        let seed_peers: Vec<PeerAddress> = seeds.iter()
+            .filter_map(|seed| {
+                match seed {
+                    Seed::Peer(peer_uri) => peer_uri.as_seed_peer_address()
+                        .map_err(|e| warn!("Ignoring invalid seed peer {}: {}", peer_uri, e))
+                        .ok(),
+                    Seed::List(_) | Seed::Dns(_) => None,
+                }
+            }).collect();
+        this.add(None, seed_peers);
+
+        if this.network_config.is_private() {
+            return;
+        }
+
+        let (seed_lists, dns_seeds) = PeerAddressSeeder::remote_seeds(seeds);
+        if seed_lists.is_empty() && dns_seeds.is_empty() {
+            return;
+        }
+        let seeder = PeerAddressSeeder::new();
+        let weak = Arc::downgrade(this);
+        seeder.notifier.lock().register(move |e: &PeerAddressSeederEvent| {
+            if let PeerAddressSeederEvent::Seeds(seeds) = e {
+                let this = upgrade_weak!(weak);
+                trace!("Adding new seeds from remote seed list");
+                this.add(None, seeds.to_vec());
+            }
+        });
+        seeder.collect_from(seed_lists, dns_seeds, &this.network_config);
+    }
+
+    fn restore(&self) {
+        let guard = self.change_lock.lock();
+
+        let mut state = self.state.write();
+        let mut restored_addresses: Vec<PeerAddress> = Vec::new();
+        for info in self.store.load(STORE_MAX_AGE) {
+            // Seeds are added on every start. Only the keys learned for keyless seeds are restored,
+            // if the seed is still configured.
+            if info.peer_address.is_seed() {
+                let seed = state.info_by_address.keys()
+                    .find(|seed| seed.ty == info.peer_address.ty && seed.matches_keyless_seed(&info.peer_address))
+                    .cloned();
+                if let Some(seed) = seed {
+                    debug!("Restoring public key {} for seed {}", info.peer_address.public_key.to_hex(), seed.to_seed_string().unwrap_or_default());
+                    state.pin_seed(&seed, &info.peer_address);
+                }
+                continue;
+            }
+
+            // Ignore our own address and addresses we already know.
+            if self.network_config.peer_address() == *info.peer_address
+                || state.info_by_address.contains_key(&info.peer_address) {
+                continue;
+            }
+
+            if state.info_by_address.len() >= MAX_SIZE {
+                break;
+            }
+
+            if info.state != PeerAddressState::Banned {
+                restored_addresses.push(info.peer_address.as_ref().clone());
+            }
+            state.add_to_store(info);
+        }
+
+        // Drop the guard before notifying.
+        drop(state);
+        drop(guard);
+
+        debug!("Restored {} peer addresses from the database", restored_addresses.len());
+        if !restored_addresses.is_empty() {
+            self.notifier.notify(PeerAddressBookEvent::Added(restored_addresses));
+        }
+    }
+
+    /// Writes the known addresses to the database.
+    pub fn persist(&self) {
+        let state = self.state.read();
+        self.store.store(state.address_info_iter());
+        trace!("Persisted {} peer addresses", state.known_addresses_count());
+    }
+
     pub fn query(&self, protocol_mask: ProtocolFlags, service_mask: ServiceFlags, max_addresses: u16) -> Vec<Arc<PeerAddress>> {
         let max_addresses = max_addresses as usize; // Internally, we need a usize.
 
@@ -524,6 +690,10 @@ impl PeerAddressBook {
                     if state.rtc_addresses.len() >= MAX_SIZE_RTC {
                         return false;
                     },
+                Protocol::Tcp =>
+                    if state.tcp_addresses.len() >= MAX_SIZE_TCP {
+                        return false;
+                    },
                 Protocol::Dumb => {}, // Dumb addresses are only part of global limit.
             }
 
@@ -581,6 +751,7 @@ impl PeerAddressBook {
         let info = state.info_by_address.get_mut(&peer_address).expect("Code above guarantees that this will never be None");
 
         info.state = PeerAddressState::Established;
+        info.restored = false;
         info.last_connected = Some(SystemTime::now());
         info.failed_attempts = 0;
         info.banned_until = None;
@@ -611,7 +782,8 @@ impl PeerAddressBook {
 
                 if info.failed_attempts >= info.max_failed_attempts() {
                     // Remove address only if we have tried the maximum number of backoffs.
-                    if info.ban_backoff >= MAX_FAILED_BACKOFF {
+                    // Allowed peers in private network mode are retried indefinitely.
+                    if info.ban_backoff >= MAX_FAILED_BACKOFF && !self.network_config.is_private() {
                         state.remove_from_store(Arc::clone(&peer_address));
                     } else {
                         info.banned_until = Some(Instant::now() + info.ban_backoff);
@@ -636,6 +808,50 @@ impl PeerAddressBook {
         }
     }
 
+    /// Called when a keyless seed announced its address in the handshake.
+    pub fn pin_seed(&self, seed: &Arc<PeerAddress>, peer_address: &PeerAddress) {
+        let _guard = self.change_lock.lock();
+
+        debug!("Pinning public key {} for seed {}", peer_address.public_key.to_hex(), seed.to_seed_string().unwrap_or_default());
+        self.state.write().pin_seed(seed, peer_address);
+    }
+
+    /// Lifts the bans of the addresses `target` applies to, or of all addresses if no target is
+    /// given. Returns the number of addresses that were unbanned.
+    pub fn unban(&self, target: Option<&BanTarget>) -> usize {
+        let guard = self.change_lock.lock();
+
+        let mut state = self.state.write();
+        let banned: Vec<Arc<PeerAddress>> = state.info_by_address.values()
+            .filter(|info| info.state == PeerAddressState::Banned)
+            .filter(|info| target.map(|target| target.matches_peer_address(&info.peer_address)).unwrap_or(true))
+            .map(|info| Arc::clone(&info.peer_address))
+            .collect();
+
+        let mut unbanned_addresses: Vec<PeerAddress> = Vec::new();
+        for peer_address in banned {
+            if let Some(mut info) = state.info_by_address.remove(&peer_address) {
+                info.state = PeerAddressState::New;
+                info.failed_attempts = 0;
+                info.banned_until = None;
+                info.ban_backoff = INITIAL_FAILED_BACKOFF;
+                unbanned_addresses.push(info.peer_address.as_ref().clone());
+                // Banned addresses may have been removed from the indices, add them again.
+                state.add_to_store(info);
+            }
+        }
+
+        // Drop the guard before notifying.
+        drop(state);
+        drop(guard);
+
+        let count = unbanned_addresses.len();
+        if count > 0 {
+            self.notifier.notify(PeerAddressBookEvent::Added(unbanned_addresses));
+        }
+        count
+    }
+
     /// Called when a message has been returned as unroutable.
     pub fn unroutable(&self, channel: Arc<PeerChannel>, peer_address: Arc<PeerAddress>) {
         let _guard = self.change_lock.lock();
@@ -676,7 +892,7 @@ impl PeerAddressBook {
             match info.state {
                 PeerAddressState::New | PeerAddressState::Tried | PeerAddressState::Failed => {
                     // Delete all new peer addresses that are older than MAX_AGE.
-                    if peer_address.exceeds_age() {
+                    if info.exceeds_age() {
                         to_remove_from_store.push(peer_address.clone());
                         continue;
                     }
@@ -745,6 +961,7 @@ impl PeerAddressBook {
     pub fn known_ws_addresses_count(&self) -> usize { self.state.read().ws_addresses.len() }
     pub fn known_wss_addresses_count(&self) -> usize { self.state.read().wss_addresses.len() }
     pub fn known_rtc_addresses_count(&self) -> usize { self.state.read().rtc_addresses.len() }
+    pub fn known_tcp_addresses_count(&self) -> usize { self.state.read().tcp_addresses.len() }
 
     pub fn is_banned(&self, peer_address: &Arc<PeerAddress>) -> bool {
         self.state.read().is_banned(peer_address)
@@ -810,7 +1027,10 @@ pub const MAX_FAILED_BACKOFF: Duration = Duration::from_secs(60 * 10); // 10 min
 const MAX_SIZE_WS: usize = 10000; // TODO different for browser
 const MAX_SIZE_WSS: usize = 10000;
 const MAX_SIZE_RTC: usize = 10000;
+const MAX_SIZE_TCP: usize = 10000;
 const MAX_SIZE: usize = 20500; // Includes dumb peers
 const MAX_SIZE_PER_IP: usize = 250;
 
 const SEEDING_TIMEOUT: Duration = Duration::from_secs(3); // 3 seconds
+const PERSIST_INTERVAL: Duration = Duration::from_secs(60 * 10); // 10 minutes
+const STORE_MAX_AGE: Duration = Duration::from_secs(60 * 60 * 24 * 3); // 3 days
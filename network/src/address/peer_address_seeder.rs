@@ -6,13 +6,18 @@ use hex::FromHex;
 use failure::Fail;
 use futures::{future::*, Future, Stream};
 use parking_lot::Mutex;
+use reqwest::Proxy;
 use reqwest::r#async::{Chunk, Client, Response};
 use url::Url;
 
 use keys::Signature;
-use crate::network_config::{NetworkConfig, Seed};
+use crate::dns;
+use crate::error::ProxyConfigError;
+use crate::network_config::{NetworkConfig, ProxyConfig, Seed};
+use network_primitives::address::dns_seed::DnsSeed;
 use network_primitives::address::peer_address::PeerAddress;
 use network_primitives::address::peer_uri::{PeerUri, PeerUriError};
+use network_primitives::address::seed_list::SeedList;
 use network_primitives::networks::{ get_network_info, NetworkId };
 
 use utils::observer::Notifier;
@@ -40,6 +45,8 @@ pub enum PeerAddressSeederError {
     SignatureVerificationFailed,
     #[fail(display = "The remote server responded with status code '{}'", _0)]
     UnexpectedHttpStatus(reqwest::StatusCode),
+    #[fail(display = "{}", _0)]
+    ProxyConfigError(#[cause] ProxyConfigError),
 }
 
 impl From<reqwest::Error> for PeerAddressSeederError {
@@ -48,6 +55,12 @@ impl From<reqwest::Error> for PeerAddressSeederError {
     }
 }
 
+impl From<ProxyConfigError> for PeerAddressSeederError {
+    fn from(error: ProxyConfigError) -> Self {
+        PeerAddressSeederError::ProxyConfigError(error)
+    }
+}
+
 impl From<PeerUriError> for PeerAddressSeederError {
     fn from(error: PeerUriError) -> Self {
         PeerAddressSeederError::PeerUriParsingError(error)
@@ -68,26 +81,27 @@ impl PeerAddressSeeder {
     pub fn collect(&self, network_id: NetworkId, network_config: Arc<NetworkConfig>) {
         let network_info = get_network_info(network_id).expect("This was validated by PeerAddressBook::new()");
 
-        // Get additional seed lists from the config file (in Iterator form)
-        let additional_seedlists = network_config.additional_seeds().iter()
-        .filter_map(|seed| {
-            match seed {
-                Seed::List(seed_list) => Some(&**seed_list),
-                Seed::Peer(_) => None,
-            }
-        });
-
-        // Create a new Iterator chaining the hardcoded seed lists with the seed lists from the config file
+        // Chain the hardcoded seed lists and DNS seeds with the ones from the config file
         // TODO: Optimize this to use references instead of cloning
-        let seed_lists = network_info.seed_lists.iter().chain(additional_seedlists);
+        let (additional_seed_lists, additional_dns_seeds) = Self::remote_seeds(network_config.additional_seeds());
+        let seed_lists = network_info.seed_lists.iter().cloned().chain(additional_seed_lists).collect();
+        let dns_seeds = network_info.dns_seeds.iter().cloned().chain(additional_dns_seeds).collect();
+        self.collect_from(seed_lists, dns_seeds, &network_config);
+
+        // Notify that we're done collecting seed addresses
+        self.notifier.lock().notify(PeerAddressSeederEvent::End);
+    }
 
+    /// Fetches the given seed lists and resolves the given DNS seeds. The seed addresses of each
+    /// of them are notified as they arrive, but no `End` event is sent.
+    pub fn collect_from(&self, seed_lists: Vec<SeedList>, dns_seeds: Vec<DnsSeed>, network_config: &NetworkConfig) {
         // Process all seed lists asynchronously
-        for seed_list in seed_lists.cloned() {
+        for seed_list in seed_lists {
             let notifier = Arc::clone(&self.notifier);
             let seed_list_url = seed_list.url().clone();
 
             trace!("Start processing remote seed list: {}", &seed_list.url());
-            let task = Self::fetch(seed_list.url().clone())
+            let task = Self::fetch(seed_list.url().clone(), network_config.proxy())
             .and_then(move |response_body| {
                 let mut signature = None;
                 let mut seed_addresses = Vec::new();
@@ -153,17 +167,72 @@ impl PeerAddressSeeder {
 
             tokio::spawn(task);
         }
-        // Notify that we're done collecting seed addresses
-        self.notifier.lock().notify(PeerAddressSeederEvent::End);
+
+        // Resolve the DNS seeds
+        if !dns_seeds.is_empty() {
+            self.resolve_dns_seeds(dns_seeds, network_config);
+        }
+    }
+
+    /// Splits the seeds that have to be fetched or resolved into seed lists and DNS seeds.
+    pub fn remote_seeds(seeds: &[Seed]) -> (Vec<SeedList>, Vec<DnsSeed>) {
+        let mut seed_lists = Vec::new();
+        let mut dns_seeds = Vec::new();
+        for seed in seeds {
+            match seed {
+                Seed::List(seed_list) => seed_lists.push((**seed_list).clone()),
+                Seed::Dns(dns_seed) => dns_seeds.push((**dns_seed).clone()),
+                Seed::Peer(_) => (),
+            }
+        }
+        (seed_lists, dns_seeds)
+    }
+
+    // Asynchronously resolves the DNS seeds and notifies the seed addresses of each of them
+    fn resolve_dns_seeds(&self, dns_seeds: Vec<DnsSeed>, network_config: &NetworkConfig) {
+        // Don't leak DNS queries if the proxy is supposed to resolve host names
+        if network_config.proxy().map(|proxy| proxy.remote_dns).unwrap_or(false) {
+            info!("Skipping DNS seeds, since host names are resolved by the proxy");
+            return;
+        }
+
+        let resolver = match network_config.dns_resolver().or_else(dns::system_resolver) {
+            Some(resolver) => resolver,
+            None => {
+                warn!("Skipping DNS seeds, since no DNS resolver is configured");
+                return;
+            },
+        };
+
+        for dns_seed in dns_seeds {
+            let notifier = Arc::clone(&self.notifier);
+            let host = dns_seed.host().clone();
+
+            trace!("Start resolving DNS seed: {}", host);
+            let task = dns::resolve_seed(resolver, &dns_seed)
+            .map(move |seed_addresses| notifier.lock().notify(PeerAddressSeederEvent::Seeds(seed_addresses)))
+            .map_err(move |err| warn!("Failed to resolve DNS seed {}: {}", host, err));
+
+            tokio::spawn(task);
+        }
     }
 
     // Asynchronously fetches a seed list from a remote location
-    fn fetch(url: Url) -> impl Future<Item=Chunk, Error=PeerAddressSeederError> {
-        Client::new().get(url).send()
-        .map_err(PeerAddressSeederError::from)
+    fn fetch(url: Url, proxy: Option<&ProxyConfig>) -> impl Future<Item=Chunk, Error=PeerAddressSeederError> {
+        result(Self::client(proxy))
+        .and_then(move |client| client.get(url).send().map_err(PeerAddressSeederError::from))
         .and_then(Self::fetch_callback)
     }
 
+    // Creates the HTTP client, which sends its requests through the SOCKS5 proxy if one is configured
+    fn client(proxy: Option<&ProxyConfig>) -> Result<Client, PeerAddressSeederError> {
+        let mut builder = Client::builder();
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(Proxy::all(proxy.url()?)?);
+        }
+        Ok(builder.build()?)
+    }
+
     // Note: this is a standalone function to help the compiler because as a closure in the fetch() function
     // it would fail to infer the types correctly
     fn fetch_callback(res: Response) -> Box<dyn Future<Item=Chunk, Error=PeerAddressSeederError> + Send> {
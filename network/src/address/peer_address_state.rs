@@ -23,6 +23,9 @@ pub struct PeerAddressInfo {
 
     pub close_types: HashMap<CloseType, usize>,
     pub added_by: HashSet<Arc<NetAddress>>,
+
+    /// Whether this address was restored from the database and hasn't been tried since.
+    pub restored: bool,
 }
 
 impl PeerAddressInfo {
@@ -37,13 +40,20 @@ impl PeerAddressInfo {
             ban_backoff: super::peer_address_book::INITIAL_FAILED_BACKOFF,
             close_types: HashMap::new(),
             added_by: HashSet::new(),
+            restored: false,
         }
     }
 
+    /// The signed timestamp of restored addresses is usually outdated, so they don't expire
+    /// until we tried to connect to them.
+    pub fn exceeds_age(&self) -> bool {
+        !self.restored && self.peer_address.exceeds_age()
+    }
+
     pub fn max_failed_attempts(&self) -> u32 {
         match self.peer_address.protocol() {
             Protocol::Rtc => super::peer_address_book::MAX_FAILED_ATTEMPTS_RTC,
-            Protocol::Ws | Protocol::Wss => super::peer_address_book::MAX_FAILED_ATTEMPTS_WS,
+            Protocol::Ws | Protocol::Wss | Protocol::Tcp => super::peer_address_book::MAX_FAILED_ATTEMPTS_WS,
             _ => 0
         }
     }
@@ -51,6 +61,7 @@ impl PeerAddressInfo {
     pub fn close(&mut self, ty: CloseType) {
         *self.close_types.entry(ty)
             .or_insert(0) += 1;
+        self.restored = false;
 
         if self.state == PeerAddressState::Banned {
             return;
@@ -0,0 +1,240 @@
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use beserial::{Deserialize, Serialize};
+use database::{Database, Environment, FromDatabaseValue, IntoDatabaseValue, ReadTransaction, WriteTransaction};
+use keys::{PublicKey, Signature};
+use network_primitives::address::net_address::NetAddress;
+use network_primitives::address::peer_address::{PeerAddress, PeerAddressType};
+use network_primitives::address::PeerId;
+use network_primitives::protocol::Protocol;
+use network_primitives::services::ServiceFlags;
+use utils::time::{systemtime_to_timestamp, timestamp_to_systemtime};
+
+use super::peer_address_state::{PeerAddressInfo, PeerAddressState};
+
+/// Persists known peer addresses and their state in the database, so that the address book
+/// doesn't need to be seeded from scratch after a restart.
+#[derive(Debug)]
+pub struct PeerAddressStore {
+    env: &'static Environment,
+    db: Database<'static>,
+}
+
+impl PeerAddressStore {
+    const DB_NAME: &'static str = "PeerAddresses";
+
+    pub fn new(env: &'static Environment) -> Self {
+        let db = env.open_database(Self::DB_NAME.to_string());
+        PeerAddressStore { env, db }
+    }
+
+    /// Returns all stored peer addresses that have been seen within `max_age`.
+    /// Expired entries are removed from the database.
+    pub fn load(&self, max_age: Duration) -> Vec<PeerAddressInfo> {
+        let mut infos = Vec::new();
+        let mut expired = Vec::new();
+        {
+            let txn = ReadTransaction::new(self.env);
+            let mut cursor = txn.cursor(&self.db);
+            let mut entry: Option<(String, StoredPeerAddress)> = cursor.first();
+            while let Some((key, stored)) = entry {
+                match stored.into_info(max_age) {
+                    Some(info) => infos.push(info),
+                    None => expired.push(key),
+                }
+                entry = cursor.next();
+            }
+        }
+
+        if !expired.is_empty() {
+            debug!("Removing {} expired peer addresses from the database", expired.len());
+            let mut txn = WriteTransaction::new(self.env);
+            for key in expired {
+                txn.remove(&self.db, key.as_str());
+            }
+            txn.commit();
+        }
+
+        infos
+    }
+
+    /// Replaces the stored peer addresses with the given ones.
+    pub fn store<'a, I>(&self, infos: I) where I: Iterator<Item=&'a PeerAddressInfo> {
+        let mut txn = WriteTransaction::new(self.env);
+
+        // Remove all entries first, so that addresses that were removed from the book are dropped.
+        let keys: Vec<String> = {
+            let mut keys = Vec::new();
+            let mut cursor = txn.cursor(&self.db);
+            let mut entry: Option<(String, StoredPeerAddress)> = cursor.first();
+            while let Some((key, _)) = entry {
+                keys.push(key);
+                entry = cursor.next();
+            }
+            keys
+        };
+        for key in keys {
+            txn.remove(&self.db, key.as_str());
+        }
+
+        for info in infos {
+            if let Some(stored) = StoredPeerAddress::from_info(info) {
+                txn.put_reserve(&self.db, Self::key(&info.peer_address).as_str(), &stored);
+            }
+        }
+        txn.commit();
+    }
+
+    /// Addresses are identified like in the address book, by their protocol, public key and
+    /// peer ID. The same peer can be stored once per protocol.
+    fn key(peer_address: &PeerAddress) -> String {
+        format!("{}/{}/{}", peer_address.protocol() as u8, peer_address.public_key.to_hex(), peer_address.peer_id.to_hex())
+    }
+}
+
+/// A peer address with its state. The fields of the address are stored separately, since seeds
+/// are unsigned and can't be encoded as a `PeerAddress`. It is public for the fuzzing targets of
+/// the decoder.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredPeerAddress {
+    ty: PeerAddressType,
+    services: ServiceFlags,
+    timestamp: u64,
+    net_address: NetAddress,
+    public_key: PublicKey,
+    distance: u8,
+    signature: Option<Signature>,
+    state: u8,
+    failed_attempts: u32,
+    /// Ban backoff in milliseconds.
+    ban_backoff: u64,
+    /// End of the ban as unix timestamp in milliseconds, 0 if not banned.
+    banned_until: u64,
+    /// Last established connection as unix timestamp in milliseconds, 0 if never connected.
+    last_connected: u64,
+    /// Last time the peer was known to be alive as unix timestamp in milliseconds.
+    last_seen: u64,
+}
+
+impl StoredPeerAddress {
+    pub fn from_info(info: &PeerAddressInfo) -> Option<Self> {
+        // Seed addresses are added on every start anyway, but the keys pinned for keyless seeds
+        // have to be kept. Other addresses must be signed. Only websocket and TCP addresses can be
+        // connected to again, RTC addresses depend on signaling routes that are gone.
+        if info.peer_address.is_seed() {
+            if !info.peer_address.has_public_key() {
+                return None;
+            }
+        } else if info.peer_address.signature.is_none() {
+            return None;
+        }
+        match info.peer_address.protocol() {
+            Protocol::Ws | Protocol::Wss | Protocol::Tcp => {},
+            _ => return None,
+        }
+
+        let now = Instant::now();
+        let banned_until = match info.banned_until {
+            Some(banned_until) if banned_until > now => systemtime_to_timestamp(SystemTime::now() + (banned_until - now)),
+            _ => 0,
+        };
+
+        let state = match info.state {
+            // Expired bans are removed by housekeeping, don't restore them.
+            PeerAddressState::Banned if banned_until == 0 => return None,
+            // There is no connection after a restart.
+            PeerAddressState::Established => PeerAddressState::Tried,
+            state => state,
+        };
+
+        // Seeds don't expire, as they are only restored while they are configured.
+        let peer_address = info.peer_address.as_ref().clone();
+        let last_connected = info.last_connected.map(systemtime_to_timestamp).unwrap_or(0);
+        let last_seen = if peer_address.is_seed() {
+            systemtime_to_timestamp(SystemTime::now())
+        } else {
+            peer_address.timestamp.max(last_connected)
+        };
+        Some(StoredPeerAddress {
+            ty: peer_address.ty,
+            services: peer_address.services,
+            timestamp: peer_address.timestamp,
+            net_address: peer_address.net_address,
+            public_key: peer_address.public_key,
+            distance: peer_address.distance,
+            signature: peer_address.signature,
+            state: state as u8,
+            failed_attempts: info.failed_attempts,
+            ban_backoff: info.ban_backoff.as_secs() * 1000 + u64::from(info.ban_backoff.subsec_millis()),
+            banned_until,
+            last_connected,
+            last_seen,
+        })
+    }
+
+    fn into_info(self, max_age: Duration) -> Option<PeerAddressInfo> {
+        let now = SystemTime::now();
+        let last_seen = timestamp_to_systemtime(self.last_seen);
+        if now.duration_since(last_seen).map(|age| age > max_age).unwrap_or(false) {
+            return None;
+        }
+
+        let state = match self.state {
+            1 => PeerAddressState::New,
+            3 => PeerAddressState::Tried,
+            4 => PeerAddressState::Failed,
+            5 => PeerAddressState::Banned,
+            _ => return None,
+        };
+
+        let banned_until = if self.banned_until > 0 {
+            match timestamp_to_systemtime(self.banned_until).duration_since(now) {
+                Ok(remaining) => Some(Instant::now() + remaining),
+                // The ban has expired while we were offline.
+                Err(_) if state == PeerAddressState::Banned => return None,
+                Err(_) => Some(Instant::now()),
+            }
+        } else {
+            None
+        };
+
+        let peer_address = PeerAddress {
+            ty: self.ty,
+            services: self.services,
+            timestamp: self.timestamp,
+            net_address: self.net_address,
+            peer_id: PeerId::from(&self.public_key),
+            public_key: self.public_key,
+            distance: self.distance,
+            signature: self.signature,
+        };
+
+        let mut info = PeerAddressInfo::new(Arc::new(peer_address));
+        info.state = state;
+        info.failed_attempts = self.failed_attempts;
+        info.ban_backoff = Duration::from_millis(self.ban_backoff);
+        info.banned_until = banned_until;
+        info.last_connected = if self.last_connected > 0 { Some(timestamp_to_systemtime(self.last_connected)) } else { None };
+        info.restored = true;
+        Some(info)
+    }
+}
+
+impl FromDatabaseValue for StoredPeerAddress {
+    fn copy_from_database(bytes: &[u8]) -> io::Result<Self> where Self: Sized {
+        let mut cursor = io::Cursor::new(bytes);
+        Ok(Deserialize::deserialize(&mut cursor)?)
+    }
+}
+
+impl IntoDatabaseValue for StoredPeerAddress {
+    fn database_byte_size(&self) -> usize {
+        self.serialized_size()
+    }
+
+    fn copy_into_database(&self, mut bytes: &mut [u8]) {
+        Serialize::serialize(&self, &mut bytes).unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use failure::Fail;
+use parking_lot::RwLock;
+
+use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+use database::{Database, Environment, FromDatabaseValue, IntoDatabaseValue, ReadTransaction, WriteTransaction};
+use network_primitives::address::net_address::{NetAddress, NetAddressType};
+use network_primitives::address::peer_address::PeerAddress;
+use network_primitives::address::PeerId;
+use utils::time::{systemtime_to_timestamp, timestamp_to_systemtime};
+
+use crate::connection::close_type::CloseType;
+
+/// What a ban applies to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BanTarget {
+    PeerId(PeerId),
+    Ip(NetAddress),
+    /// A subnet, given by its masked address and the number of prefix bits.
+    Subnet(NetAddress, u8),
+}
+
+impl BanTarget {
+    /// Creates a subnet target, masking the address to the prefix length.
+    pub fn subnet(net_address: &NetAddress, bit_count: u8) -> Self {
+        BanTarget::Subnet(net_address.subnet(bit_count), bit_count)
+    }
+
+    /// Whether connections from the IP address are covered by this ban.
+    pub fn matches_ip(&self, net_address: &NetAddress) -> bool {
+        match self {
+            BanTarget::PeerId(_) => false,
+            BanTarget::Ip(ip) => ip == net_address,
+            BanTarget::Subnet(subnet, bit_count) => {
+                subnet.get_type() == net_address.get_type() && *subnet == net_address.subnet(*bit_count)
+            },
+        }
+    }
+
+    /// Whether the peer ID or the IP address of the peer address are covered by this ban.
+    pub fn matches_peer_address(&self, peer_address: &PeerAddress) -> bool {
+        match self {
+            BanTarget::PeerId(peer_id) => *peer_id == peer_address.peer_id,
+            _ => self.matches_ip(&peer_address.net_address),
+        }
+    }
+}
+
+impl fmt::Display for BanTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BanTarget::PeerId(peer_id) => write!(f, "{}", peer_id),
+            BanTarget::Ip(ip) => write!(f, "{}", ip),
+            BanTarget::Subnet(subnet, bit_count) => write!(f, "{}/{}", subnet, bit_count),
+        }
+    }
+}
+
+#[derive(Debug, Fail)]
+#[fail(display = "Expected a peer ID, an IP address or a subnet in CIDR notation")]
+pub struct BanTargetParseError;
+
+impl FromStr for BanTarget {
+    type Err = BanTargetParseError;
+
+    /// Parses a peer ID in hex, an IP address or a subnet like `192.168.0.0/16`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(pos) = s.find('/') {
+            let net_address = NetAddress::from_str(&s[..pos]).map_err(|_| BanTargetParseError)?;
+            let bit_count = u8::from_str(&s[pos + 1..]).map_err(|_| BanTargetParseError)?;
+            let max_bit_count = if net_address.get_type() == NetAddressType::IPv4 { 32 } else { 128 };
+            if bit_count > max_bit_count {
+                return Err(BanTargetParseError);
+            }
+            return Ok(BanTarget::subnet(&net_address, bit_count));
+        }
+        if let Ok(net_address) = NetAddress::from_str(s) {
+            return Ok(BanTarget::Ip(net_address));
+        }
+        PeerId::from_str(s).map(BanTarget::PeerId).map_err(|_| BanTargetParseError)
+    }
+}
+
+impl Serialize for BanTarget {
+    fn serialize<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, SerializingError> {
+        let mut size = 0;
+        match self {
+            BanTarget::PeerId(peer_id) => {
+                size += Serialize::serialize(&0u8, writer)?;
+                size += Serialize::serialize(peer_id, writer)?;
+            },
+            BanTarget::Ip(ip) => {
+                size += Serialize::serialize(&1u8, writer)?;
+                size += Serialize::serialize(ip, writer)?;
+            },
+            BanTarget::Subnet(subnet, bit_count) => {
+                size += Serialize::serialize(&2u8, writer)?;
+                size += Serialize::serialize(subnet, writer)?;
+                size += Serialize::serialize(bit_count, writer)?;
+            },
+        }
+        Ok(size)
+    }
+
+    fn serialized_size(&self) -> usize {
+        1 + match self {
+            BanTarget::PeerId(peer_id) => peer_id.serialized_size(),
+            BanTarget::Ip(ip) => ip.serialized_size(),
+            BanTarget::Subnet(subnet, _) => subnet.serialized_size() + 1,
+        }
+    }
+}
+
+impl Deserialize for BanTarget {
+    fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
+        let ty: u8 = Deserialize::deserialize(reader)?;
+        match ty {
+            0 => Ok(BanTarget::PeerId(Deserialize::deserialize(reader)?)),
+            1 => Ok(BanTarget::Ip(Deserialize::deserialize(reader)?)),
+            2 => Ok(BanTarget::Subnet(Deserialize::deserialize(reader)?, Deserialize::deserialize(reader)?)),
+            _ => Err(SerializingError::InvalidValue),
+        }
+    }
+}
+
+/// A single ban along with why it was issued.
+#[derive(Clone, Debug)]
+pub struct BanEntry {
+    pub target: BanTarget,
+    /// End of the ban. The ban is permanent if this is not set.
+    pub banned_until: Option<SystemTime>,
+    pub reason: String,
+    /// The close type of the connection that caused the ban, if it wasn't issued manually.
+    pub close_type: Option<CloseType>,
+}
+
+impl BanEntry {
+    /// Creates a ban of `target` for `duration`, or a permanent ban if no duration is given.
+    /// Returns `None` if the ban would end too far in the future to be stored.
+    pub fn new(target: BanTarget, duration: Option<Duration>, reason: String, close_type: Option<CloseType>) -> Option<Self> {
+        let banned_until = match duration {
+            Some(duration) => {
+                let banned_until = SystemTime::now().checked_add(duration)?;
+                // Bans are stored with a timestamp in milliseconds.
+                if banned_until.duration_since(UNIX_EPOCH).ok()?.as_millis() > u128::from(u64::MAX) {
+                    return None;
+                }
+                Some(banned_until)
+            },
+            None => None,
+        };
+        Some(BanEntry {
+            target,
+            banned_until,
+            reason,
+            close_type,
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.banned_until.map(|banned_until| banned_until <= SystemTime::now()).unwrap_or(false)
+    }
+}
+
+/// Bans of peer IDs, IP addresses and subnets that survive restarts.
+///
+/// Every change is written to the database right away, bans are rare enough for that.
+pub struct BanList {
+    env: &'static Environment,
+    db: Database<'static>,
+    bans: RwLock<HashMap<BanTarget, BanEntry>>,
+}
+
+impl BanList {
+    const DB_NAME: &'static str = "BanList";
+
+    /// Opens the ban list and restores the bans that haven't expired yet.
+    pub fn new(env: &'static Environment) -> Self {
+        let db = env.open_database(Self::DB_NAME.to_string());
+        let this = BanList { env, db, bans: RwLock::new(HashMap::new()) };
+
+        let mut bans = HashMap::new();
+        {
+            let txn = ReadTransaction::new(this.env);
+            let mut cursor = txn.cursor(&this.db);
+            let mut entry: Option<(String, StoredBan)> = cursor.first();
+            while let Some((_, stored)) = entry {
+                let ban = stored.into_entry();
+                bans.insert(ban.target.clone(), ban);
+                entry = cursor.next();
+            }
+        }
+        *this.bans.write() = bans;
+        this.remove_expired();
+        this
+    }
+
+    /// Adds a ban, replacing an existing ban of the same target.
+    pub fn ban(&self, entry: BanEntry) {
+        info!("Banning {} ({})", entry.target, entry.reason);
+        let mut txn = WriteTransaction::new(self.env);
+        txn.put_reserve(&self.db, entry.target.to_string().as_str(), &StoredBan::from_entry(&entry));
+        txn.commit();
+        self.bans.write().insert(entry.target.clone(), entry);
+    }
+
+    /// Lifts a ban. Returns whether the target was banned.
+    pub fn unban(&self, target: &BanTarget) -> bool {
+        let removed = self.bans.write().remove(target).is_some();
+        if removed {
+            let mut txn = WriteTransaction::new(self.env);
+            txn.remove(&self.db, target.to_string().as_str());
+            txn.commit();
+        }
+        removed
+    }
+
+    /// Lifts all bans.
+    pub fn clear(&self) {
+        let targets: Vec<BanTarget> = self.bans.write().drain().map(|(target, _)| target).collect();
+        let mut txn = WriteTransaction::new(self.env);
+        for target in targets {
+            txn.remove(&self.db, target.to_string().as_str());
+        }
+        txn.commit();
+    }
+
+    /// Returns all bans that haven't expired yet.
+    pub fn bans(&self) -> Vec<BanEntry> {
+        self.bans.read().values()
+            .filter(|ban| !ban.is_expired())
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, target: &BanTarget) -> Option<BanEntry> {
+        self.bans.read().get(target)
+            .filter(|ban| !ban.is_expired())
+            .cloned()
+    }
+
+    pub fn is_peer_banned(&self, peer_id: &PeerId) -> bool {
+        self.get(&BanTarget::PeerId(peer_id.clone())).is_some()
+    }
+
+    /// Checks whether the IP address or any subnet it belongs to is banned.
+    pub fn is_ip_banned(&self, net_address: &NetAddress) -> bool {
+        if net_address.is_pseudo() {
+            return false;
+        }
+        self.bans.read().values()
+            .any(|ban| ban.target.matches_ip(net_address) && !ban.is_expired())
+    }
+
+    /// Removes expired bans.
+    pub fn remove_expired(&self) {
+        let expired: Vec<BanTarget> = self.bans.read().values()
+            .filter(|ban| ban.is_expired())
+            .map(|ban| ban.target.clone())
+            .collect();
+        for target in expired {
+            debug!("Ban of {} expired", target);
+            self.unban(&target);
+        }
+    }
+}
+
+/// A ban as stored in the database. It is public for the fuzzing targets of the decoder.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredBan {
+    target: BanTarget,
+    /// End of the ban as unix timestamp in milliseconds, 0 if the ban is permanent.
+    banned_until: u64,
+    #[beserial(len_type(u16))]
+    reason: String,
+    /// The close type as `u16`, 0 if the ban was issued manually.
+    close_type: u16,
+}
+
+impl StoredBan {
+    pub fn from_entry(entry: &BanEntry) -> Self {
+        StoredBan {
+            target: entry.target.clone(),
+            banned_until: entry.banned_until.map(systemtime_to_timestamp).unwrap_or(0),
+            reason: entry.reason.clone(),
+            close_type: entry.close_type.map(|ty| ty as u16).unwrap_or(0),
+        }
+    }
+
+    fn into_entry(self) -> BanEntry {
+        BanEntry {
+            target: self.target,
+            banned_until: if self.banned_until > 0 { Some(timestamp_to_systemtime(self.banned_until)) } else { None },
+            reason: self.reason,
+            close_type: if self.close_type > 0 {
+                Deserialize::deserialize_from_vec(&self.close_type.to_be_bytes().to_vec()).ok()
+            } else {
+                None
+            },
+        }
+    }
+}
+
+impl FromDatabaseValue for StoredBan {
+    fn copy_from_database(bytes: &[u8]) -> io::Result<Self> where Self: Sized {
+        let mut cursor = io::Cursor::new(bytes);
+        Ok(Deserialize::deserialize(&mut cursor)?)
+    }
+}
+
+impl IntoDatabaseValue for StoredBan {
+    fn database_byte_size(&self) -> usize {
+        self.serialized_size()
+    }
+
+    fn copy_into_database(&self, mut bytes: &mut [u8]) {
+        Serialize::serialize(&self, &mut bytes).unwrap();
+    }
+}
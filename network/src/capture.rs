@@ -0,0 +1,319 @@
+//! Capture of the messages exchanged with peers, e.g. as evidence when peers misbehave.
+//!
+//! A capture file starts with a `CaptureHeader`, followed by `CaptureRecord`s. Every record is
+//! prefixed with its length as u32, so that records which can't be decoded can be skipped and a
+//! record cut off at the end of the file can be told apart from a corrupt one.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+
+use failure::Fail;
+use parking_lot::Mutex;
+
+use beserial::{Deserialize, Serialize, SerializingError};
+use network_messages::Message;
+use network_primitives::address::net_address::NetAddress;
+use network_primitives::address::PeerId;
+use utils::time::systemtime_to_timestamp;
+use utils::unique_id::UniqueId;
+
+use crate::connection::network_connection::AddressInfo;
+use crate::websocket::stream::MAX_MESSAGE_SIZE;
+
+#[derive(Debug, Fail)]
+pub enum CaptureError {
+    #[fail(display = "{}", _0)]
+    IoError(#[cause] io::Error),
+    #[fail(display = "Invalid record: {}", _0)]
+    InvalidRecord(#[cause] SerializingError),
+    #[fail(display = "Not a capture file or unsupported version")]
+    InvalidHeader,
+    #[fail(display = "Record exceeds the maximum size ({} bytes)", _0)]
+    RecordTooLarge(usize),
+    #[fail(display = "A capture is already running")]
+    AlreadyActive,
+    #[fail(display = "No capture directory configured")]
+    NoCaptureDirectory,
+    #[fail(display = "Invalid capture file name: {}", _0)]
+    InvalidFileName(String),
+}
+
+impl From<io::Error> for CaptureError {
+    fn from(e: io::Error) -> Self {
+        CaptureError::IoError(e)
+    }
+}
+
+impl From<SerializingError> for CaptureError {
+    fn from(e: SerializingError) -> Self {
+        CaptureError::InvalidRecord(e)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum Direction {
+    /// Received from the peer.
+    Inbound = 0,
+    /// Sent to the peer.
+    Outbound = 1,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureHeader {
+    pub magic: u32,
+    pub version: u8,
+}
+
+impl CaptureHeader {
+    /// "NQCP"
+    pub const MAGIC: u32 = 0x4e51_4350;
+    pub const VERSION: u8 = 1;
+}
+
+impl Default for CaptureHeader {
+    fn default() -> Self {
+        CaptureHeader {
+            magic: Self::MAGIC,
+            version: Self::VERSION,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    /// Unix timestamp in milliseconds.
+    pub timestamp: u64,
+    pub direction: Direction,
+    /// Tells apart the connections within a capture.
+    pub connection_id: u64,
+    pub net_address: NetAddress,
+    /// The peer ID isn't known before the peer sent its version message.
+    pub peer_id: Option<PeerId>,
+    pub message: Message,
+}
+
+/// Writes capture files.
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+    num_records: u64,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Writes the header and returns the writer.
+    pub fn new(mut writer: W) -> Result<Self, CaptureError> {
+        CaptureHeader::default().serialize(&mut writer)?;
+        Ok(CaptureWriter {
+            writer,
+            num_records: 0,
+        })
+    }
+
+    pub fn write(&mut self, record: &CaptureRecord) -> Result<(), CaptureError> {
+        self.write_parts(record.timestamp, record.direction, record.connection_id, &record.net_address, &record.peer_id, &record.message)
+    }
+
+    /// Writes a record without taking ownership of the message. Uses the layout of `CaptureRecord`.
+    fn write_parts(&mut self, timestamp: u64, direction: Direction, connection_id: u64, net_address: &NetAddress, peer_id: &Option<PeerId>, message: &Message) -> Result<(), CaptureError> {
+        let mut buffer = Vec::with_capacity(64 + message.serialized_size());
+        timestamp.serialize(&mut buffer)?;
+        direction.serialize(&mut buffer)?;
+        connection_id.serialize(&mut buffer)?;
+        net_address.serialize(&mut buffer)?;
+        peer_id.serialize(&mut buffer)?;
+        message.serialize(&mut buffer)?;
+
+        (buffer.len() as u32).serialize(&mut self.writer)?;
+        self.writer.write_all(&buffer)?;
+        self.num_records += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), CaptureError> {
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn num_records(&self) -> u64 {
+        self.num_records
+    }
+}
+
+/// Reads capture files.
+pub struct CaptureReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// The maximum size of a record: The largest message we accept plus the fields of the record.
+    pub const RECORD_SIZE_MAX: usize = MAX_MESSAGE_SIZE + 64;
+
+    /// Reads and checks the header.
+    pub fn new(mut reader: R) -> Result<Self, CaptureError> {
+        let header: CaptureHeader = Deserialize::deserialize(&mut reader)
+            .map_err(|_| CaptureError::InvalidHeader)?;
+        if header != CaptureHeader::default() {
+            return Err(CaptureError::InvalidHeader);
+        }
+        Ok(CaptureReader { reader })
+    }
+
+    /// Reads the next record. Returns `None` at the end of the capture, including a record that
+    /// was cut off while it was written. A record that can't be decoded or exceeds
+    /// `RECORD_SIZE_MAX` is skipped, so reading can continue after an `InvalidRecord` or
+    /// `RecordTooLarge` error.
+    pub fn read_record(&mut self) -> Result<Option<CaptureRecord>, CaptureError> {
+        let mut length = [0u8; 4];
+        if let Some(()) = self.read_exact_or_eof(&mut length)? {
+            let length = u32::from_be_bytes(length) as usize;
+            if length > Self::RECORD_SIZE_MAX {
+                io::copy(&mut (&mut self.reader).take(length as u64), &mut io::sink())?;
+                return Err(CaptureError::RecordTooLarge(length));
+            }
+            let mut buffer = vec![0u8; length];
+            if self.read_exact_or_eof(&mut buffer)?.is_none() {
+                return Ok(None);
+            }
+            return Ok(Some(Deserialize::deserialize_from_vec(&buffer)?));
+        }
+        Ok(None)
+    }
+
+    fn read_exact_or_eof(&mut self, buffer: &mut [u8]) -> Result<Option<()>, CaptureError> {
+        match self.reader.read_exact(buffer) {
+            Ok(()) => Ok(Some(())),
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<CaptureRecord, CaptureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Describes the running capture.
+#[derive(Clone, Debug)]
+pub struct CaptureStatus {
+    pub path: PathBuf,
+    pub started: SystemTime,
+    pub num_records: u64,
+}
+
+struct ActiveCapture {
+    path: PathBuf,
+    started: SystemTime,
+    writer: CaptureWriter<BufWriter<File>>,
+}
+
+impl ActiveCapture {
+    fn status(&self) -> CaptureStatus {
+        CaptureStatus {
+            path: self.path.clone(),
+            started: self.started,
+            num_records: self.writer.num_records(),
+        }
+    }
+}
+
+/// Records the traffic of all connections of a node while a capture is running.
+pub struct TrafficCapture {
+    directory: Option<PathBuf>,
+    active: AtomicBool,
+    capture: Mutex<Option<ActiveCapture>>,
+}
+
+impl TrafficCapture {
+    /// Creates a traffic capture that writes its files to `directory`. Captures can't be
+    /// started without a directory.
+    pub fn new(directory: Option<PathBuf>) -> Self {
+        TrafficCapture {
+            directory,
+            active: AtomicBool::new(false),
+            capture: Mutex::new(None),
+        }
+    }
+
+    /// Starts writing all messages to a new capture file in the capture directory. `file_name`
+    /// must be a plain file name and the file must not exist yet.
+    pub fn start(&self, file_name: &str) -> Result<(), CaptureError> {
+        let directory = self.directory.as_ref().ok_or(CaptureError::NoCaptureDirectory)?;
+        if file_name.is_empty() || file_name == "." || file_name == ".." || file_name.contains(|c| c == '/' || c == '\\') {
+            return Err(CaptureError::InvalidFileName(file_name.to_string()));
+        }
+        let path = directory.join(file_name);
+
+        let mut capture = self.capture.lock();
+        if capture.is_some() {
+            return Err(CaptureError::AlreadyActive);
+        }
+
+        let file = OpenOptions::new().write(true).create_new(true).open(&path)?;
+        let writer = CaptureWriter::new(BufWriter::new(file))?;
+        info!("Started traffic capture to {}", path.display());
+        *capture = Some(ActiveCapture {
+            path,
+            started: SystemTime::now(),
+            writer,
+        });
+        self.active.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Stops the running capture and returns its final status.
+    pub fn stop(&self) -> Option<CaptureStatus> {
+        let mut capture = self.capture.lock();
+        self.active.store(false, Ordering::Release);
+        let mut active_capture = capture.take()?;
+        if let Err(e) = active_capture.writer.flush() {
+            warn!("Failed to flush traffic capture: {}", e);
+        }
+        info!("Stopped traffic capture to {} after {} messages", active_capture.path.display(), active_capture.writer.num_records());
+        Some(active_capture.status())
+    }
+
+    pub fn status(&self) -> Option<CaptureStatus> {
+        self.capture.lock().as_ref().map(ActiveCapture::status)
+    }
+
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Acquire)
+    }
+
+    /// Records a message of the given connection if a capture is running. The addresses are read
+    /// from `address_info` now, so that the peer ID is recorded once the peer sent its version.
+    pub fn record(&self, direction: Direction, connection_id: UniqueId, address_info: &AddressInfo, message: &Message) {
+        if !self.is_active() {
+            return;
+        }
+
+        let net_address = address_info.net_address().map_or(NetAddress::Unknown, |net_address| *net_address);
+        let peer_id = address_info.peer_address().map(|peer_address| peer_address.peer_id.clone());
+        let timestamp = systemtime_to_timestamp(SystemTime::now());
+
+        let mut capture = self.capture.lock();
+        let result = match capture.as_mut() {
+            Some(active_capture) => active_capture.writer.write_parts(timestamp, direction, usize::from(connection_id) as u64, &net_address, &peer_id, message),
+            None => return,
+        };
+        if let Err(e) = result {
+            warn!("Stopping traffic capture, failed to write: {}", e);
+            drop(capture);
+            self.stop();
+        }
+    }
+}
+
+impl Default for TrafficCapture {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
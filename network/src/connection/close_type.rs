@@ -40,6 +40,7 @@ pub enum CloseType {
     PeerConnectionRecycled = 36,
     PeerConnectionRecycledInboundExchange = 37,
     InboundConnectionsBlocked = 38,
+    PeerNotAllowed = 39,
 
     InvalidConnectionState = 40,
 
@@ -67,6 +68,7 @@ pub enum CloseType {
     BannedIp = 116,
 
     RateLimitExceeded = 120,
+    MessageSizeExceeded = 121,
 
     ManualPeerBan = 190,
 
@@ -83,6 +85,7 @@ pub enum CloseType {
     ConnectionLimitPerIp = 208,
     ChannelClosing = 209,
     ConnectionLimitDumb = 210,
+    SendQueueFull = 211,
 
     ManualPeerFail = 290,
 }
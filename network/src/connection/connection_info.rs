@@ -75,6 +75,7 @@ impl ConnectionInfo {
     pub fn connection_handle(&self) -> Option<&Arc<ConnectionHandle>> { self.connection_handle.as_ref() }
     pub fn age_established(&self) -> Duration { self.established_since.expect("No peer has been set yet").elapsed() }
     pub fn statistics(&self) -> &ConnectionStatistics { &self.statistics }
+    pub fn statistics_mut(&mut self) -> &mut ConnectionStatistics { &mut self.statistics }
 
     pub fn set_peer_address(&mut self, peer_address: Arc<PeerAddress>) { self.peer_address = Some(peer_address) }
     pub fn set_network_connection(&mut self, network_connection: NetworkConnection) {
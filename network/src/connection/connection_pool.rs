@@ -3,13 +3,14 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::LinkedList;
 use std::sync::{Arc, Weak};
-use std::time::{Duration, SystemTime};
+use std::time::Duration;
 
 use parking_lot::{ReentrantMutex, RwLock, RwLockReadGuard};
 
 use blockchain::Blockchain;
 use network_primitives::address::net_address::{NetAddress, NetAddressType};
 use network_primitives::address::peer_address::PeerAddress;
+use network_primitives::address::PeerId;
 use network_primitives::protocol::Protocol;
 use network_messages::SignalMessage;
 use utils::mutable_once::MutableOnce;
@@ -18,6 +19,8 @@ use utils::timers::Timers;
 use utils::unique_ptr::UniquePtr;
 
 use crate::address::peer_address_book::PeerAddressBook;
+use crate::ban_list::{BanEntry, BanList, BanTarget};
+use crate::capture::TrafficCapture;
 use crate::connection::{
     NetworkConnection,
     network_agent::{NetworkAgent, NetworkAgentEvent},
@@ -60,6 +63,7 @@ pub struct ConnectionPoolState {
 
     pub peer_count_ws: usize,
     pub peer_count_wss: usize,
+    pub peer_count_tcp: usize,
     peer_count_rtc: usize,
     peer_count_dumb: usize,
 
@@ -76,8 +80,6 @@ pub struct ConnectionPoolState {
 
     pub allow_inbound_connections: bool,
     pub allow_inbound_exchange: bool,
-
-    banned_ips: HashMap<NetAddress, SystemTime>,
 }
 
 impl ConnectionPoolState {
@@ -166,7 +168,7 @@ impl ConnectionPoolState {
     /// Total peer count.
     #[inline]
     pub fn peer_count(&self) -> usize {
-        self.peer_count_ws + self.peer_count_wss + self.peer_count_rtc + self.peer_count_dumb
+        self.peer_count_ws + self.peer_count_wss + self.peer_count_tcp + self.peer_count_rtc + self.peer_count_dumb
     }
 
     /// Add a new connection to the connection pool.
@@ -277,33 +279,6 @@ impl ConnectionPoolState {
         self.connections_by_peer_address.len() + self.inbound_count
     }
 
-    /// Bans an IP address.
-    fn ban_ip(&mut self, net_address: &NetAddress) {
-        if net_address.is_reliable() {
-            warn!("Banning ip {}", net_address);
-            let banned_address = if net_address.get_type() == NetAddressType::IPv4 {
-                *net_address
-            } else {
-                net_address.subnet(64)
-            };
-            let unban_time = SystemTime::now() + ConnectionPool::DEFAULT_BAN_TIME;
-            self.banned_ips.insert(banned_address, unban_time);
-        }
-    }
-
-    /// Checks whether an IP address is banned.
-    fn is_ip_banned(&self, net_address: &NetAddress) -> bool {
-        !net_address.is_pseudo() && self.banned_ips.contains_key(net_address)
-    }
-
-    /// Called to regularly unban IPs.
-    fn check_unban_ips(&mut self) {
-        let now = SystemTime::now();
-        self.banned_ips.retain(|_net_address, unban_time| {
-            *unban_time > now
-        });
-    }
-
     /// Updates the number of connected peers.
     fn update_connected_peer_count(&mut self, connection: Connection, update: PeerCountUpdate) {
         // We assume the connection to be present and having a valid peer address/network connection.
@@ -317,6 +292,7 @@ impl ConnectionPoolState {
         match peer_address.protocol() {
             Protocol::Wss => update_checked!(self.peer_count_wss, update),
             Protocol::Ws => update_checked!(self.peer_count_ws, update),
+            Protocol::Tcp => update_checked!(self.peer_count_tcp, update),
             Protocol::Rtc => update_checked!(self.peer_count_rtc, update),
             Protocol::Dumb => update_checked!(self.peer_count_dumb, update),
         }
@@ -331,7 +307,7 @@ impl ConnectionPoolState {
 
         if network_connection.outbound() {
             update_checked!(self.peer_count_outbound, update);
-            if peer_address.services.is_full_node() && (peer_address.protocol() == Protocol::Wss || peer_address.protocol() == Protocol::Ws) {
+            if peer_address.services.is_full_node() && peer_address.protocol() != Protocol::Rtc && peer_address.protocol() != Protocol::Dumb {
                 update_checked!(self.peer_count_full_ws_outbound, update);
             }
         }
@@ -365,6 +341,7 @@ pub struct ConnectionPool {
     blockchain: Arc<Blockchain<'static>>,
     network_config: Arc<NetworkConfig>,
     addresses: Arc<PeerAddressBook>,
+    ban_list: Arc<BanList>,
 
     websocket_connector: WebSocketConnector,
 
@@ -383,7 +360,7 @@ impl ConnectionPool {
     const UNBAN_IPS_INTERVAL: Duration = Duration::from_secs(60); // seconds
 
     /// Constructor.
-    pub fn new(peer_address_book: Arc<PeerAddressBook>, network_config: Arc<NetworkConfig>, blockchain: Arc<Blockchain<'static>>) -> Result<Arc<Self>, Error> {
+    pub fn new(peer_address_book: Arc<PeerAddressBook>, ban_list: Arc<BanList>, traffic_capture: Arc<TrafficCapture>, network_config: Arc<NetworkConfig>, blockchain: Arc<Blockchain<'static>>) -> Result<Arc<Self>, Error> {
         if !network_config.is_initialized() {
             return Err(Error::UninitializedPeerKey);
         }
@@ -392,8 +369,9 @@ impl ConnectionPool {
             blockchain,
             network_config: network_config.clone(),
             addresses: peer_address_book.clone(),
+            ban_list,
 
-            websocket_connector: WebSocketConnector::new(network_config.clone()),
+            websocket_connector: WebSocketConnector::new(network_config.clone(), traffic_capture),
 
             signal_processor: SignalProcessor::new(peer_address_book, network_config),
 
@@ -407,6 +385,7 @@ impl ConnectionPool {
 
                 peer_count_ws: 0,
                 peer_count_wss: 0,
+                peer_count_tcp: 0,
                 peer_count_rtc: 0,
                 peer_count_dumb: 0,
 
@@ -423,8 +402,6 @@ impl ConnectionPool {
 
                 allow_inbound_connections: false,
                 allow_inbound_exchange: false,
-
-                banned_ips: HashMap::new(),
             }),
             change_lock: ReentrantMutex::new(()),
 
@@ -459,7 +436,7 @@ impl ConnectionPool {
         let weak = self.self_weak.clone();
         self.timers.set_interval(ConnectionPoolTimer::UnbanIps, move || {
             let this = upgrade_weak!(weak);
-            this.state.write().check_unban_ips();
+            this.ban_list.remove_expired();
         }, Self::UNBAN_IPS_INTERVAL);
         Ok(())
     }
@@ -503,6 +480,22 @@ impl ConnectionPool {
     }
 
 
+    /// Closes the connections a ban applies to.
+    pub fn close_banned(&self, target: &BanTarget) {
+        let state = self.state.read();
+        for connection in state.connection_iter() {
+            let banned = match target {
+                BanTarget::PeerId(peer_id) => connection.peer_address().map(|peer_address| peer_address.peer_id == *peer_id).unwrap_or(false),
+                _ => connection.network_connection().map(|conn| target.matches_ip(&conn.net_address())).unwrap_or(false),
+            };
+            if banned {
+                if let Some(peer_channel) = connection.peer_channel() {
+                    peer_channel.close(CloseType::PeerIsBanned);
+                }
+            }
+        }
+    }
+
     /// Returns a mapped RwLockReadGuard for the internal state.
     pub fn state(&self) -> RwLockReadGuard<ConnectionPoolState> {
         self.state.read()
@@ -516,7 +509,7 @@ impl ConnectionPool {
     }
 
     /// Checks the validity of a connection from `on_connection`.
-    fn check_connection(state: &ConnectionPoolState, connection_id: ConnectionId) -> bool {
+    fn check_connection(&self, state: &ConnectionPoolState, connection_id: ConnectionId) -> bool {
         let info = state.connections.get(connection_id).unwrap();
         let conn = info.network_connection();
         assert!(conn.is_some(), "Connection must be established");
@@ -529,10 +522,22 @@ impl ConnectionPool {
             return false;
         }
 
+        // Close connection if we already know the peer and it is not allowed in private network
+        // mode, e.g. for encrypted TCP connections authenticated with the peer key.
+        let address_info = conn.address_info();
+        let peer_id = address_info.peer_address().map(|peer_address| peer_address.peer_id.clone())
+            .or_else(|| address_info.authenticated_public_key().map(|public_key| PeerId::from(&public_key)));
+        if let Some(peer_id) = peer_id {
+            if !self.network_config.is_peer_allowed(&peer_id) {
+                Self::close(info.network_connection(), CloseType::PeerNotAllowed);
+                return false;
+            }
+        }
+
         let net_address = conn.net_address();
         if net_address.is_reliable() {
             // Close connection if peer's IP is banned.
-            if state.is_ip_banned(&net_address) {
+            if self.ban_list.is_ip_banned(&net_address) {
                 Self::close(info.network_connection(), CloseType::BannedIp);
                 return false;
             }
@@ -617,7 +622,7 @@ impl ConnectionPool {
                 arc.on_close(connection_id, ty.clone());
             });
 
-            if !Self::check_connection(&state, connection_id) {
+            if !self.check_connection(&state, connection_id) {
                 return;
             }
 
@@ -650,6 +655,14 @@ impl ConnectionPool {
                 match event {
                     NetworkAgentEvent::Version(ref peer) => pool.check_handshake(connection_id, peer),
                     NetworkAgentEvent::Handshake(ref peer) => pool.on_handshake(connection_id, peer),
+                    NetworkAgentEvent::PingPong(latency) => {
+                        // Record the latency outside of the agent's lock, since address requests lock the pool first.
+                        let latency = *latency;
+                        tokio::spawn(futures::lazy(move || {
+                            pool.on_ping_pong(connection_id, latency);
+                            futures::future::ok(())
+                        }));
+                    },
                     _ => {},
                 }
             });
@@ -677,13 +690,19 @@ impl ConnectionPool {
             let state = self.state.read();
             let info = state.get_connection(connection_id).unwrap_or_else(|| panic!("Missing connection #{}", connection_id));
 
-            // Close connection if peer's address is banned.
+            // Close connection if peer's address or peer ID is banned.
             let peer_address = peer.peer_address();
-            if self.addresses.is_banned(&peer_address) {
+            if self.addresses.is_banned(&peer_address) || self.ban_list.is_peer_banned(&peer_address.peer_id) {
                 Self::close(info.network_connection(), CloseType::PeerIsBanned);
                 return;
             }
 
+            // Close connection if the peer is not allowed in private network mode.
+            if !self.network_config.is_peer_allowed(&peer_address.peer_id) {
+                Self::close(info.network_connection(), CloseType::PeerNotAllowed);
+                return;
+            }
+
             // Duplicate/simultaneous connection check (post version):
             let stored_connection_id = state.connections_by_peer_address.get(&peer_address);
             if let Some(stored_connection_id) = stored_connection_id {
@@ -704,8 +723,33 @@ impl ConnectionPool {
             }
         }
 
+        let mut state = self.state.write();
+
+        // Connections to keyless seeds are tracked by the address the seed announced from now on.
+        let stored_peer_address = state.connections.get(connection_id).and_then(ConnectionInfo::peer_address);
+        if let Some(stored_peer_address) = stored_peer_address {
+            if stored_peer_address.is_keyless_seed() {
+                let peer_address = peer.peer_address();
+                if state.connections_by_peer_address.contains_key(&peer_address) {
+                    Self::close(state.connections.get(connection_id).unwrap().network_connection(), CloseType::DuplicateConnection);
+                    return;
+                }
+                state.remove_peer_address(connection_id, &stored_peer_address);
+                state.connections.get_mut(connection_id).unwrap().set_peer_address(peer_address.clone());
+                state.add_peer_address(connection_id, peer_address);
+            }
+        }
+
         // Set peerConnection to NEGOTIATING state.
-        self.state.write().connections.get_mut(connection_id).unwrap().negotiating();
+        state.connections.get_mut(connection_id).unwrap().negotiating();
+    }
+
+    /// Callback upon a ping being answered. Records the latency in milliseconds for scoring.
+    fn on_ping_pong(&self, connection_id: ConnectionId, latency: Duration) {
+        let mut state = self.state.write();
+        if let Some(info) = state.connections.get_mut(connection_id) {
+            info.statistics_mut().add_latency(latency.as_millis() as f64);
+        }
     }
 
     /// Callback during handshake.
@@ -736,7 +780,7 @@ impl ConnectionPool {
                                 ConnectionState::Connecting => {
                                     // Abort the stored connection attempt and accept this connection.
                                     let protocol = peer_address.protocol();
-                                    assert!(protocol == Protocol::Wss || protocol == Protocol::Ws, "Duplicate connection to non-WS node");
+                                    assert!(protocol == Protocol::Wss || protocol == Protocol::Ws || protocol == Protocol::Tcp, "Duplicate connection to non-WS/TCP node");
                                     debug!("Aborting connection attempt to {}, simultaneous connection succeeded", peer_address);
 
                                     // Abort connection.
@@ -883,7 +927,7 @@ impl ConnectionPool {
                     // If closing is due to a ban, also ban the IP
                     if ty.is_banning_type() {
                         if let Some(ref net_address) = net_address {
-                            state.ban_ip(net_address);
+                            self.ban_ip(net_address, ty);
                         }
                     }
 
@@ -990,8 +1034,25 @@ impl ConnectionPool {
         }
     }
 
+    /// Bans the IP address of a peer that was disconnected with a banning close type.
+    /// IPv6 addresses are banned by their /64 subnet, since hosts usually get a whole subnet.
+    fn ban_ip(&self, net_address: &NetAddress, ty: CloseType) {
+        if net_address.is_reliable() {
+            let target = if net_address.get_type() == NetAddressType::IPv4 {
+                BanTarget::Ip(*net_address)
+            } else {
+                BanTarget::subnet(net_address, 64)
+            };
+            let reason = format!("Connection closed with {:?}", ty);
+            self.ban_list.ban(BanEntry::new(target, Some(Self::DEFAULT_BAN_TIME), reason, Some(ty))
+                .expect("Default ban time is too long"));
+        }
+    }
+
     /// Convert a net address into a subnet according to the configured bitmask.
     fn get_subnet_address(net_address: &NetAddress) -> NetAddress {
+        // Group IPv4-mapped addresses by their IPv4 subnet, instead of all into one IPv6 subnet.
+        let net_address = net_address.canonical();
         let bit_mask = if net_address.get_type() == NetAddressType::IPv4 { network_primitives::IPV4_SUBNET_MASK } else { network_primitives::IPV6_SUBNET_MASK };
         net_address.subnet(bit_mask)
     }
@@ -1001,17 +1062,25 @@ impl ConnectionPool {
         match peer_address.protocol() {
             Protocol::Wss => {},
             Protocol::Ws => {},
+            Protocol::Tcp => {},
             _ => {
                 error!("Cannot connect to {} - unsupported protocol", peer_address);
                 return false;
             },
         }
 
-        if self.addresses.is_banned(&peer_address) {
+        if self.addresses.is_banned(&peer_address)
+            || self.ban_list.is_peer_banned(&peer_address.peer_id)
+            || self.ban_list.is_ip_banned(&peer_address.net_address) {
             error!("Connecting to banned address {}", peer_address);
             return false;
         }
 
+        if !self.network_config.is_peer_allowed(&peer_address.peer_id) {
+            error!("Connecting to address {} that is not allowed", peer_address);
+            return false;
+        }
+
         let state = self.state.read();
         let info = state.get_connection_by_peer_address(&peer_address);
         if info.is_some() {
@@ -6,6 +6,7 @@ use std::time::{Duration, Instant, SystemTime};
 
 use parking_lot::RwLock;
 use rand::{Rng, rngs::OsRng};
+use tokio::clock;
 
 use beserial::Serialize;
 use blockchain::Blockchain;
@@ -165,7 +166,7 @@ impl NetworkAgent {
         // Try again in this case.
         let network_info = get_network_info(self.blockchain.network_id).unwrap();
         let msg = VersionMessage::new(
-            self.network_config.peer_address(),
+            self.own_peer_address(),
             self.blockchain.head_hash(),
             network_info.genesis_hash.clone(),
             self.challenge_nonce.clone(),
@@ -211,6 +212,15 @@ impl NetworkAgent {
         }, Self::HANDSHAKE_TIMEOUT);
     }
 
+    /// Our peer address as advertised to this peer, with a host that it can reach over the same
+    /// IP version as this connection if one is configured.
+    fn own_peer_address(&self) -> PeerAddress {
+        match self.channel.address_info.net_address() {
+            Some(net_address) => self.network_config.peer_address_for(&net_address),
+            None => self.network_config.peer_address(),
+        }
+    }
+
     fn send_ver_ack(&mut self) {
         assert!(self.peer_address_verified);
         assert!(self.peer_challenge_nonce.is_some());
@@ -270,18 +280,47 @@ impl NetworkAgent {
 
         // TODO Check services?
 
+        // Peers must always announce their public key.
+        if !msg.peer_address.has_public_key() {
+            self.channel.close(CloseType::InvalidPeerAddressInVersionMessage);
+            return;
+        }
+
+        // In private network mode, don't process anything from peers that are not allowed.
+        if !self.network_config.is_peer_allowed(&msg.peer_address.peer_id) {
+            self.channel.close(CloseType::PeerNotAllowed);
+            return;
+        }
+
         // Check that the given peerAddress matches the one we expect.
         // In case of inbound WebSocket connections, this is the first time we
         // see the remote peer's peerAddress.
         let mut peer_address = msg.peer_address.clone();
         if let Some(channel_peer_address) = self.channel.address_info.peer_address() {
-            if &peer_address != channel_peer_address.as_ref() {
+            if channel_peer_address.is_keyless_seed() {
+                // We connected to a seed without knowing its public key. Learn the key now and
+                // only accept this key for the seed from now on.
+                if !channel_peer_address.matches_keyless_seed(&peer_address) {
+                    self.channel.close(CloseType::UnexpectedPeerAddressInVersionMessage);
+                    return;
+                }
+                self.addresses.pin_seed(&channel_peer_address, &peer_address);
+            } else if &peer_address != channel_peer_address.as_ref() {
                 self.channel.close(CloseType::UnexpectedPeerAddressInVersionMessage);
                 return;
             }
             self.peer_address_verified = true;
         }
 
+        // Encrypted TCP connections are authenticated with the peer key during the handshake,
+        // so the announced address must belong to that key.
+        if let Some(public_key) = self.channel.address_info.authenticated_public_key() {
+            if peer_address.public_key != public_key {
+                self.channel.close(CloseType::UnexpectedPeerAddressInVersionMessage);
+                return;
+            }
+        }
+
         // The client might not send its netAddress. Set it from our address database if we have it.
         if peer_address.net_address.is_pseudo() {
             let addresses = self.addresses.state();
@@ -389,13 +428,16 @@ impl NetworkAgent {
             agent.check_connectivity();
         }, Self::CONNECTIVITY_CHECK_INTERVAL);
 
-        // Regularly announce our address.
-        let weak = self.self_weak.clone();
-        self.timers.set_interval(NetworkAgentTimer::AnnounceAddr, move || {
-            let arc = upgrade_weak!(weak);
-            let agent = arc.read();
-            agent.channel.send_or_close(AddrMessage::new(vec![agent.network_config.peer_address()]));
-        }, Self::ANNOUNCE_ADDR_INTERVAL);
+        // Regularly announce our address. Private networks don't gossip addresses, and there is
+        // no point in announcing an address that doesn't accept connections.
+        if !self.network_config.is_private() && self.network_config.accept_inbound() {
+            let weak = self.self_weak.clone();
+            self.timers.set_interval(NetworkAgentTimer::AnnounceAddr, move || {
+                let arc = upgrade_weak!(weak);
+                let agent = arc.read();
+                agent.channel.send_or_close(AddrMessage::new(vec![agent.own_peer_address()]));
+            }, Self::ANNOUNCE_ADDR_INTERVAL);
+        }
 
         // Tell listeners that the handshake with this peer succeeded.
         self.notifier.notify(NetworkAgentEvent::Handshake(UniquePtr::new(self.peer.as_ref().unwrap())));
@@ -406,6 +448,11 @@ impl NetworkAgent {
 
     pub fn request_addresses(&mut self, max_results: Option<u16>) {
         assert!(self.peer.is_some());
+
+        // In private network mode, we only ever talk to the allowed peers.
+        if self.network_config.is_private() {
+            return;
+        }
         debug!("Requesting addresses from {}", self.peer.as_ref().unwrap());
 
         let max_results = max_results.unwrap_or(Self::NUM_ADDR_PER_REQUEST);
@@ -430,6 +477,11 @@ impl NetworkAgent {
             return;
         }
 
+        // Don't learn addresses in private network mode.
+        if self.network_config.is_private() {
+            return;
+        }
+
         // Reject unsolicited address messages unless it is the peer's own address.
         let peer_address = self.peer.as_ref().unwrap().peer_address();
         let is_own_address = msg.addresses.len() == 1 && peer_address.as_ref() == &msg.addresses[0];
@@ -467,7 +519,7 @@ impl NetworkAgent {
             }
         }
 
-        // Filter out addresses that are not globally reachable
+        // Filter out addresses that are not globally reachable.
         addresses.retain(|x| x.is_globally_reachable(false));
 
         // Update peer with new address.
@@ -488,6 +540,11 @@ impl NetworkAgent {
             return;
         }
 
+        // Don't relay addresses in private network mode.
+        if self.network_config.is_private() {
+            return;
+        }
+
         if !self.get_address_limit.note_single() {
             warn!("Rejecting GetAddr message - rate limit exceeded");
             return;
@@ -518,12 +575,12 @@ impl NetworkAgent {
         }
 
         // Save ping timestamp to detect the speed of the connection.
-        let start_time = Instant::now();
+        let start_time = clock::now();
         self.ping_times.insert(nonce, start_time);
 
         // Expect the peer to answer with a pong message if we haven't heard anything from it
         // within the last CONNECTIVITY_CHECK_INTERVAL. Drop the peer otherwise.
-        if self.channel.last_message_received.load(Ordering::Relaxed).elapsed() > Self::CONNECTIVITY_CHECK_INTERVAL {
+        if clock::now().duration_since(self.channel.last_message_received.load(Ordering::Relaxed)) > Self::CONNECTIVITY_CHECK_INTERVAL {
             let weak = self.self_weak.clone();
             self.timers.set_delay(NetworkAgentTimer::Ping(nonce), move || {
                 let arc = upgrade_weak!(weak);
@@ -551,7 +608,7 @@ impl NetworkAgent {
 
         let start_time = self.ping_times.remove(&nonce);
         if let Some(start_time) = start_time {
-            let delta = start_time.elapsed();
+            let delta = clock::now().duration_since(start_time);
             self.notifier.notify(NetworkAgentEvent::PingPong(delta));
         }
     }
@@ -2,23 +2,27 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use futures::prelude::*;
-use futures::stream::Forward;
 use futures::sync::mpsc::*;
 use parking_lot::Mutex;
 use parking_lot::RwLock;
 
+use keys::PublicKey;
+use network_messages::Message as NimiqMessage;
 use network_primitives::address::net_address::NetAddress;
 use network_primitives::address::peer_address::PeerAddress;
 use utils::observer::PassThroughNotifier;
 use utils::unique_id::UniqueId;
 
+use crate::capture::{Direction, TrafficCapture};
 use crate::connection::close_type::CloseType;
+use crate::message_limits::MessageLimits;
 #[cfg(feature = "metrics")]
 use crate::network_metrics::NetworkMetrics;
 use crate::peer_channel::PeerSink;
 use crate::peer_channel::PeerStream;
 use crate::peer_channel::PeerStreamEvent;
 use crate::websocket::{Message, SharedNimiqMessageStream};
+use crate::websocket::error::Error;
 use std::fmt;
 
 #[derive(Debug, Clone, Default)]
@@ -62,19 +66,36 @@ pub struct NetworkConnection {
     address_info: AddressInfo,
     unique_id: UniqueId,
     closed_flag: ClosedFlag,
+    message_limits: Arc<MessageLimits>,
     pub notifier: Arc<RwLock<PassThroughNotifier<'static, PeerStreamEvent>>>,
 }
 
 impl NetworkConnection {
-    pub fn new_connection_setup(stream: SharedNimiqMessageStream, address_info: AddressInfo) -> (Self, ProcessConnectionFuture) {
+    /// Sets up a connection over the given stream. Its messages are recorded by `traffic_capture`
+    /// while a capture is running. Fails if the stream is already in use.
+    pub fn new_connection_setup(stream: SharedNimiqMessageStream, address_info: AddressInfo, message_limits: &MessageLimits, traffic_capture: Arc<TrafficCapture>) -> Result<(Self, ProcessConnectionFuture), Error> {
         let id = UniqueId::new();
         let closed_flag = ClosedFlag::new();
-        let (tx, rx) = unbounded(); // TODO: use bounded channel?
-
-        let forward_future = rx.forward(stream.clone());
+        let message_limits = Arc::new(message_limits.clone());
+        stream.set_message_limits(Arc::clone(&message_limits))?;
+
+        let (tx, rx) = channel(message_limits.send_queue_size());
+        let recorder = ConnectionRecorder {
+            traffic_capture,
+            connection_id: id,
+            address_info: address_info.clone(),
+        };
+        let outbound_recorder = recorder.clone();
+        let forward_future = rx
+            .inspect(move |msg| {
+                if let Message::Message(msg) = msg {
+                    outbound_recorder.record(Direction::Outbound, msg);
+                }
+            })
+            .forward(stream.clone());
 
         let notifier = Arc::new(RwLock::new(PassThroughNotifier::new()));
-        let peer_stream = PeerStream::new(stream.clone(), notifier.clone(), closed_flag.clone());
+        let peer_stream = PeerStream::new(stream.clone(), notifier.clone(), closed_flag.clone(), recorder);
         let process_connection = ProcessConnectionFuture::new(peer_stream, forward_future, id);
 
         let peer_sink = PeerSink::new(tx, id, closed_flag.clone());
@@ -85,10 +106,11 @@ impl NetworkConnection {
             address_info,
             notifier,
             closed_flag,
+            message_limits,
             unique_id: id,
         };
 
-        (network_connection, process_connection)
+        Ok((network_connection, process_connection))
     }
 
     pub fn close(&self, ty: CloseType) {
@@ -121,6 +143,7 @@ impl NetworkConnection {
     }
 
     pub fn peer_sink(&self) -> PeerSink { self.peer_sink.clone() }
+    pub fn message_limits(&self) -> &MessageLimits { &self.message_limits }
     pub fn address_info(&self) -> AddressInfo {
         self.address_info.clone()
     }
@@ -131,12 +154,28 @@ impl NetworkConnection {
     }
 }
 
+/// Records the messages of one connection in the traffic capture of the node.
+#[derive(Clone)]
+pub struct ConnectionRecorder {
+    traffic_capture: Arc<TrafficCapture>,
+    connection_id: UniqueId,
+    /// Shared with the connection, so that records carry the peer address once it is known.
+    address_info: AddressInfo,
+}
+
+impl ConnectionRecorder {
+    pub fn record(&self, direction: Direction, msg: &NimiqMessage) {
+        self.traffic_capture.record(direction, self.connection_id, &self.address_info, msg);
+    }
+}
+
 pub struct ProcessConnectionFuture {
     inner: Box<dyn Future<Item=(), Error=()> + Send + Sync + 'static>,
 }
 
 impl ProcessConnectionFuture {
-    pub fn new(peer_stream: PeerStream, forward_future: Forward<UnboundedReceiver<Message>, SharedNimiqMessageStream>, _id: UniqueId) -> Self {
+    pub fn new<F>(peer_stream: PeerStream, forward_future: F, _id: UniqueId) -> Self
+        where F: Future + Send + Sync + 'static {
         // `select` required Item/Error to be the same, that's why we need to map them both to ().
         // TODO We're discarding any errors here, especially those coming from the forward future.
         // Results by the peer_stream have been processes already.
@@ -165,6 +204,7 @@ pub struct AddressInfo {
 struct AddressInfoInternal {
     pub peer_address: RwLock<Option<Arc<PeerAddress>>>,
     pub net_address: RwLock<Option<Arc<NetAddress>>>,
+    pub authenticated_public_key: Option<PublicKey>,
 }
 
 impl Clone for AddressInfo {
@@ -177,10 +217,17 @@ impl Clone for AddressInfo {
 
 impl AddressInfo {
     pub fn new(net_address: Option<Arc<NetAddress>>, peer_address: Option<Arc<PeerAddress>>) -> Self {
+        Self::new_authenticated(net_address, peer_address, None)
+    }
+
+    /// Creates the address info for a connection whose transport already authenticated the
+    /// peer key of the remote side.
+    pub fn new_authenticated(net_address: Option<Arc<NetAddress>>, peer_address: Option<Arc<PeerAddress>>, authenticated_public_key: Option<PublicKey>) -> Self {
         AddressInfo {
             inner: Arc::new(AddressInfoInternal {
                 peer_address: RwLock::new(peer_address),
                 net_address: RwLock::new(net_address),
+                authenticated_public_key,
             })
         }
     }
@@ -197,6 +244,9 @@ impl AddressInfo {
     pub fn set_net_address(&self, net_address: Arc<NetAddress>) {
         self.inner.net_address.write().replace(net_address);
     }
+    pub fn authenticated_public_key(&self) -> Option<PublicKey> {
+        self.inner.authenticated_public_key
+    }
 }
 
 impl fmt::Display for AddressInfo {
@@ -0,0 +1,323 @@
+//! Minimal DNS client (RFC 1035) to resolve DNS seeds. Only A, AAAA and TXT queries over UDP are
+//! supported, truncated responses are used as they are.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::Error as IoError;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+
+use failure::Fail;
+use futures::future;
+use futures::prelude::*;
+use tokio::net::UdpSocket;
+use tokio::timer::Timeout;
+
+use network_primitives::address::dns_seed::DnsSeed;
+use network_primitives::address::peer_address::{PeerAddress, PeerAddressType};
+use network_primitives::address::peer_uri::PeerUri;
+
+const DNS_PORT: u16 = 53;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_RESPONSE_SIZE: usize = 4096;
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const CLASS_IN: u16 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u16)]
+pub enum RecordType {
+    A = 1,
+    Txt = 16,
+    Aaaa = 28,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Record {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Txt(String),
+}
+
+#[derive(Fail, Debug)]
+pub enum DnsError {
+    #[fail(display = "{}", _0)]
+    IoError(#[cause] IoError),
+    #[fail(display = "The DNS query timed out")]
+    Timeout,
+    #[fail(display = "Invalid host name")]
+    InvalidName,
+    #[fail(display = "Malformed DNS response")]
+    MalformedResponse,
+    #[fail(display = "The DNS server responded with error code {}", _0)]
+    ServerFailure(u8),
+    #[fail(display = "The DNS seed didn't resolve to any seed address")]
+    NoSeedAddresses,
+}
+
+impl From<IoError> for DnsError {
+    fn from(e: IoError) -> Self {
+        DnsError::IoError(e)
+    }
+}
+
+/// Returns the first name server configured in `/etc/resolv.conf`.
+pub fn system_resolver() -> Option<SocketAddr> {
+    let resolv_conf = fs::read_to_string("/etc/resolv.conf").ok()?;
+    resolv_conf.lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some("nameserver"), Some(address)) => IpAddr::from_str(address).ok(),
+                _ => None,
+            }
+        })
+        .next()
+        .map(|ip| SocketAddr::new(ip, DNS_PORT))
+}
+
+/// Queries the records of the given type for `name`.
+pub fn query(resolver: SocketAddr, name: &str, ty: RecordType) -> Box<dyn Future<Item=Vec<Record>, Error=DnsError> + Send> {
+    let id: u16 = rand::random();
+    let request = match encode_query(id, name, ty) {
+        Ok(request) => request,
+        Err(e) => return Box::new(future::err(e)),
+    };
+
+    let bind_address: SocketAddr = if resolver.is_ipv4() {
+        ([0u8; 4], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = match UdpSocket::bind(&bind_address) {
+        Ok(socket) => socket,
+        Err(e) => return Box::new(future::err(e.into())),
+    };
+
+    // Datagrams from other sources or for other queries are ignored until the query times out.
+    let exchange = socket.send_dgram(request, &resolver)
+        .map_err(DnsError::from)
+        .and_then(move |(socket, _)| future::loop_fn((socket, vec![0u8; MAX_RESPONSE_SIZE]), move |(socket, buffer)| {
+            socket.recv_dgram(buffer)
+                .map_err(DnsError::from)
+                .and_then(move |(socket, buffer, size, source)| {
+                    if source != resolver || size < 2 || u16::from_be_bytes([buffer[0], buffer[1]]) != id {
+                        trace!("Ignoring unexpected DNS response from {}", source);
+                        return Ok(future::Loop::Continue((socket, buffer)));
+                    }
+                    decode_response(id, &buffer[..size]).map(future::Loop::Break)
+                })
+        }));
+
+    Box::new(Timeout::new(exchange, QUERY_TIMEOUT)
+        .map_err(|e| if e.is_elapsed() {
+            DnsError::Timeout
+        } else {
+            e.into_inner().unwrap_or(DnsError::Timeout)
+        }))
+}
+
+/// Resolves the seed addresses of a DNS seed. Seeds from TXT records take precedence over
+/// keyless seeds for the same host from A and AAAA records. Failing queries are ignored as long
+/// as another query yields seed addresses.
+pub fn resolve_seed(resolver: SocketAddr, seed: &DnsSeed) -> Box<dyn Future<Item=Vec<PeerAddress>, Error=DnsError> + Send> {
+    let lookup = |ty| query(resolver, seed.host(), ty).then(|result| Ok::<_, DnsError>(result));
+    let lookups = lookup(RecordType::Txt).join3(lookup(RecordType::A), lookup(RecordType::Aaaa));
+
+    let seed = seed.clone();
+    Box::new(lookups
+        .and_then(move |(txt, a, aaaa)| {
+            let mut last_error = None;
+            let mut records = Vec::new();
+            for result in vec![txt, a, aaaa] {
+                match result {
+                    Ok(mut result) => records.append(&mut result),
+                    Err(e) => last_error = Some(e),
+                }
+            }
+
+            let seed_addresses = seed_addresses(&seed, records);
+            if seed_addresses.is_empty() {
+                return Err(last_error.unwrap_or(DnsError::NoSeedAddresses));
+            }
+            Ok(seed_addresses)
+        }))
+}
+
+/// Turns the resolved records of a DNS seed into seed addresses.
+pub fn seed_addresses(seed: &DnsSeed, records: Vec<Record>) -> Vec<PeerAddress> {
+    let mut seed_addresses = Vec::new();
+    let mut hosts = HashSet::new();
+
+    for record in records.iter() {
+        if let Record::Txt(txt) = record {
+            let peer_address = PeerUri::from_str(txt.trim()).ok()
+                .filter(|peer_uri| peer_uri.public_key().is_some())
+                .and_then(|peer_uri| peer_uri.as_seed_peer_address().ok());
+            match peer_address {
+                Some(peer_address) => {
+                    match peer_address.ty {
+                        PeerAddressType::Ws(ref host, _) | PeerAddressType::Wss(ref host, _) | PeerAddressType::Tcp(ref host, _) => {
+                            hosts.insert(host.clone());
+                        },
+                        _ => {},
+                    }
+                    seed_addresses.push(peer_address);
+                },
+                None => debug!("Ignoring TXT record of DNS seed {}: {}", seed.host(), txt),
+            }
+        }
+    }
+
+    for record in records.iter() {
+        let ip = match record {
+            Record::A(ip) => IpAddr::V4(*ip),
+            Record::Aaaa(ip) => IpAddr::V6(*ip),
+            Record::Txt(_) => continue,
+        };
+        // IPv6 addresses need to be enclosed in brackets in URIs.
+        let host = match ip {
+            IpAddr::V4(ip) => ip.to_string(),
+            IpAddr::V6(ip) => format!("[{}]", ip),
+        };
+        if hosts.contains(&host) {
+            continue;
+        }
+        let port = seed.port().or_else(|| seed.protocol().default_port());
+        let uri = match port {
+            Some(port) => format!("{}://{}:{}", seed.protocol(), host, port),
+            None => format!("{}://{}", seed.protocol(), host),
+        };
+        match PeerUri::from_str(&uri).and_then(|peer_uri| peer_uri.as_seed_peer_address()) {
+            Ok(peer_address) => seed_addresses.push(peer_address),
+            Err(e) => debug!("Ignoring address {} of DNS seed {}: {}", ip, seed.host(), e),
+        }
+    }
+
+    seed_addresses
+}
+
+fn encode_query(id: u16, name: &str, ty: RecordType) -> Result<Vec<u8>, DnsError> {
+    let mut request = Vec::with_capacity(18 + name.len());
+    request.extend_from_slice(&id.to_be_bytes());
+    request.extend_from_slice(&FLAG_RECURSION_DESIRED.to_be_bytes());
+    // One question, no answer, authority or additional records.
+    request.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(DnsError::InvalidName);
+        }
+        request.push(label.len() as u8);
+        request.extend_from_slice(label.as_bytes());
+    }
+    request.push(0);
+    if request.len() > 12 + 255 {
+        return Err(DnsError::InvalidName);
+    }
+
+    request.extend_from_slice(&(ty as u16).to_be_bytes());
+    request.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(request)
+}
+
+fn decode_response(id: u16, response: &[u8]) -> Result<Vec<Record>, DnsError> {
+    let mut reader = Reader { data: response, pos: 0 };
+    if reader.u16()? != id {
+        return Err(DnsError::MalformedResponse);
+    }
+    let flags = reader.u16()?;
+    if flags & FLAG_RESPONSE == 0 {
+        return Err(DnsError::MalformedResponse);
+    }
+    let response_code = (flags & 0x000f) as u8;
+    if response_code != 0 {
+        return Err(DnsError::ServerFailure(response_code));
+    }
+    let question_count = reader.u16()?;
+    let answer_count = reader.u16()?;
+    reader.skip(4)?;
+
+    for _ in 0..question_count {
+        reader.skip_name()?;
+        reader.skip(4)?;
+    }
+
+    let mut records = Vec::new();
+    for _ in 0..answer_count {
+        reader.skip_name()?;
+        let ty = reader.u16()?;
+        let class = reader.u16()?;
+        reader.skip(4)?;
+        let length = reader.u16()? as usize;
+        let data = reader.bytes(length)?;
+        if class != CLASS_IN {
+            continue;
+        }
+
+        // Other record types, e.g. CNAME records, are skipped.
+        if ty == RecordType::A as u16 && length == 4 {
+            let mut ip = [0u8; 4];
+            ip.copy_from_slice(data);
+            records.push(Record::A(ip.into()));
+        } else if ty == RecordType::Aaaa as u16 && length == 16 {
+            let mut ip = [0u8; 16];
+            ip.copy_from_slice(data);
+            records.push(Record::Aaaa(ip.into()));
+        } else if ty == RecordType::Txt as u16 {
+            // A TXT record consists of multiple character strings that form a single text.
+            let mut txt = Vec::new();
+            let mut pos = 0;
+            while pos < data.len() {
+                let end = pos + 1 + data[pos] as usize;
+                if end > data.len() {
+                    return Err(DnsError::MalformedResponse);
+                }
+                txt.extend_from_slice(&data[pos + 1..end]);
+                pos = end;
+            }
+            records.push(Record::Txt(String::from_utf8_lossy(&txt).into_owned()));
+        }
+    }
+    Ok(records)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], DnsError> {
+        if self.pos + length > self.data.len() {
+            return Err(DnsError::MalformedResponse);
+        }
+        let bytes = &self.data[self.pos..self.pos + length];
+        self.pos += length;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, length: usize) -> Result<(), DnsError> {
+        self.bytes(length).map(|_| ())
+    }
+
+    fn u16(&mut self) -> Result<u16, DnsError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Skips a domain name, which ends with an empty label or a compression pointer.
+    fn skip_name(&mut self) -> Result<(), DnsError> {
+        loop {
+            let length = self.bytes(1)?[0];
+            if length == 0 {
+                return Ok(());
+            }
+            if length & 0xc0 == 0xc0 {
+                return self.skip(1);
+            }
+            self.skip(length as usize)?;
+        }
+    }
+}
@@ -60,3 +60,11 @@ impl From<url::ParseError> for SeedError {
         SeedError::Url(e)
     }
 }
+
+#[derive(Debug, Fail)]
+pub enum ProxyConfigError {
+    #[fail(display = "Invalid proxy host: {}", _0)]
+    InvalidHost(String),
+    #[fail(display = "Invalid proxy credentials")]
+    InvalidCredentials,
+}
@@ -12,16 +12,26 @@ extern crate nimiq_blockchain as blockchain;
 extern crate nimiq_hash as hash;
 extern crate nimiq_keys as keys;
 extern crate nimiq_collections as collections;
+extern crate nimiq_database as database;
 
 pub mod address;
 pub mod websocket;
+pub mod tcp;
+pub mod socks;
+pub mod proxy_protocol;
+pub mod dns;
 pub mod peer_channel;
 pub mod peer_scorer;
 pub mod connection;
 pub mod peer;
 pub mod network_config;
+pub mod ban_list;
+pub mod capture;
+pub mod message_limits;
 pub mod network;
 pub mod error;
+#[cfg(feature = "memory-transport")]
+pub mod memory;
 #[cfg(feature = "metrics")]
 mod network_metrics;
 
@@ -0,0 +1,457 @@
+//! In-memory transport for simulated networks.
+//!
+//! All nodes of a simulation share one `MemoryNetwork`. Each node gets a `MemoryTransport` with
+//! its own host name and private IP address, which is set on its `NetworkConfig`. The
+//! `WebSocketConnector` then listens on and dials through the transport instead of opening
+//! sockets, so everything above the `NimiqMessageStream` runs unmodified.
+//!
+//! Connections are pairs of queues carrying the same binary chunks and close frames as a
+//! WebSocket. The network can delay, drop and partition traffic between hosts:
+//! * Latency is measured on the network's manual clock, i.e. delayed chunks are only delivered
+//!   once the clock has been advanced far enough.
+//! * Drops and partitions always affect whole messages. The chunks of the remaining messages are
+//!   re-tagged, so the receiving side doesn't notice the gap.
+//! * Chunks that are already in flight are still delivered when a partition is created.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use futures::future;
+use futures::prelude::*;
+use futures::sync::mpsc;
+use futures::task::{self, Task};
+use parking_lot::Mutex;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use tungstenite::error::Error as WebSocketError;
+use tungstenite::protocol::Message as WebSocketMessage;
+
+use network_primitives::address::PeerAddress;
+use network_primitives::address::peer_address::PeerAddressType;
+use utils::time::systemtime_to_timestamp;
+
+use crate::websocket::{Error, NimiqMessageStream};
+
+/// Conditions of the link between two hosts.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkConditions {
+    /// Time it takes for a chunk to arrive, measured on the network's clock.
+    pub latency: Duration,
+    /// Probability in `[0, 1]` that a message is lost.
+    pub drop_rate: f64,
+}
+
+type PipeId = u64;
+
+/// One direction of a connection.
+struct Pipe {
+    from: String,
+    to: String,
+    /// Chunks and their delivery time.
+    queue: VecDeque<(u64, WebSocketMessage)>,
+    /// Set once the sending side hung up.
+    closed: bool,
+    /// The receiving side, if it waits for a chunk.
+    task: Option<Task>,
+
+    /// Tag of the message that is currently being sent, as set by the sender.
+    sending_tag: Option<u8>,
+    /// Whether the chunks of the current message are dropped.
+    dropping: bool,
+    /// Tag of the current message, as seen by the receiver.
+    delivered_tag: u8,
+    /// Tag of the next message that isn't dropped.
+    next_tag: u8,
+}
+
+impl Pipe {
+    fn new(from: String, to: String) -> Self {
+        Pipe {
+            from,
+            to,
+            queue: VecDeque::new(),
+            closed: false,
+            task: None,
+            sending_tag: None,
+            dropping: false,
+            delivered_tag: 0,
+            next_tag: 0,
+        }
+    }
+
+    fn notify(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.notify();
+        }
+    }
+}
+
+struct NetworkState {
+    /// The manual clock in milliseconds since the unix epoch.
+    now: u64,
+    rng: StdRng,
+    hosts: HashMap<String, IpAddr>,
+    listeners: HashMap<(String, u16), (SocketAddr, mpsc::UnboundedSender<MemoryLayer>)>,
+    pipes: HashMap<PipeId, Pipe>,
+    next_pipe_id: PipeId,
+    next_port: u16,
+    default_conditions: LinkConditions,
+    /// Conditions of individual links, keyed by the host names in lexicographic order.
+    conditions: HashMap<(String, String), LinkConditions>,
+    /// The group of each host in the current partition. Hosts without a group can reach everyone.
+    partition: HashMap<String, usize>,
+}
+
+impl NetworkState {
+    fn link_key(a: &str, b: &str) -> (String, String) {
+        if a <= b {
+            (a.to_string(), b.to_string())
+        } else {
+            (b.to_string(), a.to_string())
+        }
+    }
+
+    fn link_conditions(&self, a: &str, b: &str) -> &LinkConditions {
+        self.conditions.get(&Self::link_key(a, b)).unwrap_or(&self.default_conditions)
+    }
+
+    fn is_reachable(&self, a: &str, b: &str) -> bool {
+        match (self.partition.get(a), self.partition.get(b)) {
+            (Some(group_a), Some(group_b)) => group_a == group_b,
+            _ => true,
+        }
+    }
+
+    fn add_pipe(&mut self, from: &str, to: &str) -> PipeId {
+        let id = self.next_pipe_id;
+        self.next_pipe_id += 1;
+        self.pipes.insert(id, Pipe::new(from.to_string(), to.to_string()));
+        id
+    }
+
+    fn notify_all(&mut self) {
+        for pipe in self.pipes.values_mut() {
+            pipe.notify();
+        }
+    }
+}
+
+/// A simulated network that connects `MemoryTransport`s.
+#[derive(Clone)]
+pub struct MemoryNetwork {
+    state: Arc<Mutex<NetworkState>>,
+}
+
+impl MemoryNetwork {
+    /// Creates a network whose clock starts at the current system time.
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    /// Creates a network whose message drops are determined by the given seed.
+    pub fn with_seed(seed: u64) -> Self {
+        let mut rng_seed = <StdRng as SeedableRng>::Seed::default();
+        rng_seed[..8].copy_from_slice(&seed.to_le_bytes());
+
+        MemoryNetwork {
+            state: Arc::new(Mutex::new(NetworkState {
+                now: systemtime_to_timestamp(SystemTime::now()),
+                rng: StdRng::from_seed(rng_seed),
+                hosts: HashMap::new(),
+                listeners: HashMap::new(),
+                pipes: HashMap::new(),
+                next_pipe_id: 0,
+                next_port: 1024,
+                default_conditions: LinkConditions::default(),
+                conditions: HashMap::new(),
+                partition: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Returns the transport of the given host. Every host is assigned a private IP address in
+    /// its own /24 subnet, so the per-subnet connection limits don't get in the way.
+    pub fn transport(&self, host: &str) -> MemoryTransport {
+        let mut state = self.state.lock();
+        let num_hosts = state.hosts.len() as u32 + 1;
+        assert!(num_hosts < 1 << 16, "Too many hosts");
+        let ip = *state.hosts.entry(host.to_string())
+            .or_insert_with(|| IpAddr::V4(Ipv4Addr::new(10, (num_hosts >> 8) as u8, num_hosts as u8, 1)));
+
+        MemoryTransport {
+            network: self.clone(),
+            host: host.to_string(),
+            ip,
+        }
+    }
+
+    /// The current time of the manual clock in milliseconds since the unix epoch.
+    pub fn now(&self) -> u64 {
+        self.state.lock().now
+    }
+
+    /// Advances the clock and delivers the chunks that are due.
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state.lock();
+        state.now += duration.as_secs() * 1000 + u64::from(duration.subsec_millis());
+        state.notify_all();
+    }
+
+    /// Sets the conditions of all links that weren't configured individually.
+    pub fn set_default_link_conditions(&self, conditions: LinkConditions) {
+        self.state.lock().default_conditions = conditions;
+    }
+
+    /// Sets the conditions of the link between two hosts, in both directions.
+    pub fn set_link_conditions(&self, a: &str, b: &str, conditions: LinkConditions) {
+        self.state.lock().conditions.insert(NetworkState::link_key(a, b), conditions);
+    }
+
+    /// Splits the network into groups of hosts that can only reach hosts of their own group.
+    /// Hosts that aren't part of any group can still reach everyone. Replaces the current partition.
+    pub fn partition(&self, groups: &[&[&str]]) {
+        let mut state = self.state.lock();
+        state.partition = groups.iter()
+            .enumerate()
+            .flat_map(|(i, group)| group.iter().map(move |host| (host.to_string(), i)))
+            .collect();
+    }
+
+    /// Removes the partition.
+    pub fn heal(&self) {
+        self.state.lock().partition.clear();
+    }
+
+    pub fn is_reachable(&self, a: &str, b: &str) -> bool {
+        self.state.lock().is_reachable(a, b)
+    }
+}
+
+impl Default for MemoryNetwork {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MemoryNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MemoryNetwork {{ now: {} }}", self.now())
+    }
+}
+
+/// The access of a single host to a `MemoryNetwork`.
+#[derive(Clone)]
+pub struct MemoryTransport {
+    network: MemoryNetwork,
+    host: String,
+    ip: IpAddr,
+}
+
+impl MemoryTransport {
+    pub fn network(&self) -> &MemoryNetwork {
+        &self.network
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    /// Accepts connections to the given port of this host.
+    pub fn listen(&self, port: u16) -> io::Result<mpsc::UnboundedReceiver<MemoryLayer>> {
+        let mut state = self.network.state.lock();
+        let key = (self.host.clone(), port);
+        if state.listeners.contains_key(&key) {
+            return Err(io::Error::new(io::ErrorKind::AddrInUse, "Port is already in use"));
+        }
+
+        let (tx, rx) = mpsc::unbounded();
+        state.listeners.insert(key, (SocketAddr::new(self.ip, port), tx));
+        Ok(rx)
+    }
+
+    /// Connects to the given port of another host.
+    pub fn connect(&self, host: &str, port: u16) -> io::Result<MemoryLayer> {
+        let mut state = self.network.state.lock();
+        let server_addr = match state.listeners.get(&(host.to_string(), port)) {
+            Some((addr, _)) => *addr,
+            None => return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "Nobody listens on this port")),
+        };
+        if !state.is_reachable(&self.host, host) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Host is unreachable"));
+        }
+
+        let client_addr = SocketAddr::new(self.ip, state.next_port);
+        state.next_port = state.next_port.checked_add(1).unwrap_or(1024);
+
+        let outgoing = state.add_pipe(&self.host, host);
+        let incoming = state.add_pipe(host, &self.host);
+        let client = MemoryLayer {
+            network: self.network.clone(),
+            incoming,
+            outgoing,
+            peer_addr: server_addr,
+        };
+        let server = MemoryLayer {
+            network: self.network.clone(),
+            incoming: outgoing,
+            outgoing: incoming,
+            peer_addr: client_addr,
+        };
+
+        let (_, listener) = &state.listeners[&(host.to_string(), port)];
+        if listener.unbounded_send(server).is_err() {
+            state.listeners.remove(&(host.to_string(), port));
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "Listener was closed"));
+        }
+        Ok(client)
+    }
+}
+
+impl fmt::Debug for MemoryTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MemoryTransport {{ host: {}, ip: {} }}", self.host, self.ip)
+    }
+}
+
+/// One end of an in-memory connection.
+pub struct MemoryLayer {
+    network: MemoryNetwork,
+    incoming: PipeId,
+    outgoing: PipeId,
+    peer_addr: SocketAddr,
+}
+
+impl MemoryLayer {
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+}
+
+impl Stream for MemoryLayer {
+    type Item = WebSocketMessage;
+    type Error = WebSocketError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let mut state = self.network.state.lock();
+        let now = state.now;
+        let pipe = match state.pipes.get_mut(&self.incoming) {
+            Some(pipe) => pipe,
+            None => return Ok(Async::Ready(None)),
+        };
+
+        let due = pipe.queue.front().map(|(deliver_at, _)| *deliver_at <= now);
+        match due {
+            Some(true) => {
+                let (_, msg) = pipe.queue.pop_front().unwrap();
+                return Ok(Async::Ready(Some(msg)));
+            },
+            None if pipe.closed => {
+                state.pipes.remove(&self.incoming);
+                return Ok(Async::Ready(None));
+            },
+            _ => {},
+        }
+
+        pipe.task = Some(task::current());
+        Ok(Async::NotReady)
+    }
+}
+
+impl Sink for MemoryLayer {
+    type SinkItem = WebSocketMessage;
+    type SinkError = WebSocketError;
+
+    fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        let mut state = self.network.state.lock();
+        let now = state.now;
+        let (reachable, conditions) = match state.pipes.get(&self.outgoing) {
+            Some(pipe) if !pipe.closed => (state.is_reachable(&pipe.from, &pipe.to), state.link_conditions(&pipe.from, &pipe.to).clone()),
+            _ => return Err(WebSocketError::ConnectionClosed),
+        };
+        let dropped = !reachable || (conditions.drop_rate > 0.0 && state.rng.gen::<f64>() < conditions.drop_rate);
+        let pipe = state.pipes.get_mut(&self.outgoing).unwrap();
+
+        let item = match item {
+            WebSocketMessage::Binary(mut chunk) => {
+                if let Some(tag) = chunk.first().cloned() {
+                    // A new tag starts a new message. Only whole messages are dropped.
+                    if pipe.sending_tag != Some(tag) {
+                        pipe.sending_tag = Some(tag);
+                        pipe.dropping = dropped;
+                        if !pipe.dropping {
+                            pipe.delivered_tag = pipe.next_tag;
+                            // Wrap at 255 like the NimiqMessageStream does.
+                            pipe.next_tag = (pipe.next_tag + 1) % 255;
+                        }
+                    }
+                    if pipe.dropping {
+                        return Ok(AsyncSink::Ready);
+                    }
+                    chunk[0] = pipe.delivered_tag;
+                }
+                WebSocketMessage::Binary(chunk)
+            },
+            item => {
+                if !reachable {
+                    return Ok(AsyncSink::Ready);
+                }
+                item
+            },
+        };
+
+        // Keep the order of chunks even if the latency decreased.
+        let latency = conditions.latency.as_secs() * 1000 + u64::from(conditions.latency.subsec_millis());
+        let deliver_at = pipe.queue.back()
+            .map_or(now + latency, |(last, _)| (now + latency).max(*last));
+        pipe.queue.push_back((deliver_at, item));
+        pipe.notify();
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        Ok(Async::Ready(()))
+    }
+
+    fn close(&mut self) -> Poll<(), Self::SinkError> {
+        let mut state = self.network.state.lock();
+        if let Some(pipe) = state.pipes.get_mut(&self.outgoing) {
+            pipe.closed = true;
+            pipe.notify();
+        }
+        Ok(Async::Ready(()))
+    }
+}
+
+impl Drop for MemoryLayer {
+    fn drop(&mut self) {
+        let mut state = self.network.state.lock();
+        if let Some(pipe) = state.pipes.get_mut(&self.outgoing) {
+            pipe.closed = true;
+            pipe.notify();
+        }
+        // Nobody reads from the incoming pipe anymore, further sends fail.
+        state.pipes.remove(&self.incoming);
+    }
+}
+
+/// Connects to the host of the given peer address through the transport and returns a future
+/// that resolves to a NimiqMessageStream.
+pub fn nimiq_memory_connect_async(transport: &MemoryTransport, peer_address: &PeerAddress) -> Box<dyn Future<Item = NimiqMessageStream, Error = Error> + Send> {
+    let result = match peer_address.ty {
+        PeerAddressType::Ws(ref host, port)
+        | PeerAddressType::Wss(ref host, port)
+        | PeerAddressType::Tcp(ref host, port) => transport.connect(host, port)
+            .map_err(Error::IoError)
+            .and_then(|layer| NimiqMessageStream::new_memory(layer, true)),
+        _ => Err(Error::IoError(io::Error::new(io::ErrorKind::InvalidInput, "Peer address has no host"))),
+    };
+    Box::new(future::result(result))
+}
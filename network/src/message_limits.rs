@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use network_messages::{InvVector, InvVectorType, Message};
+use utils::rate_limit::RateLimit;
+
+pub use network_messages::MessageType;
+
+/// Limits for a single message type received from a peer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MessageLimit {
+    /// Maximum number of messages per minute. Unlimited if not set.
+    pub per_minute: Option<usize>,
+    /// Maximum size of a message in bytes. Only the global maximum applies if not set.
+    pub max_size: Option<usize>,
+}
+
+impl MessageLimit {
+    pub fn new(per_minute: Option<usize>, max_size: Option<usize>) -> Self {
+        MessageLimit { per_minute, max_size }
+    }
+}
+
+/// Per-peer limits on the messages we accept and the messages we queue for sending.
+///
+/// A peer exceeding a rate limit or sending an oversized message is banned. Objects we requested
+/// from the peer don't count towards the rate limits.
+#[derive(Clone, Debug)]
+pub struct MessageLimits {
+    limits: HashMap<MessageType, MessageLimit>,
+    send_queue_size: usize,
+}
+
+impl MessageLimits {
+    pub const DEFAULT_SEND_QUEUE_SIZE: usize = 4096; // messages
+
+    /// Creates limits that don't restrict any message type.
+    pub fn unlimited() -> Self {
+        MessageLimits {
+            limits: HashMap::new(),
+            send_queue_size: Self::DEFAULT_SEND_QUEUE_SIZE,
+        }
+    }
+
+    pub fn get(&self, ty: MessageType) -> MessageLimit {
+        self.limits.get(&ty).cloned().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, ty: MessageType, limit: MessageLimit) {
+        if limit == MessageLimit::default() {
+            self.limits.remove(&ty);
+        } else {
+            self.limits.insert(ty, limit);
+        }
+    }
+
+    pub fn max_size(&self, ty: MessageType) -> Option<usize> {
+        self.get(ty).max_size
+    }
+
+    /// The maximum number of messages queued for sending to a peer. If a peer doesn't read
+    /// fast enough to keep the queue below this size, sending fails and the peer is dropped.
+    pub fn send_queue_size(&self) -> usize {
+        self.send_queue_size
+    }
+
+    pub fn set_send_queue_size(&mut self, send_queue_size: usize) {
+        self.send_queue_size = send_queue_size;
+    }
+
+    /// Creates the rate limiters for a single peer.
+    pub fn rate_limiter(&self) -> MessageRateLimiter {
+        MessageRateLimiter {
+            limits: self.limits.iter()
+                .filter_map(|(ty, limit)| limit.per_minute.map(|per_minute| (*ty, RateLimit::new_per_minute(per_minute))))
+                .collect(),
+            requested: HashMap::new(),
+        }
+    }
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        let mut limits = Self::unlimited();
+        limits.set(MessageType::Version, MessageLimit::new(Some(10), Some(1024 * 4)));
+        limits.set(MessageType::VerAck, MessageLimit::new(Some(10), Some(1024)));
+        limits.set(MessageType::Inv, MessageLimit::new(Some(1200), Some(1024 * 64)));
+        limits.set(MessageType::GetData, MessageLimit::new(Some(300), Some(1024 * 64)));
+        limits.set(MessageType::GetHeader, MessageLimit::new(Some(300), Some(1024 * 64)));
+        limits.set(MessageType::NotFound, MessageLimit::new(None, Some(1024 * 64)));
+        limits.set(MessageType::GetBlocks, MessageLimit::new(Some(120), Some(1024 * 8)));
+        limits.set(MessageType::Tx, MessageLimit::new(Some(1200), Some(1024 * 256)));
+        limits.set(MessageType::Mempool, MessageLimit::new(Some(6), Some(1024)));
+        limits.set(MessageType::Subscribe, MessageLimit::new(Some(60), None));
+        limits.set(MessageType::GetAddr, MessageLimit::new(Some(30), Some(1024)));
+        limits.set(MessageType::Ping, MessageLimit::new(Some(60), Some(1024)));
+        limits.set(MessageType::Pong, MessageLimit::new(Some(60), Some(1024)));
+        limits
+    }
+}
+
+/// Tracks how many messages of each type a peer sent us within the last minute.
+pub struct MessageRateLimiter {
+    limits: HashMap<MessageType, RateLimit>,
+    /// Number of objects we requested from the peer and haven't received yet, by message type.
+    requested: HashMap<MessageType, usize>,
+}
+
+impl MessageRateLimiter {
+    /// Notes a message sent to the peer. The objects it requests are exempt from the rate limits
+    /// when they arrive.
+    pub fn note_sent(&mut self, msg: &Message) {
+        let (vectors, block_type) = match msg {
+            Message::GetData(vectors) => (vectors, MessageType::Block),
+            Message::GetHeader(vectors) => (vectors, MessageType::Header),
+            _ => return,
+        };
+        for vector in vectors {
+            let ty = match vector.ty {
+                InvVectorType::Transaction => MessageType::Tx,
+                InvVectorType::Block => block_type,
+                InvVectorType::Error => continue,
+            };
+            // Objects the peer never sends stay requested, so keep this bounded to a single
+            // request per message type.
+            let requested = self.requested.entry(ty).or_insert(0);
+            *requested = (*requested + 1).min(InvVector::VECTORS_MAX_COUNT);
+        }
+    }
+
+    /// Notes a received message and returns whether it is still within the rate limit.
+    pub fn note(&mut self, ty: MessageType) -> bool {
+        if let Some(requested) = self.requested.get_mut(&ty) {
+            if *requested > 0 {
+                *requested -= 1;
+                return true;
+            }
+        }
+
+        match self.limits.get_mut(&ty) {
+            Some(limit) => limit.note_single(),
+            None => true,
+        }
+    }
+}
@@ -6,20 +6,23 @@ use atomic::Ordering;
 use parking_lot::RwLock;
 
 use blockchain::Blockchain;
+use database::Environment;
 use network_primitives::networks::NetworkId;
-use network_primitives::time::NetworkTime;
+use network_primitives::time::{NetworkTime, TimeOffsetEstimate};
 use utils::mutable_once::MutableOnce;
 use utils::observer::PassThroughNotifier;
 use utils::timers::Timers;
 
 use crate::address::peer_address_book::PeerAddressBook;
+use crate::ban_list::{BanEntry, BanList, BanTarget};
+use crate::capture::TrafficCapture;
 use crate::connection::close_type::CloseType;
 use crate::connection::connection_info::ConnectionState;
 use crate::connection::connection_pool::ConnectionPool;
 use crate::connection::connection_pool::ConnectionPoolEvent;
 use crate::network_config::NetworkConfig;
 use crate::Peer;
-use crate::peer_scorer::PeerScorer;
+use crate::peer_scorer::{PeerScorer, ScoringStrategy};
 use parking_lot::RwLockReadGuard;
 use std::cmp;
 use rand::Rng;
@@ -39,15 +42,20 @@ pub enum NetworkEvent {
     PeerJoined(Peer),
     PeerLeft(Peer),
     PeersChanged,
+    /// The peers agree that the local clock is off by the given number of milliseconds.
+    ClockSkew(i64),
 }
 
 pub struct Network {
     pub network_config: Arc<NetworkConfig>,
     pub network_time: Arc<NetworkTime>,
+    time_offset: RwLock<TimeOffsetEstimate>,
     auto_connect: Atomic<bool>,
     backed_off: Atomic<bool>,
     backoff: Atomic<Duration>,
     pub addresses: Arc<PeerAddressBook>,
+    pub ban_list: Arc<BanList>,
+    pub traffic_capture: Arc<TrafficCapture>,
     pub connections: Arc<ConnectionPool>,
     scorer: Arc<RwLock<PeerScorer>>,
     timers: Timers<NetworkTimer>,
@@ -71,21 +79,26 @@ impl Network {
 
     pub const SIGNALING_ENABLED: bool = true;
 
-    pub fn new(blockchain: Arc<Blockchain<'static>>, network_config: NetworkConfig, network_time: Arc<NetworkTime>, network_id: NetworkId) -> Result<Arc<Self>, Error> {
+    pub fn new(env: &'static Environment, blockchain: Arc<Blockchain<'static>>, network_config: NetworkConfig, network_time: Arc<NetworkTime>, network_id: NetworkId) -> Result<Arc<Self>, Error> {
         if !network_config.is_initialized() {
             return Err(Error::UninitializedPeerKey);
         }
 
         let net_config = Arc::new(network_config);
-        let addresses = Arc::new(PeerAddressBook::new(net_config.clone(), network_id)?);
-        let connections = ConnectionPool::new(addresses.clone(), net_config.clone(), blockchain)?;
+        let addresses = Arc::new(PeerAddressBook::new(net_config.clone(), network_id, env)?);
+        let ban_list = Arc::new(BanList::new(env));
+        let traffic_capture = Arc::new(TrafficCapture::new(net_config.capture_dir().cloned()));
+        let connections = ConnectionPool::new(addresses.clone(), ban_list.clone(), Arc::clone(&traffic_capture), net_config.clone(), blockchain)?;
         let this = Arc::new(Network {
             network_config: net_config.clone(),
             network_time,
+            time_offset: RwLock::new(TimeOffsetEstimate::default()),
             auto_connect: Atomic::new(false),
             backed_off: Atomic::new(false),
             backoff: Atomic::new(Self::CONNECT_BACKOFF_INITIAL),
             addresses: addresses.clone(),
+            ban_list,
+            traffic_capture,
             connections: connections.clone(),
             scorer: Arc::new(RwLock::new(PeerScorer::new(net_config, addresses, connections.clone()))),
             timers: Timers::new(),
@@ -126,6 +139,12 @@ impl Network {
             Self::housekeeping(Arc::clone(&connections), Arc::clone(&scorer));
         }, Self::HOUSEKEEPING_INTERVAL);
 
+        // Passive nodes only have inbound peers, so they never establish consensus without them.
+        if !self.network_config.dial_outbound() {
+            info!("Passive mode, waiting for inbound connections");
+            self.set_allow_inbound_connections(true);
+        }
+
         // Start connecting to peers.
         self.check_peer_count();
         Ok(())
@@ -138,6 +157,8 @@ impl Network {
 
         self.connections.disconnect();
         self.connections.set_allow_inbound_exchange(false);
+
+        self.addresses.persist();
     }
 
     fn on_peer_joined(&self, peer: Peer) {
@@ -179,6 +200,7 @@ impl Network {
 
     fn check_peer_count(&self) {
         if self.auto_connect.load(Ordering::Relaxed)
+            && self.network_config.dial_outbound()
             && self.addresses.seeded()
             && !self.scorer.read().is_good_peer_set()
             && self.connections.connecting_count() < Self::CONNECTING_COUNT_MAX {
@@ -216,7 +238,7 @@ impl Network {
 
                     // Allow inbound connections. This is important for the first seed node on the network which
                     // will never establish a consensus and needs to accept incoming connections eventually.
-                    self.connections.set_allow_inbound_connections(true);
+                    self.set_allow_inbound_connections(true);
                 }
                 return;
             }
@@ -233,7 +255,6 @@ impl Network {
 
     fn update_time_offset(&self) {
         let mut offsets = Vec::new();
-        offsets.push(0i64);
         let pool_state = self.connections.state();
         for connection_info in pool_state.connection_iter() {
             if connection_info.state() == ConnectionState::Established {
@@ -242,17 +263,20 @@ impl Network {
                 }
             }
         }
+        drop(pool_state);
 
-        offsets.sort_by(|a, b| { i64::cmp(a, b) } );
+        let estimate = TimeOffsetEstimate::new(self.network_time.offset(), offsets);
+        self.network_time.set_offset(estimate.offset);
 
-        let offsets_len = offsets.len();
-        let time_offset = if offsets_len % 2 == 0 {
-            (offsets[(offsets_len / 2) - 1] + offsets[(offsets_len / 2) - 1]) / 2
-        } else {
-            offsets[(offsets_len - 1) / 2]
-        };
+        // Only warn when the clock goes off, not on every peer that confirms it.
+        let was_clock_off = self.time_offset.read().is_clock_off();
+        let skew = if estimate.is_clock_off() && !was_clock_off { estimate.target } else { None };
+        *self.time_offset.write() = estimate;
 
-        self.network_time.set_offset(time_offset);
+        if let Some(target) = skew {
+            warn!("The local clock is off by {} ms according to the peers, please check the system time", target);
+            self.notifier.read().notify(NetworkEvent::ClockSkew(target));
+        }
     }
 
     fn housekeeping(connections: Arc<ConnectionPool>, scorer: Arc<RwLock<PeerScorer>>) {
@@ -340,11 +364,43 @@ impl Network {
         self.connections.peer_count()
     }
 
+    /// Allows or rejects inbound connections. Outbound-only nodes always reject them.
     pub fn set_allow_inbound_connections(&self, allow_inbound_connections: bool) {
-        self.connections.set_allow_inbound_connections(allow_inbound_connections);
+        self.connections.set_allow_inbound_connections(allow_inbound_connections && self.network_config.accept_inbound());
+    }
+
+    /// The current estimate of the offset to the peers' clocks.
+    pub fn time_offset(&self) -> RwLockReadGuard<TimeOffsetEstimate> {
+        self.time_offset.read()
     }
 
     pub fn scorer(&self) -> RwLockReadGuard<PeerScorer> {
         self.scorer.read()
     }
+
+    /// Replaces the default strategy for scoring peers and their connections.
+    pub fn set_scoring_strategy(&self, strategy: Arc<dyn ScoringStrategy>) {
+        self.scorer.write().set_strategy(strategy);
+    }
+
+    /// Adds a ban to the persistent ban list and closes the connections it applies to.
+    pub fn ban(&self, entry: BanEntry) {
+        let target = entry.target.clone();
+        self.ban_list.ban(entry);
+        self.connections.close_banned(&target);
+    }
+
+    /// Lifts a ban from the persistent ban list and the bans of the peer addresses it applies to.
+    /// Returns the number of bans lifted.
+    pub fn unban(&self, target: &BanTarget) -> usize {
+        let count = self.addresses.unban(Some(target));
+        if self.ban_list.unban(target) { count + 1 } else { count }
+    }
+
+    /// Lifts all bans of the ban list and the peer addresses. Returns the number of bans lifted.
+    pub fn clear_bans(&self) -> usize {
+        let count = self.ban_list.bans().len();
+        self.ban_list.clear();
+        count + self.addresses.unban(None)
+    }
 }
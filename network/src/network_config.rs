@@ -1,25 +1,64 @@
+use std::collections::HashSet;
+use std::fmt;
 use std::fs;
+use std::iter;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::time::SystemTime;
 
 use beserial::{Deserialize, Serialize};
+use hex::FromHex;
 use keys::{KeyPair, PublicKey};
-use network_primitives::address::net_address::NetAddress;
+use network_primitives::address::net_address::{NetAddress, NetAddressRange};
 use network_primitives::address::peer_address::{PeerAddress, PeerAddressType};
 use network_primitives::address::PeerId;
 use network_primitives::address::seed_list::SeedList;
+use network_primitives::address::dns_seed::DnsSeed;
 use network_primitives::protocol::{Protocol, ProtocolFlags};
 use network_primitives::services::Services;
+use url::{Host, Url};
 use utils::time::systemtime_to_timestamp;
 use network_primitives::address::{PeerUri};
 
-use crate::error::Error;
+use crate::error::{Error, ProxyConfigError};
+#[cfg(feature = "memory-transport")]
+use crate::memory::MemoryTransport;
+use crate::message_limits::MessageLimits;
 
 
-// One or multiple seed nodes. Either a peer URI or a http(s) URL to a seed list
+// One or multiple seed nodes. Either a peer URI, a http(s) URL to a seed list or a host name
+// whose DNS records point to seed nodes
 #[derive(Clone, Debug)]
 pub enum Seed {
     Peer(Box<PeerUri>),
-    List(Box<SeedList>)
+    List(Box<SeedList>),
+    Dns(Box<DnsSeed>),
+}
+
+/// A peer that may connect in private network mode. Either a peer URI, which is also dialed, or
+/// just a peer ID, which is only accepted on inbound connections.
+#[derive(Clone, Debug)]
+pub enum AllowedPeer {
+    Peer(Box<PeerUri>),
+    PeerId(PeerId),
+}
+
+impl AllowedPeer {
+    /// Returns the peer ID of the allowed peer. For URIs it is derived from the public key if
+    /// the URI contains one.
+    pub fn peer_id(&self) -> Option<PeerId> {
+        match self {
+            AllowedPeer::Peer(peer_uri) => {
+                if let Some(public_key) = peer_uri.public_key() {
+                    PublicKey::from_hex(public_key).ok().map(|public_key| PeerId::from(&public_key))
+                } else {
+                    peer_uri.peer_id().and_then(|peer_id| PeerId::from_str(peer_id).ok())
+                }
+            },
+            AllowedPeer::PeerId(peer_id) => Some(peer_id.clone()),
+        }
+    }
 }
 
 
@@ -30,8 +69,20 @@ pub struct NetworkConfig {
     peer_id: Option<PeerId>,
     services: Services,
     protocol_config: ProtocolConfig,
+    listen_addresses: Vec<IpAddr>,
+    additional_hosts: Vec<String>,
     user_agent: Option<String>,
     additional_seeds: Vec<Seed>,
+    allowed_peers: Option<Vec<AllowedPeer>>,
+    allowed_peer_ids: HashSet<PeerId>,
+    proxy: Option<ProxyConfig>,
+    connection_mode: ConnectionMode,
+    message_limits: MessageLimits,
+    scoring: ScoringConfig,
+    dns_resolver: Option<SocketAddr>,
+    capture_dir: Option<PathBuf>,
+    #[cfg(feature = "memory-transport")]
+    memory_transport: Option<MemoryTransport>,
 }
 
 impl NetworkConfig {
@@ -46,8 +97,20 @@ impl NetworkConfig {
                 port,
                 reverse_proxy_config,
             },
+            listen_addresses: Vec::new(),
+            additional_hosts: Vec::new(),
             user_agent: None,
-            additional_seeds: Vec::new()
+            additional_seeds: Vec::new(),
+            allowed_peers: None,
+            allowed_peer_ids: HashSet::new(),
+            proxy: None,
+            connection_mode: ConnectionMode::default(),
+            message_limits: MessageLimits::default(),
+            scoring: ScoringConfig::default(),
+            dns_resolver: None,
+            capture_dir: None,
+            #[cfg(feature = "memory-transport")]
+            memory_transport: None,
         }
     }
 
@@ -64,8 +127,49 @@ impl NetworkConfig {
                 identity_password,
                 reverse_proxy_config,
             },
+            listen_addresses: Vec::new(),
+            additional_hosts: Vec::new(),
+            user_agent: None,
+            additional_seeds: Vec::new(),
+            allowed_peers: None,
+            allowed_peer_ids: HashSet::new(),
+            proxy: None,
+            connection_mode: ConnectionMode::default(),
+            message_limits: MessageLimits::default(),
+            scoring: ScoringConfig::default(),
+            dns_resolver: None,
+            capture_dir: None,
+            #[cfg(feature = "memory-transport")]
+            memory_transport: None,
+        }
+    }
+
+    /// Creates a config for a node in a private cluster that only talks raw TCP with other cluster
+    /// nodes. Connections are encrypted and both sides authenticate with their peer keys.
+    pub fn new_tcp_network_config(host: String, port: u16) -> Self {
+        Self {
+            protocol_mask: ProtocolFlags::TCP,
+            key_pair: None,
+            peer_id: None,
+            services: Services::full(),
+            protocol_config: ProtocolConfig::Tcp {
+                host,
+                port,
+            },
+            listen_addresses: Vec::new(),
+            additional_hosts: Vec::new(),
             user_agent: None,
-            additional_seeds: Vec::new()
+            additional_seeds: Vec::new(),
+            allowed_peers: None,
+            allowed_peer_ids: HashSet::new(),
+            proxy: None,
+            connection_mode: ConnectionMode::default(),
+            message_limits: MessageLimits::default(),
+            scoring: ScoringConfig::default(),
+            dns_resolver: None,
+            capture_dir: None,
+            #[cfg(feature = "memory-transport")]
+            memory_transport: None,
         }
     }
 
@@ -76,8 +180,20 @@ impl NetworkConfig {
             peer_id: None,
             services: Services::full(),
             protocol_config: ProtocolConfig::Dumb,
+            listen_addresses: Vec::new(),
+            additional_hosts: Vec::new(),
             user_agent: None,
-            additional_seeds: Vec::new()
+            additional_seeds: Vec::new(),
+            allowed_peers: None,
+            allowed_peer_ids: HashSet::new(),
+            proxy: None,
+            connection_mode: ConnectionMode::default(),
+            message_limits: MessageLimits::default(),
+            scoring: ScoringConfig::default(),
+            dns_resolver: None,
+            capture_dir: None,
+            #[cfg(feature = "memory-transport")]
+            memory_transport: None,
         }
     }
 
@@ -154,11 +270,163 @@ impl NetworkConfig {
         self.additional_seeds = seeds
     }
 
+    pub fn allowed_peers(&self) -> Option<&Vec<AllowedPeer>> {
+        self.allowed_peers.as_ref()
+    }
+
+    /// Switches to private network mode: Only the given peers are dialed and accepted, and
+    /// addresses are neither learned from nor relayed to other peers.
+    pub fn set_allowed_peers(&mut self, allowed_peers: Vec<AllowedPeer>) {
+        self.allowed_peer_ids = allowed_peers.iter()
+            .filter_map(AllowedPeer::peer_id)
+            .collect();
+        self.allowed_peers = Some(allowed_peers);
+    }
+
+    /// Whether the node only talks to the allowed peers.
+    pub fn is_private(&self) -> bool {
+        self.allowed_peers.is_some()
+    }
+
+    /// Whether we may connect to the given peer. Outside of private network mode all peers are
+    /// allowed.
+    pub fn is_peer_allowed(&self, peer_id: &PeerId) -> bool {
+        !self.is_private() || self.allowed_peer_ids.contains(peer_id)
+    }
+
+    pub fn proxy(&self) -> Option<&ProxyConfig> {
+        self.proxy.as_ref()
+    }
+
+    /// Routes outbound peer connections and seed list requests through a SOCKS5 proxy.
+    pub fn set_proxy(&mut self, proxy: ProxyConfig) {
+        self.proxy = Some(proxy);
+    }
+
+    pub fn connection_mode(&self) -> ConnectionMode {
+        self.connection_mode
+    }
+
+    pub fn set_connection_mode(&mut self, connection_mode: ConnectionMode) {
+        self.connection_mode = connection_mode;
+    }
+
+    /// Whether we listen for inbound connections.
+    pub fn accept_inbound(&self) -> bool {
+        self.connection_mode != ConnectionMode::OutboundOnly
+    }
+
+    /// Whether we connect to peers on our own.
+    pub fn dial_outbound(&self) -> bool {
+        self.connection_mode != ConnectionMode::Passive
+    }
+
+    pub fn message_limits(&self) -> &MessageLimits {
+        &self.message_limits
+    }
+
+    /// Sets the limits on messages we accept from each peer.
+    pub fn set_message_limits(&mut self, message_limits: MessageLimits) {
+        self.message_limits = message_limits;
+    }
+
+    /// The name server used to resolve DNS seeds. If not set, the system's name server is used.
+    pub fn dns_resolver(&self) -> Option<SocketAddr> {
+        self.dns_resolver
+    }
+
+    pub fn set_dns_resolver(&mut self, dns_resolver: SocketAddr) {
+        self.dns_resolver = Some(dns_resolver);
+    }
+
+    /// The directory that traffic captures are written to. Captures can't be started if not set.
+    pub fn capture_dir(&self) -> Option<&PathBuf> {
+        self.capture_dir.as_ref()
+    }
+
+    pub fn set_capture_dir(&mut self, capture_dir: PathBuf) {
+        self.capture_dir = Some(capture_dir);
+    }
+
+    #[cfg(feature = "memory-transport")]
+    pub fn memory_transport(&self) -> Option<&MemoryTransport> {
+        self.memory_transport.as_ref()
+    }
+
+    /// Listens on and connects through a simulated network instead of sockets.
+    #[cfg(feature = "memory-transport")]
+    pub fn set_memory_transport(&mut self, memory_transport: MemoryTransport) {
+        self.memory_transport = Some(memory_transport);
+    }
+
+    pub fn scoring(&self) -> &ScoringConfig {
+        &self.scoring
+    }
+
+    pub fn set_scoring(&mut self, scoring: ScoringConfig) {
+        self.scoring = scoring;
+    }
+
     pub fn protocol_config(&self) -> &ProtocolConfig {
         &self.protocol_config
     }
 
+    /// The addresses of the interfaces we listen on. If none are set, we listen on all interfaces.
+    pub fn listen_addresses(&self) -> &[IpAddr] {
+        &self.listen_addresses
+    }
+
+    /// Listens on the given interfaces only, with a separate socket for each address. Listening
+    /// on both `0.0.0.0` and `::` gives separate IPv4 and IPv6 sockets.
+    pub fn set_listen_addresses(&mut self, listen_addresses: Vec<IpAddr>) {
+        self.listen_addresses = listen_addresses;
+    }
+
+    /// Host names or IP addresses we are reachable at besides the configured host.
+    pub fn additional_hosts(&self) -> &[String] {
+        &self.additional_hosts
+    }
+
+    pub fn set_additional_hosts(&mut self, additional_hosts: Vec<String>) {
+        self.additional_hosts = additional_hosts;
+    }
+
+    /// The host we advertise to a peer at the given address: the first IP address of the same
+    /// family among the configured host and the additional hosts, or the configured host if there
+    /// is none.
+    fn host_for(&self, net_address: &NetAddress) -> Option<&str> {
+        let host = match self.protocol_config {
+            ProtocolConfig::Ws { ref host, .. } | ProtocolConfig::Wss { ref host, .. } | ProtocolConfig::Tcp { ref host, .. } => host.as_str(),
+            _ => return None,
+        };
+        let ty = net_address.canonical().get_type();
+        iter::once(host).chain(self.additional_hosts.iter().map(String::as_str))
+            .find(|host| NetAddress::from_str(host.trim_matches(|c| c == '[' || c == ']'))
+                .map_or(false, |address| address.canonical().get_type() == ty))
+            .or(Some(host))
+    }
+
+    /// Our peer address with the configured host.
     pub fn peer_address(&self) -> PeerAddress {
+        self.build_peer_address(None)
+    }
+
+    /// Our peer address as advertised to a peer at the given address, see `additional_hosts`.
+    pub fn peer_address_for(&self, net_address: &NetAddress) -> PeerAddress {
+        self.build_peer_address(self.host_for(net_address))
+    }
+
+    /// Our peer addresses with the configured host and each of the additional hosts.
+    pub fn peer_addresses(&self) -> Vec<PeerAddress> {
+        iter::once(None)
+            .chain(self.additional_hosts.iter().map(|host| Some(host.as_str())))
+            .map(|host| self.build_peer_address(host))
+            .collect()
+    }
+
+    /// Builds our peer address, with the given host instead of the configured one if set.
+    fn build_peer_address(&self, host_override: Option<&str>) -> PeerAddress {
+        let advertised = |host: &String| host_override.map_or_else(|| host.clone(), str::to_string);
         // TODO Check PeerAddress globally reachable.
         let mut addr = PeerAddress {
             ty: match self.protocol_config {
@@ -172,12 +440,12 @@ impl NetworkConfig {
                 } => {
                     if let Some(reverse_proxy_config) = reverse_proxy_config.as_ref() {
                         if reverse_proxy_config.with_tls_termination {
-                            PeerAddressType::Wss(host.clone(), reverse_proxy_config.port)
+                            PeerAddressType::Wss(advertised(host), reverse_proxy_config.port)
                         } else {
-                            PeerAddressType::Ws(host.clone(), reverse_proxy_config.port)
+                            PeerAddressType::Ws(advertised(host), reverse_proxy_config.port)
                         }
                     } else {
-                        PeerAddressType::Ws(host.clone(), port)
+                        PeerAddressType::Ws(advertised(host), port)
                     }
                 },
                 ProtocolConfig::Wss {
@@ -187,11 +455,15 @@ impl NetworkConfig {
                     ..
                 } => {
                     if let Some(reverse_proxy_config) = reverse_proxy_config.as_ref() {
-                        PeerAddressType::Wss(host.clone(), reverse_proxy_config.port)
+                        PeerAddressType::Wss(advertised(host), reverse_proxy_config.port)
                     } else {
-                        PeerAddressType::Wss(host.clone(), port)
+                        PeerAddressType::Wss(advertised(host), port)
                     }
                 },
+                ProtocolConfig::Tcp {
+                    ref host,
+                    port,
+                } => PeerAddressType::Tcp(advertised(host), port),
             },
             services: self.services.provided,
             timestamp: systemtime_to_timestamp(SystemTime::now()),
@@ -216,11 +488,129 @@ impl NetworkConfig {
 #[derive(Debug, Clone)]
 pub struct ReverseProxyConfig {
     pub port: u16,
-    pub address: NetAddress,
-    pub header: String,
+    /// The addresses of the proxies that connections are accepted from.
+    pub trusted_proxies: Vec<NetAddressRange>,
+    pub client_address: ClientAddressSource,
     pub with_tls_termination: bool,
 }
 
+impl ReverseProxyConfig {
+    pub fn is_trusted(&self, net_address: &NetAddress) -> bool {
+        self.trusted_proxies.iter().any(|range| range.contains(net_address))
+    }
+}
+
+/// How a reverse proxy passes on the address of the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientAddressSource {
+    /// In an HTTP header of the WebSocket upgrade request, e.g. `x-forwarded-for`.
+    Header(String),
+    /// In a PROXY protocol (v1 or v2) header in front of the connection, for TCP load balancers.
+    ProxyProtocol,
+}
+
+/// A SOCKS5 proxy for outbound connections.
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Let the proxy resolve host names instead of resolving them locally.
+    pub remote_dns: bool,
+}
+
+impl ProxyConfig {
+    pub const DEFAULT_PORT: u16 = 1080;
+
+    /// The proxy URL in the format understood by HTTP clients. Fails if the host is not a host
+    /// name or an IP address.
+    pub fn url(&self) -> Result<Url, ProxyConfigError> {
+        let scheme = if self.remote_dns { "socks5h" } else { "socks5" };
+        // Parsing the host on its own rejects anything that would end up in other parts of the
+        // URL. IPv6 addresses are put in brackets when the host is formatted.
+        let host = match self.host.parse::<Ipv6Addr>() {
+            Ok(ip) => Host::Ipv6(ip),
+            Err(_) => Host::parse(&self.host)
+                .map_err(|_| ProxyConfigError::InvalidHost(self.host.clone()))?,
+        };
+        let mut url = Url::parse(&format!("{}://{}:{}", scheme, host, self.port))
+            .map_err(|_| ProxyConfigError::InvalidHost(self.host.clone()))?;
+
+        if let Some(ref username) = self.username {
+            url.set_username(username).map_err(|_| ProxyConfigError::InvalidCredentials)?;
+            url.set_password(self.password.as_ref().map(String::as_str)).map_err(|_| ProxyConfigError::InvalidCredentials)?;
+        }
+        Ok(url)
+    }
+}
+
+/// Which connections a node makes and accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionMode {
+    /// Connect to peers and accept inbound connections.
+    Normal,
+    /// Never dial peers, only accept inbound connections.
+    Passive,
+    /// Don't listen for inbound connections, e.g. if the node can only egress through a proxy.
+    OutboundOnly,
+}
+
+impl Default for ConnectionMode {
+    fn default() -> Self {
+        ConnectionMode::Normal
+    }
+}
+
+impl fmt::Display for ConnectionMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectionMode::Normal => write!(f, "normal"),
+            ConnectionMode::Passive => write!(f, "passive"),
+            ConnectionMode::OutboundOnly => write!(f, "outbound-only"),
+        }
+    }
+}
+
+/// Parameters for scoring peers and their connections.
+#[derive(Debug, Clone)]
+pub struct ScoringConfig {
+    /// Peers that are picked before other peers when connecting and scored higher.
+    pub preferred_peers: HashSet<PeerId>,
+    /// Peers whose connections are never recycled.
+    pub pinned_peers: HashSet<PeerId>,
+    /// Share of WebSocket and TCP connections below which those connections are scored higher.
+    pub protocol_distribution_target: f64,
+    /// Number of connections per subnet above which the connections of that subnet are scored
+    /// lower. Subnets are not taken into account if this is not set.
+    pub subnet_target: Option<usize>,
+    /// Weight of the ping latency in the connection score, between 0 and 1. The other components
+    /// of the default score weigh 0.8 in total.
+    pub latency_weight: f64,
+}
+
+impl ScoringConfig {
+    pub fn is_preferred(&self, peer_id: &PeerId) -> bool {
+        self.preferred_peers.contains(peer_id)
+    }
+
+    pub fn is_pinned(&self, peer_id: &PeerId) -> bool {
+        self.pinned_peers.contains(peer_id)
+    }
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        ScoringConfig {
+            preferred_peers: HashSet::new(),
+            pinned_peers: HashSet::new(),
+            protocol_distribution_target: 0.15,
+            subnet_target: None,
+            latency_weight: 0.2,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ProtocolConfig {
     Dumb,
@@ -237,6 +627,10 @@ pub enum ProtocolConfig {
         reverse_proxy_config: Option<ReverseProxyConfig>,
     },
     Rtc,
+    Tcp {
+        host: String,
+        port: u16,
+    },
 }
 
 impl From<&ProtocolConfig> for Protocol {
@@ -252,6 +646,7 @@ impl From<&ProtocolConfig> for Protocol {
                 }
             },
             ProtocolConfig::Wss { .. } => Protocol::Wss,
+            ProtocolConfig::Tcp { .. } => Protocol::Tcp,
         }
     }
 }
@@ -157,6 +157,7 @@ pub enum PeerProtocol {
     Wss,
     Rtc,
     Ws,
+    Tcp,
     Unknown,
 }
 
@@ -167,6 +168,7 @@ impl Display for PeerProtocol {
             PeerProtocol::Wss => "websocket-secure",
             PeerProtocol::Ws => "websocket",
             PeerProtocol::Rtc => "webrtc",
+            PeerProtocol::Tcp => "tcp",
             PeerProtocol::Unknown => "unknown",
         })
     }
@@ -179,6 +181,7 @@ impl From<Protocol> for PeerProtocol {
             Protocol::Ws => PeerProtocol::Ws,
             Protocol::Wss => PeerProtocol::Wss,
             Protocol::Rtc => PeerProtocol::Rtc,
+            Protocol::Tcp => PeerProtocol::Tcp,
         }
     }
 }
@@ -6,8 +6,7 @@ use std::hash::Hasher;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 
-use futures::sync::mpsc::*;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 
 use network_messages::{Message, MessageNotifier};
 use utils::observer::Notifier;
@@ -16,14 +15,16 @@ use crate::connection::close_type::CloseType;
 use crate::connection::network_connection::AddressInfo;
 use crate::connection::network_connection::ClosedFlag;
 use crate::connection::network_connection::NetworkConnection;
+use crate::message_limits::MessageRateLimiter;
 #[cfg(feature = "metrics")]
 use crate::network_metrics::MessageMetrics;
-use crate::websocket::Message as WebSocketMessage;
+use crate::websocket::Error;
 
-use super::sink::PeerSink;
+use super::sink::{PeerSink, SendError};
 use super::stream::PeerStreamEvent;
 use std::time::Instant;
 use atomic::Atomic;
+use tokio::clock;
 
 #[derive(Clone)]
 pub struct PeerChannel {
@@ -34,6 +35,7 @@ pub struct PeerChannel {
     closed_flag: ClosedFlag,
     pub last_message_received: Arc<Atomic<Instant>>,
     close_event_sent: Arc<AtomicBool>,
+    rate_limiter: Arc<Mutex<MessageRateLimiter>>,
 
     #[cfg(feature = "metrics")]
     pub message_metrics: Arc<MessageMetrics>,
@@ -46,7 +48,8 @@ impl PeerChannel {
 
         #[cfg(feature = "metrics")]
         let message_metrics = Arc::new(MessageMetrics::new());
-        let last_message_received = Arc::new(Atomic::new(Instant::now()));
+        // Use the clock of the timers, so the connectivity check also works with a simulated one.
+        let last_message_received = Arc::new(Atomic::new(clock::now()));
 
         let msg_notifier1 = msg_notifier.clone();
         let close_notifier1 = close_notifier.clone();
@@ -56,6 +59,9 @@ impl PeerChannel {
         let message_metrics1 = message_metrics.clone();
 
         let info = network_connection.address_info();
+        let peer_sink = network_connection.peer_sink();
+        let rate_limiter = Arc::new(Mutex::new(network_connection.message_limits().rate_limiter()));
+        let rate_limiter1 = rate_limiter.clone();
         let close_event_sent = Arc::new(AtomicBool::new(false));
         let close_event_sent_inner = close_event_sent.clone();
         network_connection.notifier.write().register(move |e: PeerStreamEvent| {
@@ -63,8 +69,18 @@ impl PeerChannel {
                 PeerStreamEvent::Message(msg) => {
                     #[cfg(feature = "metrics")]
                     let start = Instant::now();
-                    last_message_received1.store(Instant::now(), Ordering::Relaxed);
+                    last_message_received1.store(clock::now(), Ordering::Relaxed);
                     let msg_type = msg.ty();
+
+                    // Ban peers flooding us with messages.
+                    if !rate_limiter1.lock().note(msg_type) {
+                        peer_sink.close(CloseType::RateLimitExceeded, Some(format!("Too many {:?} messages", msg_type)));
+                        if !close_event_sent_inner.swap(true, Ordering::AcqRel) {
+                            close_notifier1.read().notify(CloseType::RateLimitExceeded);
+                        }
+                        return;
+                    }
+
                     msg_notifier1.notify(msg);
                     #[cfg(feature = "metrics")] {
                         let time: usize = usize::try_from(start.elapsed().as_micros()).expect("Fatal error while converting processing time to usize");
@@ -82,7 +98,11 @@ impl PeerChannel {
                     // Only send close event once, i.e., if close_event_sent was false.
                     if !close_event_sent_inner.swap(true, Ordering::AcqRel) {
                         debug!("Stream with peer closed with error: {} ({})", error.as_ref(), info);
-                        close_notifier1.read().notify(CloseType::NetworkError);
+                        let ty = match error.as_ref() {
+                            Error::MessageSizeExceeded => CloseType::MessageSizeExceeded,
+                            _ => CloseType::NetworkError,
+                        };
+                        close_notifier1.read().notify(ty);
                     }
                 }
             }
@@ -96,19 +116,23 @@ impl PeerChannel {
             closed_flag: network_connection.closed_flag(),
             last_message_received,
             close_event_sent,
+            rate_limiter,
 
             #[cfg(feature = "metrics")]
             message_metrics,
         }
     }
 
-    pub fn send(&self, msg: Message) -> Result<(), SendError<WebSocketMessage>> {
+    pub fn send(&self, msg: Message) -> Result<(), SendError> {
+        self.rate_limiter.lock().note_sent(&msg);
         self.peer_sink.send(msg)
     }
 
     pub fn send_or_close(&self, msg: Message) {
-        if self.peer_sink.send(msg).is_err() {
-            self.peer_sink.close(CloseType::SendFailed, Some("SendFailed".to_string()));
+        match self.send(msg) {
+            Err(SendError::QueueFull) => self.peer_sink.close(CloseType::SendQueueFull, Some("SendQueueFull".to_string())),
+            Err(SendError::Disconnected) => self.peer_sink.close(CloseType::SendFailed, Some("SendFailed".to_string())),
+            Ok(()) => {},
         }
     }
 
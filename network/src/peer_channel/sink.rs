@@ -2,8 +2,10 @@ use std::fmt;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::sync::Arc;
 
 use futures::sync::mpsc::*;
+use parking_lot::Mutex;
 
 use network_messages::Message;
 use utils::unique_id::UniqueId;
@@ -12,29 +14,50 @@ use crate::connection::close_type::CloseType;
 use crate::websocket::Message as WebSocketMessage;
 use crate::connection::network_connection::ClosedFlag;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SendError {
+    /// The connection is gone.
+    Disconnected,
+    /// The peer didn't read the messages we queued for it so far.
+    QueueFull,
+}
+
 #[derive(Clone)]
 pub struct PeerSink {
-    sink: UnboundedSender<WebSocketMessage>,
+    sink: Arc<Mutex<Sender<WebSocketMessage>>>,
+    close_sink: Arc<Mutex<Sender<WebSocketMessage>>>,
     unique_id: UniqueId,
     closed_flag: ClosedFlag,
 }
 
 impl PeerSink {
-    pub fn new(channel: UnboundedSender<WebSocketMessage>, unique_id: UniqueId, closed_flag: ClosedFlag) -> Self {
+    /// Creates a sink that sends over the given bounded channel. The size of the channel limits
+    /// the number of queued messages.
+    pub fn new(channel: Sender<WebSocketMessage>, unique_id: UniqueId, closed_flag: ClosedFlag) -> Self {
+        // Every sender has a slot of its own in the channel. The close frame is sent over a
+        // separate sender, so that it can be queued even if the channel is full.
+        let close_sink = channel.clone();
         PeerSink {
-            sink: channel,
+            sink: Arc::new(Mutex::new(channel)),
+            close_sink: Arc::new(Mutex::new(close_sink)),
             unique_id,
             closed_flag,
         }
     }
 
-    pub fn send(&self, msg: Message) -> Result<(), SendError<WebSocketMessage>> {
+    pub fn send(&self, msg: Message) -> Result<(), SendError> {
         // Do not send messages over already closed connections.
         // Stop sending silently until connection is really closed.
         if self.closed_flag.is_closed() {
             return Ok(());
         }
-        self.sink.unbounded_send(WebSocketMessage::Message(msg))
+        self.sink.lock().try_send(WebSocketMessage::Message(msg)).map_err(|error| {
+            if error.is_full() {
+                SendError::QueueFull
+            } else {
+                SendError::Disconnected
+            }
+        })
     }
 
     /// Closes the connection.
@@ -47,7 +70,8 @@ impl PeerSink {
         }
         self.closed_flag.set_close_type(ty);
         debug!("Closing connection, reason: {:?} ({:?})", ty, reason);
-        if let Err(error) = self.sink.unbounded_send(WebSocketMessage::Close(None)) {
+        // The close frame uses the slot of its own sender, so that we can always close the connection.
+        if let Err(error) = self.close_sink.lock().try_send(WebSocketMessage::Close(None)) {
             debug!("Error closing connection: {}", error);
         }
 
@@ -11,8 +11,9 @@ use network_messages::Message;
 use utils::observer::PassThroughNotifier;
 use utils::unique_ptr::UniquePtr;
 
+use crate::capture::Direction;
 use crate::connection::close_type::CloseType;
-use crate::connection::network_connection::ClosedFlag;
+use crate::connection::network_connection::{ClosedFlag, ConnectionRecorder};
 use crate::websocket::{Error, SharedNimiqMessageStream};
 use crate::websocket::Message as WebSocketMessage;
 use futures::future;
@@ -26,15 +27,17 @@ pub enum PeerStreamEvent {
 pub struct PeerStream {
     stream: SharedNimiqMessageStream,
     closed_flag: ClosedFlag,
+    recorder: ConnectionRecorder,
     pub notifier: Arc<RwLock<PassThroughNotifier<'static, PeerStreamEvent>>>,
 }
 
 impl PeerStream {
-    pub fn new(stream: SharedNimiqMessageStream, notifier: Arc<RwLock<PassThroughNotifier<'static, PeerStreamEvent>>>, closed_flag: ClosedFlag) -> Self {
+    pub fn new(stream: SharedNimiqMessageStream, notifier: Arc<RwLock<PassThroughNotifier<'static, PeerStreamEvent>>>, closed_flag: ClosedFlag, recorder: ConnectionRecorder) -> Self {
         PeerStream {
             stream,
             notifier,
             closed_flag,
+            recorder,
         }
     }
 
@@ -44,10 +47,12 @@ impl PeerStream {
         let error_notifier = self.notifier;
         let msg_closed_flag = self.closed_flag.clone();
         let error_closed_flag = self.closed_flag;
+        let recorder = self.recorder;
 
         stream.for_each(move |msg| {
             match msg {
                 WebSocketMessage::Message(msg) => {
+                    recorder.record(Direction::Inbound, &msg);
                     // Ignore messages from peer if connection has been closed by us, but await close frame.
                     if !msg_closed_flag.is_closed() {
                         msg_notifier.read().notify(PeerStreamEvent::Message(msg));
@@ -12,25 +12,177 @@ use network_primitives::services::ServiceFlags;
 use crate::{
     address::{
         peer_address_book::PeerAddressBook,
-        peer_address_state::PeerAddressState,
+        peer_address_state::{PeerAddressInfo, PeerAddressState},
     },
     connection::{
         close_type::CloseType,
         connection_info::{ConnectionInfo, ConnectionState},
-        connection_pool::{ConnectionId, ConnectionPool},
+        connection_pool::{ConnectionId, ConnectionPool, ConnectionPoolState},
         network_agent::NetworkAgent,
     },
-    network_config::NetworkConfig,
+    network_config::{NetworkConfig, ScoringConfig},
 };
 use crate::address::peer_address_book::PeerAddressBookState;
 use parking_lot::RwLockReadGuard;
 
 pub type Score = f64;
 
+/// A connection score and the components it was computed from.
+#[derive(Clone, Debug, Default)]
+pub struct ScoreBreakdown {
+    pub components: Vec<(&'static str, Score)>,
+    pub total: Score,
+}
+
+/// What a `ScoringStrategy` may take into account besides the connection it scores.
+pub struct ScoringContext<'a> {
+    pub state: &'a ConnectionPoolState,
+    pub config: &'a ScoringConfig,
+    /// Share of WebSocket and TCP connections among all connections.
+    pub distribution: f64,
+    pub peer_count_full_ws_outbound: usize,
+}
+
+/// Decides which peers we connect to and which connections are recycled first.
+pub trait ScoringStrategy: Send + Sync {
+    /// Scores a known address we might connect to. Addresses with a negative score are never
+    /// picked, higher scores are picked first. Addresses that we must not connect to are filtered
+    /// before.
+    fn score_address(&self, peer_address: &PeerAddress, peer_address_info: &PeerAddressInfo, config: &ScoringConfig) -> i32;
+
+    /// Scores an established connection. Connections with lower scores are recycled first.
+    fn score_connection(&self, connection_info: &ConnectionInfo, context: &ScoringContext) -> ScoreBreakdown;
+}
+
+/// Scores connections by age, direction, node type, protocol, latency and, if configured, by
+/// subnet diversity.
+pub struct DefaultScoringStrategy;
+
+impl ScoringStrategy for DefaultScoringStrategy {
+    fn score_address(&self, _peer_address: &PeerAddress, peer_address_info: &PeerAddressInfo, _config: &ScoringConfig) -> i32 {
+        // Give all peers the same base score. Penalize peers with failed connection attempts.
+        let score = 1;
+        match peer_address_info.state {
+            PeerAddressState::Banned => -1,
+            PeerAddressState::New | PeerAddressState::Tried => score,
+            PeerAddressState::Failed => {
+                // Don't pick failed addresses when they have failed the maximum number of times.
+                (1 - ((peer_address_info.failed_attempts + 1) as i32 / peer_address_info.max_failed_attempts() as i32)) * score
+            },
+            _ => -1
+        }
+    }
+
+    fn score_connection(&self, connection_info: &ConnectionInfo, context: &ScoringContext) -> ScoreBreakdown {
+        // Connection age
+        let score_age = Self::score_connection_age(connection_info);
+
+        // Connection type (inbound/outbound)
+        let score_outbound = if connection_info.network_connection().expect("Missing network connection").outbound() {
+            0.0
+        } else {
+            1.0
+        };
+
+        let peer_address = connection_info.peer_address().expect("Missing peer address");
+
+        // Node type (full/light/nano)
+        let score_type: Score;
+        if peer_address.services.is_full_node() {
+            score_type = 1.0;
+        } else if peer_address.services.is_light_node() {
+            score_type = 0.5;
+        } else {
+            score_type = 0.0;
+        }
+
+        // Protocol: Prefer WebSocket over WebRTC over Dumb.
+        let score_protocol: Score = match peer_address.protocol() {
+            Protocol::Wss | Protocol::Ws | Protocol::Tcp => {
+                // Boost WebSocket score when low on WebSocket connections.
+                if context.distribution < context.config.protocol_distribution_target || context.peer_count_full_ws_outbound <= PeerScorer::PEER_COUNT_MIN_FULL_WS_OUTBOUND {
+                    1.0
+                } else {
+                    0.6
+                }
+            },
+            Protocol::Rtc => 0.3,
+            Protocol::Dumb => 0.0,
+        };
+
+        // Connection speed, based on ping-pong latency median
+        let score_speed = Self::score_speed(connection_info.statistics().latency_median());
+
+        let latency_weight = f64::max(f64::min(context.config.latency_weight, 1.0), 0.0);
+        let mut total = 0.15 * score_age + 0.25 * score_outbound + 0.2 * score_type + 0.2 * score_protocol + latency_weight * score_speed;
+
+        let mut components = vec![
+            ("age", score_age),
+            ("outbound", score_outbound),
+            ("type", score_type),
+            ("protocol", score_protocol),
+            ("speed", score_speed),
+        ];
+
+        // Subnet: Penalize connections from subnets we have many connections to.
+        if let Some(subnet_target) = context.config.subnet_target {
+            let net_address = connection_info.network_connection().expect("Missing network connection").net_address();
+            let count = context.state.get_num_connections_by_subnet(&net_address);
+            let score_subnet = if count > subnet_target { subnet_target as f64 / count as f64 } else { 1.0 };
+            total *= score_subnet;
+            components.push(("subnet", score_subnet));
+        }
+
+        if context.config.is_preferred(&peer_address.peer_id) {
+            total += Self::PREFERRED_PEER_BONUS;
+            components.push(("preferred", Self::PREFERRED_PEER_BONUS));
+        }
+
+        ScoreBreakdown { components, total }
+    }
+}
+
+impl DefaultScoringStrategy {
+    const BEST_AGE_FULL: Duration = Duration::from_secs(24 * 60 * 60); // 24 hours
+
+    const BEST_AGE_LIGHT: Duration = Duration::from_secs(15 * 60); // 15 minutes
+    const MAX_AGE_LIGHT: Duration = Duration::from_secs(6 * 60 * 60); // 6 hours
+
+    const BEST_AGE_NANO: Duration = Duration::from_secs(5 * 60); // 5 minutes
+    const MAX_AGE_NANO: Duration = Duration::from_secs(30 * 60); // 30 minutes
+
+    const PREFERRED_PEER_BONUS: Score = 1.0;
+
+    /// Scores the median ping latency of a connection.
+    pub fn score_speed(median_latency: f64) -> Score {
+        if median_latency > 0.0 && median_latency < NetworkAgent::PING_TIMEOUT.as_secs() as f64 {
+            1.0 - median_latency / NetworkAgent::PING_TIMEOUT.as_secs() as f64
+        } else { 0.0 }
+    }
+
+    fn score_by_age(age: u128, best_age: u128, max_age: u128) -> Score {
+        f64::max(f64::min(1. - (age as f64 - best_age as f64) / max_age as f64, 1.), 0.)
+    }
+
+    fn score_connection_age(connection_info: &ConnectionInfo) -> Score {
+        let age = connection_info.age_established().as_millis();
+        let services = connection_info.peer_address().expect("No peer address").services;
+
+        if services.is_full_node() {
+            (age as f64 / (2.0 * (Self::BEST_AGE_FULL.as_millis()) as f64) + 0.5) as Score
+        } else if services.is_light_node() {
+            Self::score_by_age(age, Self::BEST_AGE_LIGHT.as_millis(), Self::MAX_AGE_LIGHT.as_millis()) as Score
+        } else {
+            Self::score_by_age(age, Self::BEST_AGE_NANO.as_millis(), Self::MAX_AGE_NANO.as_millis()) as Score
+        }
+    }
+}
+
 pub struct PeerScorer {
     network_config: Arc<NetworkConfig>,
     addresses: Arc<PeerAddressBook>,
     connections: Arc<ConnectionPool>,
+    strategy: Arc<dyn ScoringStrategy>,
     connection_scores: Vec<(ConnectionId, Score)>,
 }
 
@@ -41,28 +193,25 @@ impl PeerScorer {
     const PICK_SELECTION_SIZE: usize = 100;
 
     const MIN_AGE_FULL: Duration = Duration::from_secs(5 * 60); // 5 minutes
-    const BEST_AGE_FULL: Duration = Duration::from_secs(24 * 60 * 60); // 24 hours
-
     const MIN_AGE_LIGHT: Duration = Duration::from_secs(2 * 60); // 2 minutes
-    const BEST_AGE_LIGHT: Duration = Duration::from_secs(15 * 60); // 15 minutes
-    const MAX_AGE_LIGHT: Duration = Duration::from_secs(6 * 60 * 60); // 6 hours
-
     const MIN_AGE_NANO: Duration = Duration::from_secs(60); // 1 minute
-    const BEST_AGE_NANO: Duration = Duration::from_secs(5 * 60); // 5 minutes
-    const MAX_AGE_NANO: Duration = Duration::from_secs(30 * 60); // 30 minutes
-
-    const BEST_PROTOCOL_WS_DISTRIBUTION: f64 = 0.15; // 15%
-
 
     pub fn new(network_config: Arc<NetworkConfig>, addresses: Arc<PeerAddressBook>, connections: Arc<ConnectionPool>) -> Self {
         PeerScorer {
             network_config,
             addresses,
             connections,
+            strategy: Arc::new(DefaultScoringStrategy),
             connection_scores: Vec::new(),
         }
     }
 
+    /// Replaces the strategy used to score addresses and connections.
+    pub fn set_strategy(&mut self, strategy: Arc<dyn ScoringStrategy>) {
+        self.strategy = strategy;
+        self.connection_scores.clear();
+    }
+
     pub fn pick_address(&self) -> Option<Arc<PeerAddress>> {
         let mut candidates = self.find_candidates(1000, false);
         if candidates.is_empty() {
@@ -71,6 +220,12 @@ impl PeerScorer {
         if candidates.is_empty() {
             return None;
         }
+        // Preferred peers are picked first.
+        let scoring = self.network_config.scoring();
+        if let Some((peer_address, _)) = candidates.iter().find(|(peer_address, _)| scoring.is_preferred(&peer_address.peer_id)) {
+            return Some(Arc::clone(peer_address));
+        }
+
         candidates.sort_by(|a, b| { a.1.cmp(&b.1) });
         let mut randrng: OsRng = OsRng::new().unwrap();
         let rand_ind = randrng.gen_range(0, usize::min(Self::PICK_SELECTION_SIZE, candidates.len()));
@@ -121,13 +276,18 @@ impl PeerScorer {
                     return -1;
                 }
 
+                // In private network mode, only connect to allowed peers.
+                if !self.network_config.is_peer_allowed(&peer_address.peer_id) {
+                    return -1;
+                }
+
                 // Filter addresses not matching our accepted services.
                 if (peer_address.services & self.network_config.services().accepted) == ServiceFlags::NONE {
                     return -1;
                 }
 
                 // Filter addresses that are too old.
-                if peer_address.exceeds_age() {
+                if peer_address_info.exceeds_age() {
                     return -1;
                 }
 
@@ -141,17 +301,7 @@ impl PeerScorer {
                     return -1;
                 }
 
-                // Give all peers the same base score. Penalize peers with failed connection attempts.
-                let score = 1;
-                match peer_address_info.state {
-                    PeerAddressState::Banned => -1,
-                    PeerAddressState::New | PeerAddressState::Tried => score,
-                    PeerAddressState::Failed => {
-                        // Don't pick failed addresses when they have failed the maximum number of times.
-                        (1 - ((peer_address_info.failed_attempts + 1) as i32 / peer_address_info.max_failed_attempts() as i32)) * score
-                    },
-                    _ => -1
-                }
+                self.strategy.score_address(peer_address, peer_address_info, self.network_config.scoring())
             }
         }
     }
@@ -169,21 +319,23 @@ impl PeerScorer {
     }
 
     pub fn is_good_peer(&self, peer_address: &Arc<PeerAddress>) -> bool {
-        peer_address.services.is_full_node() && (peer_address.protocol() == Protocol::Ws || peer_address.protocol() == Protocol::Wss)
+        peer_address.services.is_full_node() && match peer_address.protocol() {
+            Protocol::Ws | Protocol::Wss | Protocol::Tcp => true,
+            _ => false,
+        }
     }
 
     pub fn score_connections(&mut self) {
         let mut connection_scores: Vec<(ConnectionId, Score)> = Vec::new();
 
         let state = self.connections.state();
-        let distribution: f64 = (state.peer_count_ws as f64 + state.peer_count_wss as f64) / state.peer_count() as f64;
-        let peer_count_full_ws_outbound = state.get_peer_count_full_ws_outbound();
+        let context = self.context(&state);
         let connections: Vec<(ConnectionId, &ConnectionInfo)> = state.id_and_connection_iter();
 
         for connection in connections {
             if connection.1.state() == ConnectionState::Established
                 && connection.1.age_established() > self.get_min_age(connection.1.peer_address().expect("No peer address")) {
-                let score = Self::score_connection(connection.1, distribution, peer_count_full_ws_outbound);
+                let score = self.strategy.score_connection(connection.1, &context).total;
                 connection_scores.push((connection.0, score));
             }
         }
@@ -192,108 +344,76 @@ impl PeerScorer {
         self.connection_scores = connection_scores
     }
 
-    pub fn recycle_connections(&mut self, mut count: u32, ty: CloseType, reason: &str) {
-        while count > 0 && !self.connection_scores.is_empty() {
-            let connection_id = self.connection_scores.pop().map(|(connection_id, _)| connection_id).unwrap();
-            let state = self.connections.state();
-            let connection_info = state.get_connection(connection_id).expect("Missing connection");
-
-            if connection_info.state() == ConnectionState::Established {
-                connection_info.peer_channel().expect("Missing PeerChannel").close(ty); // FIXME: what about `reason`?
-                debug!("Closed connection with reason: {}", reason);
-                count -= 1;
-            }
-        }
-    }
-
-    fn score_connection(connection_info: &ConnectionInfo, distribution: f64, peer_count_full_ws_outbound: usize) -> Score {
-        // Connection age
-        let score_age = Self::score_connection_age(connection_info);
-
-        // Connection type (inbound/outbound)
-        let score_outbound = if connection_info.network_connection().expect("Missing network connection").outbound() {
-            0.0
-        } else {
-            1.0
-        };
-
-        let peer_address = connection_info.peer_address().expect("Missing peer address");
-
-        // Node type (full/light/nano)
-        let score_type: Score;
+    fn get_min_age(&self, peer_address: Arc<PeerAddress>) -> Duration {
         if peer_address.services.is_full_node() {
-            score_type = 1.0;
+            Self::MIN_AGE_FULL
         } else if peer_address.services.is_light_node() {
-            score_type = 0.5;
+            Self::MIN_AGE_LIGHT
         } else {
-            score_type = 0.0;
+            Self::MIN_AGE_NANO
         }
-
-        // Protocol: Prefer WebSocket over WebRTC over Dumb.
-        let score_protocol: Score = match peer_address.protocol() {
-            Protocol::Wss | Protocol::Ws => {
-                // Boost WebSocket score when low on WebSocket connections.
-                if distribution < Self::BEST_PROTOCOL_WS_DISTRIBUTION || peer_count_full_ws_outbound <= Self::PEER_COUNT_MIN_FULL_WS_OUTBOUND {
-                    1.0
-                } else {
-                    0.6
-                }
-            },
-            Protocol::Rtc => 0.3,
-            Protocol::Dumb => 0.0,
-        };
-
-        // Connection speed, based on ping-pong latency median
-        let median_latency = connection_info.statistics().latency_median();
-        let score_speed: f64 = if median_latency > 0.0 && median_latency < NetworkAgent::PING_TIMEOUT.as_secs() as f64 {
-            1.0 - median_latency / NetworkAgent::PING_TIMEOUT.as_secs() as f64
-        } else { 0.0 };
-
-        0.15 * score_age + 0.25 * score_outbound + 0.2 * score_type + 0.2 * score_protocol + 0.2 * score_speed
     }
 
-    fn score_by_age(age: u128, best_age: u128, max_age: u128) -> Score {
-        f64::max(f64::min(1. - (age as f64 - best_age as f64) / max_age as f64, 1.), 0.)
+    /// Computes the score of an established connection, along with its components.
+    pub fn score_breakdown(&self, state: &ConnectionPoolState, connection_info: &ConnectionInfo) -> Option<ScoreBreakdown> {
+        if connection_info.state() != ConnectionState::Established || connection_info.peer_address().is_none() {
+            return None;
+        }
+        Some(self.strategy.score_connection(connection_info, &self.context(state)))
     }
 
-    fn score_connection_age(connection_info: &ConnectionInfo) -> Score {
-        let age = connection_info.age_established().as_millis();
-        let services = connection_info.peer_address().expect("No peer address").services;
-
-        if services.is_full_node() {
-            (age as f64 / (2.0 * (Self::BEST_AGE_FULL.as_millis()) as f64) + 0.5) as Score
-        } else if services.is_light_node() {
-            Self::score_by_age(age, Self::BEST_AGE_LIGHT.as_millis(), Self::MAX_AGE_LIGHT.as_millis()) as Score
-        } else {
-            Self::score_by_age(age, Self::BEST_AGE_NANO.as_millis(), Self::MAX_AGE_NANO.as_millis()) as Score
+    fn context<'a>(&'a self, state: &'a ConnectionPoolState) -> ScoringContext<'a> {
+        ScoringContext {
+            state,
+            config: self.network_config.scoring(),
+            distribution: (state.peer_count_ws as f64 + state.peer_count_wss as f64 + state.peer_count_tcp as f64) / state.peer_count() as f64,
+            peer_count_full_ws_outbound: state.get_peer_count_full_ws_outbound(),
         }
     }
 
-    fn get_min_age(&self, peer_address: Arc<PeerAddress>) -> Duration {
-        if peer_address.services.is_full_node() {
-            Self::MIN_AGE_FULL
-        } else if peer_address.services.is_light_node() {
-            Self::MIN_AGE_LIGHT
-        } else {
-            Self::MIN_AGE_NANO
-        }
+    /// Whether the connection is to a pinned peer and must not be recycled.
+    fn is_pinned(&self, connection_info: &ConnectionInfo) -> bool {
+        connection_info.peer_address()
+            .map(|peer_address| self.network_config.scoring().is_pinned(&peer_address.peer_id))
+            .unwrap_or(false)
     }
 
-    pub fn lowest_connection_score(&mut self) -> Option<Score> {
-        while !self.connection_scores.is_empty() {
-            let connection_id = self.connection_scores.last().map(|(connection_id, _)| *connection_id).unwrap();
+    pub fn recycle_connections(&mut self, mut count: u32, ty: CloseType, reason: &str) {
+        let mut index = self.connection_scores.len();
+        while count > 0 && index > 0 {
+            index -= 1;
+            let connection_id = self.connection_scores[index].0;
             let state = self.connections.state();
             let connection_info = state.get_connection(connection_id).expect("Missing connection");
 
+            // Keep pinned connections scored, so that they can still be picked for address requests.
+            if self.is_pinned(connection_info) {
+                continue;
+            }
+            self.connection_scores.remove(index);
+
             if connection_info.state() == ConnectionState::Established {
-                self.connection_scores.pop();
+                connection_info.peer_channel().expect("Missing PeerChannel").close(ty); // FIXME: what about `reason`?
+                debug!("Closed connection with reason: {}", reason);
+                count -= 1;
             }
         }
+    }
 
-        match self.connection_scores.last() {
-            None => None,
-            Some(tuple) => Some(tuple.1),
-        }
+    /// The lowest score of a connection that may be recycled.
+    pub fn lowest_connection_score(&mut self) -> Option<Score> {
+        // Drop connections that are not established anymore.
+        let connections = Arc::clone(&self.connections);
+        let state = connections.state();
+        self.connection_scores.retain(|(connection_id, _)| {
+            state.get_connection(*connection_id)
+                .map(|connection_info| connection_info.state() == ConnectionState::Established)
+                .unwrap_or(false)
+        });
+
+        self.connection_scores.iter().rev()
+            .find(|(connection_id, _)| !self.is_pinned(state.get_connection(*connection_id).expect("Missing connection")))
+            .map(|(_, score)| *score)
     }
 
     pub fn connection_scores(&self) -> &Vec<(ConnectionId, Score)> {
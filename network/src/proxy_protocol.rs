@@ -0,0 +1,151 @@
+//! Server side of the PROXY protocol (versions 1 and 2), which TCP load balancers use to pass on
+//! the address of the client in front of a forwarded connection.
+//! See https://www.haproxy.org/download/1.8/doc/proxy-protocol.txt
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str;
+
+use futures::future;
+use futures::future::Loop;
+use futures::prelude::*;
+use tokio::io::{AsyncRead, read_exact};
+
+use network_primitives::address::NetAddress;
+
+use crate::websocket::error::Error;
+
+/// Signature that starts a version 2 header.
+pub const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+const V1_PREFIX: &[u8] = b"PROXY ";
+/// Maximum length of a version 1 header, including the line break.
+const V1_MAX_LENGTH: usize = 107;
+const V2_VERSION: u8 = 0x20;
+const V2_COMMAND_LOCAL: u8 = 0x00;
+const V2_COMMAND_PROXY: u8 = 0x01;
+const V2_FAMILY_INET: u8 = 0x10;
+const V2_FAMILY_INET6: u8 = 0x20;
+
+/// Reads the PROXY protocol header at the start of a connection. Resolves to the stream, positioned
+/// after the header, and the address of the client. The address is `None` if the proxy didn't
+/// forward a client, e.g. for its health checks.
+pub fn accept<S>(stream: S) -> Box<dyn Future<Item=(S, Option<NetAddress>), Error=Error> + Send>
+    where S: AsyncRead + Send + 'static {
+    // The signature of version 2 is as long as the shortest possible version 1 header.
+    Box::new(read_exact(stream, [0u8; 12])
+        .map_err(Error::IoError)
+        .and_then(|(stream, start)| -> Box<dyn Future<Item=(S, Option<NetAddress>), Error=Error> + Send> {
+            if start == V2_SIGNATURE {
+                read_v2(stream)
+            } else if start.starts_with(V1_PREFIX) {
+                read_v1(stream, start.to_vec())
+            } else {
+                Box::new(future::err(Error::ProxyProtocolError("Missing PROXY protocol header")))
+            }
+        }))
+}
+
+/// Reads the rest of the line of a version 1 header byte by byte, so that nothing after the header
+/// is consumed.
+fn read_v1<S>(stream: S, line: Vec<u8>) -> Box<dyn Future<Item=(S, Option<NetAddress>), Error=Error> + Send>
+    where S: AsyncRead + Send + 'static {
+    Box::new(future::loop_fn((stream, line), |(stream, mut line)| {
+        read_exact(stream, [0u8; 1])
+            .map_err(Error::IoError)
+            .and_then(move |(stream, byte)| {
+                line.push(byte[0]);
+                if line.ends_with(b"\r\n") {
+                    parse_v1(&line).map(|net_address| Loop::Break((stream, net_address)))
+                } else if line.len() >= V1_MAX_LENGTH {
+                    Err(Error::ProxyProtocolError("PROXY protocol header is too long"))
+                } else {
+                    Ok(Loop::Continue((stream, line)))
+                }
+            })
+    }))
+}
+
+fn read_v2<S>(stream: S) -> Box<dyn Future<Item=(S, Option<NetAddress>), Error=Error> + Send>
+    where S: AsyncRead + Send + 'static {
+    Box::new(read_exact(stream, [0u8; 4])
+        .and_then(|(stream, header)| {
+            let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+            read_exact(stream, vec![0u8; length])
+                .map(move |(stream, addresses)| (stream, header, addresses))
+        })
+        .map_err(Error::IoError)
+        .and_then(|(stream, header, addresses)| {
+            parse_v2(header[0], header[1], &addresses).map(|net_address| (stream, net_address))
+        }))
+}
+
+/// Parses a version 1 header line like `PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n` and
+/// returns the source address.
+pub fn parse_v1(line: &[u8]) -> Result<Option<NetAddress>, Error> {
+    let invalid = || Error::ProxyProtocolError("Invalid PROXY protocol header");
+
+    let line = str::from_utf8(line).map_err(|_| invalid())?;
+    if !line.ends_with("\r\n") {
+        return Err(invalid());
+    }
+    let fields: Vec<&str> = line[..line.len() - 2].split(' ').collect();
+    if fields[0] != "PROXY" {
+        return Err(invalid());
+    }
+
+    // Anything may follow `UNKNOWN`, and the connection's own addresses are to be used.
+    if fields.get(1) == Some(&"UNKNOWN") {
+        return Ok(None);
+    }
+    if fields.len() != 6 || fields[4].parse::<u16>().is_err() || fields[5].parse::<u16>().is_err() {
+        return Err(invalid());
+    }
+    match fields[1] {
+        "TCP4" => {
+            let source: Ipv4Addr = fields[2].parse().map_err(|_| invalid())?;
+            fields[3].parse::<Ipv4Addr>().map_err(|_| invalid())?;
+            Ok(Some(NetAddress::IPv4(source)))
+        },
+        "TCP6" => {
+            let source: Ipv6Addr = fields[2].parse().map_err(|_| invalid())?;
+            fields[3].parse::<Ipv6Addr>().map_err(|_| invalid())?;
+            Ok(Some(NetAddress::IPv6(source).canonical()))
+        },
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses the part of a version 2 header after the signature: the version and command byte, the
+/// address family and protocol byte and the addresses, and returns the source address.
+pub fn parse_v2(version_command: u8, family: u8, addresses: &[u8]) -> Result<Option<NetAddress>, Error> {
+    if version_command & 0xf0 != V2_VERSION {
+        return Err(Error::ProxyProtocolError("Unsupported PROXY protocol version"));
+    }
+    match version_command & 0x0f {
+        V2_COMMAND_LOCAL => return Ok(None),
+        V2_COMMAND_PROXY => {},
+        _ => return Err(Error::ProxyProtocolError("Unsupported PROXY protocol command")),
+    }
+
+    // Source and destination address followed by source and destination port. Further TLVs may
+    // follow, which are ignored.
+    match family & 0xf0 {
+        V2_FAMILY_INET => {
+            if addresses.len() < 12 {
+                return Err(Error::ProxyProtocolError("PROXY protocol addresses are too short"));
+            }
+            let mut source = [0u8; 4];
+            source.copy_from_slice(&addresses[..4]);
+            Ok(Some(NetAddress::IPv4(Ipv4Addr::from(source))))
+        },
+        V2_FAMILY_INET6 => {
+            if addresses.len() < 36 {
+                return Err(Error::ProxyProtocolError("PROXY protocol addresses are too short"));
+            }
+            let mut source = [0u8; 16];
+            source.copy_from_slice(&addresses[..16]);
+            Ok(Some(NetAddress::IPv6(Ipv6Addr::from(source)).canonical()))
+        },
+        // Unspecified and UNIX socket addresses don't identify a client.
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,153 @@
+//! Minimal SOCKS5 client (RFC 1928) to route outbound connections through a proxy.
+//! Only the `CONNECT` command and username/password authentication (RFC 1929) are supported.
+
+use std::net::IpAddr;
+
+use futures::future;
+use futures::prelude::*;
+use tokio::io::{read_exact, write_all};
+use tokio::net::TcpStream;
+
+use crate::network_config::ProxyConfig;
+use crate::websocket::error::Error;
+
+const VERSION: u8 = 5;
+const AUTH_VERSION: u8 = 1;
+const METHOD_NO_AUTH: u8 = 0;
+const METHOD_PASSWORD: u8 = 2;
+const METHOD_NONE_ACCEPTABLE: u8 = 0xff;
+const COMMAND_CONNECT: u8 = 1;
+const ADDRESS_IPV4: u8 = 1;
+const ADDRESS_DOMAIN: u8 = 3;
+const ADDRESS_IPV6: u8 = 4;
+const REPLY_SUCCEEDED: u8 = 0;
+
+/// Opens a TCP connection to `host:port`, either directly or through the proxy if one is given.
+pub fn connect(host: String, port: u16, proxy: Option<ProxyConfig>) -> Box<dyn Future<Item=TcpStream, Error=Error> + Send> {
+    match proxy {
+        Some(proxy) => connect_via_proxy(host, port, proxy),
+        None => Box::new(tokio_dns::TcpStream::connect((host.as_str(), port)).map_err(Error::IoError)),
+    }
+}
+
+/// Opens a TCP connection to `host:port` through the SOCKS5 proxy. Unless the proxy is configured
+/// to resolve host names, they are resolved locally before connecting.
+pub fn connect_via_proxy(host: String, port: u16, proxy: ProxyConfig) -> Box<dyn Future<Item=TcpStream, Error=Error> + Send> {
+    let destination: Box<dyn Future<Item=Destination, Error=Error> + Send> = match host.parse::<IpAddr>() {
+        Ok(ip) => Box::new(future::ok(Destination::Ip(ip))),
+        Err(_) if proxy.remote_dns => Box::new(future::ok(Destination::Domain(host))),
+        Err(_) => Box::new(tokio_dns::resolve::<&str>(&host)
+            .map_err(Error::IoError)
+            .and_then(|ips| ips.into_iter().next()
+                .map(Destination::Ip)
+                .ok_or(Error::ProxyError("Could not resolve host"))
+            )),
+    };
+
+    // A username without password is sent with an empty password.
+    let credentials = proxy.username.clone()
+        .map(|username| (username, proxy.password.clone().unwrap_or_default()));
+    Box::new(destination
+        .join(tokio_dns::TcpStream::connect((proxy.host.as_str(), proxy.port)).map_err(Error::IoError))
+        .and_then(move |(destination, socket)| {
+            authenticate(socket, credentials)
+                .and_then(move |socket| request_connect(socket, destination, port))
+        }))
+}
+
+enum Destination {
+    Ip(IpAddr),
+    Domain(String),
+}
+
+/// Negotiates the authentication method and authenticates if the proxy asks for credentials.
+fn authenticate(socket: TcpStream, credentials: Option<(String, String)>) -> Box<dyn Future<Item=TcpStream, Error=Error> + Send> {
+    let greeting = if credentials.is_some() {
+        vec![VERSION, 2, METHOD_NO_AUTH, METHOD_PASSWORD]
+    } else {
+        vec![VERSION, 1, METHOD_NO_AUTH]
+    };
+
+    Box::new(write_all(socket, greeting)
+        .and_then(|(socket, _)| read_exact(socket, [0u8; 2]))
+        .map_err(Error::IoError)
+        .and_then(move |(socket, reply)| -> Box<dyn Future<Item=TcpStream, Error=Error> + Send> {
+            if reply[0] != VERSION {
+                return Box::new(future::err(Error::ProxyError("Unexpected protocol version")));
+            }
+            match (reply[1], credentials) {
+                (METHOD_NO_AUTH, _) => Box::new(future::ok(socket)),
+                (METHOD_PASSWORD, Some((username, password))) => {
+                    if username.len() > 255 || password.len() > 255 {
+                        return Box::new(future::err(Error::ProxyError("Credentials are too long")));
+                    }
+                    let mut request = vec![AUTH_VERSION, username.len() as u8];
+                    request.extend_from_slice(username.as_bytes());
+                    request.push(password.len() as u8);
+                    request.extend_from_slice(password.as_bytes());
+
+                    Box::new(write_all(socket, request)
+                        .and_then(|(socket, _)| read_exact(socket, [0u8; 2]))
+                        .map_err(Error::IoError)
+                        .and_then(|(socket, reply)| {
+                            if reply[1] == REPLY_SUCCEEDED {
+                                Ok(socket)
+                            } else {
+                                Err(Error::ProxyError("Authentication failed"))
+                            }
+                        }))
+                },
+                (METHOD_NONE_ACCEPTABLE, _) => Box::new(future::err(Error::ProxyError("No acceptable authentication method"))),
+                _ => Box::new(future::err(Error::ProxyError("Unsupported authentication method"))),
+            }
+        }))
+}
+
+/// Asks the proxy to connect to the destination and skips the bound address in the reply.
+fn request_connect(socket: TcpStream, destination: Destination, port: u16) -> Box<dyn Future<Item=TcpStream, Error=Error> + Send> {
+    let mut request = vec![VERSION, COMMAND_CONNECT, 0];
+    match destination {
+        Destination::Ip(IpAddr::V4(ip)) => {
+            request.push(ADDRESS_IPV4);
+            request.extend_from_slice(&ip.octets());
+        },
+        Destination::Ip(IpAddr::V6(ip)) => {
+            request.push(ADDRESS_IPV6);
+            request.extend_from_slice(&ip.octets());
+        },
+        Destination::Domain(domain) => {
+            if domain.len() > 255 {
+                return Box::new(future::err(Error::ProxyError("Host name is too long")));
+            }
+            request.push(ADDRESS_DOMAIN);
+            request.push(domain.len() as u8);
+            request.extend_from_slice(domain.as_bytes());
+        },
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+
+    Box::new(write_all(socket, request)
+        .and_then(|(socket, _)| read_exact(socket, [0u8; 4]))
+        .map_err(Error::IoError)
+        .and_then(|(socket, reply)| -> Box<dyn Future<Item=TcpStream, Error=Error> + Send> {
+            if reply[0] != VERSION {
+                return Box::new(future::err(Error::ProxyError("Unexpected protocol version")));
+            }
+            if reply[1] != REPLY_SUCCEEDED {
+                return Box::new(future::err(Error::ProxyError("Proxy refused to connect")));
+            }
+
+            // The bound address is followed by the bound port.
+            let address_length: Box<dyn Future<Item=(TcpStream, usize), Error=Error> + Send> = match reply[3] {
+                ADDRESS_IPV4 => Box::new(future::ok((socket, 4))),
+                ADDRESS_IPV6 => Box::new(future::ok((socket, 16))),
+                ADDRESS_DOMAIN => Box::new(read_exact(socket, [0u8; 1])
+                    .map(|(socket, length)| (socket, length[0] as usize))
+                    .map_err(Error::IoError)),
+                _ => return Box::new(future::err(Error::ProxyError("Unknown address type"))),
+            };
+            Box::new(address_length
+                .and_then(|(socket, length)| read_exact(socket, vec![0u8; length + 2]).map_err(Error::IoError))
+                .map(|(socket, _)| socket))
+        }))
+}
@@ -0,0 +1,25 @@
+use futures::prelude::*;
+use tokio::codec::Framed;
+
+use keys::{KeyPair, PublicKey};
+
+use crate::network_config::ProxyConfig;
+use crate::socks;
+use crate::tcp::codec::EncryptedCodec;
+use crate::tcp::handshake::handshake;
+use crate::websocket::error::Error;
+use crate::websocket::NimiqMessageStream;
+
+/// Connect to a given host and return a Future that will resolve to a NimiqMessageStream
+/// once the peer authenticated with `public_key`. If no public key is given, any key is accepted.
+/// The connection is routed through `proxy` if one is given.
+pub fn nimiq_tcp_connect_async(host: String, port: u16, key_pair: KeyPair, public_key: Option<PublicKey>, proxy: Option<ProxyConfig>) -> Box<dyn Future<Item = NimiqMessageStream, Error = Error> + Send> {
+    Box::new(
+        socks::connect(host, port, proxy)
+            .and_then(move |socket| handshake(socket, key_pair, public_key, true))
+            .and_then(|(socket, keys)| {
+                let codec = EncryptedCodec::new(&keys);
+                NimiqMessageStream::new_tcp(Framed::new(socket, codec), keys.remote_public_key, true)
+            })
+    )
+}
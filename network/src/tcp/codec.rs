@@ -0,0 +1,133 @@
+use bytes::{BufMut, BytesMut};
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::aead::generic_array::GenericArray;
+use chacha20poly1305::ChaCha20Poly1305;
+use tokio::codec::{Decoder, Encoder};
+use tungstenite::error::Error as WebSocketError;
+use tungstenite::protocol::CloseFrame;
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::Message as WebSocketMessage;
+
+use super::handshake::SessionKeys;
+
+const LENGTH_SIZE: usize = 4;
+const AUTH_TAG_SIZE: usize = 16;
+/// Frames larger than this are rejected before decrypting them. Nimiq message chunks are at most
+/// 16 kb, so this leaves enough room for the frame type.
+const MAX_FRAME_SIZE: usize = 1024 * 32;
+
+const FRAME_BINARY: u8 = 0;
+const FRAME_CLOSE: u8 = 1;
+
+/// Encrypts and authenticates frames with the keys negotiated in the handshake.
+///
+/// Each frame is a big endian `u32` length followed by the ciphertext. The plaintext starts with
+/// the frame type, followed by the binary payload or the close code and reason. Nonces are frame
+/// counters, so reordered, replayed or dropped frames fail to decrypt.
+pub struct EncryptedCodec {
+    send_cipher: ChaCha20Poly1305,
+    receive_cipher: ChaCha20Poly1305,
+    send_counter: u64,
+    receive_counter: u64,
+}
+
+impl EncryptedCodec {
+    pub fn new(keys: &SessionKeys) -> Self {
+        EncryptedCodec {
+            send_cipher: ChaCha20Poly1305::new(GenericArray::from_slice(&keys.send_key)),
+            receive_cipher: ChaCha20Poly1305::new(GenericArray::from_slice(&keys.receive_key)),
+            send_counter: 0,
+            receive_counter: 0,
+        }
+    }
+
+    fn nonce(counter: u64) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&counter.to_be_bytes());
+        nonce
+    }
+
+    fn next_counter(counter: &mut u64) -> Result<u64, WebSocketError> {
+        let current = *counter;
+        *counter = current.checked_add(1)
+            .ok_or_else(|| WebSocketError::Protocol("Frame counter exhausted".into()))?;
+        Ok(current)
+    }
+}
+
+impl Encoder for EncryptedCodec {
+    type Item = WebSocketMessage;
+    type Error = WebSocketError;
+
+    fn encode(&mut self, msg: WebSocketMessage, dst: &mut BytesMut) -> Result<(), WebSocketError> {
+        let mut plaintext = Vec::with_capacity(msg.len() + 3);
+        match msg {
+            WebSocketMessage::Binary(data) => {
+                plaintext.push(FRAME_BINARY);
+                plaintext.extend(data);
+            },
+            WebSocketMessage::Close(frame) => {
+                plaintext.push(FRAME_CLOSE);
+                if let Some(frame) = frame {
+                    let code: u16 = frame.code.into();
+                    plaintext.extend_from_slice(&code.to_be_bytes());
+                    plaintext.extend_from_slice(frame.reason.as_bytes());
+                }
+            },
+            // The NimiqMessageStream only sends binary data and close frames.
+            _ => return Err(WebSocketError::Protocol("Unsupported message type on TCP connection".into())),
+        }
+
+        let nonce = Self::nonce(Self::next_counter(&mut self.send_counter)?);
+        let ciphertext = self.send_cipher.encrypt(GenericArray::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| WebSocketError::Protocol("Failed to encrypt frame".into()))?;
+
+        dst.reserve(LENGTH_SIZE + ciphertext.len());
+        dst.put_u32_be(ciphertext.len() as u32);
+        dst.extend_from_slice(&ciphertext);
+        Ok(())
+    }
+}
+
+impl Decoder for EncryptedCodec {
+    type Item = WebSocketMessage;
+    type Error = WebSocketError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<WebSocketMessage>, WebSocketError> {
+        if src.len() < LENGTH_SIZE {
+            return Ok(None);
+        }
+
+        let mut length = [0u8; LENGTH_SIZE];
+        length.copy_from_slice(&src[..LENGTH_SIZE]);
+        let length = u32::from_be_bytes(length) as usize;
+        if length <= AUTH_TAG_SIZE || length > MAX_FRAME_SIZE {
+            return Err(WebSocketError::Capacity("Invalid frame size".into()));
+        }
+        if src.len() < LENGTH_SIZE + length {
+            src.reserve(LENGTH_SIZE + length - src.len());
+            return Ok(None);
+        }
+
+        src.split_to(LENGTH_SIZE);
+        let ciphertext = src.split_to(length);
+        let nonce = Self::nonce(Self::next_counter(&mut self.receive_counter)?);
+        let plaintext = self.receive_cipher.decrypt(GenericArray::from_slice(&nonce), &ciphertext[..])
+            .map_err(|_| WebSocketError::Protocol("Failed to decrypt frame".into()))?;
+
+        match plaintext[0] {
+            FRAME_BINARY => Ok(Some(WebSocketMessage::Binary(plaintext[1..].to_vec()))),
+            FRAME_CLOSE => {
+                let frame = if plaintext.len() >= 3 {
+                    let code = u16::from_be_bytes([plaintext[1], plaintext[2]]);
+                    let reason = String::from_utf8(plaintext[3..].to_vec()).map_err(|_| WebSocketError::Utf8)?;
+                    Some(CloseFrame { code: CloseCode::from(code), reason: reason.into() })
+                } else {
+                    None
+                };
+                Ok(Some(WebSocketMessage::Close(frame)))
+            },
+            _ => Err(WebSocketError::Protocol("Unknown frame type".into())),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::io::Write;
+
+use curve25519_dalek::constants::X25519_BASEPOINT;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+use futures::future;
+use futures::prelude::*;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use tokio::io::{read_exact, write_all};
+use tokio::net::TcpStream;
+
+use hash::{Blake2bHasher, Hasher};
+use keys::{KeyPair, PublicKey, Signature};
+
+use crate::websocket::error::Error;
+
+/// Identifies the handshake format, so that it can be changed in a later version.
+const MAGIC: [u8; 4] = *b"NQT\x01";
+const EPHEMERAL_KEY_SIZE: usize = 32;
+const HELLO_SIZE: usize = 4 + PublicKey::SIZE + EPHEMERAL_KEY_SIZE;
+
+/// The result of a successful handshake.
+pub struct SessionKeys {
+    /// The peer key the other side proved to own.
+    pub remote_public_key: PublicKey,
+    pub(crate) send_key: [u8; 32],
+    pub(crate) receive_key: [u8; 32],
+}
+
+/// Runs the handshake on a freshly connected socket.
+///
+/// Both sides send a hello containing their peer key and an ephemeral X25519 key, followed by a
+/// signature over both hellos. The session keys are derived from the ephemeral Diffie-Hellman
+/// secret and the hellos, so each connection uses fresh keys. If `expected_public_key` is given,
+/// the handshake fails unless the other side authenticates with that key.
+pub fn handshake(socket: TcpStream, key_pair: KeyPair, expected_public_key: Option<PublicKey>, outbound: bool)
+        -> Box<dyn Future<Item=(TcpStream, SessionKeys), Error=Error> + Send> {
+    let state = match HandshakeState::new(key_pair, expected_public_key, outbound) {
+        Ok(state) => state,
+        Err(e) => return Box::new(future::err(e)),
+    };
+
+    Box::new(write_all(socket, state.local_hello.clone())
+        .and_then(|(socket, _)| read_exact(socket, vec![0u8; HELLO_SIZE]))
+        .map_err(Error::IoError)
+        .and_then(move |(socket, remote_hello)| {
+            future::result(state.process_hello(&remote_hello))
+                .map(|(state, signature)| (socket, state, signature))
+        })
+        .and_then(|(socket, state, signature)| {
+            write_all(socket, signature.to_bytes().to_vec())
+                .and_then(|(socket, _)| read_exact(socket, vec![0u8; Signature::SIZE]))
+                .map_err(Error::IoError)
+                .and_then(move |(socket, remote_signature)| {
+                    future::result(state.process_signature(&remote_signature))
+                        .map(|keys| (socket, keys))
+                })
+        }))
+}
+
+struct HandshakeState {
+    key_pair: KeyPair,
+    expected_public_key: Option<PublicKey>,
+    outbound: bool,
+    ephemeral_secret: Scalar,
+    local_hello: Vec<u8>,
+}
+
+impl HandshakeState {
+    fn new(key_pair: KeyPair, expected_public_key: Option<PublicKey>, outbound: bool) -> Result<Self, Error> {
+        let mut bytes = [0u8; 32];
+        OsRng::new().map_err(|_| Error::HandshakeFailed("No randomness available"))?.fill_bytes(&mut bytes);
+        // Clamp the secret like X25519 does.
+        bytes[0] &= 248;
+        bytes[31] &= 127;
+        bytes[31] |= 64;
+        let ephemeral_secret = Scalar::from_bits(bytes);
+        let ephemeral_public = &X25519_BASEPOINT * &ephemeral_secret;
+
+        let mut local_hello = Vec::with_capacity(HELLO_SIZE);
+        local_hello.extend_from_slice(&MAGIC);
+        local_hello.extend_from_slice(key_pair.public.as_bytes());
+        local_hello.extend_from_slice(&ephemeral_public.to_bytes());
+
+        Ok(HandshakeState {
+            key_pair,
+            expected_public_key,
+            outbound,
+            ephemeral_secret,
+            local_hello,
+        })
+    }
+
+    /// Checks the remote hello and returns our signature over the handshake.
+    fn process_hello(self, remote_hello: &[u8]) -> Result<(AuthenticationState, Signature), Error> {
+        if remote_hello[..MAGIC.len()] != MAGIC {
+            return Err(Error::HandshakeFailed("Unknown handshake format"));
+        }
+
+        let key_end = MAGIC.len() + PublicKey::SIZE;
+        let remote_public_key = PublicKey::from_bytes(&remote_hello[MAGIC.len()..key_end])
+            .map_err(|_| Error::HandshakeFailed("Invalid public key"))?;
+        if let Some(expected_public_key) = self.expected_public_key {
+            if remote_public_key != expected_public_key {
+                return Err(Error::HandshakeFailed("Unexpected public key"));
+            }
+        }
+
+        let mut remote_ephemeral = [0u8; EPHEMERAL_KEY_SIZE];
+        remote_ephemeral.copy_from_slice(&remote_hello[key_end..]);
+        let shared_secret = (&MontgomeryPoint(remote_ephemeral) * &self.ephemeral_secret).to_bytes();
+        // Reject low order points, which would make the shared secret predictable.
+        if shared_secret.iter().all(|b| *b == 0) {
+            return Err(Error::HandshakeFailed("Invalid ephemeral key"));
+        }
+
+        // The transcript is always ordered initiator first.
+        let mut transcript = Vec::with_capacity(2 * HELLO_SIZE);
+        if self.outbound {
+            transcript.extend_from_slice(&self.local_hello);
+            transcript.extend_from_slice(remote_hello);
+        } else {
+            transcript.extend_from_slice(remote_hello);
+            transcript.extend_from_slice(&self.local_hello);
+        }
+
+        let signature = self.key_pair.sign(&signature_data(self.outbound, &transcript));
+        Ok((AuthenticationState {
+            remote_public_key,
+            outbound: self.outbound,
+            shared_secret,
+            transcript,
+        }, signature))
+    }
+}
+
+struct AuthenticationState {
+    remote_public_key: PublicKey,
+    outbound: bool,
+    shared_secret: [u8; 32],
+    transcript: Vec<u8>,
+}
+
+impl AuthenticationState {
+    /// Verifies that the other side owns the key it sent and derives the session keys.
+    fn process_signature(self, remote_signature: &[u8]) -> Result<SessionKeys, Error> {
+        let signature = Signature::from_bytes(remote_signature)
+            .map_err(|_| Error::HandshakeFailed("Invalid signature"))?;
+        if !self.remote_public_key.verify(&signature, &signature_data(!self.outbound, &self.transcript)) {
+            return Err(Error::HandshakeFailed("Invalid signature"));
+        }
+
+        let initiator_key = self.derive_key(b"initiator");
+        let responder_key = self.derive_key(b"responder");
+        let (send_key, receive_key) = if self.outbound {
+            (initiator_key, responder_key)
+        } else {
+            (responder_key, initiator_key)
+        };
+
+        Ok(SessionKeys {
+            remote_public_key: self.remote_public_key,
+            send_key,
+            receive_key,
+        })
+    }
+
+    fn derive_key(&self, label: &[u8]) -> [u8; 32] {
+        let mut hasher = Blake2bHasher::default();
+        hasher.write_all(b"nimiq-tcp-key").unwrap();
+        hasher.write_all(label).unwrap();
+        hasher.write_all(&self.shared_secret).unwrap();
+        hasher.write_all(&self.transcript).unwrap();
+
+        let mut key = [0u8; 32];
+        key.copy_from_slice(hasher.finish().as_bytes());
+        key
+    }
+}
+
+/// The data signed by each side. The role is included, so that a signature can't be reflected.
+fn signature_data(initiator: bool, transcript: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(15 + transcript.len());
+    data.extend_from_slice(b"nimiq-tcp-auth");
+    data.push(initiator as u8);
+    data.extend_from_slice(transcript);
+    data
+}
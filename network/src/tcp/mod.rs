@@ -0,0 +1,14 @@
+//! Raw TCP transport for private clusters.
+//!
+//! After connecting, both sides exchange ephemeral X25519 keys and sign the handshake with their
+//! peer keys (see `handshake`). All following frames are encrypted and authenticated with
+//! ChaCha20-Poly1305 (see `codec`). Nimiq messages are chunked and tagged on top of that exactly
+//! like on WebSocket connections, so the `NimiqMessageStream` can be used for both transports.
+
+pub use self::client::nimiq_tcp_connect_async;
+pub use self::server::nimiq_tcp_accept_async;
+
+pub mod codec;
+pub mod handshake;
+pub mod client;
+pub mod server;
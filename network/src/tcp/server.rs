@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use futures::prelude::*;
+use tokio::codec::Framed;
+use tokio::net::TcpStream;
+use tokio::prelude::FutureExt;
+
+use keys::KeyPair;
+
+use crate::tcp::codec::EncryptedCodec;
+use crate::tcp::handshake::handshake;
+use crate::websocket::error::Error;
+use crate::websocket::NimiqMessageStream;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Accept an incoming connection and return a Future that will resolve to a NimiqMessageStream
+/// once the peer authenticated with its peer key.
+pub fn nimiq_tcp_accept_async(socket: TcpStream, key_pair: KeyPair) -> Box<dyn Future<Item = NimiqMessageStream, Error = Error> + Send> {
+    Box::new(
+        handshake(socket, key_pair, None, false)
+            .timeout(HANDSHAKE_TIMEOUT)
+            .map_err(|e| e.into_inner().unwrap_or(Error::HandshakeFailed("Handshake timed out")))
+            .and_then(|(socket, keys)| {
+                let codec = EncryptedCodec::new(&keys);
+                NimiqMessageStream::new_tcp(Framed::new(socket, codec), keys.remote_public_key, false)
+            })
+    )
+}
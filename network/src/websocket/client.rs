@@ -1,19 +1,36 @@
 use futures::future;
 use futures::prelude::*;
-use tokio_tungstenite::connect_async;
+use tokio_tungstenite::{client_async_tls, connect_async};
 use url::Url;
 
+use crate::network_config::ProxyConfig;
+use crate::socks;
 use crate::websocket::error::Error;
 use crate::websocket::NimiqMessageStream;
 
-/// Connect to a given URL and return a Future that will resolve to a NimiqMessageStream
-pub fn nimiq_connect_async(url: Url) -> Box<dyn Future<Item = NimiqMessageStream, Error = Error> + Send> {
+/// Connect to a given URL and return a Future that will resolve to a NimiqMessageStream.
+/// The connection is routed through `proxy` if one is given.
+pub fn nimiq_connect_async(url: Url, proxy: Option<ProxyConfig>) -> Box<dyn Future<Item = NimiqMessageStream, Error = Error> + Send> {
+    let proxy = match proxy {
+        Some(proxy) => proxy,
+        None => return Box::new(
+            connect_async(url).then(|result| {
+                match result {
+                    Ok((ws_stream,_)) => future::result(NimiqMessageStream::new(ws_stream, true)),
+                    Err(e) => future::err(e.into())
+                }
+            })
+        ),
+    };
+
+    let host = match url.host_str() {
+        Some(host) => host.to_string(),
+        None => return Box::new(future::err(Error::WebSocketError(tungstenite::error::Error::Url("no host name in the url".into())))),
+    };
+    let port = url.port_or_known_default().expect("WebSocket URLs have a default port");
     Box::new(
-        connect_async(url).then(|result| {
-            match result {
-                Ok((ws_stream,_)) => future::result(NimiqMessageStream::new(ws_stream, true)),
-                Err(e) => future::err(e.into())
-            }
-        })
+        socks::connect_via_proxy(host, port, proxy)
+            .and_then(move |socket| client_async_tls(url, socket).map_err(Error::from))
+            .and_then(|(ws_stream, _)| NimiqMessageStream::new(ws_stream, true))
     )
-}
\ No newline at end of file
+}
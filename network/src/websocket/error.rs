@@ -33,6 +33,14 @@ pub enum Error {
     NetAddressMissing(#[cause] IoError),
     #[fail(display = "Message format is incorrect and could not be parsed correctly")]
     InvalidMessageFormat,
+    #[fail(display = "Encrypted TCP handshake failed: {}", _0)]
+    HandshakeFailed(&'static str),
+    #[fail(display = "SOCKS5 proxy error: {}", _0)]
+    ProxyError(&'static str),
+    #[fail(display = "PROXY protocol error: {}", _0)]
+    ProxyProtocolError(&'static str),
+    #[fail(display = "Stream is already in use")]
+    StreamInUse,
 }
 
 impl From<IoError> for Error {
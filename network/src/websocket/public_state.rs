@@ -1,6 +1,7 @@
 #[cfg(feature = "metrics")]
 use std::sync::Arc;
 
+use keys::PublicKey;
 use network_primitives::address::net_address::NetAddress;
 
 #[cfg(feature = "metrics")]
@@ -13,6 +14,8 @@ pub struct PublicStreamInfo {
     // Constant info.
     pub net_address: NetAddress,
     pub outbound: bool,
+    /// The peer key the remote side authenticated with, if the transport does authentication.
+    pub authenticated_public_key: Option<PublicKey>,
 
     #[cfg(feature = "metrics")]
     pub network_metrics: Arc<NetworkMetrics>,
@@ -35,6 +38,7 @@ impl PublicStreamInfo {
         PublicStreamInfo {
             net_address,
             outbound,
+            authenticated_public_key: None,
 
             #[cfg(feature = "metrics")]
             network_metrics: Arc::new(NetworkMetrics::default()),
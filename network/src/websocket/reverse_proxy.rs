@@ -2,13 +2,18 @@ use std::str::from_utf8;
 use std::str::FromStr;
 use std::sync::Arc;
 
+use futures::future;
+use futures::prelude::*;
 use parking_lot::Mutex;
 use reqwest::StatusCode;
+use tokio::net::TcpStream;
 use tungstenite::handshake::server::{Callback, ErrorResponse, Request};
 
 use network_primitives::address::NetAddress;
 
-use crate::network_config::ReverseProxyConfig;
+use crate::network_config::{ClientAddressSource, ReverseProxyConfig};
+use crate::proxy_protocol;
+use crate::websocket::error::Error;
 
 /// Struct that stores relevant data for setting up reverse proxy support.
 #[derive(Debug)]
@@ -30,6 +35,31 @@ impl ReverseProxyCallback {
         *self.remote_address.lock()
     }
 
+    /// Reads the PROXY protocol header of a new connection if the reverse proxy is configured to
+    /// send one, and remembers the net address found in it. Connections that don't come from a
+    /// trusted proxy are rejected before anything is read from them.
+    pub fn read_proxy_header(self: &Arc<Self>, stream: TcpStream) -> Box<dyn Future<Item=TcpStream, Error=Error> + Send> {
+        match self.reverse_proxy_config {
+            Some(ReverseProxyConfig { client_address: ClientAddressSource::ProxyProtocol, ref trusted_proxies, .. }) => {
+                let stream_net_address = match stream.peer_addr() {
+                    Ok(addr) => NetAddress::from(addr.ip()).canonical(),
+                    Err(e) => return Box::new(future::err(Error::NetAddressMissing(e))),
+                };
+                if !trusted_proxies.iter().any(|range| range.contains(&stream_net_address)) {
+                    return Box::new(future::err(Error::ProxyProtocolError("Connection is not from a trusted proxy")));
+                }
+
+                let this = Arc::clone(self);
+                Box::new(proxy_protocol::accept(stream).map(move |(stream, net_address)| {
+                    trace!("Remote peer address (according to PROXY protocol header): {:?}", net_address);
+                    *this.remote_address.lock() = net_address;
+                    stream
+                }))
+            },
+            _ => Box::new(future::ok(stream)),
+        }
+    }
+
     /// This function takes the net address given by the stream
     /// and checks it against the reverse proxy configuration.
     /// The function returns the correct net address of the peer
@@ -40,15 +70,14 @@ impl ReverseProxyCallback {
     /// 1) Check whether a reverse proxy was configured.
     ///   - If so, continue,
     ///   - Else, return net address given by the stream.
-    /// 2) Check whether the stream net address equals to the configured reverse proxy address.
-    ///   - Return None on failure (except if config could not be parsed correctly, then display warning).
-    /// 3) Check whether there was a header present with the real peer's net address.
+    /// 2) Check whether the stream net address is one of the trusted proxy addresses.
+    ///   - Return None on failure.
+    /// 3) Check whether there was a HTTP or PROXY protocol header present with the real peer's net address.
     ///   - Return this if it was found, None otherwise.
     pub fn check_reverse_proxy(&self, stream_net_address: NetAddress) -> Option<NetAddress> {
         if let Some(ref config) = self.reverse_proxy_config {
-            let proxy_net_address = &config.address;
-            if proxy_net_address != &stream_net_address {
-                error!("Received connection from {} when all connections were expected from the reverse proxy at {}: closing the connection", stream_net_address, proxy_net_address);
+            if !config.is_trusted(&stream_net_address) {
+                error!("Received connection from {} when all connections were expected from the reverse proxies at {:?}: closing the connection", stream_net_address, config.trusted_proxies);
                 return None;
             }
 
@@ -61,8 +90,8 @@ impl ReverseProxyCallback {
 
 impl<'a> Callback for &'a ReverseProxyCallback {
     fn on_request(self, request: &Request) -> Result<Option<Vec<(String, String)>>, ErrorResponse> {
-        if let Some(ref config) = self.reverse_proxy_config {
-            if let Some(value) = request.headers.find_first(&config.header) {
+        if let Some(ReverseProxyConfig { client_address: ClientAddressSource::Header(ref header), .. }) = self.reverse_proxy_config {
+            if let Some(value) = request.headers.find_first(header) {
                 let str_value = from_utf8(value).map_err(|_| ErrorResponse {
                     error_code: StatusCode::INTERNAL_SERVER_ERROR,
                     headers: None,
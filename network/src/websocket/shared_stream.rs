@@ -1,13 +1,14 @@
-#[cfg(feature = "metrics")]
 use std::sync::Arc;
 
 use futures::prelude::*;
 
+use keys::PublicKey;
 use utils::locking::MultiLock;
 use network_primitives::address::net_address::NetAddress;
 
 #[cfg(feature = "metrics")]
 use crate::network_metrics::NetworkMetrics;
+use crate::message_limits::MessageLimits;
 use crate::websocket::error::Error;
 use crate::websocket::Message;
 use crate::websocket::NimiqMessageStream;
@@ -28,6 +29,23 @@ impl SharedNimiqMessageStream {
         self.state.outbound
     }
 
+    pub fn authenticated_public_key(&self) -> Option<PublicKey> {
+        self.state.authenticated_public_key
+    }
+
+    /// Sets the limits that incoming messages are checked against.
+    /// This must be called before the stream is polled for the first time, otherwise
+    /// `Error::StreamInUse` is returned.
+    pub fn set_message_limits(&self, message_limits: Arc<MessageLimits>) -> Result<(), Error> {
+        match self.inner.poll_lock() {
+            Async::Ready(mut inner) => {
+                inner.set_message_limits(message_limits);
+                Ok(())
+            },
+            Async::NotReady => Err(Error::StreamInUse),
+        }
+    }
+
     #[cfg(feature = "metrics")]
     pub fn network_metrics(&self) -> &Arc<NetworkMetrics> {
         &self.state.network_metrics
@@ -1,11 +1,12 @@
 use std::collections::vec_deque::VecDeque;
 use std::fmt;
 use std::fmt::Debug;
+use std::io;
 use std::net;
-#[cfg(feature = "metrics")]
 use std::sync::Arc;
 
 use futures::prelude::*;
+use tokio::codec::Framed;
 use tokio::net::TcpStream;
 use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 use tokio_tungstenite::stream::PeerAddr;
@@ -14,16 +15,87 @@ use tungstenite::protocol::CloseFrame;
 use tungstenite::protocol::Message as WebSocketMessage;
 
 use beserial::{Deserialize, Serialize};
+use keys::PublicKey;
 use network_messages::Message as NimiqMessage;
 use network_primitives::address::net_address::NetAddress;
 
+#[cfg(feature = "memory-transport")]
+use crate::memory::MemoryLayer;
+use crate::message_limits::MessageLimits;
 #[cfg(feature = "metrics")]
 use crate::network_metrics::NetworkMetrics;
+use crate::tcp::codec::EncryptedCodec;
 use crate::websocket::error::Error;
 use crate::websocket::Message;
 use crate::websocket::public_state::PublicStreamInfo;
 
 type WebSocketLayer = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type TcpLayer = Framed<TcpStream, EncryptedCodec>;
+
+/// The transport below our message framing. All layers transfer binary chunks and close frames.
+enum MessageLayer {
+    WebSocket(WebSocketLayer),
+    Tcp(TcpLayer),
+    #[cfg(feature = "memory-transport")]
+    Memory(MemoryLayer),
+}
+
+impl MessageLayer {
+    fn peer_addr(&self) -> io::Result<net::SocketAddr> {
+        match self {
+            MessageLayer::WebSocket(ws_socket) => ws_socket.peer_addr(),
+            MessageLayer::Tcp(tcp_socket) => tcp_socket.get_ref().peer_addr(),
+            #[cfg(feature = "memory-transport")]
+            MessageLayer::Memory(memory_socket) => Ok(memory_socket.peer_addr()),
+        }
+    }
+}
+
+impl Stream for MessageLayer {
+    type Item = WebSocketMessage;
+    type Error = WebSocketError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match self {
+            MessageLayer::WebSocket(ws_socket) => ws_socket.poll(),
+            MessageLayer::Tcp(tcp_socket) => tcp_socket.poll(),
+            #[cfg(feature = "memory-transport")]
+            MessageLayer::Memory(memory_socket) => memory_socket.poll(),
+        }
+    }
+}
+
+impl Sink for MessageLayer {
+    type SinkItem = WebSocketMessage;
+    type SinkError = WebSocketError;
+
+    fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        match self {
+            MessageLayer::WebSocket(ws_socket) => ws_socket.start_send(item),
+            MessageLayer::Tcp(tcp_socket) => tcp_socket.start_send(item),
+            #[cfg(feature = "memory-transport")]
+            MessageLayer::Memory(memory_socket) => memory_socket.start_send(item),
+        }
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        match self {
+            MessageLayer::WebSocket(ws_socket) => ws_socket.poll_complete(),
+            MessageLayer::Tcp(tcp_socket) => tcp_socket.poll_complete(),
+            #[cfg(feature = "memory-transport")]
+            MessageLayer::Memory(memory_socket) => memory_socket.poll_complete(),
+        }
+    }
+
+    fn close(&mut self) -> Poll<(), Self::SinkError> {
+        match self {
+            MessageLayer::WebSocket(ws_socket) => ws_socket.close(),
+            MessageLayer::Tcp(tcp_socket) => tcp_socket.close(),
+            #[cfg(feature = "memory-transport")]
+            MessageLayer::Memory(memory_socket) => memory_socket.close(),
+        }
+    }
+}
 
 /// This enum describes the current state of the connection.
 #[derive(Clone, Debug)]
@@ -52,18 +124,19 @@ impl WebSocketState {
 }
 
 const MAX_CHUNK_SIZE: usize = 1024 * 16; // 16 kb
-const MAX_MESSAGE_SIZE: usize = 1024 * 1024 * 10; // 10 mb
+pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024 * 10; // 10 mb
 
-/// This struct encapsulates the underlying WebSocket layer
+/// This struct encapsulates the underlying WebSocket or encrypted TCP layer
 /// and instead sends/receives our own Message type encapsulating Nimiq messages.
 pub struct NimiqMessageStream {
     // Internal state.
-    inner: WebSocketLayer,
+    inner: MessageLayer,
     receiving_tag: u8,
     sending_tag: u8,
     ws_queue: VecDeque<WebSocketMessage>,
     msg_buf: Option<Vec<u8>>,
     state: WebSocketState,
+    message_limits: Option<Arc<MessageLimits>>,
 
     // Public state.
     pub(crate) public_state: PublicStreamInfo,
@@ -71,19 +144,33 @@ pub struct NimiqMessageStream {
 
 impl NimiqMessageStream {
     pub(super) fn new(ws_socket: WebSocketLayer, outbound: bool) -> Result<Self, Error> {
-        let peer_addr = ws_socket.peer_addr().map_err(Error::NetAddressMissing)?;
+        Self::with_layer(MessageLayer::WebSocket(ws_socket), outbound)
+    }
+
+    pub(crate) fn new_tcp(tcp_socket: TcpLayer, remote_public_key: PublicKey, outbound: bool) -> Result<Self, Error> {
+        let mut stream = Self::with_layer(MessageLayer::Tcp(tcp_socket), outbound)?;
+        stream.public_state.authenticated_public_key = Some(remote_public_key);
+        Ok(stream)
+    }
+
+    #[cfg(feature = "memory-transport")]
+    pub(crate) fn new_memory(memory_socket: MemoryLayer, outbound: bool) -> Result<Self, Error> {
+        Self::with_layer(MessageLayer::Memory(memory_socket), outbound)
+    }
+
+    fn with_layer(layer: MessageLayer, outbound: bool) -> Result<Self, Error> {
+        let peer_addr = layer.peer_addr().map_err(Error::NetAddressMissing)?;
         Ok(NimiqMessageStream {
-            inner: ws_socket,
+            inner: layer,
             receiving_tag: 254,
             sending_tag: 0,
             ws_queue: VecDeque::new(),
             msg_buf: None,
             state: WebSocketState::Active,
+            message_limits: None,
 
-            public_state: PublicStreamInfo::new(match peer_addr.ip() {
-                net::IpAddr::V4(ip4) => NetAddress::IPv4(ip4),
-                net::IpAddr::V6(ip6) => NetAddress::IPv6(ip6),
-            }, outbound),
+            // Sockets listening on both IPv4 and IPv6 see IPv4 peers as IPv4-mapped addresses.
+            public_state: PublicStreamInfo::new(NetAddress::from(peer_addr.ip()).canonical(), outbound),
         })
     }
 
@@ -95,6 +182,10 @@ impl NimiqMessageStream {
         self.state.is_closed()
     }
 
+    pub fn set_message_limits(&mut self, message_limits: Arc<MessageLimits>) {
+        self.message_limits = Some(message_limits);
+    }
+
     fn next_tag(&mut self) -> u8 {
         // Save and increment tag.
         let tag = self.sending_tag;
@@ -227,6 +318,15 @@ impl NimiqMessageStream {
                         error!("Max message size exceeded ({} > {})", msg_size, MAX_MESSAGE_SIZE);
                         return Err(Error::MessageSizeExceeded);
                     }
+                    if let Some(ref message_limits) = self.message_limits {
+                        let ty = NimiqMessage::peek_type(chunk).map_err(|_| Error::InvalidMessageFormat)?;
+                        if let Some(max_size) = message_limits.max_size(ty) {
+                            if msg_size > max_size {
+                                error!("Max message size for {:?} exceeded ({} > {})", ty, msg_size, max_size);
+                                return Err(Error::MessageSizeExceeded);
+                            }
+                        }
+                    }
                     self.msg_buf = Some(Vec::with_capacity(msg_size));
                 } else {
                     return Err(Error::InvalidMessageFormat);
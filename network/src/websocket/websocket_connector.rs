@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
@@ -8,10 +8,12 @@ use futures::future::poll_fn;
 use futures::prelude::*;
 use futures::sync::oneshot;
 use native_tls::{Identity, TlsAcceptor};
+use net2::TcpBuilder;
 use parking_lot::{Mutex, RwLock};
 use tk_listen::ListenExt;
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 use tokio::prelude::*;
+use tokio::reactor::Handle;
 use tokio_tls::TlsAcceptor as TokioTlsAcceptor;
 use tokio_tungstenite::MaybeTlsStream;
 use tokio_tungstenite::stream::Stream as StreamSwitcher;
@@ -19,12 +21,17 @@ use tungstenite::stream::Mode;
 use url::Url;
 
 use network_primitives::address::PeerAddress;
+use network_primitives::address::peer_address::PeerAddressType;
 use network_primitives::protocol::ProtocolFlags;
 use utils::observer::PassThroughNotifier;
 
+use crate::capture::TrafficCapture;
 use crate::connection::{AddressInfo, NetworkConnection};
 use crate::connection::close_type::CloseType;
+#[cfg(feature = "memory-transport")]
+use crate::memory::{MemoryTransport, nimiq_memory_connect_async};
 use crate::network_config::{NetworkConfig, ProtocolConfig};
+use crate::tcp::{nimiq_tcp_accept_async, nimiq_tcp_connect_async};
 use crate::websocket::{
     Error,
     nimiq_accept_async,
@@ -121,8 +128,11 @@ fn setup_tls_acceptor(identity_file: Option<String>, identity_passphrase: Option
     }
 }
 
+/// Listens for and establishes connections. Despite its name, this also handles encrypted TCP
+/// connections if the node is configured to use them.
 pub struct WebSocketConnector {
     network_config: Arc<NetworkConfig>,
+    traffic_capture: Arc<TrafficCapture>,
     pub notifier: Arc<RwLock<PassThroughNotifier<'static, WebSocketConnectorEvent>>>,
 }
 
@@ -130,16 +140,33 @@ impl WebSocketConnector {
     const CONNECTIONS_MAX: usize = 4050; // A little more than Network.PEER_COUNT_MAX to allow for inbound exchange
     const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
     const WAIT_TIME_ON_ERROR: Duration = Duration::from_millis(100);
+    const LISTEN_BACKLOG: i32 = 1024;
 
-    pub fn new(network_config: Arc<NetworkConfig>) -> WebSocketConnector {
+    pub fn new(network_config: Arc<NetworkConfig>, traffic_capture: Arc<TrafficCapture>) -> WebSocketConnector {
         WebSocketConnector {
             network_config,
+            traffic_capture,
             notifier: Arc::new(RwLock::new(PassThroughNotifier::new())),
         }
     }
 
     pub fn start(&self) -> Result<(), ServerStartError> {
+        // Don't listen at all if inbound connections are disabled, e.g. behind an egress proxy.
+        if !self.network_config.accept_inbound() {
+            info!("Inbound connections are disabled");
+            return Ok(());
+        }
+
         let protocol_config = self.network_config.protocol_config();
+        #[cfg(feature = "memory-transport")]
+        {
+            if let Some(transport) = self.network_config.memory_transport() {
+                return self.start_memory(transport);
+            }
+        }
+        if let ProtocolConfig::Tcp { port, .. } = protocol_config {
+            return self.start_tcp(*port);
+        }
 
         let (port, identity_file, identity_passphrase, mode, reverse_proxy_config) = match protocol_config {
             ProtocolConfig::Ws{port, reverse_proxy_config, ..} => {
@@ -153,26 +180,29 @@ impl WebSocketConnector {
 
         let tls_acceptor = setup_tls_acceptor(identity_file, identity_passphrase, mode)?;
 
-        let addr = SocketAddr::new("::".parse().unwrap(), port);
-        let socket = TcpListener::bind(&addr).map_err(ServerStartError::IoError)?;
         let notifier = Arc::clone(&self.notifier);
+        let network_config = Arc::clone(&self.network_config);
+        let traffic_capture = Arc::clone(&self.traffic_capture);
 
-        let srv = socket.incoming()
-            .sleep_on_error(Self::WAIT_TIME_ON_ERROR)
+        let srv = self.incoming(port)?
             .map(move |tcp| {
                 let reverse_proxy_config = reverse_proxy_config.clone();
                 trace!("Reverse proxy config: {:?}", reverse_proxy_config);
 
                 let notifier = Arc::clone(&notifier);
+                let network_config = Arc::clone(&network_config);
+                let traffic_capture = Arc::clone(&traffic_capture);
                 let acceptor = tls_acceptor.clone();
-                wrap_stream(tcp, acceptor, mode).and_then(move |ss| {
-                    let callback = ReverseProxyCallback::new(reverse_proxy_config.clone());
-                    nimiq_accept_async(ss, callback.clone().to_callback()).map(move |msg_stream: NimiqMessageStream| {
+                let callback = ReverseProxyCallback::new(reverse_proxy_config);
+                callback.read_proxy_header(tcp).and_then(move |tcp| {
+                    wrap_stream(tcp, acceptor, mode)
+                }).and_then(move |ss| {
+                    nimiq_accept_async(ss, callback.clone().to_callback()).and_then(move |msg_stream: NimiqMessageStream| {
                         let mut shared_stream: SharedNimiqMessageStream = msg_stream.into();
                         // Only accept connection, if net address could be determined.
                         if let Some(net_address) = callback.check_reverse_proxy(shared_stream.net_address()) {
                             let net_address = Some(Arc::new(net_address));
-                            let (nc, ncfut) = NetworkConnection::new_connection_setup(shared_stream, AddressInfo::new(net_address, None));
+                            let (nc, ncfut) = NetworkConnection::new_connection_setup(shared_stream, AddressInfo::new(net_address, None), network_config.message_limits(), traffic_capture)?;
                             notifier.read().notify(WebSocketConnectorEvent::Connection(nc));
                             tokio::spawn(ncfut);
                         } else {
@@ -180,6 +210,7 @@ impl WebSocketConnector {
                                 warn!("Could not close connection: {}", e);
                             }));
                         }
+                        Ok(())
                     })
                 }).or_else(|err| {
                     error!("Could not accept connection: {:?}", err);
@@ -197,6 +228,117 @@ impl WebSocketConnector {
         Ok(())
     }
 
+    /// Listens for encrypted TCP connections from other cluster nodes.
+    fn start_tcp(&self, port: u16) -> Result<(), ServerStartError> {
+        let notifier = Arc::clone(&self.notifier);
+        let key_pair = self.network_config.key_pair().clone();
+        let network_config = Arc::clone(&self.network_config);
+        let traffic_capture = Arc::clone(&self.traffic_capture);
+
+        let srv = self.incoming(port)?
+            .map(move |tcp| {
+                let notifier = Arc::clone(&notifier);
+                let network_config = Arc::clone(&network_config);
+                let traffic_capture = Arc::clone(&traffic_capture);
+                nimiq_tcp_accept_async(tcp, key_pair.clone()).and_then(move |msg_stream: NimiqMessageStream| {
+                    let shared_stream: SharedNimiqMessageStream = msg_stream.into();
+                    let net_address = Some(Arc::new(shared_stream.net_address()));
+                    let address_info = AddressInfo::new_authenticated(net_address, None, shared_stream.authenticated_public_key());
+                    let (nc, ncfut) = NetworkConnection::new_connection_setup(shared_stream, address_info, network_config.message_limits(), traffic_capture)?;
+                    notifier.read().notify(WebSocketConnectorEvent::Connection(nc));
+                    tokio::spawn(ncfut);
+                    Ok(())
+                }).or_else(|err| {
+                    error!("Could not accept connection: {:?}", err);
+                    // Do not stop the TCP server on inner connection errors!
+                    future::ok(())
+                })
+            })
+            .listen(Self::CONNECTIONS_MAX)
+            .then(#[allow(unreachable_code)] |_result| {
+                panic!("TCP stream ended unexpectedly");
+                _result
+            });
+
+        tokio::spawn(srv);
+        Ok(())
+    }
+
+    /// Accepts connections on all listen addresses. The sockets are merged into a single stream
+    /// so that `CONNECTIONS_MAX` limits the connections across all of them.
+    fn incoming(&self, port: u16) -> Result<Box<dyn Stream<Item=TcpStream, Error=()> + Send>, ServerStartError> {
+        let incoming = self.bind(port)?.into_iter()
+            .map(|socket| Box::new(socket.incoming().sleep_on_error(Self::WAIT_TIME_ON_ERROR)) as Box<dyn Stream<Item=TcpStream, Error=()> + Send>)
+            .fold(None, |merged: Option<Box<dyn Stream<Item=TcpStream, Error=()> + Send>>, incoming| match merged {
+                Some(merged) => Some(Box::new(merged.select(incoming))),
+                None => Some(incoming),
+            });
+        Ok(incoming.expect("At least one socket is bound"))
+    }
+
+    /// Binds a socket on each of the configured listen addresses, or a single one on all
+    /// interfaces if none are configured.
+    fn bind(&self, port: u16) -> Result<Vec<TcpListener>, ServerStartError> {
+        let listen_addresses = self.network_config.listen_addresses();
+        if listen_addresses.is_empty() {
+            let addr = SocketAddr::new("::".parse().unwrap(), port);
+            return Ok(vec![TcpListener::bind(&addr)?]);
+        }
+
+        // An IPv6 socket would also accept IPv4 connections, which conflicts with separate IPv4
+        // sockets on the same port.
+        let only_v6 = listen_addresses.iter().any(IpAddr::is_ipv4);
+        listen_addresses.iter().map(|ip| {
+            let addr = SocketAddr::new(*ip, port);
+            let builder = match ip {
+                IpAddr::V4(_) => TcpBuilder::new_v4()?,
+                IpAddr::V6(_) => {
+                    let builder = TcpBuilder::new_v6()?;
+                    builder.only_v6(only_v6)?;
+                    builder
+                },
+            };
+            if cfg!(unix) {
+                builder.reuse_address(true)?;
+            }
+            let listener = builder.bind(&addr)?.listen(Self::LISTEN_BACKLOG)?;
+            info!("Listening on {}", addr);
+            Ok(TcpListener::from_std(listener, &Handle::default())?)
+        }).collect()
+    }
+
+    /// Listens for connections on a simulated network.
+    #[cfg(feature = "memory-transport")]
+    fn start_memory(&self, transport: &MemoryTransport) -> Result<(), ServerStartError> {
+        let port = match self.network_config.protocol_config() {
+            ProtocolConfig::Ws { port, .. } | ProtocolConfig::Wss { port, .. } | ProtocolConfig::Tcp { port, .. } => *port,
+            config => return Err(ServerStartError::UnsupportedProtocol(format!("{:?}", config))),
+        };
+        let listener = transport.listen(port).map_err(ServerStartError::IoError)?;
+        let notifier = Arc::clone(&self.notifier);
+        let network_config = Arc::clone(&self.network_config);
+        let traffic_capture = Arc::clone(&self.traffic_capture);
+
+        let srv = listener.for_each(move |layer| {
+            let connection = NimiqMessageStream::new_memory(layer, false).and_then(|msg_stream| {
+                let shared_stream: SharedNimiqMessageStream = msg_stream.into();
+                let net_address = Some(Arc::new(shared_stream.net_address()));
+                NetworkConnection::new_connection_setup(shared_stream, AddressInfo::new(net_address, None), network_config.message_limits(), Arc::clone(&traffic_capture))
+            });
+            match connection {
+                Ok((nc, ncfut)) => {
+                    notifier.read().notify(WebSocketConnectorEvent::Connection(nc));
+                    tokio::spawn(ncfut);
+                },
+                Err(err) => error!("Could not accept connection: {:?}", err),
+            }
+            Ok(())
+        });
+
+        tokio::spawn(srv);
+        Ok(())
+    }
+
     pub fn connect(&self, peer_address: Arc<PeerAddress>) -> Result<Arc<ConnectionHandle>, ConnectError> {
         let notifier = Arc::clone(&self.notifier);
 
@@ -210,21 +352,39 @@ impl WebSocketConnector {
         // implementation where the data structures are there for something else and then you
         // get this check "for free")
 
-        let url = Url::parse(&peer_address.as_uri().to_string()).map_err(ConnectError::InvalidUri)?;
+        let msg_stream = match peer_address.ty {
+            #[cfg(feature = "memory-transport")]
+            _ if self.network_config.memory_transport().is_some() => {
+                nimiq_memory_connect_async(self.network_config.memory_transport().unwrap(), &peer_address)
+            },
+            PeerAddressType::Tcp(ref host, port) => {
+                // Keyless seeds can authenticate with any key, it is checked against the version message.
+                let public_key = if peer_address.has_public_key() { Some(peer_address.public_key) } else { None };
+                nimiq_tcp_connect_async(host.clone(), port, self.network_config.key_pair().clone(), public_key, self.network_config.proxy().cloned())
+            },
+            _ => {
+                let url = Url::parse(&peer_address.as_uri().to_string()).map_err(ConnectError::InvalidUri)?;
+                nimiq_connect_async(url, self.network_config.proxy().cloned())
+            },
+        };
         let error_notifier = Arc::clone(&self.notifier);
         let error_peer_address = Arc::clone(&peer_address);
+        let network_config = Arc::clone(&self.network_config);
+        let traffic_capture = Arc::clone(&self.traffic_capture);
         let (tx, rx) = oneshot::channel::<CloseType>();
         let connection_handle = Arc::new(ConnectionHandle::new(tx));
 
-        let connect = nimiq_connect_async(url)
-            .timeout(Self::CONNECT_TIMEOUT)
-            .map(move |msg_stream| {
+        let connect = msg_stream
+            .and_then(move |msg_stream| {
                 let shared_stream: SharedNimiqMessageStream = msg_stream.into();
                 let net_address = Some(Arc::new(shared_stream.net_address()));
-                let (nc, ncfut) = NetworkConnection::new_connection_setup(shared_stream, AddressInfo::new(net_address, Some(peer_address)));
+                let address_info = AddressInfo::new_authenticated(net_address, Some(peer_address), shared_stream.authenticated_public_key());
+                let (nc, ncfut) = NetworkConnection::new_connection_setup(shared_stream, address_info, network_config.message_limits(), traffic_capture)?;
                 notifier.read().notify(WebSocketConnectorEvent::Connection(nc));
                 tokio::spawn(ncfut);
+                Ok(())
             })
+            .timeout(Self::CONNECT_TIMEOUT)
             .map_err(move |error| {
                 if error.is_inner() {
                     let error = error.into_inner().expect("There was no inner_error inside the timeout::Error struct: abort.");
@@ -0,0 +1,126 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use nimiq_database::Environment;
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_keys::KeyPair;
+use nimiq_network::address::peer_address_book::PeerAddressBook;
+use nimiq_network::address::peer_address_state::PeerAddressState;
+use nimiq_network::ban_list::{BanEntry, BanList, BanTarget};
+use nimiq_network::connection::close_type::CloseType;
+use nimiq_network::network_config::NetworkConfig;
+use nimiq_network_primitives::address::net_address::NetAddress;
+use nimiq_network_primitives::address::peer_address::{PeerAddress, PeerAddressType};
+use nimiq_network_primitives::address::PeerId;
+use nimiq_network_primitives::networks::NetworkId;
+use nimiq_network_primitives::services::ServiceFlags;
+use nimiq_utils::time::systemtime_to_timestamp;
+
+fn env() -> &'static Environment {
+    Box::leak(Box::new(VolatileEnvironment::new(10).unwrap()))
+}
+
+fn ip(s: &str) -> NetAddress {
+    NetAddress::from_str(s).unwrap()
+}
+
+/// Creates a signed TCP peer address with the given IP address.
+fn peer_address(net_address: NetAddress) -> PeerAddress {
+    let keypair = KeyPair::generate();
+    let mut peer_address = PeerAddress {
+        ty: PeerAddressType::Tcp(net_address.to_string(), 8444),
+        services: ServiceFlags::FULL,
+        timestamp: systemtime_to_timestamp(SystemTime::now()),
+        net_address,
+        public_key: keypair.public.clone(),
+        distance: 0,
+        signature: None,
+        peer_id: PeerId::from(&keypair.public),
+    };
+    peer_address.signature = Some(keypair.sign(&peer_address.get_signature_data()));
+    peer_address
+}
+
+#[test]
+fn it_parses_ban_targets() {
+    let peer_id = PeerId::from(&KeyPair::generate().public);
+    assert_eq!(BanTarget::from_str(&peer_id.to_hex()).unwrap(), BanTarget::PeerId(peer_id));
+    assert_eq!(BanTarget::from_str("1.2.3.4").unwrap(), BanTarget::Ip(ip("1.2.3.4")));
+    assert_eq!(BanTarget::from_str("10.1.2.3/16").unwrap(), BanTarget::Subnet(ip("10.1.0.0"), 16));
+    assert_eq!(BanTarget::from_str("10.1.0.0/16").unwrap().to_string(), "10.1.0.0/16");
+    assert!(BanTarget::from_str("10.1.0.0/33").is_err());
+    assert!(BanTarget::from_str("not a target").is_err());
+}
+
+#[test]
+fn it_matches_banned_ips_and_subnets() {
+    let ban_list = BanList::new(env());
+    ban_list.ban(BanEntry::new(BanTarget::Ip(ip("1.2.3.4")), None, "test".to_string(), None).unwrap());
+    ban_list.ban(BanEntry::new(BanTarget::subnet(&ip("2001:db8::1"), 64), None, "test".to_string(), None).unwrap());
+
+    assert!(ban_list.is_ip_banned(&ip("1.2.3.4")));
+    assert!(!ban_list.is_ip_banned(&ip("1.2.3.5")));
+    assert!(ban_list.is_ip_banned(&ip("2001:db8::ffff")));
+    assert!(!ban_list.is_ip_banned(&ip("2001:db9::1")));
+    assert!(!ban_list.is_ip_banned(&NetAddress::Unspecified));
+
+    assert!(ban_list.unban(&BanTarget::Ip(ip("1.2.3.4"))));
+    assert!(!ban_list.is_ip_banned(&ip("1.2.3.4")));
+    assert!(!ban_list.unban(&BanTarget::Ip(ip("1.2.3.4"))));
+}
+
+#[test]
+fn it_rejects_bans_that_end_too_late() {
+    let target = BanTarget::Ip(ip("1.2.3.4"));
+    assert!(BanEntry::new(target.clone(), Some(Duration::from_secs(u64::max_value())), "test".to_string(), None).is_none());
+    assert!(BanEntry::new(target.clone(), Some(Duration::from_millis(u64::max_value())), "test".to_string(), None).is_none());
+    assert!(BanEntry::new(target, Some(Duration::from_secs(3600 * 24 * 365)), "test".to_string(), None).is_some());
+}
+
+#[test]
+fn it_persists_bans() {
+    let env = env();
+    let peer_id = PeerId::from(&KeyPair::generate().public);
+    {
+        let ban_list = BanList::new(env);
+        ban_list.ban(BanEntry::new(BanTarget::PeerId(peer_id.clone()), Some(Duration::from_secs(600)), "misbehaving".to_string(), Some(CloseType::ManualPeerBan)).unwrap());
+        ban_list.ban(BanEntry::new(BanTarget::Ip(ip("1.2.3.4")), Some(Duration::from_secs(0)), "expired".to_string(), None).unwrap());
+    }
+
+    let ban_list = BanList::new(env);
+    let bans = ban_list.bans();
+    assert_eq!(bans.len(), 1);
+    assert_eq!(bans[0].target, BanTarget::PeerId(peer_id.clone()));
+    assert_eq!(bans[0].reason, "misbehaving");
+    assert_eq!(bans[0].close_type, Some(CloseType::ManualPeerBan));
+    assert!(bans[0].banned_until.is_some());
+    assert!(ban_list.is_peer_banned(&peer_id));
+
+    ban_list.clear();
+    // A database can only be opened once at a time.
+    drop(ban_list);
+    assert!(BanList::new(env).bans().is_empty());
+}
+
+#[test]
+fn it_unbans_peer_addresses() {
+    let mut network_config = NetworkConfig::new_tcp_network_config("127.0.0.1".to_string(), 8444);
+    network_config.init_volatile();
+    let addresses = PeerAddressBook::new(Arc::new(network_config), NetworkId::Dev, env()).unwrap();
+    let first = Arc::new(peer_address(ip("1.2.3.4")));
+    let second = Arc::new(peer_address(ip("5.6.7.8")));
+    addresses.add(None, vec![first.as_ref().clone(), second.as_ref().clone()]);
+    addresses.close(None, Arc::clone(&first), CloseType::ManualPeerBan);
+    addresses.close(None, Arc::clone(&second), CloseType::ManualPeerBan);
+    assert!(addresses.is_banned(&first) && addresses.is_banned(&second));
+
+    assert_eq!(addresses.unban(Some(&BanTarget::Ip(ip("9.9.9.9")))), 0);
+    assert_eq!(addresses.unban(Some(&BanTarget::subnet(&ip("1.2.0.0"), 16))), 1);
+    assert!(!addresses.is_banned(&first));
+    assert!(addresses.state().get_info(&first).unwrap().state == PeerAddressState::New);
+    assert!(addresses.is_banned(&second));
+
+    assert_eq!(addresses.unban(None), 1);
+    assert!(!addresses.is_banned(&second));
+}
@@ -0,0 +1,162 @@
+use std::fs::File;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use tempdir::TempDir;
+
+use nimiq_keys::KeyPair;
+use nimiq_messages::Message;
+use nimiq_network::capture::{CaptureError, CaptureHeader, CaptureReader, CaptureRecord, CaptureWriter, Direction, TrafficCapture};
+use nimiq_network::connection::network_connection::AddressInfo;
+use nimiq_network_primitives::address::net_address::NetAddress;
+use nimiq_network_primitives::address::peer_address::{PeerAddress, PeerAddressType};
+use nimiq_network_primitives::address::PeerId;
+use nimiq_network_primitives::services::ServiceFlags;
+use nimiq_utils::unique_id::UniqueId;
+
+fn record(connection_id: u64, direction: Direction, message: Message) -> CaptureRecord {
+    CaptureRecord {
+        timestamp: 1_500_000_000_000 + connection_id,
+        direction,
+        connection_id,
+        net_address: NetAddress::from_str("1.2.3.4").unwrap(),
+        peer_id: if connection_id > 0 { Some(PeerId::from([connection_id as u8; 16])) } else { None },
+        message,
+    }
+}
+
+fn write(records: &[CaptureRecord]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    let mut writer = CaptureWriter::new(&mut buffer).unwrap();
+    for record in records {
+        writer.write(record).unwrap();
+    }
+    writer.flush().unwrap();
+    assert_eq!(writer.num_records(), records.len() as u64);
+    buffer
+}
+
+#[test]
+fn it_reads_written_records() {
+    let records = vec![
+        record(0, Direction::Inbound, Message::Ping(1)),
+        record(1, Direction::Outbound, Message::Pong(1)),
+        record(2, Direction::Inbound, Message::GetHead),
+    ];
+    let buffer = write(&records);
+
+    let read: Vec<CaptureRecord> = CaptureReader::new(&buffer[..]).unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(read.len(), records.len());
+    for (read, written) in read.iter().zip(records.iter()) {
+        assert_eq!(read.timestamp, written.timestamp);
+        assert_eq!(read.direction, written.direction);
+        assert_eq!(read.connection_id, written.connection_id);
+        assert_eq!(read.net_address, written.net_address);
+        assert_eq!(read.peer_id, written.peer_id);
+        assert_eq!(read.message.ty(), written.message.ty());
+    }
+}
+
+#[test]
+fn it_ignores_a_truncated_record() {
+    let records = vec![
+        record(1, Direction::Inbound, Message::Ping(7)),
+        record(1, Direction::Outbound, Message::Pong(7)),
+    ];
+    let buffer = write(&records);
+
+    let mut reader = CaptureReader::new(&buffer[..buffer.len() - 3]).unwrap();
+    assert!(reader.read_record().unwrap().is_some());
+    assert!(reader.read_record().unwrap().is_none());
+}
+
+#[test]
+fn it_rejects_unknown_files() {
+    assert!(match CaptureReader::new(&b"not a capture"[..]) {
+        Err(CaptureError::InvalidHeader) => true,
+        _ => false,
+    });
+    assert!(match CaptureReader::new(&[0u8; 0][..]) {
+        Err(CaptureError::InvalidHeader) => true,
+        _ => false,
+    });
+
+    let mut header = Vec::new();
+    beserial::Serialize::serialize(&CaptureHeader { magic: CaptureHeader::MAGIC, version: 2 }, &mut header).unwrap();
+    assert!(CaptureReader::new(&header[..]).is_err());
+}
+
+#[test]
+fn it_skips_oversized_records() {
+    let records = vec![record(1, Direction::Inbound, Message::Ping(7))];
+    let header_size = write(&[]).len();
+    let length = CaptureReader::<&[u8]>::RECORD_SIZE_MAX + 1;
+
+    let mut buffer = write(&[]);
+    buffer.extend_from_slice(&(length as u32).to_be_bytes());
+    buffer.extend(std::iter::repeat(0u8).take(length));
+    buffer.extend_from_slice(&write(&records)[header_size..]);
+
+    let mut reader = CaptureReader::new(&buffer[..]).unwrap();
+    assert!(match reader.read_record() {
+        Err(CaptureError::RecordTooLarge(size)) => size == length,
+        _ => false,
+    });
+    assert_eq!(reader.read_record().unwrap().unwrap().message.ty(), records[0].message.ty());
+    assert!(reader.read_record().unwrap().is_none());
+}
+
+#[test]
+fn it_only_captures_to_new_files_in_the_capture_directory() {
+    let dir = TempDir::new("capture").unwrap();
+    assert!(match TrafficCapture::new(None).start("capture") {
+        Err(CaptureError::NoCaptureDirectory) => true,
+        _ => false,
+    });
+
+    let traffic_capture = TrafficCapture::new(Some(dir.path().to_path_buf()));
+    for file_name in &["", ".", "..", "../capture", "sub/capture", "/tmp/capture", "sub\\capture"] {
+        assert!(match traffic_capture.start(file_name) {
+            Err(CaptureError::InvalidFileName(_)) => true,
+            _ => false,
+        }, "{:?} was accepted", file_name);
+    }
+
+    File::create(dir.path().join("existing")).unwrap();
+    assert!(match traffic_capture.start("existing") {
+        Err(CaptureError::IoError(_)) => true,
+        _ => false,
+    });
+
+    traffic_capture.start("capture").unwrap();
+    assert_eq!(traffic_capture.status().unwrap().path, dir.path().join("capture"));
+    traffic_capture.stop();
+}
+
+#[test]
+fn it_records_the_peer_id_once_it_is_known() {
+    let dir = TempDir::new("capture").unwrap();
+    let traffic_capture = TrafficCapture::new(Some(dir.path().to_path_buf()));
+    traffic_capture.start("capture").unwrap();
+
+    let connection_id = UniqueId::new();
+    let address_info = AddressInfo::new(Some(Arc::new(NetAddress::from_str("1.2.3.4").unwrap())), None);
+    traffic_capture.record(Direction::Inbound, connection_id, &address_info, &Message::Ping(1));
+
+    // The version message sets the peer address on the shared address info of the connection.
+    let public_key = KeyPair::generate().public;
+    let peer_id = PeerId::from(&public_key);
+    let peer_address = PeerAddress { ty: PeerAddressType::Ws("node.nimiq.com".to_string(), 8443), services: ServiceFlags::FULL, timestamp: 0, net_address: NetAddress::Unspecified, public_key, distance: 0, signature: None, peer_id: peer_id.clone() };
+    address_info.clone().set_peer_address(Arc::new(peer_address));
+    traffic_capture.record(Direction::Inbound, connection_id, &address_info, &Message::Ping(2));
+    traffic_capture.stop();
+
+    let records: Vec<CaptureRecord> = CaptureReader::new(File::open(dir.path().join("capture")).unwrap()).unwrap()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].peer_id, None);
+    assert_eq!(records[1].peer_id, Some(peer_id));
+}
@@ -0,0 +1,118 @@
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::thread;
+
+use tokio::runtime::current_thread::Runtime;
+
+use nimiq_keys::KeyPair;
+use nimiq_network::dns::{query, resolve_seed, seed_addresses, Record, RecordType};
+use nimiq_network_primitives::address::dns_seed::DnsSeed;
+use nimiq_network_primitives::address::peer_address::PeerAddressType;
+use nimiq_network_primitives::address::PeerId;
+use nimiq_network_primitives::protocol::Protocol;
+
+/// Builds the response to a query. TXT queries are answered with the given text, split into two
+/// character strings, A queries with the given address and all other queries with an empty answer.
+fn response(request: &[u8], txt: &str, ip: Ipv4Addr) -> Vec<u8> {
+    let size = request.len();
+    let ty = u16::from_be_bytes([request[size - 4], request[size - 3]]);
+
+    let answer: Option<(u16, Vec<u8>)> = match ty {
+        16 => {
+            let (first, second) = txt.as_bytes().split_at(txt.len() / 2);
+            let mut data = vec![first.len() as u8];
+            data.extend_from_slice(first);
+            data.push(second.len() as u8);
+            data.extend_from_slice(second);
+            Some((16, data))
+        },
+        1 => Some((1, ip.octets().to_vec())),
+        _ => None,
+    };
+
+    // Echo the header and question, set the response flag and the answer count.
+    let mut response = request.to_vec();
+    response[2] |= 0x80;
+    response[7] = answer.is_some() as u8;
+    if let Some((ty, data)) = answer {
+        // Compression pointer to the name in the question.
+        response.extend_from_slice(&[0xc0, 12]);
+        response.extend_from_slice(&ty.to_be_bytes());
+        response.extend_from_slice(&[0, 1, 0, 0, 0, 60]);
+        response.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        response.extend_from_slice(&data);
+    }
+    response
+}
+
+/// Runs a stub name server that answers the given number of queries. If `spoof` is set, every
+/// answer is preceded by a forged answer from another port and an answer with the wrong
+/// transaction id.
+fn stub_resolver(txt: String, ip: Ipv4Addr, queries: usize, spoof: bool) -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = socket.local_addr().unwrap();
+    let spoofer = UdpSocket::bind("127.0.0.1:0").unwrap();
+
+    thread::spawn(move || {
+        for _ in 0..queries {
+            let mut request = [0u8; 512];
+            let (size, client) = socket.recv_from(&mut request).unwrap();
+            let request = &request[..size];
+
+            if spoof {
+                spoofer.send_to(&response(request, "", Ipv4Addr::new(6, 6, 6, 6)), client).unwrap();
+                let mut wrong_id = response(request, "", Ipv4Addr::new(6, 6, 6, 6));
+                wrong_id[0] ^= 0xff;
+                socket.send_to(&wrong_id, client).unwrap();
+            }
+            socket.send_to(&response(request, &txt, ip), client).unwrap();
+        }
+    });
+
+    address
+}
+
+#[test]
+fn it_resolves_dns_seeds() {
+    let keypair = KeyPair::generate();
+    let txt = format!("wss://seed.nimiq.example:8443/{}", keypair.public.to_hex());
+    let resolver = stub_resolver(txt, Ipv4Addr::new(1, 2, 3, 4), 3, false);
+    let seed = DnsSeed::new("seeds.nimiq.example".to_string(), Protocol::Ws, None);
+
+    let mut runtime = Runtime::new().unwrap();
+    let seed_addresses = runtime.block_on(resolve_seed(resolver, &seed)).unwrap();
+
+    assert_eq!(seed_addresses.len(), 2);
+    assert_eq!(seed_addresses[0].ty, PeerAddressType::Wss("seed.nimiq.example".to_string(), 8443));
+    assert_eq!(seed_addresses[0].peer_id, PeerId::from(&keypair.public));
+    assert_eq!(seed_addresses[1].ty, PeerAddressType::Ws("1.2.3.4".to_string(), 8443));
+    assert!(seed_addresses[1].is_keyless_seed());
+}
+
+#[test]
+fn it_prefers_txt_records_over_addresses() {
+    let keypair = KeyPair::generate();
+    let seed = DnsSeed::new("seeds.nimiq.example".to_string(), Protocol::Tcp, Some(9000));
+    let records = vec![
+        Record::A(Ipv4Addr::new(1, 2, 3, 4)),
+        Record::A(Ipv4Addr::new(5, 6, 7, 8)),
+        Record::Txt(format!("tcp://1.2.3.4:8444/{}", keypair.public.to_hex())),
+        Record::Txt("v=spf1 -all".to_string()),
+    ];
+
+    let seed_addresses = seed_addresses(&seed, records);
+    assert_eq!(seed_addresses.len(), 2);
+    assert_eq!(seed_addresses[0].ty, PeerAddressType::Tcp("1.2.3.4".to_string(), 8444));
+    assert!(!seed_addresses[0].is_keyless_seed());
+    assert_eq!(seed_addresses[1].ty, PeerAddressType::Tcp("5.6.7.8".to_string(), 9000));
+    assert!(seed_addresses[1].is_keyless_seed());
+}
+
+#[test]
+fn it_ignores_responses_from_other_sources_and_queries() {
+    let resolver = stub_resolver(String::new(), Ipv4Addr::new(1, 2, 3, 4), 1, true);
+
+    let mut runtime = Runtime::new().unwrap();
+    let records = runtime.block_on(query(resolver, "seeds.nimiq.example", RecordType::A)).unwrap();
+
+    assert_eq!(records, vec![Record::A(Ipv4Addr::new(1, 2, 3, 4))]);
+}
@@ -0,0 +1,90 @@
+use futures::prelude::*;
+use futures::sync::mpsc::channel;
+
+use nimiq_hash::Blake2bHash;
+use nimiq_messages::{InvVector, InvVectorType, Message, MessageType};
+use nimiq_network::connection::close_type::CloseType;
+use nimiq_network::connection::network_connection::ClosedFlag;
+use nimiq_network::message_limits::{MessageLimit, MessageLimits};
+use nimiq_network::peer_channel::sink::{PeerSink, SendError};
+use nimiq_network::websocket::Message as WebSocketMessage;
+use nimiq_utils::unique_id::UniqueId;
+
+#[test]
+fn it_rate_limits_per_message_type() {
+    let mut limits = MessageLimits::unlimited();
+    limits.set(MessageType::GetData, MessageLimit::new(Some(2), None));
+
+    let mut rate_limiter = limits.rate_limiter();
+    assert!(rate_limiter.note(MessageType::GetData));
+    assert!(rate_limiter.note(MessageType::GetData));
+    assert!(!rate_limiter.note(MessageType::GetData));
+
+    // Other message types are not affected.
+    for _ in 0..10 {
+        assert!(rate_limiter.note(MessageType::Tx));
+    }
+}
+
+#[test]
+fn it_tracks_rates_per_peer() {
+    let mut limits = MessageLimits::unlimited();
+    limits.set(MessageType::Tx, MessageLimit::new(Some(1), None));
+
+    let mut first_peer = limits.rate_limiter();
+    let mut second_peer = limits.rate_limiter();
+    assert!(first_peer.note(MessageType::Tx));
+    assert!(!first_peer.note(MessageType::Tx));
+    assert!(second_peer.note(MessageType::Tx));
+}
+
+#[test]
+fn it_does_not_count_requested_objects() {
+    let mut limits = MessageLimits::unlimited();
+    limits.set(MessageType::Tx, MessageLimit::new(Some(1), None));
+
+    let mut rate_limiter = limits.rate_limiter();
+    let vectors = (0..3u8)
+        .map(|i| InvVector::new(InvVectorType::Transaction, Blake2bHash::from([i; 32])))
+        .collect();
+    rate_limiter.note_sent(&Message::GetData(vectors));
+
+    // The requested transactions and then one more are within the limit.
+    for _ in 0..4 {
+        assert!(rate_limiter.note(MessageType::Tx));
+    }
+    assert!(!rate_limiter.note(MessageType::Tx));
+}
+
+#[test]
+fn it_limits_message_sizes() {
+    let mut limits = MessageLimits::default();
+    assert!(limits.max_size(MessageType::GetData).is_some());
+    assert!(limits.max_size(MessageType::Block).is_none());
+
+    limits.set(MessageType::GetData, MessageLimit::default());
+    assert_eq!(limits.get(MessageType::GetData), MessageLimit::default());
+    assert!(limits.max_size(MessageType::GetData).is_none());
+}
+
+#[test]
+fn it_bounds_the_send_queue_but_always_sends_close_frames() {
+    let (tx, rx) = channel(2);
+    let sink = PeerSink::new(tx, UniqueId::new(), ClosedFlag::new());
+
+    let mut sent = 0;
+    while sink.send(Message::Ping(sent)).is_ok() {
+        sent += 1;
+        assert!(sent <= 3, "Send queue is not bounded");
+    }
+    assert_eq!(sink.send(Message::Ping(sent)), Err(SendError::QueueFull));
+
+    sink.close(CloseType::SendQueueFull, None);
+    drop(sink);
+    let queued = rx.collect().wait().unwrap();
+    assert_eq!(queued.len(), sent as usize + 1);
+    match queued.last() {
+        Some(WebSocketMessage::Close(_)) => (),
+        _ => panic!("Expected a close frame"),
+    }
+}
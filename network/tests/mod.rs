@@ -0,0 +1,62 @@
+use futures::future;
+use futures::prelude::*;
+use tokio::net::TcpListener;
+use tokio::runtime::current_thread::Runtime;
+
+use nimiq_keys::{KeyPair, PublicKey};
+use nimiq_messages::Message as NimiqMessage;
+use nimiq_network::tcp::{nimiq_tcp_accept_async, nimiq_tcp_connect_async};
+use nimiq_network::websocket::{Error, Message, NimiqMessageStream};
+
+type ConnectResult = Result<NimiqMessageStream, Error>;
+
+/// Connects a client to a local server. The client expects the server to authenticate with
+/// `expected_public_key`. The sockets are registered with the reactor of `runtime`.
+fn connect(runtime: &mut Runtime, server_key_pair: KeyPair, client_key_pair: KeyPair, expected_public_key: PublicKey) -> (ConnectResult, ConnectResult) {
+    let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let accept = listener.incoming().into_future()
+        .map_err(|(e, _)| Error::IoError(e))
+        .and_then(move |(socket, _)| nimiq_tcp_accept_async(socket.unwrap(), server_key_pair));
+    let connect = nimiq_tcp_connect_async("127.0.0.1".to_string(), port, client_key_pair, Some(expected_public_key), None);
+
+    runtime.block_on(accept.then(Ok::<_, ()>).join(connect.then(Ok::<_, ()>))).unwrap()
+}
+
+#[test]
+fn it_exchanges_messages() {
+    let server_key_pair = KeyPair::generate();
+    let client_key_pair = KeyPair::generate();
+    let mut runtime = Runtime::new().unwrap();
+    let (server, client) = connect(&mut runtime, server_key_pair.clone(), client_key_pair.clone(), server_key_pair.public);
+    let (server, client) = (server.unwrap(), client.unwrap());
+
+    assert_eq!(server.state().authenticated_public_key, Some(client_key_pair.public));
+    assert_eq!(client.state().authenticated_public_key, Some(server_key_pair.public));
+    assert!(!server.state().outbound);
+    assert!(client.state().outbound);
+
+    let received = runtime.block_on(client.send(Message::Message(NimiqMessage::Mempool))
+        .join(server.into_future().map_err(|(e, _)| e))
+        .and_then(|(_, (msg, _))| future::ok(msg)))
+        .unwrap();
+    match received {
+        Some(Message::Message(NimiqMessage::Mempool)) => {},
+        msg => panic!("Unexpected message: {:?}", msg),
+    }
+}
+
+#[test]
+fn it_rejects_unexpected_public_keys() {
+    let server_key_pair = KeyPair::generate();
+    let mut runtime = Runtime::new().unwrap();
+    let (server, client) = connect(&mut runtime, server_key_pair, KeyPair::generate(), KeyPair::generate().public);
+
+    match client {
+        Err(Error::HandshakeFailed(_)) => {},
+        _ => panic!("Expected the handshake to fail"),
+    }
+    // The client hangs up before authenticating itself.
+    assert!(server.is_err());
+}
@@ -0,0 +1,138 @@
+use std::str::FromStr;
+
+use url::Url;
+
+use nimiq_keys::KeyPair;
+use nimiq_network::address::peer_address_seeder::PeerAddressSeeder;
+use nimiq_network::network_config::{AllowedPeer, ConnectionMode, NetworkConfig, ScoringConfig, Seed};
+use nimiq_network_primitives::address::{DnsSeed, NetAddress, PeerAddress, PeerAddressType, PeerId, PeerUri, SeedList};
+use nimiq_network_primitives::protocol::Protocol;
+
+fn network_config() -> NetworkConfig {
+    NetworkConfig::new_tcp_network_config("127.0.0.1".to_string(), 8444)
+}
+
+#[test]
+fn it_allows_all_peers_by_default() {
+    let config = network_config();
+    assert!(!config.is_private());
+    assert!(config.is_peer_allowed(&PeerId::from(&KeyPair::generate().public)));
+}
+
+#[test]
+fn it_only_allows_configured_peers_in_private_mode() {
+    let uri_peer = KeyPair::generate().public;
+    let id_peer = PeerId::from(&KeyPair::generate().public);
+    let uri = PeerUri::from_str(&format!("tcp://node-1.cluster.local:8444/{}", uri_peer.to_hex())).unwrap();
+
+    let mut config = network_config();
+    config.set_allowed_peers(vec![
+        AllowedPeer::Peer(Box::new(uri)),
+        AllowedPeer::PeerId(id_peer.clone()),
+    ]);
+
+    assert!(config.is_private());
+    assert!(config.is_peer_allowed(&PeerId::from(&uri_peer)));
+    assert!(config.is_peer_allowed(&id_peer));
+    assert!(!config.is_peer_allowed(&PeerId::from(&KeyPair::generate().public)));
+}
+
+#[test]
+fn it_allows_no_peers_with_an_empty_allow_list() {
+    let mut config = network_config();
+    config.set_allowed_peers(Vec::new());
+
+    assert!(config.is_private());
+    assert!(!config.is_peer_allowed(&PeerId::from(&KeyPair::generate().public)));
+}
+
+#[test]
+fn it_pins_and_prefers_configured_peers() {
+    let pinned = PeerId::from(&KeyPair::generate().public);
+    let preferred = PeerId::from(&KeyPair::generate().public);
+
+    let mut scoring = ScoringConfig::default();
+    scoring.pinned_peers.insert(pinned.clone());
+    scoring.preferred_peers.insert(preferred.clone());
+
+    let mut config = network_config();
+    assert!(!config.scoring().is_pinned(&pinned));
+    config.set_scoring(scoring);
+
+    assert!(config.scoring().is_pinned(&pinned));
+    assert!(!config.scoring().is_preferred(&pinned));
+    assert!(config.scoring().is_preferred(&preferred));
+    assert!(!config.scoring().is_pinned(&preferred));
+}
+
+#[test]
+fn it_dials_and_listens_in_normal_mode() {
+    let config = network_config();
+    assert_eq!(config.connection_mode(), ConnectionMode::Normal);
+    assert!(config.accept_inbound());
+    assert!(config.dial_outbound());
+}
+
+#[test]
+fn it_only_listens_in_passive_mode() {
+    let mut config = network_config();
+    config.set_connection_mode(ConnectionMode::Passive);
+    assert!(config.accept_inbound());
+    assert!(!config.dial_outbound());
+}
+
+#[test]
+fn it_only_dials_in_outbound_only_mode() {
+    let mut config = network_config();
+    config.set_connection_mode(ConnectionMode::OutboundOnly);
+    assert!(!config.accept_inbound());
+    assert!(config.dial_outbound());
+    assert_eq!(config.connection_mode().to_string(), "outbound-only");
+}
+
+fn host(peer_address: &PeerAddress) -> String {
+    match peer_address.ty {
+        PeerAddressType::Tcp(ref host, _) => host.clone(),
+        ref ty => panic!("Unexpected peer address type {:?}", ty),
+    }
+}
+
+#[test]
+fn it_advertises_a_host_of_the_peers_ip_version() {
+    let mut config = NetworkConfig::new_tcp_network_config("node.cluster.local".to_string(), 8444);
+    config.init_volatile();
+    config.set_additional_hosts(vec!["203.0.113.5".to_string(), "[2001:db8::1]".to_string()]);
+
+    assert_eq!(host(&config.peer_address()), "node.cluster.local");
+    assert_eq!(host(&config.peer_address_for(&NetAddress::from_str("2001:db8::7").unwrap())), "[2001:db8::1]");
+    assert_eq!(host(&config.peer_address_for(&NetAddress::from_str("198.51.100.1").unwrap())), "203.0.113.5");
+    assert_eq!(host(&config.peer_address_for(&NetAddress::from_str("::ffff:198.51.100.1").unwrap())), "203.0.113.5");
+
+    let peer_addresses = config.peer_addresses();
+    assert_eq!(peer_addresses.iter().map(host).collect::<Vec<_>>(), vec!["node.cluster.local", "203.0.113.5", "[2001:db8::1]"]);
+    assert!(peer_addresses.iter().all(PeerAddress::verify_signature));
+}
+
+#[test]
+fn it_falls_back_to_the_configured_host() {
+    let mut config = NetworkConfig::new_tcp_network_config("node.cluster.local".to_string(), 8444);
+    config.init_volatile();
+    config.set_additional_hosts(vec!["203.0.113.5".to_string()]);
+    assert_eq!(host(&config.peer_address_for(&NetAddress::from_str("2001:db8::7").unwrap())), "node.cluster.local");
+    assert!(config.listen_addresses().is_empty());
+}
+
+#[test]
+fn it_splits_remote_seeds() {
+    let seeds = vec![
+        Seed::Peer(Box::new(PeerUri::new_wss("seed.example.com".to_string(), None, None, None))),
+        Seed::List(Box::new(SeedList::new(Url::from_str("https://example.com/seeds.txt").unwrap(), None))),
+        Seed::Dns(Box::new(DnsSeed::new("seeds.example.com".to_string(), Protocol::Wss, None))),
+    ];
+
+    let (seed_lists, dns_seeds) = PeerAddressSeeder::remote_seeds(&seeds);
+    assert_eq!(seed_lists.len(), 1);
+    assert_eq!(seed_lists[0].url().as_str(), "https://example.com/seeds.txt");
+    assert_eq!(dns_seeds.len(), 1);
+    assert_eq!(dns_seeds[0].host(), "seeds.example.com");
+}
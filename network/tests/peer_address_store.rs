@@ -0,0 +1,156 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use futures::future;
+use tokio::runtime::current_thread::Runtime;
+
+use nimiq_database::Environment;
+use nimiq_database::volatile::VolatileEnvironment;
+use nimiq_keys::KeyPair;
+use nimiq_network::address::peer_address_book::PeerAddressBook;
+use nimiq_network::address::peer_address_state::{PeerAddressInfo, PeerAddressState};
+use nimiq_network::address::peer_address_store::PeerAddressStore;
+use nimiq_network::network_config::{AllowedPeer, NetworkConfig};
+use nimiq_network_primitives::address::net_address::NetAddress;
+use nimiq_network_primitives::address::peer_address::{PeerAddress, PeerAddressType};
+use nimiq_network_primitives::address::{PeerId, PeerUri};
+use nimiq_network_primitives::networks::NetworkId;
+use nimiq_network_primitives::protocol::Protocol;
+use nimiq_network_primitives::services::ServiceFlags;
+use nimiq_utils::time::systemtime_to_timestamp;
+
+fn env() -> &'static Environment {
+    Box::leak(Box::new(VolatileEnvironment::new(10).unwrap()))
+}
+
+/// Creates a signed websocket peer address that was announced at `timestamp`.
+fn peer_address(host: &str, timestamp: SystemTime) -> PeerAddress {
+    let keypair = KeyPair::generate();
+    let mut peer_address = PeerAddress {
+        ty: PeerAddressType::Wss(host.to_string(), 8443),
+        services: ServiceFlags::FULL,
+        timestamp: systemtime_to_timestamp(timestamp),
+        net_address: NetAddress::Unspecified,
+        public_key: keypair.public.clone(),
+        distance: 0,
+        signature: None,
+        peer_id: PeerId::from(&keypair.public),
+    };
+    peer_address.signature = Some(keypair.sign(&peer_address.get_signature_data()));
+    peer_address
+}
+
+#[test]
+fn it_restores_stored_addresses() {
+    let store = PeerAddressStore::new(env());
+
+    let mut tried = PeerAddressInfo::new(Arc::new(peer_address("tried.example.com", SystemTime::now())));
+    tried.state = PeerAddressState::Established;
+    tried.last_connected = Some(SystemTime::now());
+    let mut failed = PeerAddressInfo::new(Arc::new(peer_address("failed.example.com", SystemTime::now())));
+    failed.state = PeerAddressState::Failed;
+    failed.failed_attempts = 2;
+    store.store(vec![tried, failed].iter());
+
+    let mut infos = store.load(Duration::from_secs(60 * 60));
+    infos.sort_by_key(|info| info.peer_address.to_string());
+    assert_eq!(infos.len(), 2);
+    assert!(infos[0].peer_address.to_string().starts_with("wss://failed.example.com"));
+    assert!(infos[0].state == PeerAddressState::Failed);
+    assert_eq!(infos[0].failed_attempts, 2);
+    // Established connections are restored as tried addresses.
+    assert!(infos[1].state == PeerAddressState::Tried);
+    assert!(infos[1].last_connected.is_some());
+    assert!(infos.iter().all(|info| info.restored && !info.exceeds_age()));
+}
+
+#[test]
+fn it_expires_stale_addresses() {
+    let store = PeerAddressStore::new(env());
+
+    let fresh = PeerAddressInfo::new(Arc::new(peer_address("fresh.example.com", SystemTime::now())));
+    let stale = PeerAddressInfo::new(Arc::new(peer_address("stale.example.com", SystemTime::now() - Duration::from_secs(60 * 60 * 2))));
+    store.store(vec![fresh, stale].iter());
+
+    let infos = store.load(Duration::from_secs(60 * 60));
+    assert_eq!(infos.len(), 1);
+    assert!(infos[0].peer_address.to_string().starts_with("wss://fresh.example.com"));
+
+    // Expired entries are removed from the database.
+    assert_eq!(store.load(Duration::from_secs(60 * 60 * 24)).len(), 1);
+}
+
+#[test]
+fn it_stores_a_peer_once_per_protocol() {
+    let store = PeerAddressStore::new(env());
+
+    let wss = peer_address("node.example.com", SystemTime::now());
+    let mut ws = wss.clone();
+    ws.ty = PeerAddressType::Ws("node.example.com".to_string(), 8080);
+    store.store(vec![PeerAddressInfo::new(Arc::new(wss)), PeerAddressInfo::new(Arc::new(ws))].iter());
+
+    let mut protocols: Vec<Protocol> = store.load(Duration::from_secs(60 * 60)).iter()
+        .map(|info| info.peer_address.protocol())
+        .collect();
+    protocols.sort();
+    assert_eq!(protocols, vec![Protocol::Wss, Protocol::Ws]);
+}
+
+#[test]
+fn it_only_stores_seeds_with_a_known_key() {
+    let store = PeerAddressStore::new(env());
+
+    let mut seed = peer_address("seed.example.com", SystemTime::now());
+    seed.timestamp = 0;
+    seed.signature = None;
+    let keyless_seed = PeerAddress::new_keyless_seed(PeerAddressType::Wss("keyless.example.com".to_string(), 8443), None);
+    store.store(vec![PeerAddressInfo::new(Arc::new(seed.clone())), PeerAddressInfo::new(Arc::new(keyless_seed))].iter());
+
+    let infos = store.load(Duration::from_secs(60 * 60));
+    assert_eq!(infos.len(), 1);
+    assert_eq!(*infos[0].peer_address, seed);
+    assert!(infos[0].peer_address.is_seed());
+    assert!(infos[0].peer_address.signature.is_none());
+}
+
+fn address_book(env: &'static Environment, allowed_peers: Vec<AllowedPeer>) -> Arc<PeerAddressBook> {
+    let mut network_config = NetworkConfig::new_tcp_network_config("127.0.0.1".to_string(), 8444);
+    network_config.init_volatile();
+    network_config.set_allowed_peers(allowed_peers);
+    Arc::new(PeerAddressBook::new(Arc::new(network_config), NetworkId::Dev, env).unwrap())
+}
+
+#[test]
+fn it_restores_the_keys_pinned_for_keyless_seeds() {
+    let env = env();
+    let seed_uri = PeerUri::from_str("tcp://seed.example.com:8443").unwrap();
+    let allowed_seed = || vec![AllowedPeer::Peer(Box::new(seed_uri.clone()))];
+    let seed = Arc::new(seed_uri.as_seed_peer_address().unwrap());
+    let mut announced = peer_address("seed.example.com", SystemTime::now());
+    announced.ty = PeerAddressType::Tcp("seed.example.com".to_string(), 8443);
+
+    let addresses = address_book(env, allowed_seed());
+    addresses.pin_seed(&seed, &announced);
+    addresses.persist();
+    // A database can only be opened once at a time.
+    drop(addresses);
+
+    // Timers are spawned on initialization.
+    let mut runtime = Runtime::new().unwrap();
+    runtime.block_on(future::lazy(|| {
+        let addresses = address_book(env, allowed_seed());
+        PeerAddressBook::initialize(&addresses).unwrap();
+        let pinned = addresses.state().get_by_peer_id(&announced.peer_id).unwrap();
+        assert!(pinned.is_seed());
+        assert_eq!(pinned.public_key, announced.public_key);
+        assert!(addresses.state().get_info(&seed).is_none());
+        drop(addresses);
+
+        // The key is not restored once the seed has been removed from the config.
+        let addresses = address_book(env, Vec::new());
+        PeerAddressBook::initialize(&addresses).unwrap();
+        assert!(addresses.state().get_by_peer_id(&announced.peer_id).is_none());
+        Ok::<(), ()>(())
+    })).unwrap();
+}
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use nimiq_keys::KeyPair;
+use nimiq_network::address::peer_address_state::{PeerAddressInfo, PeerAddressState};
+use nimiq_network::network_config::ScoringConfig;
+use nimiq_network::peer_scorer::{DefaultScoringStrategy, ScoringStrategy};
+use nimiq_network_primitives::address::net_address::NetAddress;
+use nimiq_network_primitives::address::peer_address::{PeerAddress, PeerAddressType};
+use nimiq_network_primitives::address::PeerId;
+use nimiq_network_primitives::services::ServiceFlags;
+
+fn peer_address_info(state: PeerAddressState, failed_attempts: u32) -> PeerAddressInfo {
+    let public_key = KeyPair::generate().public;
+    let peer_address = PeerAddress { ty: PeerAddressType::Wss("node.nimiq.com".to_string(), 8443), services: ServiceFlags::FULL, timestamp: 0, net_address: NetAddress::Unspecified, public_key, distance: 0, signature: None, peer_id: PeerId::from(&public_key) };
+    let mut info = PeerAddressInfo::new(Arc::new(peer_address));
+    info.state = state;
+    info.failed_attempts = failed_attempts;
+    info
+}
+
+fn score_address(info: &PeerAddressInfo) -> i32 {
+    DefaultScoringStrategy.score_address(&info.peer_address, info, &ScoringConfig::default())
+}
+
+#[test]
+fn it_scores_addresses_by_state() {
+    assert_eq!(score_address(&peer_address_info(PeerAddressState::New, 0)), 1);
+    assert_eq!(score_address(&peer_address_info(PeerAddressState::Tried, 0)), 1);
+    assert_eq!(score_address(&peer_address_info(PeerAddressState::Established, 0)), -1);
+    assert_eq!(score_address(&peer_address_info(PeerAddressState::Banned, 0)), -1);
+}
+
+#[test]
+fn it_stops_picking_addresses_that_failed_too_often() {
+    let info = peer_address_info(PeerAddressState::Failed, 0);
+    let max_failed_attempts = info.max_failed_attempts();
+    assert_eq!(score_address(&info), 1);
+    assert_eq!(score_address(&peer_address_info(PeerAddressState::Failed, max_failed_attempts - 2)), 1);
+    assert_eq!(score_address(&peer_address_info(PeerAddressState::Failed, max_failed_attempts - 1)), 0);
+}
+
+#[test]
+fn it_scores_the_median_latency_relative_to_the_ping_timeout() {
+    // The ping timeout is 10 seconds.
+    assert_eq!(DefaultScoringStrategy::score_speed(0.0), 0.0);
+    assert_eq!(DefaultScoringStrategy::score_speed(2.5), 0.75);
+    assert_eq!(DefaultScoringStrategy::score_speed(5.0), 0.5);
+    assert_eq!(DefaultScoringStrategy::score_speed(10.0), 0.0);
+    assert_eq!(DefaultScoringStrategy::score_speed(250.0), 0.0);
+}
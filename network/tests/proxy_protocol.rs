@@ -0,0 +1,95 @@
+use std::io::{Cursor, Read};
+use std::str::FromStr;
+
+use futures::prelude::*;
+
+use nimiq_network::proxy_protocol::{accept, parse_v1, parse_v2, V2_SIGNATURE};
+use nimiq_network_primitives::address::net_address::NetAddress;
+
+/// Reads the header in front of `data` and returns the address and what's left of the stream.
+fn read(data: Vec<u8>) -> (Option<NetAddress>, Vec<u8>) {
+    let (mut stream, net_address) = accept(Cursor::new(data)).wait().unwrap();
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).unwrap();
+    (net_address, rest)
+}
+
+fn v2_header(version_command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+    let mut header = V2_SIGNATURE.to_vec();
+    header.push(version_command);
+    header.push(family);
+    header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+    header.extend_from_slice(addresses);
+    header
+}
+
+#[test]
+fn it_reads_v1_headers() {
+    let (net_address, rest) = read(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET / HTTP/1.1\r\n".to_vec());
+    assert_eq!(net_address, Some(NetAddress::from_str("192.0.2.1").unwrap()));
+    assert_eq!(rest, b"GET / HTTP/1.1\r\n".to_vec());
+
+    let (net_address, _) = read(b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\n".to_vec());
+    assert_eq!(net_address, Some(NetAddress::from_str("2001:db8::1").unwrap()));
+
+    let (net_address, rest) = read(b"PROXY UNKNOWN\r\nGET".to_vec());
+    assert_eq!(net_address, None);
+    assert_eq!(rest, b"GET".to_vec());
+}
+
+#[test]
+fn it_rejects_invalid_v1_headers() {
+    assert!(parse_v1(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324\r\n").is_err());
+    assert!(parse_v1(b"PROXY TCP4 2001:db8::1 198.51.100.1 56324 443\r\n").is_err());
+    assert!(parse_v1(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 65536\r\n").is_err());
+    assert!(parse_v1(b"PROXY UDP4 192.0.2.1 198.51.100.1 56324 443\r\n").is_err());
+    assert!(parse_v1(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\n").is_err());
+
+    // Without a line break within the maximum length.
+    let mut data = b"PROXY UNKNOWN ".to_vec();
+    data.extend_from_slice(&[b'a'; 200]);
+    assert!(accept(Cursor::new(data)).wait().is_err());
+}
+
+#[test]
+fn it_reads_v2_headers() {
+    let mut data = v2_header(0x21, 0x11, &[192, 0, 2, 1, 198, 51, 100, 1, 0xdc, 0x04, 0x01, 0xbb]);
+    data.extend_from_slice(b"GET");
+    let (net_address, rest) = read(data);
+    assert_eq!(net_address, Some(NetAddress::from_str("192.0.2.1").unwrap()));
+    assert_eq!(rest, b"GET".to_vec());
+
+    let source = NetAddress::from_str("2001:db8::1").unwrap();
+    let mut addresses = match source {
+        NetAddress::IPv6(ip) => ip.octets().to_vec(),
+        _ => unreachable!(),
+    };
+    addresses.extend_from_slice(&[0u8; 20]);
+    // A TLV after the addresses is skipped.
+    addresses.extend_from_slice(&[0x04, 0x00, 0x01, 0x00]);
+    let (net_address, rest) = read(v2_header(0x21, 0x21, &addresses));
+    assert_eq!(net_address, Some(source));
+    assert!(rest.is_empty());
+
+    // Health checks of the proxy.
+    let (net_address, _) = read(v2_header(0x20, 0x00, &[]));
+    assert_eq!(net_address, None);
+}
+
+#[test]
+fn it_rejects_invalid_v2_headers() {
+    assert!(parse_v2(0x11, 0x11, &[0u8; 12]).is_err());
+    assert!(parse_v2(0x22, 0x11, &[0u8; 12]).is_err());
+    assert!(parse_v2(0x21, 0x11, &[0u8; 11]).is_err());
+    assert!(parse_v2(0x21, 0x21, &[0u8; 12]).is_err());
+
+    // Fewer addresses than announced.
+    let data = v2_header(0x21, 0x11, &[0u8; 12]);
+    assert!(accept(Cursor::new(data[..24].to_vec())).wait().is_err());
+}
+
+#[test]
+fn it_requires_a_header() {
+    assert!(accept(Cursor::new(b"GET / HTTP/1.1\r\n\r\n".to_vec())).wait().is_err());
+    assert!(accept(Cursor::new(b"PROXY".to_vec())).wait().is_err());
+}
@@ -1,12 +1,110 @@
-use beserial::{Deserialize, Serialize};
-use std::fmt::Display;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::RwLock;
 
-#[derive(Serialize, Deserialize, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Display)]
-#[repr(u8)]
+use beserial::{Deserialize, ReadBytesExt, Serialize, SerializingError, WriteBytesExt};
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
 pub enum NetworkId {
-    Test = 1,
-    Dev = 2,
-    Bounty = 3,
-    Dummy = 4,
-    Main = 42,
-}
\ No newline at end of file
+    Test,
+    Dev,
+    Bounty,
+    Dummy,
+    /// Local-only network for integration tests, blocks are mined on demand at minimal difficulty.
+    Regtest,
+    Main,
+    /// A network loaded from a network file. It can only be created with `NetworkId::from(u8)`, so
+    /// that IDs of the networks above are never used for custom networks.
+    Custom(CustomNetworkId),
+}
+
+/// The ID of a custom network. It is never the ID of a built-in network.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Hash)]
+pub struct CustomNetworkId(u8);
+
+lazy_static! {
+    static ref CUSTOM_NETWORK_IDS: RwLock<HashSet<u8>> = RwLock::new(HashSet::new());
+}
+
+impl From<u8> for NetworkId {
+    fn from(id: u8) -> Self {
+        match id {
+            1 => NetworkId::Test,
+            2 => NetworkId::Dev,
+            3 => NetworkId::Bounty,
+            4 => NetworkId::Dummy,
+            5 => NetworkId::Regtest,
+            42 => NetworkId::Main,
+            id => NetworkId::Custom(CustomNetworkId(id)),
+        }
+    }
+}
+
+impl From<NetworkId> for u8 {
+    fn from(network_id: NetworkId) -> Self {
+        match network_id {
+            NetworkId::Test => 1,
+            NetworkId::Dev => 2,
+            NetworkId::Bounty => 3,
+            NetworkId::Dummy => 4,
+            NetworkId::Regtest => 5,
+            NetworkId::Main => 42,
+            NetworkId::Custom(CustomNetworkId(id)) => id,
+        }
+    }
+}
+
+impl NetworkId {
+    pub fn is_custom(self) -> bool {
+        match self {
+            NetworkId::Custom(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Makes a custom network ID known to this process, so it can be deserialized. This happens
+    /// when the custom network is registered.
+    pub fn register_custom(self) {
+        if let NetworkId::Custom(CustomNetworkId(id)) = self {
+            CUSTOM_NETWORK_IDS.write().unwrap().insert(id);
+        }
+    }
+
+    /// Whether this is a built-in network or a registered custom network.
+    pub fn is_known(self) -> bool {
+        match self {
+            NetworkId::Custom(CustomNetworkId(id)) => CUSTOM_NETWORK_IDS.read().unwrap().contains(&id),
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for NetworkId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkId::Custom(CustomNetworkId(id)) => write!(f, "Custom{}", id),
+            network_id => fmt::Debug::fmt(network_id, f),
+        }
+    }
+}
+
+impl Serialize for NetworkId {
+    fn serialize<W: WriteBytesExt>(&self, writer: &mut W) -> Result<usize, SerializingError> {
+        Serialize::serialize(&u8::from(*self), writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        1
+    }
+}
+
+impl Deserialize for NetworkId {
+    fn deserialize<R: ReadBytesExt>(reader: &mut R) -> Result<Self, SerializingError> {
+        let id: u8 = Deserialize::deserialize(reader)?;
+        let network_id = NetworkId::from(id);
+        if !network_id.is_known() {
+            return Err(SerializingError::InvalidValue);
+        }
+        Ok(network_id)
+    }
+}
@@ -9,6 +9,11 @@ use crate::error::AuthenticationError;
 
 pub trait Handler: Send + Sync {
     fn get_method(&self, name: &str) -> Option<fn(&Self, params: Array) -> Result<JsonValue, JsonValue>>;
+    /// Returns a future that the call of the given method waits for, e.g. to answer long polling
+    /// requests once there is something new. The method is called right away if this is `None`.
+    fn wait_before_method(&self, _name: &str, _params: &Array) -> Option<Box<dyn Future<Item=(), Error=()> + Send>> {
+        None
+    }
     fn authorize(&self, _username: &str, _password: &str) -> Result<(), AuthenticationError> {
         Ok(())
     }
@@ -42,9 +47,11 @@ impl std::fmt::Display for Never {
     }
 }
 
-fn handle_request<H>(handler: Arc<H>, str_o: Result<&str, std::str::Utf8Error>) -> Response<Body> where H: Handler {
+type ResponseFuture = Box<dyn Future<Item=Response<Body>, Error=hyper::Error> + Send>;
+
+fn handle_request<H>(handler: Arc<H>, str_o: Result<&str, std::str::Utf8Error>) -> ResponseFuture where H: Handler + 'static {
     if str_o.is_err() {
-        return Response::builder()
+        return Box::new(future::ok(Response::builder()
             .status(StatusCode::BAD_REQUEST)
             .body(Body::from(json::stringify(object!{
                             "jsonrpc" => "2.0",
@@ -54,11 +61,11 @@ fn handle_request<H>(handler: Arc<H>, str_o: Result<&str, std::str::Utf8Error>)
                                 "message" => "Invalid encoding"
                             }
                         })))
-            .unwrap();
+            .unwrap()));
     }
     let json_o = json::parse(str_o.unwrap());
     if json_o.is_err() {
-        return Response::builder()
+        return Box::new(future::ok(Response::builder()
             .status(StatusCode::BAD_REQUEST)
             .body(Body::from(json::stringify(object!{
                             "jsonrpc" => "2.0",
@@ -68,7 +75,7 @@ fn handle_request<H>(handler: Arc<H>, str_o: Result<&str, std::str::Utf8Error>)
                                 "message" => "Invalid JSON"
                             }
                         })))
-            .unwrap();
+            .unwrap()));
     }
     let mut json = json_o.unwrap();
     let single = json.is_object();
@@ -76,7 +83,7 @@ fn handle_request<H>(handler: Arc<H>, str_o: Result<&str, std::str::Utf8Error>)
         json = array![json];
     }
     if !json.is_array() {
-        return Response::builder()
+        return Box::new(future::ok(Response::builder()
             .status(StatusCode::BAD_REQUEST)
             .body(Body::from(json::stringify(object!{
                             "jsonrpc" => "2.0",
@@ -86,32 +93,32 @@ fn handle_request<H>(handler: Arc<H>, str_o: Result<&str, std::str::Utf8Error>)
                                 "message" => "Invalid request"
                             }
                         })))
-            .unwrap();
+            .unwrap()));
     }
-    let mut results = vec![];
+    let mut results: Vec<Box<dyn Future<Item=JsonValue, Error=hyper::Error> + Send>> = vec![];
     for msg in json.members() {
         if msg["jsonrpc"] != "2.0" || !msg.has_key("method") || !msg["method"].is_string() {
-            results.push(object!{
+            results.push(Box::new(future::ok(object!{
                             "jsonrpc" => "2.0",
                             "id" => msg["id"].clone(),
                             "error" => object!{
                                 "code" => -32600,
                                 "message" => "Invalid request"
                             }
-                        });
+                        })));
             continue;
         }
         let method_o = handler.get_method(msg["method"].as_str().unwrap());
         if method_o.is_none() {
             warn!("Unknown method called: {}", msg["method"]);
-            results.push(object!{
+            results.push(Box::new(future::ok(object!{
                             "jsonrpc" => "2.0",
                             "id" => msg["id"].clone(),
                             "error" => object!{
                                 "code" => -32601,
                                 "message" => "Method not found"
                             }
-                        });
+                        })));
             continue;
         }
         let method = method_o.unwrap();
@@ -121,25 +128,34 @@ fn handle_request<H>(handler: Arc<H>, str_o: Result<&str, std::str::Utf8Error>)
             _ => vec![params]
         };
 
-        results.push(match method(&handler, params_array) {
+        let wait = handler.wait_before_method(msg["method"].as_str().unwrap(), &params_array);
+        let id = msg["id"].clone();
+        let handler = Arc::clone(&handler);
+        let call = move || match method(&handler, params_array) {
             Ok(result) => object!{
                             "jsonrpc" => "2.0",
-                            "id" => msg["id"].clone(),
+                            "id" => id,
                             "result" => result
                         },
             Err(error) => object!{
                             "jsonrpc" => "2.0",
-                            "id" => msg["id"].clone(),
+                            "id" => id,
                             "error" => error
                         }
+        };
+        results.push(match wait {
+            Some(wait) => Box::new(wait.then(move |_| Ok(call()))),
+            None => Box::new(future::ok(call())),
         });
     }
 
-    if single {
-        Response::new(Body::from(results.pop().map(json::stringify).unwrap_or_else(String::new)))
-    } else {
-        Response::new(Body::from(json::stringify(JsonValue::Array(results))))
-    }
+    Box::new(future::join_all(results).map(move |mut results| {
+        if single {
+            Response::new(Body::from(results.pop().map(json::stringify).unwrap_or_else(String::new)))
+        } else {
+            Response::new(Body::from(json::stringify(JsonValue::Array(results))))
+        }
+    }))
 }
 
 fn check_authentication<H: Handler>(handler: Arc<H>, authorization: Option<&HeaderValue>) -> Result<(), AuthenticationError> {
@@ -195,7 +211,7 @@ impl<H> hyper::service::Service for Service<H> where H: Handler + 'static {
                         .unwrap()))
                 }
                 Box::new(req.into_body().concat2()
-                    .map(|b| handle_request(handler, std::str::from_utf8(&b))))
+                    .and_then(|b| handle_request(handler, std::str::from_utf8(&b))))
             },
             _ => Box::new(future::ok(Response::new(Body::from(""))))
         }
@@ -11,6 +11,7 @@ extern crate nimiq_keys as keys;
 extern crate nimiq_mempool as mempool;
 extern crate nimiq_network as network;
 extern crate nimiq_network_primitives as network_primitives;
+extern crate nimiq_primitives as primitives;
 extern crate nimiq_transaction as transaction;
 extern crate nimiq_utils as utils;
 
@@ -18,29 +19,39 @@ use std::collections::{HashMap, HashSet};
 use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, Instant};
 
+use futures::future;
 use futures::future::Future;
+use futures::sync::oneshot;
 use hex;
 use hyper::Server;
 use json::{Array, JsonValue, Null};
 use json::object::Object;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use tokio::timer::Delay;
 
 use beserial::{Deserialize, Serialize};
 use block::{Block, BlockHeader, Difficulty};
 use block_production::BlockProducer;
-use blockchain::PushResult;
+use block_production::policy::{DefaultSelectionPolicy, TransactionSelectionPolicy};
+use blockchain::{BlockchainEvent, PushResult};
 use consensus::consensus::{Consensus, ConsensusEvent};
 use hash::{Argon2dHash, Blake2bHash, Blake2bHasher, Hash};
 use keys::Address;
-use mempool::ReturnCode;
+use mempool::{MempoolEvent, ReturnCode};
+use mempool::filter::{AccountTypeRules, Rules};
 use network::address::peer_address_state::{PeerAddressInfo, PeerAddressState};
+use network::ban_list::{BanEntry, BanTarget};
+use network::capture::CaptureStatus;
 use network::connection::close_type::CloseType;
 use network::connection::connection_info::ConnectionInfo;
 use network::connection::connection_pool::ConnectionId;
-use network::peer_scorer::Score;
+use network::peer_scorer::{Score, ScoreBreakdown};
 use network_primitives::address::{PeerId, PeerUri};
+use network_primitives::networks::NetworkId;
+use primitives::account::AccountType;
+use primitives::coin::Coin;
 use transaction::{Transaction, TransactionReceipt};
 use utils::merkle::MerklePath;
 use utils::time::systemtime_to_timestamp;
@@ -62,6 +73,36 @@ pub struct JsonRpcConfig {
     pub methods: HashSet<String>,
     pub allowip: (),
     pub corsdomain: Vec<String>,
+    /// Policy used to select the transactions for block templates.
+    pub block_template_policy: Arc<dyn TransactionSelectionPolicy>,
+}
+
+impl Default for JsonRpcConfig {
+    fn default() -> Self {
+        JsonRpcConfig {
+            credentials: None,
+            methods: HashSet::new(),
+            allowip: (),
+            corsdomain: Vec::new(),
+            block_template_policy: Arc::new(DefaultSelectionPolicy::default()),
+        }
+    }
+}
+
+/// Re-reads the configuration of the node, used by the `reloadConfig` method.
+pub trait ConfigReload: Send + Sync {
+    fn reload_config(&self) -> Result<ConfigReloadReport, failure::Error>;
+}
+
+/// The settings that changed with a reload of the configuration.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigReloadReport {
+    /// Settings that were applied while the node is running.
+    pub applied: Vec<String>,
+    /// Settings that changed, but are overridden on the command line.
+    pub overridden: Vec<String>,
+    /// Settings that only take effect after a restart.
+    pub restart_required: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -84,20 +125,111 @@ pub(crate) struct JsonRpcServerState {
     consensus_state: &'static str,
 }
 
+/// Tracks changes that are relevant for block templates, so that long-polling
+/// `getBlockTemplate` requests can be answered as soon as a better template is available.
+pub(crate) struct BlockTemplateState {
+    revision: Mutex<BlockTemplateRevision>,
+}
+
+struct BlockTemplateRevision {
+    head_hash: Blake2bHash,
+    counter: u64,
+    /// Requests waiting for the next change.
+    waiting: Vec<oneshot::Sender<()>>,
+}
+
+impl BlockTemplateRevision {
+    fn long_poll_id(&self) -> String {
+        format!("{}-{}", self.head_hash.to_hex(), self.counter)
+    }
+
+    fn changed(&mut self) {
+        self.counter += 1;
+        for waiting in self.waiting.drain(..) {
+            waiting.send(()).ok();
+        }
+    }
+}
+
+impl BlockTemplateState {
+    /// Default time to wait for a change of the block template.
+    const LONG_POLL_TIMEOUT_DEFAULT: Duration = Duration::from_secs(60);
+    /// Maximum time to wait for a change of the block template.
+    const LONG_POLL_TIMEOUT_MAX: Duration = Duration::from_secs(300);
+
+    fn new(head_hash: Blake2bHash) -> Self {
+        BlockTemplateState {
+            revision: Mutex::new(BlockTemplateRevision { head_hash, counter: 0, waiting: Vec::new() }),
+        }
+    }
+
+    fn on_head_changed(&self, head_hash: Blake2bHash) {
+        let mut revision = self.revision.lock();
+        revision.head_hash = head_hash;
+        revision.changed();
+    }
+
+    fn on_transactions_changed(&self) {
+        self.revision.lock().changed();
+    }
+
+    fn long_poll_id(&self) -> String {
+        self.revision.lock().long_poll_id()
+    }
+
+    /// Resolves once the template identified by `long_poll_id` is outdated or the timeout expires.
+    fn wait_for_change(&self, long_poll_id: &str, timeout: Duration) -> Box<dyn Future<Item=(), Error=()> + Send> {
+        let mut revision = self.revision.lock();
+        if revision.long_poll_id() != long_poll_id {
+            return Box::new(future::ok(()));
+        }
+
+        // Forget requests that timed out in the meantime.
+        revision.waiting.retain(|waiting| !waiting.is_canceled());
+        let (tx, rx) = oneshot::channel();
+        revision.waiting.push(tx);
+        Box::new(rx.select2(Delay::new(Instant::now() + timeout)).then(|_| Ok(())))
+    }
+
+    /// Parses the long poll id and timeout parameters of `getBlockTemplate`.
+    fn long_poll_params(params: &Array) -> Result<Option<(&str, Duration)>, JsonValue> {
+        let long_poll_id = match params.get(2).and_then(JsonValue::as_str) {
+            Some(long_poll_id) => long_poll_id,
+            None => return Ok(None),
+        };
+        let timeout = match params.get(3) {
+            None | Some(JsonValue::Null) => Self::LONG_POLL_TIMEOUT_DEFAULT,
+            Some(timeout) => timeout.as_u64()
+                .map(Duration::from_secs)
+                .ok_or_else(|| object!{"message" => "Timeout must be a number of seconds"})?
+                .min(Self::LONG_POLL_TIMEOUT_MAX),
+        };
+        Ok(Some((long_poll_id, timeout)))
+    }
+}
+
 pub(crate) struct JsonRpcHandler {
     state: Arc<RwLock<JsonRpcServerState>>,
+    block_template_state: Arc<BlockTemplateState>,
     consensus: Arc<Consensus>,
     starting_block: u32,
-    config: Arc<JsonRpcConfig>
+    config: Arc<RwLock<JsonRpcConfig>>,
+    config_reload: Option<Arc<dyn ConfigReload>>,
 }
 
 impl JsonRpcHandler {
-    pub(crate) fn new(consensus: Arc<Consensus>, state: Arc<RwLock<JsonRpcServerState>>, config: Arc<JsonRpcConfig>) -> Self {
+    const MANUAL_BAN_TIME: Duration = Duration::from_secs(60 * 10);
+    /// Maximum number of blocks generated with a single `generate` call.
+    const GENERATE_COUNT_MAX: u32 = 1000;
+
+    pub(crate) fn new(consensus: Arc<Consensus>, state: Arc<RwLock<JsonRpcServerState>>, block_template_state: Arc<BlockTemplateState>, config: Arc<RwLock<JsonRpcConfig>>, config_reload: Option<Arc<dyn ConfigReload>>) -> Self {
         JsonRpcHandler {
             state,
+            block_template_state,
             consensus: consensus.clone(),
             starting_block: consensus.blockchain.height(),
-            config
+            config,
+            config_reload,
         }
     }
 
@@ -108,6 +240,28 @@ impl JsonRpcHandler {
         Ok(self.consensus.network.peer_count().into())
     }
 
+    fn connection_mode(&self, _params: Array) -> Result<JsonValue, JsonValue> {
+        let network_config = &self.consensus.network.network_config;
+        Ok(object! {
+            "mode" => network_config.connection_mode().to_string(),
+            "inbound" => self.consensus.network.connections.allow_inbound_connections(),
+            "outbound" => network_config.dial_outbound()
+        })
+    }
+
+    fn network_time(&self, _params: Array) -> Result<JsonValue, JsonValue> {
+        let network = &self.consensus.network;
+        let time_offset = network.time_offset();
+        Ok(object! {
+            "now" => network.network_time.now(),
+            "offset" => network.network_time.offset(),
+            "target" => time_offset.target.map(JsonValue::from).unwrap_or(Null),
+            "clockOff" => time_offset.is_clock_off(),
+            "samples" => JsonValue::Array(time_offset.samples.iter().map(|&offset| offset.into()).collect()),
+            "outliers" => JsonValue::Array(time_offset.outliers.iter().map(|&offset| offset.into()).collect())
+        })
+    }
+
     fn consensus(&self, _params: Array) -> Result<JsonValue, JsonValue> {
         Ok(self.state.read().consensus_state.into())
     }
@@ -127,17 +281,22 @@ impl JsonRpcHandler {
     }
 
     fn peer_list(&self, _params: Array) -> Result<JsonValue, JsonValue> {
+        // Lock in the same order as the network's housekeeping: scorer, addresses, connections.
+        let scorer = self.consensus.network.scorer();
         let mut scores: HashMap<ConnectionId, Score> = HashMap::new();
-        for (id, score) in self.consensus.network.scorer().connection_scores() {
+        for (id, score) in scorer.connection_scores() {
             scores.insert(*id, *score);
         }
 
-        Ok(self.consensus.network.addresses.state().address_info_iter()
+        let address_book = self.consensus.network.addresses.state();
+        let connection_pool = self.consensus.network.connections.state();
+        Ok(address_book.address_info_iter()
             .map(|info| {
-                let conn_id = self.consensus.network.connections.state()
-                    .get_connection_id_by_peer_address(&info.peer_address);
-                self.peer_address_info_to_obj(info, None,
-                                              conn_id.and_then(|id| scores.get(&id)).map(|s| *s))
+                let conn_id = connection_pool.get_connection_id_by_peer_address(&info.peer_address);
+                let connection_info = connection_pool.get_connection_by_peer_address(&info.peer_address);
+                let score_breakdown = connection_info.and_then(|conn| scorer.score_breakdown(&connection_pool, conn));
+                self.peer_address_info_to_obj(info, connection_info,
+                                              conn_id.and_then(|id| scores.get(&id)).map(|s| *s), score_breakdown)
             })
             .collect::<Array>().into())
     }
@@ -153,6 +312,8 @@ impl JsonRpcHandler {
             .and_then(|s| PeerId::from_str(s)
                 .map_err(|e| object!{"message" => e.to_string()}))?;
 
+        // Lock in the same order as the network's housekeeping: scorer, addresses, connections.
+        let scorer = self.consensus.network.scorer();
         let mut address_book = self.consensus.network.addresses.state_mut();
         let peer_address = address_book.get_by_peer_id(&peer_id)
             .ok_or_else(|| object!{"message" => "Unknown peer"})?;
@@ -176,15 +337,20 @@ impl JsonRpcHandler {
                 },
                 "ban" => {
                     peer_channel.map(|p| p.close(CloseType::ManualPeerBan));
+                    self.consensus.network.ban_list.ban(BanEntry::new(BanTarget::PeerId(peer_id),
+                        Some(Self::MANUAL_BAN_TIME), "Banned via peerState".to_string(), Some(CloseType::ManualPeerBan))
+                        .expect("Manual ban time is too long"));
                 },
                 "unban" => {
                     if peer_address_info.state == PeerAddressState::Banned {
                         peer_address_info.state = PeerAddressState::Tried;
                     }
+                    self.consensus.network.ban_list.unban(&BanTarget::PeerId(peer_id));
                 },
                 "connect" => {
                     drop(address_book);
                     drop(connection_pool);
+                    drop(scorer);
                     self.consensus.network.connections.connect_outbound(peer_address);
                 }
                 _ => return Err(object!{"message" => "Unknown 'set' command."})
@@ -192,10 +358,97 @@ impl JsonRpcHandler {
             Ok(Null)
         }
         else {
-            Ok(self.peer_address_info_to_obj(peer_address_info, connection_info, None))
+            let score_breakdown = connection_info.and_then(|conn| scorer.score_breakdown(&connection_pool, conn));
+            Ok(self.peer_address_info_to_obj(peer_address_info, connection_info, None, score_breakdown))
         }
     }
 
+    fn list_banned(&self, _params: Array) -> Result<JsonValue, JsonValue> {
+        Ok(self.consensus.network.ban_list.bans().iter()
+            .map(Self::ban_entry_to_obj)
+            .collect::<Array>().into())
+    }
+
+    /// Bans a peer ID, IP address or subnet (e.g. "192.168.0.0/16").
+    /// Parameters:
+    /// - target (string)
+    /// - duration (number, optional): Duration of the ban in seconds. The ban is permanent if not given.
+    /// - reason (string, optional)
+    fn set_ban(&self, params: Array) -> Result<JsonValue, JsonValue> {
+        let target = Self::parse_ban_target(params.get(0).unwrap_or(&Null))?;
+
+        let duration = params.get(1).unwrap_or(&Null);
+        let duration = if duration.is_null() {
+            None
+        } else {
+            Some(Duration::from_secs(duration.as_u64()
+                .ok_or_else(|| object!{"message" => "Duration must be a number of seconds"})?))
+        };
+
+        let reason = params.get(2).unwrap_or(&Null);
+        let reason = if reason.is_null() {
+            "Manual ban".to_string()
+        } else {
+            reason.as_str().ok_or_else(|| object!{"message" => "Reason must be a string"})?.to_string()
+        };
+
+        let entry = BanEntry::new(target, duration, reason, None)
+            .ok_or_else(|| object!{"message" => "Duration is too long"})?;
+        let obj = Self::ban_entry_to_obj(&entry);
+        self.consensus.network.ban(entry);
+        Ok(obj)
+    }
+
+    /// Lifts the ban of the given target, or all bans if no target is given. This includes the
+    /// bans of peer addresses in the address book.
+    /// Returns the number of bans lifted.
+    fn clear_banned(&self, params: Array) -> Result<JsonValue, JsonValue> {
+        let target = params.get(0).unwrap_or(&Null);
+        if target.is_null() {
+            Ok(self.consensus.network.clear_bans().into())
+        } else {
+            let target = Self::parse_ban_target(target)?;
+            Ok(self.consensus.network.unban(&target).into())
+        }
+    }
+
+    /// Starts recording all messages exchanged with peers to a capture file.
+    /// Parameters:
+    /// - fileName (string): Name of the new capture file in the configured capture directory.
+    fn start_capture(&self, params: Array) -> Result<JsonValue, JsonValue> {
+        let file_name = params.get(0).unwrap_or(&Null).as_str()
+            .ok_or_else(|| object!{"message" => "File name must be a string"})?;
+        let traffic_capture = &self.consensus.network.traffic_capture;
+        traffic_capture.start(file_name)
+            .map_err(|e| object!{"message" => format!("Failed to start capture: {}", e)})?;
+        Ok(traffic_capture.status().as_ref().map_or(Null, Self::capture_status_to_obj))
+    }
+
+    /// Stops the running capture. Returns its final status or null if no capture was running.
+    fn stop_capture(&self, _params: Array) -> Result<JsonValue, JsonValue> {
+        Ok(self.consensus.network.traffic_capture.stop().as_ref().map_or(Null, Self::capture_status_to_obj))
+    }
+
+    /// Returns the status of the running capture or null if no capture is running.
+    fn capture_status(&self, _params: Array) -> Result<JsonValue, JsonValue> {
+        Ok(self.consensus.network.traffic_capture.status().as_ref().map_or(Null, Self::capture_status_to_obj))
+    }
+
+    /// Re-reads the config file and applies the settings that can be changed at runtime.
+    /// Returns the changed settings that were applied, those that are overridden on the command
+    /// line and those that require a restart.
+    fn reload_config(&self, _params: Array) -> Result<JsonValue, JsonValue> {
+        let config_reload = self.config_reload.as_ref()
+            .ok_or_else(|| object!{"message" => "Reloading the config is not supported"})?;
+        let report = config_reload.reload_config()
+            .map_err(|e| object!{"message" => format!("Failed to reload config: {}", e)})?;
+        Ok(object!{
+            "applied" => report.applied,
+            "overridden" => report.overridden,
+            "restartRequired" => report.restart_required,
+        })
+    }
+
 
     // Transaction
 
@@ -353,6 +606,38 @@ impl JsonRpcHandler {
         Ok(JsonValue::Object(transactions_per_bucket))
     }
 
+    fn estimate_fee(&self, params: Array) -> Result<JsonValue, JsonValue> {
+        let target_blocks = params.get(0).unwrap_or(&Null);
+        let target_blocks = if target_blocks.is_null() {
+            1
+        } else {
+            target_blocks.as_u32()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| object!{"message" => "Invalid number of target blocks"})?
+        };
+
+        Ok(self.consensus.mempool.estimate_fee(target_blocks).into())
+    }
+
+
+    fn mempool_filter(&self, _params: Array) -> Result<JsonValue, JsonValue> {
+        Ok(Self::rules_to_obj(&self.consensus.mempool.filter_rules()))
+    }
+
+    fn set_mempool_filter(&self, params: Array) -> Result<JsonValue, JsonValue> {
+        let obj = params.get(0).unwrap_or(&Null);
+        if !obj.is_object() {
+            return Err(object!{"message" => "Filter rules must be an object"});
+        }
+
+        // Only the given rules are changed, all others keep their current value.
+        let mut rules = self.consensus.mempool.filter_rules();
+        Self::update_rules_from_obj(&mut rules, obj)?;
+        self.consensus.mempool.set_filter_rules(rules);
+
+        Ok(Self::rules_to_obj(&self.consensus.mempool.filter_rules()))
+    }
+
 
     // Blockchain
 
@@ -396,6 +681,12 @@ impl JsonRpcHandler {
     }
 
     fn get_block_template(&self, params: Array) -> Result<JsonValue, JsonValue> {
+        // If a long poll id is given, `wait_before_method` waited until the template it refers to
+        // is outdated.
+        BlockTemplateState::long_poll_params(&params)?;
+
+        // Take the id before producing the block, so that changes in between are not missed.
+        let long_poll_id = self.block_template_state.long_poll_id();
         let block = self.produce_block(params)?;
         let header = block.header;
         let json_header = object!{
@@ -422,6 +713,7 @@ impl JsonRpcHandler {
             "interlink" => hex::encode(block.interlink.serialize_to_vec()),
             "target" => u32::from(header.n_bits),
             "body" => json_body,
+            "longPollId" => long_poll_id,
         })
     }
 
@@ -440,6 +732,65 @@ impl JsonRpcHandler {
         }
     }
 
+    /// Mines and pushes the given number of blocks right away, each with a timestamp after its
+    /// predecessor's. Only available on the regtest network.
+    /// Parameters:
+    /// - count (number)
+    /// - miner address (string)
+    /// Returns the hashes of the new blocks.
+    fn generate(&self, params: Array) -> Result<JsonValue, JsonValue> {
+        if self.consensus.blockchain.network_id != NetworkId::Regtest {
+            return Err(object!{"message" => "Blocks can only be generated on the regtest network"});
+        }
+
+        let count = params.get(0).and_then(JsonValue::as_u32)
+            .ok_or_else(|| object!{"message" => "Block count must be a number"})?;
+        if count > Self::GENERATE_COUNT_MAX {
+            return Err(object!{"message" => format!("At most {} blocks can be generated at once", Self::GENERATE_COUNT_MAX)});
+        }
+        let miner = params.get(1).and_then(JsonValue::as_str)
+            .ok_or_else(|| object!{"message" => "Miner address must be a string"})
+            .and_then(|s| Address::from_any_str(s)
+                .map_err(|_| object!{"message" => "Invalid miner address"}))?;
+
+        let producer = BlockProducer::with_policy(self.consensus.blockchain.clone(), self.consensus.mempool.clone(), Arc::clone(&self.config.read().block_template_policy));
+        let mut hashes = Array::new();
+        for _ in 0..count {
+            let timestamp = self.timestamp().max(self.consensus.blockchain.head().header.timestamp + 1);
+            let mut block = producer.next_block(timestamp, miner.clone(), Vec::new());
+            while !block.header.verify_proof_of_work() {
+                block.header.nonce += 1;
+            }
+            let hash = block.header.hash::<Blake2bHash>();
+            match self.consensus.blockchain.push(block) {
+                PushResult::Extended | PushResult::Rebranched => hashes.push(hash.to_hex().into()),
+                result => return Err(object!{"message" => format!("Generated block rejected: {:?}", result)}),
+            }
+        }
+        Ok(hashes.into())
+    }
+
+    /// Freezes the network clock at the given unix timestamp in seconds, or lets it run again if no
+    /// timestamp is given. Only available on the regtest network.
+    fn set_network_time(&self, params: Array) -> Result<JsonValue, JsonValue> {
+        if self.consensus.blockchain.network_id != NetworkId::Regtest {
+            return Err(object!{"message" => "The network time can only be set on the regtest network"});
+        }
+
+        let timestamp = params.get(0).unwrap_or(&Null);
+        let network_time = &self.consensus.network.network_time;
+        if timestamp.is_null() {
+            network_time.set_fixed_time(None);
+        } else {
+            let timestamp = timestamp.as_u64()
+                .ok_or_else(|| object!{"message" => "Timestamp must be a number of seconds"})?;
+            let timestamp = timestamp.checked_mul(1000)
+                .ok_or_else(|| object!{"message" => "Timestamp is too large"})?;
+            network_time.set_fixed_time(Some(timestamp));
+        }
+        Ok((network_time.now() / 1000).into())
+    }
+
 
     // Helper functions
     
@@ -582,13 +933,18 @@ impl JsonRpcHandler {
         Ok(self.transaction_to_obj(&transaction, Some(&block), Some(index as usize)))
     }
 
-    fn peer_address_info_to_obj(&self, peer_address_info: &PeerAddressInfo, connection_info: Option<&ConnectionInfo>, score: Option<Score>) -> JsonValue {
-        let state = self.consensus.network.connections.state();
-        let connection_info = connection_info.or_else(|| {
-            state.get_connection_by_peer_address(&peer_address_info.peer_address)
-        });
+    fn peer_address_info_to_obj(&self, peer_address_info: &PeerAddressInfo, connection_info: Option<&ConnectionInfo>, score: Option<Score>, score_breakdown: Option<ScoreBreakdown>) -> JsonValue {
         let peer = connection_info.and_then(|conn| conn.peer());
         let network_connection = connection_info.and_then(|conn| conn.network_connection());
+        let score_breakdown = score_breakdown
+            .map(|breakdown| {
+                let mut obj = Object::new();
+                for (name, value) in breakdown.components.iter() {
+                    obj.insert(name, (*value).into());
+                }
+                obj.insert("total", breakdown.total.into());
+                JsonValue::Object(obj)
+            });
 
         object!{
             "id" => peer_address_info.peer_address.peer_id().to_hex(),
@@ -600,12 +956,42 @@ impl JsonRpcHandler {
             "timeOffset" => peer.map(|peer| peer.time_offset.into()).unwrap_or(Null),
             "headHash" => peer.map(|peer| peer.head_hash.to_hex().into()).unwrap_or(Null),
             "score" => score.map(|s| s.into()).unwrap_or(Null),
+            "scoreBreakdown" => score_breakdown.unwrap_or(Null),
             "latency" => connection_info.map(|conn| conn.statistics().latency_median().into()).unwrap_or(Null),
             "rx" => network_connection.map(|conn| conn.metrics().bytes_received().into()).unwrap_or(Null),
             "tx" => network_connection.map(|conn| conn.metrics().bytes_sent().into()).unwrap_or(Null)
         }
     }
 
+    fn parse_ban_target(target: &JsonValue) -> Result<BanTarget, JsonValue> {
+        target.as_str()
+            .ok_or_else(|| object!{"message" => "Ban target must be a string"})
+            .and_then(|target| BanTarget::from_str(target)
+                .map_err(|e| object!{"message" => e.to_string()}))
+    }
+
+    fn capture_status_to_obj(status: &CaptureStatus) -> JsonValue {
+        object!{
+            "path" => status.path.to_string_lossy().to_string(),
+            "started" => systemtime_to_timestamp(status.started),
+            "messages" => status.num_records,
+        }
+    }
+
+    fn ban_entry_to_obj(entry: &BanEntry) -> JsonValue {
+        object!{
+            "target" => entry.target.to_string(),
+            "type" => match entry.target {
+                BanTarget::PeerId(_) => "peer",
+                BanTarget::Ip(_) => "ip",
+                BanTarget::Subnet(_, _) => "subnet",
+            },
+            "bannedUntil" => entry.banned_until.map(|t| systemtime_to_timestamp(t).into()).unwrap_or(Null),
+            "reason" => entry.reason.clone(),
+            "closeType" => entry.close_type.map(|ty| (ty as u16).into()).unwrap_or(Null)
+        }
+    }
+
     fn transaction_receipt_to_obj(&self, receipt: &TransactionReceipt, index: Option<u16>, block: Option<&Block>) -> JsonValue {
         object!{
             "transactionHash" => receipt.transaction_hash.to_hex(),
@@ -617,6 +1003,140 @@ impl JsonRpcHandler {
         }
     }
 
+    fn rules_to_obj(rules: &Rules) -> JsonValue {
+        let addresses_to_array = |addresses: &HashSet<Address>| JsonValue::Array(addresses.iter()
+            .map(|address| address.to_user_friendly_address().into())
+            .collect());
+
+        let mut account_types = Object::new();
+        for (account_type, type_rules) in rules.account_types.iter() {
+            account_types.insert(&Self::account_type_to_str(*account_type), object!{
+                "reject" => type_rules.reject,
+                "txFeePerByte" => type_rules.tx_fee_per_byte,
+                "txValue" => u64::from(type_rules.tx_value),
+                "dataSizeMax" => type_rules.data_size_max.map(|max| max.into()).unwrap_or(Null),
+            });
+        }
+
+        object!{
+            "txFee" => u64::from(rules.tx_fee),
+            "txFeePerByte" => rules.tx_fee_per_byte,
+            "txValue" => u64::from(rules.tx_value),
+            "txValueTotal" => u64::from(rules.tx_value_total),
+            "contractFee" => u64::from(rules.contract_fee),
+            "contractFeePerByte" => rules.contract_fee_per_byte,
+            "contractValue" => u64::from(rules.contract_value),
+            "creationFee" => u64::from(rules.creation_fee),
+            "creationFeePerByte" => rules.creation_fee_per_byte,
+            "creationValue" => u64::from(rules.creation_value),
+            "senderBalance" => u64::from(rules.sender_balance),
+            "recipientBalance" => u64::from(rules.recipient_balance),
+            "senderBlacklist" => addresses_to_array(&rules.sender_blacklist),
+            "senderWhitelist" => addresses_to_array(&rules.sender_whitelist),
+            "recipientBlacklist" => addresses_to_array(&rules.recipient_blacklist),
+            "recipientWhitelist" => addresses_to_array(&rules.recipient_whitelist),
+            "dataSizeMax" => rules.data_size_max.map(|max| max.into()).unwrap_or(Null),
+            "accountTypes" => JsonValue::Object(account_types),
+        }
+    }
+
+    fn update_rules_from_obj(rules: &mut Rules, obj: &JsonValue) -> Result<(), JsonValue> {
+        fn coin(obj: &JsonValue, key: &str, value: &mut Coin) -> Result<(), JsonValue> {
+            if !obj[key].is_null() {
+                *value = obj[key].as_u64()
+                    .and_then(|v| Coin::from_u64(v).ok())
+                    .ok_or_else(|| object!{"message" => format!("Invalid value for '{}'", key)})?;
+            }
+            Ok(())
+        }
+        fn fee_per_byte(obj: &JsonValue, key: &str, value: &mut f64) -> Result<(), JsonValue> {
+            if !obj[key].is_null() {
+                *value = obj[key].as_f64()
+                    .ok_or_else(|| object!{"message" => format!("Invalid value for '{}'", key)})?;
+            }
+            Ok(())
+        }
+        fn size_max(obj: &JsonValue, key: &str, value: &mut Option<usize>) -> Result<(), JsonValue> {
+            if obj.has_key(key) {
+                *value = match &obj[key] {
+                    JsonValue::Null => None,
+                    v => Some(v.as_usize().ok_or_else(|| object!{"message" => format!("Invalid value for '{}'", key)})?),
+                };
+            }
+            Ok(())
+        }
+        fn addresses(obj: &JsonValue, key: &str, value: &mut HashSet<Address>) -> Result<(), JsonValue> {
+            if !obj[key].is_null() {
+                if !obj[key].is_array() {
+                    return Err(object!{"message" => format!("'{}' must be an array of addresses", key)});
+                }
+                *value = obj[key].members()
+                    .map(|address| address.as_str()
+                        .and_then(|s| Address::from_any_str(s).ok())
+                        .ok_or_else(|| object!{"message" => format!("Invalid address in '{}'", key)}))
+                    .collect::<Result<HashSet<Address>, JsonValue>>()?;
+            }
+            Ok(())
+        }
+
+        coin(obj, "txFee", &mut rules.tx_fee)?;
+        fee_per_byte(obj, "txFeePerByte", &mut rules.tx_fee_per_byte)?;
+        coin(obj, "txValue", &mut rules.tx_value)?;
+        coin(obj, "txValueTotal", &mut rules.tx_value_total)?;
+        coin(obj, "contractFee", &mut rules.contract_fee)?;
+        fee_per_byte(obj, "contractFeePerByte", &mut rules.contract_fee_per_byte)?;
+        coin(obj, "contractValue", &mut rules.contract_value)?;
+        coin(obj, "creationFee", &mut rules.creation_fee)?;
+        fee_per_byte(obj, "creationFeePerByte", &mut rules.creation_fee_per_byte)?;
+        coin(obj, "creationValue", &mut rules.creation_value)?;
+        coin(obj, "senderBalance", &mut rules.sender_balance)?;
+        coin(obj, "recipientBalance", &mut rules.recipient_balance)?;
+        addresses(obj, "senderBlacklist", &mut rules.sender_blacklist)?;
+        addresses(obj, "senderWhitelist", &mut rules.sender_whitelist)?;
+        addresses(obj, "recipientBlacklist", &mut rules.recipient_blacklist)?;
+        addresses(obj, "recipientWhitelist", &mut rules.recipient_whitelist)?;
+        size_max(obj, "dataSizeMax", &mut rules.data_size_max)?;
+
+        if !obj["accountTypes"].is_null() {
+            if !obj["accountTypes"].is_object() {
+                return Err(object!{"message" => "'accountTypes' must be an object"});
+            }
+            rules.account_types.clear();
+            for (name, type_obj) in obj["accountTypes"].entries() {
+                let account_type = Self::account_type_from_str(name)
+                    .ok_or_else(|| object!{"message" => format!("Unknown account type '{}'", name)})?;
+                let mut type_rules = AccountTypeRules::default();
+                if !type_obj["reject"].is_null() {
+                    type_rules.reject = type_obj["reject"].as_bool()
+                        .ok_or_else(|| object!{"message" => "Invalid value for 'reject'"})?;
+                }
+                fee_per_byte(type_obj, "txFeePerByte", &mut type_rules.tx_fee_per_byte)?;
+                coin(type_obj, "txValue", &mut type_rules.tx_value)?;
+                size_max(type_obj, "dataSizeMax", &mut type_rules.data_size_max)?;
+                rules.account_types.insert(account_type, type_rules);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn account_type_to_str(account_type: AccountType) -> String {
+        match account_type {
+            AccountType::Basic => "basic",
+            AccountType::Vesting => "vesting",
+            AccountType::HTLC => "htlc",
+        }.to_string()
+    }
+
+    fn account_type_from_str(s: &str) -> Option<AccountType> {
+        match s {
+            "basic" => Some(AccountType::Basic),
+            "vesting" => Some(AccountType::Vesting),
+            "htlc" => Some(AccountType::HTLC),
+            _ => None,
+        }
+    }
+
     fn push_transaction(&self, transaction: Transaction) -> Result<JsonValue, JsonValue> {
         match self.consensus.mempool.push_transaction(transaction) {
             ReturnCode::Accepted | ReturnCode::Known => Ok(object!{"message" => "Ok"}),
@@ -635,10 +1155,13 @@ impl JsonRpcHandler {
             .and_then(|s| hex::decode(s)
                 .map_err(|_| object!{"message" => "Extra data must be hex-encoded"}))?;
 
-        let timestamp = (systemtime_to_timestamp(SystemTime::now()) / 1000) as u32;
+        let producer = BlockProducer::with_policy(self.consensus.blockchain.clone(), self.consensus.mempool.clone(), Arc::clone(&self.config.read().block_template_policy));
+        return Ok(producer.next_block(self.timestamp(), miner, extra_data));
+    }
 
-        let producer = BlockProducer::new(self.consensus.blockchain.clone(), self.consensus.mempool.clone());
-        return Ok(producer.next_block(timestamp, miner, extra_data));
+    /// The current network time in seconds, as used in block headers.
+    fn timestamp(&self) -> u32 {
+        (self.consensus.network.network_time.now() / 1000) as u32
     }
 }
 
@@ -646,7 +1169,8 @@ impl jsonrpc::Handler for JsonRpcHandler {
     fn get_method(&self, name: &str) -> Option<fn(&Self, Array) -> Result<JsonValue, JsonValue>> {
         trace!("RPC method called: {}", name);
 
-        if !self.config.methods.is_empty() && !self.config.methods.contains(name) {
+        let config = self.config.read();
+        if !config.methods.is_empty() && !config.methods.contains(name) {
             info!("RPC call to black-listed method: {}", name);
             //return Some(|_, _| Err(object!("message" => "Method is not allowed.")))
             return None
@@ -657,8 +1181,17 @@ impl jsonrpc::Handler for JsonRpcHandler {
             "peerCount" => Some(JsonRpcHandler::peer_count),
             "syncing" => Some(JsonRpcHandler::syncing),
             "consensus" => Some(JsonRpcHandler::consensus),
+            "connectionMode" => Some(JsonRpcHandler::connection_mode),
+            "networkTime" => Some(JsonRpcHandler::network_time),
             "peerList" => Some(JsonRpcHandler::peer_list),
             "peerState" => Some(JsonRpcHandler::peer_state),
+            "listBanned" => Some(JsonRpcHandler::list_banned),
+            "setBan" => Some(JsonRpcHandler::set_ban),
+            "clearBanned" => Some(JsonRpcHandler::clear_banned),
+            "startCapture" => Some(JsonRpcHandler::start_capture),
+            "stopCapture" => Some(JsonRpcHandler::stop_capture),
+            "captureStatus" => Some(JsonRpcHandler::capture_status),
+            "reloadConfig" => Some(JsonRpcHandler::reload_config),
 
             // Transactions
             "sendRawTransaction" => Some(JsonRpcHandler::send_raw_transaction),
@@ -672,6 +1205,9 @@ impl jsonrpc::Handler for JsonRpcHandler {
             "getTransactionsByAddress" => Some(JsonRpcHandler::get_transactions_by_address),
             "mempoolContent" => Some(JsonRpcHandler::mempool_content),
             "mempool" => Some(JsonRpcHandler::mempool),
+            "estimateFee" => Some(JsonRpcHandler::estimate_fee),
+            "mempoolFilter" => Some(JsonRpcHandler::mempool_filter),
+            "setMempoolFilter" => Some(JsonRpcHandler::set_mempool_filter),
 
             // Blockchain
             "blockNumber" => Some(JsonRpcHandler::block_number),
@@ -684,13 +1220,26 @@ impl jsonrpc::Handler for JsonRpcHandler {
             "getWork" => Some(JsonRpcHandler::get_work),
             "getBlockTemplate" => Some(JsonRpcHandler::get_block_template),
             "submitBlock" => Some(JsonRpcHandler::submit_block),
+            "generate" => Some(JsonRpcHandler::generate),
+            "setNetworkTime" => Some(JsonRpcHandler::set_network_time),
 
             _ => None
         }
     }
 
+    fn wait_before_method(&self, name: &str, params: &Array) -> Option<Box<dyn Future<Item=(), Error=()> + Send>> {
+        match name {
+            // Long polling for a block template waits until the template is outdated.
+            "getBlockTemplate" => match BlockTemplateState::long_poll_params(params) {
+                Ok(Some((long_poll_id, timeout))) => Some(self.block_template_state.wait_for_change(long_poll_id, timeout)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     fn authorize(&self, username: &str, password: &str) -> Result<(), AuthenticationError> {
-        if !self.config.credentials.as_ref().map(|c| c.check(username, password)).unwrap_or(true) {
+        if !self.config.read().credentials.as_ref().map(|c| c.check(username, password)).unwrap_or(true) {
             return Err(AuthenticationError::IncorrectCredentials);
         }
         Ok(())
@@ -698,7 +1247,9 @@ impl jsonrpc::Handler for JsonRpcHandler {
 }
 
 
-pub fn rpc_server(consensus: Arc<Consensus>, ip: IpAddr, port: u16, config: JsonRpcConfig) -> Result<Box<dyn Future<Item=(), Error=()> + Send + Sync>, Error> {
+/// Starts the RPC server. Changes to the shared `config` apply to the following requests.
+/// `config_reload` is used by the `reloadConfig` method, which is not available without it.
+pub fn rpc_server(consensus: Arc<Consensus>, ip: IpAddr, port: u16, config: Arc<RwLock<JsonRpcConfig>>, config_reload: Option<Arc<dyn ConfigReload>>) -> Result<Box<dyn Future<Item=(), Error=()> + Send + Sync>, Error> {
     let state = Arc::new(RwLock::new(JsonRpcServerState {
         consensus_state: "syncing",
     }));
@@ -720,10 +1271,44 @@ pub fn rpc_server(consensus: Arc<Consensus>, ip: IpAddr, port: u16, config: Json
         });
     }
 
-    let config = Arc::new(config);
+    let block_template_state = Arc::new(BlockTemplateState::new(consensus.blockchain.head_hash()));
+
+    // Register for blockchain and mempool events to answer long-polling block template requests.
+    {
+        let block_template_state = Arc::downgrade(&block_template_state);
+        consensus.blockchain.notifier.write().register(move |e: &BlockchainEvent| {
+            if let Some(block_template_state) = block_template_state.upgrade() {
+                match e {
+                    BlockchainEvent::Extended(hash) => block_template_state.on_head_changed(hash.clone()),
+                    BlockchainEvent::Rebranched(_, adopted_blocks) => {
+                        if let Some((hash, _)) = adopted_blocks.last() {
+                            block_template_state.on_head_changed(hash.clone());
+                        }
+                    },
+                }
+            }
+        });
+    }
+    {
+        let block_template_state = Arc::downgrade(&block_template_state);
+        let config = Arc::clone(&config);
+        consensus.mempool.notifier.write().register(move |e: &MempoolEvent| {
+            if let Some(block_template_state) = block_template_state.upgrade() {
+                match e {
+                    MempoolEvent::TransactionAdded(_, tx) | MempoolEvent::TransactionReplaced(_, tx) => {
+                        if config.read().block_template_policy.is_relevant(tx) {
+                            block_template_state.on_transactions_changed();
+                        }
+                    },
+                    _ => ()
+                }
+            }
+        });
+    }
+
     Ok(Box::new(Server::try_bind(&SocketAddr::new(ip, port))?
         .serve(move || {
-            jsonrpc::Service::new(JsonRpcHandler::new(Arc::clone(&consensus), Arc::clone(&state), Arc::clone(&config)))
+            jsonrpc::Service::new(JsonRpcHandler::new(Arc::clone(&consensus), Arc::clone(&state), Arc::clone(&block_template_state), Arc::clone(&config), config_reload.clone()))
         })
         .map_err(|e| error!("RPC server failed: {}", e)))) // as Box<dyn Future<Item=(), Error=()> + Send + Sync>
 }
@@ -0,0 +1,67 @@
+"""
+Builds the seed corpus of the fuzzing targets in `fuzz/` from the test vectors of the repository.
+Run it from the root of the repository after changing the test vectors.
+"""
+import os
+import os.path
+import re
+import shutil
+
+hex_constant = re.compile(r'const\s+(\w+)\s*:\s*&(?:\'static\s+)?str\s*=\s*"([0-9a-fA-F]+)"\s*;')
+
+# Length of the header of the messages in the test vectors: magic, type, length and checksum.
+MESSAGE_HEADER = 13
+
+
+def constants(path):
+    """
+    Loads the hex string constants of a Rust file.
+    :param path: The path to the Rust file
+    :return: A dict from constant name to bytes
+    """
+    with open(path) as f:
+        return {name: bytes.fromhex(value) for name, value in hex_constant.findall(f.read())}
+
+
+def seeds():
+    """
+    :return: A list of (target, name, bytes) tuples
+    """
+    messages = constants('messages/tests/message/mod.rs')
+    blocks = constants('primitives/block/tests/block/block.rs')
+    headers = constants('primitives/block/tests/block/header.rs')
+    transactions = constants('primitives/transaction/tests/mod.rs')
+    nodes = constants('accounts/tree-primitives/src/accounts_tree_node.rs')
+
+    result = [('message', name, value) for name, value in messages.items()]
+    result += [('block', 'GENESIS_BLOCK', blocks['GENESIS_BLOCK'])]
+    result += [('block_header', 'GENESIS_HEADER', headers['GENESIS_HEADER'])]
+    result += [('transaction', name, value) for name, value in transactions.items()]
+
+    # Parts of messages: the chain proof follows the header directly, the accounts proof
+    # follows the block hash and the option flag, and the peer addresses follow the protocol
+    # version or the number of addresses.
+    result += [('chain_proof', 'CHAIN_PROOF_MESSAGE', messages['CHAIN_PROOF_MESSAGE'][MESSAGE_HEADER:])]
+    result += [('accounts_proof', 'ACCOUNTS_PROOF_MESSAGE_WPROOF', messages['ACCOUNTS_PROOF_MESSAGE_WPROOF'][MESSAGE_HEADER + 33:])]
+    result += [('peer_address', 'VERSION_MESSAGE', messages['VERSION_MESSAGE'][MESSAGE_HEADER + 4:])]
+    result += [('peer_address', 'ADDR_MESSAGE', messages['ADDR_MESSAGE'][MESSAGE_HEADER + 2:])]
+
+    # The first byte selects the type of the database value.
+    result += [('database_value', 'GENESIS_BLOCK', b'\x00' + blocks['GENESIS_BLOCK'])]
+    result += [('database_value', name, b'\x01' + value) for name, value in nodes.items()]
+    return result
+
+
+def main():
+    corpus = os.path.join('fuzz', 'corpus')
+    if os.path.exists(corpus):
+        shutil.rmtree(corpus)
+    for target, name, value in seeds():
+        directory = os.path.join(corpus, target)
+        os.makedirs(directory, exist_ok=True)
+        with open(os.path.join(directory, name.lower()), 'wb') as f:
+            f.write(value)
+
+
+if __name__ == '__main__':
+    main()
@@ -0,0 +1,387 @@
+//! In-process simulation of a network of full nodes for tests.
+//!
+//! A `Simulation` runs several `Consensus` instances, each on its own volatile database, in one
+//! process. The nodes talk to each other through a `MemoryNetwork`, whose links can be delayed,
+//! made lossy or partitioned. Nodes only connect when told to, so the topology of a test is fully
+//! under its control.
+//!
+//! The simulation is deterministic:
+//! * All nodes run on a single-threaded executor that the simulation only drives from its own
+//!   methods. Each of them returns once every node waits for the network or a timer.
+//! * The manual clock of the memory network serves as the network time of every node and as the
+//!   clock of their timers, e.g. for sync throttling and ping timeouts. Time only passes in
+//!   `advance`.
+//! * Everything that happens on a node is recorded as an `Event` with the time of the manual
+//!   clock, so tests can check the exact sequence of events.
+
+extern crate nimiq_block_production as block_production;
+extern crate nimiq_blockchain as blockchain;
+extern crate nimiq_consensus as consensus;
+extern crate nimiq_database as database;
+extern crate nimiq_hash as hash;
+extern crate nimiq_keys as keys;
+extern crate nimiq_mempool as mempool;
+extern crate nimiq_network as network;
+extern crate nimiq_network_primitives as network_primitives;
+
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use failure::Fail;
+use futures::future;
+use parking_lot::Mutex;
+use tokio_current_thread::{CurrentThread, TaskExecutor};
+use tokio_executor::park::ParkThread;
+use tokio_timer::clock::{self, Clock, Now};
+use tokio_timer::timer::{self, Timer};
+
+use block_production::BlockProducer;
+use blockchain::{BlockchainEvent, PushResult};
+use consensus::consensus::{Consensus, ConsensusEvent};
+use consensus::error::Error as ConsensusError;
+use database::Environment;
+use database::volatile::{VolatileDatabaseError, VolatileEnvironment};
+use hash::{Blake2bHash, Hash};
+use keys::{Address, KeyPair};
+use mempool::MempoolConfig;
+use network::NetworkConfig;
+use network_primitives::address::PeerAddress;
+use network_primitives::networks::NetworkId;
+
+pub use network::memory::{LinkConditions, MemoryNetwork};
+
+#[derive(Debug, Fail)]
+pub enum SimulationError {
+    #[fail(display = "{}", _0)]
+    DatabaseError(#[cause] VolatileDatabaseError),
+    #[fail(display = "{}", _0)]
+    ConsensusError(#[cause] ConsensusError),
+    #[fail(display = "{}", _0)]
+    IoError(#[cause] std::io::Error),
+}
+
+impl From<VolatileDatabaseError> for SimulationError {
+    fn from(e: VolatileDatabaseError) -> Self {
+        SimulationError::DatabaseError(e)
+    }
+}
+
+impl From<ConsensusError> for SimulationError {
+    fn from(e: ConsensusError) -> Self {
+        SimulationError::ConsensusError(e)
+    }
+}
+
+impl From<std::io::Error> for SimulationError {
+    fn from(e: std::io::Error) -> Self {
+        SimulationError::IoError(e)
+    }
+}
+
+/// Something that happened on a node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Consensus(ConsensusEvent),
+    /// The block with the given hash became the new head.
+    Extended(Blake2bHash),
+    /// The blocks with the first hashes were reverted and those with the second ones adopted.
+    Rebranched(Vec<Blake2bHash>, Vec<Blake2bHash>),
+}
+
+/// An event with the time it happened, in milliseconds since the start of the simulation, and
+/// the index of the node it happened on.
+pub type Record = (u64, usize, Event);
+
+/// Records the events of all nodes.
+#[derive(Clone)]
+struct Recorder {
+    network: MemoryNetwork,
+    start: u64,
+    records: Arc<Mutex<Vec<Record>>>,
+}
+
+impl Recorder {
+    fn record(&self, node: usize, event: Event) {
+        let time = self.network.now() - self.start;
+        self.records.lock().push((time, node, event));
+    }
+}
+
+/// The clock of the timers of all nodes, which follows the manual clock of the memory network.
+struct NetworkClock {
+    network: MemoryNetwork,
+    start: u64,
+    start_instant: Instant,
+}
+
+impl Now for NetworkClock {
+    fn now(&self) -> Instant {
+        self.start_instant + Duration::from_millis(self.network.now() - self.start)
+    }
+}
+
+/// A simulated full node.
+pub struct Node {
+    pub consensus: Arc<Consensus>,
+    host: String,
+    miner: Address,
+}
+
+impl Node {
+    /// The host name of the node on the memory network.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// The signed address under which other nodes can dial this node.
+    pub fn peer_address(&self) -> PeerAddress {
+        self.consensus.network.network_config.peer_address()
+    }
+
+    pub fn is_established(&self) -> bool {
+        self.consensus.established()
+    }
+
+    pub fn head_hash(&self) -> Blake2bHash {
+        self.consensus.blockchain.head_hash()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.consensus.blockchain.height()
+    }
+
+    pub fn peer_count(&self) -> usize {
+        self.consensus.network.peer_count()
+    }
+}
+
+/// A network of nodes running in this process.
+pub struct Simulation {
+    network: MemoryNetwork,
+    nodes: Vec<Node>,
+    recorder: Recorder,
+    executor: RefCell<CurrentThread<Timer<ParkThread, Clock>>>,
+    timer: timer::Handle,
+    clock: Clock,
+}
+
+impl Simulation {
+    /// Port that all nodes listen on.
+    pub const PORT: u16 = 8443;
+    const MAX_DBS: u32 = 10;
+    /// Number of times the nodes are polled without the clock advancing before the simulation
+    /// gives up, e.g. because a task keeps waking itself up.
+    const MAX_TURNS: usize = 100_000;
+
+    /// Starts `num_nodes` nodes on the regtest network, where blocks are mined at minimal difficulty.
+    pub fn new(num_nodes: usize) -> Result<Self, SimulationError> {
+        Self::with_network(num_nodes, NetworkId::Regtest, MemoryNetwork::new())
+    }
+
+    /// Starts `num_nodes` nodes on the given network, connected through `network`.
+    pub fn with_network(num_nodes: usize, network_id: NetworkId, network: MemoryNetwork) -> Result<Self, SimulationError> {
+        let start = network.now();
+        let clock = Clock::new_with_now(NetworkClock { network: network.clone(), start, start_instant: Instant::now() });
+        let timer = Timer::new_with_now(ParkThread::new(), clock.clone());
+
+        let mut simulation = Simulation {
+            recorder: Recorder {
+                network: network.clone(),
+                start,
+                records: Arc::new(Mutex::new(Vec::new())),
+            },
+            network,
+            nodes: Vec::with_capacity(num_nodes),
+            timer: timer.handle(),
+            executor: RefCell::new(CurrentThread::new_with_park(timer)),
+            clock,
+        };
+        for _ in 0..num_nodes {
+            simulation.add_node(network_id)?;
+        }
+        Ok(simulation)
+    }
+
+    /// Starts another node and returns its index.
+    pub fn add_node(&mut self, network_id: NetworkId) -> Result<usize, SimulationError> {
+        let index = self.nodes.len();
+        let host = format!("node{}.sim", index);
+
+        let mut network_config = NetworkConfig::new_ws_network_config(host.clone(), Self::PORT, None);
+        network_config.init_volatile();
+        network_config.set_memory_transport(self.network.transport(&host));
+
+        // Consensus needs the database for the lifetime of the program.
+        let env: &'static Environment = Box::leak(Box::new(VolatileEnvironment::new(Self::MAX_DBS)?));
+        let consensus = self.run(move || -> Result<Arc<Consensus>, ConsensusError> {
+            let consensus = Consensus::new(env, network_id, network_config, MempoolConfig::default())?;
+            consensus.network.initialize()?;
+            // Nodes never establish consensus without peers, so accept them right away.
+            consensus.network.set_allow_inbound_connections(true);
+            Ok(consensus)
+        })?;
+        consensus.network.network_time.set_fixed_time(Some(self.network.now()));
+        self.register_listeners(index, &consensus);
+
+        self.nodes.push(Node {
+            consensus,
+            host,
+            miner: Address::from(&KeyPair::generate().public),
+        });
+        Ok(index)
+    }
+
+    fn register_listeners(&self, index: usize, consensus: &Consensus) {
+        let recorder = self.recorder.clone();
+        consensus.notifier.write().register(move |e: &ConsensusEvent| {
+            recorder.record(index, Event::Consensus(e.clone()));
+        });
+
+        let recorder = self.recorder.clone();
+        consensus.blockchain.notifier.write().register(move |e: &BlockchainEvent| {
+            let event = match e {
+                BlockchainEvent::Extended(hash) => Event::Extended(hash.clone()),
+                BlockchainEvent::Rebranched(reverted, adopted) => Event::Rebranched(
+                    reverted.iter().map(|(hash, _)| hash.clone()).collect(),
+                    adopted.iter().map(|(hash, _)| hash.clone()).collect(),
+                ),
+            };
+            recorder.record(index, event);
+        });
+    }
+
+    pub fn node(&self, index: usize) -> &Node {
+        &self.nodes[index]
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn memory_network(&self) -> &MemoryNetwork {
+        &self.network
+    }
+
+    /// Makes node `from` dial node `to`. Returns false if the connection pool refused to dial,
+    /// e.g. because the nodes are already connected.
+    pub fn connect(&self, from: usize, to: usize) -> bool {
+        let connections = Arc::clone(&self.nodes[from].consensus.network.connections);
+        let peer_address = Arc::new(self.nodes[to].peer_address());
+        self.run(move || connections.connect_outbound(peer_address))
+    }
+
+    /// Connects every node to every other node.
+    pub fn connect_all(&self) {
+        for from in 0..self.nodes.len() {
+            for to in from + 1..self.nodes.len() {
+                self.connect(from, to);
+            }
+        }
+    }
+
+    /// Mines `count` blocks on top of the head of the given node and returns their hashes. Each
+    /// block has a timestamp after its predecessor's and is relayed to the node's peers.
+    pub fn produce_blocks(&self, node: usize, count: usize) -> Vec<Blake2bHash> {
+        let consensus = Arc::clone(&self.nodes[node].consensus);
+        let miner = self.nodes[node].miner.clone();
+        self.run(move || {
+            let producer = BlockProducer::new(Arc::clone(&consensus.blockchain), Arc::clone(&consensus.mempool));
+            (0..count).map(|_| {
+                let timestamp = ((consensus.network.network_time.now() / 1000) as u32)
+                    .max(consensus.blockchain.head().header.timestamp + 1);
+                let mut block = producer.next_block(timestamp, miner.clone(), Vec::new());
+                while !block.header.verify_proof_of_work() {
+                    block.header.nonce += 1;
+                }
+                let hash = block.header.hash::<Blake2bHash>();
+                match consensus.blockchain.push(block) {
+                    PushResult::Extended | PushResult::Rebranched => hash,
+                    result => panic!("Produced block was not accepted: {:?}", result),
+                }
+            }).collect()
+        })
+    }
+
+    /// Advances the clock of the memory network, the network time of all nodes and the clock of
+    /// their timers millisecond by millisecond. Delayed messages are delivered and timers fire
+    /// once they are due.
+    pub fn advance(&self, duration: Duration) {
+        let millis = duration.as_secs() * 1000 + u64::from(duration.subsec_millis());
+        for _ in 0..millis {
+            self.network.advance(Duration::from_millis(1));
+            let now = self.network.now();
+            for node in self.nodes.iter() {
+                node.consensus.network.network_time.set_fixed_time(Some(now));
+            }
+            self.run(|| ());
+        }
+    }
+
+    /// The time that passed since the start of the simulation.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_millis(self.network.now() - self.recorder.start)
+    }
+
+    /// Returns the events of all nodes that were recorded since the last call, ordered by time
+    /// and node. The events of a node keep the order they happened in.
+    pub fn take_events(&self) -> Vec<Record> {
+        let mut records: Vec<Record> = self.recorder.records.lock().drain(..).collect();
+        records.sort_by_key(|&(time, node, _)| (time, node));
+        records
+    }
+
+    /// Whether all nodes have the same head.
+    pub fn is_synced(&self) -> bool {
+        let head_hash = self.nodes[0].head_hash();
+        self.nodes.iter().all(|node| node.head_hash() == head_hash)
+    }
+
+    /// Sets the conditions of the link between two nodes.
+    pub fn set_link(&self, a: usize, b: usize, conditions: LinkConditions) {
+        self.network.set_link_conditions(&self.nodes[a].host, &self.nodes[b].host, conditions);
+    }
+
+    /// Splits the nodes into groups that can't reach each other. Nodes that aren't part of any
+    /// group can still reach everyone.
+    pub fn partition(&self, groups: &[&[usize]]) {
+        let groups: Vec<Vec<&str>> = groups.iter()
+            .map(|group| group.iter().map(|&i| self.nodes[i].host.as_str()).collect())
+            .collect();
+        let groups: Vec<&[&str]> = groups.iter().map(Vec::as_slice).collect();
+        self.network.partition(&groups);
+    }
+
+    /// Removes the partition. Nodes that lost their connections need to be connected again.
+    pub fn heal(&self) {
+        self.network.heal();
+    }
+
+    /// Runs `f` on the executor of the simulation, where the nodes may spawn tasks, and then polls
+    /// the nodes until all of them wait for the network or a timer.
+    fn run<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        let mut executor = self.executor.borrow_mut();
+        let mut enter = tokio_executor::enter().expect("Simulation runs inside another executor");
+        clock::with_default(&self.clock, &mut enter, |enter| {
+            timer::with_default(&self.timer, enter, |enter| {
+                // Like the current thread runtime of tokio, spawn through the executor that is
+                // currently running.
+                let mut default_executor = TaskExecutor::current();
+                tokio_executor::with_default(&mut default_executor, enter, |enter| {
+                    let mut executor = executor.enter(enter);
+                    let result = match executor.block_on(future::lazy(|| Ok::<R, ()>(f()))) {
+                        Ok(result) => result,
+                        Err(_) => unreachable!(),
+                    };
+
+                    let mut turns = 0;
+                    while executor.turn(Some(Duration::from_millis(0))).expect("Failed to poll the nodes").has_polled() {
+                        turns += 1;
+                        assert!(turns < Self::MAX_TURNS, "Nodes don't settle without the clock advancing");
+                    }
+                    result
+                })
+            })
+        })
+    }
+}
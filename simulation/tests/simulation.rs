@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use nimiq_consensus::consensus::ConsensusEvent;
+use nimiq_hash::Blake2bHash;
+use nimiq_simulation::{Event, LinkConditions, Record, Simulation};
+
+fn extended(time: u64, node: usize, hashes: &[Blake2bHash]) -> Vec<Record> {
+    hashes.iter().map(|hash| (time, node, Event::Extended(hash.clone()))).collect()
+}
+
+fn established(time: u64, node: usize) -> Vec<Record> {
+    vec![
+        (time, node, Event::Consensus(ConsensusEvent::Syncing)),
+        (time, node, Event::Consensus(ConsensusEvent::Established)),
+    ]
+}
+
+#[test]
+fn it_relays_blocks_between_nodes() {
+    let simulation = Simulation::new(3).unwrap();
+    assert!(simulation.connect(0, 1));
+    assert!(simulation.connect(1, 2));
+    assert_eq!(simulation.take_events(), vec![]);
+
+    // Nodes start syncing once no more peers connected for 1.5 seconds.
+    simulation.advance(Duration::from_millis(1500));
+    assert_eq!(simulation.take_events(), vec![
+        (1500, 0, Event::Consensus(ConsensusEvent::Syncing)),
+        (1500, 0, Event::Consensus(ConsensusEvent::Established)),
+        (1500, 1, Event::Consensus(ConsensusEvent::Syncing)),
+        (1500, 1, Event::Consensus(ConsensusEvent::Syncing)),
+        (1500, 1, Event::Consensus(ConsensusEvent::Established)),
+        (1500, 2, Event::Consensus(ConsensusEvent::Syncing)),
+        (1500, 2, Event::Consensus(ConsensusEvent::Established)),
+    ]);
+
+    // Inventory is announced in batches every 500 milliseconds.
+    let hashes = simulation.produce_blocks(0, 3);
+    simulation.advance(Duration::from_secs(1));
+    let mut expected = extended(1500, 0, &hashes);
+    expected.extend(extended(2000, 1, &hashes));
+    expected.extend(extended(2500, 2, &hashes));
+    assert_eq!(simulation.take_events(), expected);
+    assert!(simulation.is_synced());
+    assert_eq!(simulation.node(2).height(), 4);
+}
+
+#[test]
+fn it_delays_messages_by_the_link_latency() {
+    let simulation = Simulation::new(2).unwrap();
+    simulation.set_link(0, 1, LinkConditions {
+        latency: Duration::from_millis(100),
+        drop_rate: 0.0,
+    });
+    assert!(simulation.connect(0, 1));
+
+    // The peer joins after the handshake, which takes 200 milliseconds on the listening node and
+    // 300 milliseconds on the dialing one.
+    simulation.advance(Duration::from_secs(2));
+    let mut expected = established(1700, 1);
+    expected.extend(established(1800, 0));
+    assert_eq!(simulation.take_events(), expected);
+
+    // The block is announced after 500 milliseconds, then the inventory, the request and the
+    // block each take 100 milliseconds.
+    let hashes = simulation.produce_blocks(0, 1);
+    simulation.advance(Duration::from_millis(799));
+    assert_eq!(simulation.take_events(), extended(2000, 0, &hashes));
+    simulation.advance(Duration::from_millis(1));
+    assert_eq!(simulation.take_events(), extended(2800, 1, &hashes));
+}
+
+#[test]
+fn it_syncs_after_a_partition_heals() {
+    let simulation = Simulation::new(2).unwrap();
+    assert!(simulation.connect(0, 1));
+    simulation.advance(Duration::from_millis(1500));
+    let mut expected = established(1500, 0);
+    expected.extend(established(1500, 1));
+    assert_eq!(simulation.take_events(), expected);
+
+    simulation.partition(&[&[0], &[1]]);
+    assert!(!simulation.memory_network().is_reachable(simulation.node(0).host(), simulation.node(1).host()));
+    let hashes = simulation.produce_blocks(0, 2);
+
+    // The connectivity check runs every minute, but only expects a pong once nothing was received
+    // for a minute. At two minutes, the ping times out after another ten seconds.
+    simulation.advance(Duration::from_millis(130_000 - 1500));
+    let lost = |node| vec![
+        (130_000, node, Event::Consensus(ConsensusEvent::Lost)),
+        (130_000, node, Event::Consensus(ConsensusEvent::Waiting)),
+    ];
+    let mut expected = extended(1500, 0, &hashes);
+    expected.extend(lost(0));
+    expected.extend(lost(1));
+    assert_eq!(simulation.take_events(), expected);
+    assert_eq!(simulation.node(0).peer_count(), 0);
+    assert_eq!(simulation.node(1).height(), 1);
+
+    simulation.heal();
+    assert!(simulation.connect(1, 0));
+    simulation.advance(Duration::from_secs(2));
+    let mut expected = established(131_500, 0);
+    expected.push((131_500, 1, Event::Consensus(ConsensusEvent::Syncing)));
+    expected.extend(extended(132_000, 1, &hashes));
+    expected.push((132_000, 1, Event::Consensus(ConsensusEvent::Established)));
+    assert_eq!(simulation.take_events(), expected);
+    assert!(simulation.is_synced());
+}
+
+#[test]
+fn it_mines_blocks_with_increasing_timestamps() {
+    let simulation = Simulation::new(1).unwrap();
+    let hashes = simulation.produce_blocks(0, 3);
+
+    let blockchain = &simulation.node(0).consensus.blockchain;
+    let mut prev_timestamp = blockchain.get_block_at(1, false).unwrap().header.timestamp;
+    for hash in hashes.iter() {
+        let block = blockchain.get_block(hash, false, false).unwrap();
+        assert!(block.header.verify_proof_of_work());
+        assert!(block.header.timestamp > prev_timestamp);
+        prev_timestamp = block.header.timestamp;
+    }
+}
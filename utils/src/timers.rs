@@ -3,11 +3,11 @@ use std::fmt;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::time::Duration;
-use std::time::Instant;
 
 use futures::prelude::*;
 use futures::sync::oneshot;
 use parking_lot::{Mutex, MutexGuard};
+use tokio::clock;
 use tokio::timer::{Delay, Interval};
 
 #[derive(Default)]
@@ -107,7 +107,8 @@ impl<K: Eq + Hash + Debug> Timers<K> {
             return;
         }
 
-        let task = Delay::new(Instant::now() + delay)
+        // Use the clock of the timer like `Interval` does, which may be a simulated one.
+        let task = Delay::new(clock::now() + delay)
             .and_then(move |_| {
                 func();
                 Ok(())
@@ -13,6 +13,12 @@ impl UniqueId {
     }
 }
 
+impl From<UniqueId> for usize {
+    fn from(id: UniqueId) -> Self {
+        id.0
+    }
+}
+
 impl fmt::Display for UniqueId {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f, "{}", self.0)